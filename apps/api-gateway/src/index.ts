@@ -14,6 +14,8 @@ import { orderRoutes } from "./routes/orders";
 import { payoutRoutes } from "./routes/payout";
 import {walletRoutes} from "./routes/wallet"
 import { portfolioRoutes } from "./routes/portfolio";
+import { CorrectionService } from "order-service/corrections";
+import { TriggerService } from "order-service/triggers";
 const PORT = parseInt(process.env.PORT || '3000');
 
 const app = new Elysia()
@@ -107,8 +109,18 @@ console.log(`
 🔥 Runtime: Bun ${Bun.version}
 `);
 
+// Busts and reprices from the matching engine, applied to the ledger
+const corrections = new CorrectionService();
+corrections.start();
+
+// Fills of stops and take-profits the engine fired, settled like any trade
+const triggers = new TriggerService();
+triggers.start();
+
 process.on('SIGINT', () => {
   console.log('\n🛑 Shutting down gracefully...');
+  corrections.stop();
+  triggers.stop();
   app.stop();
   process.exit(0);
 });
\ No newline at end of file
@@ -16,6 +16,7 @@
 
 import { Elysia, t } from 'elysia';
 import { prisma } from 'db/client';
+import Redis from 'ioredis';
 import { Connection, Keypair, PublicKey } from '@solana/web3.js';
 import * as anchor from '@coral-xyz/anchor';
 import { getAssociatedTokenAddress, TOKEN_PROGRAM_ID,getOrCreateAssociatedTokenAccount } from '@solana/spl-token';
@@ -37,6 +38,7 @@ function requireEnv(name: string): string {
 }
 
 const SOLANA_RPC_URL             = requireEnv('SOLANA_RPC_URL');
+const redis                      = new Redis(process.env.REDIS_URL || 'redis://localhost:6379');
 const ESCROW_VAULT_PROGRAM_ID    = new PublicKey('CRyAfXPmf11myj8X1dZ3AdjSfwXEjB5Ep4HpXmf6D6QP');
 const MARKET_REGISTRY_PROGRAM_ID = new PublicKey('H42DouiugXCKGn9sHrC7N6PtvRQFwwDLZsHJW1Q58N2h');
 const USDC_MINT                  = new PublicKey(requireEnv('USDC_MINT_ADDRESS'));
@@ -182,6 +184,9 @@ export const payoutRoutes = new Elysia({ prefix: '/payouts' })
       });
 
       steps.push('DB updated: state=RESOLVED');
+
+      // The matching engine stops taking orders once it reloads
+      await redis.publish('markets:updated', params.marketId);
       return { success: true, data: { marketId: params.marketId, outcome: outcome.toUpperCase(), steps } };
     },
     { body: t.Object({ outcome: t.String() }) },
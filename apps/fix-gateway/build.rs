@@ -0,0 +1,7 @@
+fn main() {
+    // Client only, straight from the engine's copy of the proto
+    tonic_build::configure()
+        .build_server(false)
+        .compile(&["../matching-engine/proto/matching_engine_v2.proto"], &["../matching-engine/proto"])
+        .unwrap_or_else(|e| panic!("Failed to compile protos: {:?}", e));
+}
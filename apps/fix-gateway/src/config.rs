@@ -0,0 +1,86 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::env;
+
+/// What a counterparty's session is for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionKind {
+    /// Order entry for one user id
+    Orders { user_id: String },
+    /// Read-only copy of every fill for these user ids
+    DropCopy { user_ids: Vec<String> },
+}
+
+pub struct Config {
+    // FIX acceptor
+    pub port: u16,
+    pub comp_id: String,
+    // Keyed by the counterparty's SenderCompID
+    pub sessions: HashMap<String, SessionKind>,
+    // Logon Password (554) per SenderCompID; sessions without one need none
+    pub passwords: HashMap<String, String>,
+    // Seconds a new connection has to log on
+    pub logon_timeout_secs: u64,
+
+    // Matching engine
+    pub matching_engine_url: String,
+    pub matching_engine_token: Option<String>,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        let sessions = parse_sessions(&env::var("FIX_SESSIONS").unwrap_or_default())?;
+        if sessions.is_empty() {
+            bail!("FIX_SESSIONS not set");
+        }
+
+        Ok(Self {
+            port: env::var("FIX_PORT")
+                .unwrap_or_else(|_| "9878".to_string())
+                .parse()
+                .unwrap_or(9878),
+            comp_id: env::var("FIX_COMP_ID").unwrap_or_else(|_| "EXCHANGE".to_string()),
+            sessions,
+            passwords: parse_pairs(&env::var("FIX_PASSWORDS").unwrap_or_default())?,
+            logon_timeout_secs: env::var("FIX_LOGON_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .unwrap_or(10),
+            matching_engine_url: env::var("MATCHING_ENGINE_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:50052".to_string()),
+            matching_engine_token: env::var("MATCHING_ENGINE_TOKEN").ok().filter(|t| !t.is_empty()),
+        })
+    }
+}
+
+/// `<SenderCompID>=order:<user_id>` or `<SenderCompID>=dropcopy:<user_id>|<user_id>...`
+/// entries, comma-separated
+pub fn parse_sessions(value: &str) -> Result<HashMap<String, SessionKind>> {
+    parse_pairs(value)?
+        .into_iter()
+        .map(|(comp_id, session)| {
+            let kind = match session.split_once(':') {
+                Some(("order", user_id)) if !user_id.is_empty() => SessionKind::Orders { user_id: user_id.to_string() },
+                Some(("dropcopy", user_ids)) if !user_ids.is_empty() => SessionKind::DropCopy {
+                    user_ids: user_ids.split('|').map(str::to_string).collect(),
+                },
+                _ => bail!("Expected order:<user_id> or dropcopy:<user_id>|..., got {}", session),
+            };
+            Ok((comp_id, kind))
+        })
+        .collect()
+}
+
+fn parse_pairs(value: &str) -> Result<HashMap<String, String>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (key, value) = entry.split_once('=').ok_or_else(|| anyhow!("Expected <key>=<value>, got {}", entry))?;
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::config::{Config, SessionKind};
+use crate::dropcopy::DropCopy;
+use crate::engine::{pb, EngineClient, Subscriptions};
+use crate::message::{msg_type, tags, DecodeError, Decoder, Message};
+use crate::orders::OrderEntry;
+use crate::session::{Received, Session, SessionState};
+
+// Reports queued for a session while it works through earlier ones
+const EXECUTION_BUFFER: usize = 1024;
+const TIMER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a session does with application messages and execution reports
+pub enum Application {
+    Orders(Box<OrderEntry>),
+    DropCopy(DropCopy),
+}
+
+impl Application {
+    fn user_ids(&self) -> Vec<String> {
+        match self {
+            Application::Orders(orders) => vec![orders.user_id().to_string()],
+            Application::DropCopy(dropcopy) => dropcopy.user_ids().to_vec(),
+        }
+    }
+
+    async fn on_message(&mut self, message: &Message) -> Vec<Message> {
+        match self {
+            Application::Orders(orders) => orders.on_message(message).await,
+            Application::DropCopy(dropcopy) => dropcopy.on_message(message),
+        }
+    }
+
+    async fn on_execution(&mut self, report: pb::ExecutionReport) -> Vec<Message> {
+        match self {
+            Application::Orders(orders) => orders.on_execution(report).await,
+            Application::DropCopy(dropcopy) => dropcopy.on_execution(report),
+        }
+    }
+}
+
+/// A counterparty that has logged on, handed to its session's task
+struct Connection {
+    reader: OwnedReadHalf,
+    writer: OwnedWriteHalf,
+    decoder: Decoder,
+    logon: Message,
+    heartbeat: Duration,
+}
+
+/// One configured session. Its task outlives connections and takes the
+/// execution reports whether or not the counterparty is logged on.
+struct Counterparty {
+    comp_id: String,
+    target_comp_id: String,
+    // Handed to the Session while connected
+    state: SessionState,
+    application: Application,
+    connected: Arc<AtomicBool>,
+    // Held for the life of the gateway
+    _subscriptions: Subscriptions,
+}
+
+impl Counterparty {
+    async fn run(mut self, mut connections: mpsc::Receiver<Connection>, mut executions: mpsc::Receiver<pb::ExecutionReport>) {
+        loop {
+            tokio::select! {
+                Some(connection) = connections.recv() => {
+                    self.serve(connection, &mut executions).await;
+                    self.connected.store(false, Ordering::SeqCst);
+                }
+                Some(report) = executions.recv() => {
+                    // Sequenced and stored, so a resend after the next logon covers it
+                    for message in self.application.on_execution(report).await {
+                        self.state.store(&self.comp_id, &self.target_comp_id, message);
+                    }
+                }
+                else => return,
+            }
+        }
+    }
+
+    async fn serve(&mut self, connection: Connection, executions: &mut mpsc::Receiver<pb::ExecutionReport>) {
+        let Connection { mut reader, mut writer, mut decoder, logon, heartbeat } = connection;
+        let state = std::mem::take(&mut self.state);
+        let mut session = Session::new(&self.comp_id, &self.target_comp_id, state, heartbeat, Instant::now());
+        let mut open = session.logon(&logon, Instant::now()) == Received::Handled;
+        open &= flush(&mut writer, &mut session).await;
+
+        let mut timer = tokio::time::interval(TIMER_INTERVAL);
+        let mut buf = vec![0u8; 8192];
+        while open {
+            tokio::select! {
+                read = reader.read(&mut buf) => {
+                    let n = match read {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n,
+                    };
+                    decoder.extend(&buf[..n]);
+                    open = drain(&mut decoder, &mut session, &mut self.application).await;
+                }
+                Some(report) = executions.recv() => {
+                    for message in self.application.on_execution(report).await {
+                        session.send(message, Instant::now());
+                    }
+                }
+                _ = timer.tick() => {
+                    open = session.on_timer(Instant::now());
+                }
+            }
+            open &= flush(&mut writer, &mut session).await;
+        }
+        // Whatever the session queued on its way out
+        flush(&mut writer, &mut session).await;
+        info!("{} disconnected", self.target_comp_id);
+        self.state = session.into_state();
+    }
+}
+
+/// Where to hand a session's connections
+struct SessionHandle {
+    connections: mpsc::Sender<Connection>,
+    connected: Arc<AtomicBool>,
+}
+
+/// Accepts counterparties configured in FIX_SESSIONS, one connection each
+pub struct Gateway {
+    comp_id: String,
+    passwords: HashMap<String, String>,
+    logon_timeout: Duration,
+    // Keyed by the counterparty's SenderCompID
+    sessions: HashMap<String, SessionHandle>,
+}
+
+impl Gateway {
+    /// Subscribe every configured session to its executions and start the
+    /// tasks that keep them, so reports are sequenced even before a logon
+    pub fn start(config: Config, client: EngineClient) -> Self {
+        let sessions = config
+            .sessions
+            .iter()
+            .map(|(target_comp_id, kind)| {
+                let application = match kind {
+                    SessionKind::Orders { user_id } => Application::Orders(Box::new(OrderEntry::new(user_id, client.clone()))),
+                    SessionKind::DropCopy { user_ids } => Application::DropCopy(DropCopy::new(user_ids)),
+                };
+                let (sender, executions) = mpsc::channel(EXECUTION_BUFFER);
+                let (connections, incoming) = mpsc::channel(1);
+                let connected = Arc::new(AtomicBool::new(false));
+                let counterparty = Counterparty {
+                    comp_id: config.comp_id.clone(),
+                    target_comp_id: target_comp_id.clone(),
+                    state: SessionState::default(),
+                    _subscriptions: Subscriptions::open(&client, &application.user_ids(), sender),
+                    application,
+                    connected: connected.clone(),
+                };
+                tokio::spawn(counterparty.run(incoming, executions));
+                (target_comp_id.clone(), SessionHandle { connections, connected })
+            })
+            .collect();
+        Self {
+            comp_id: config.comp_id,
+            passwords: config.passwords,
+            logon_timeout: Duration::from_secs(config.logon_timeout_secs),
+            sessions,
+        }
+    }
+
+    pub async fn serve(&self, stream: TcpStream, peer: SocketAddr) {
+        let (mut reader, writer) = stream.into_split();
+        let mut decoder = Decoder::default();
+
+        let logon = match tokio::time::timeout(self.logon_timeout, read_message(&mut reader, &mut decoder)).await {
+            Ok(Some(logon)) => logon,
+            Ok(None) => return,
+            Err(_) => return warn!("{} did not log on in time", peer),
+        };
+        let (target_comp_id, heartbeat) = match self.check_logon(&logon) {
+            Ok(checked) => checked,
+            Err(reason) => return warn!("Refused logon from {}: {}", peer, reason),
+        };
+        let handle = &self.sessions[&target_comp_id];
+        if handle.connected.swap(true, Ordering::SeqCst) {
+            return warn!("Refused logon from {}: {} is already logged on", peer, target_comp_id);
+        }
+        info!("{} logging on from {}", target_comp_id, peer);
+
+        let connection = Connection { reader, writer, decoder, logon, heartbeat };
+        if handle.connections.send(connection).await.is_err() {
+            handle.connected.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// The counterparty's SenderCompID and heartbeat interval, if it may log on
+    fn check_logon(&self, logon: &Message) -> Result<(String, Duration), String> {
+        if logon.msg_type() != msg_type::LOGON {
+            return Err("first message is not a Logon".to_string());
+        }
+        if logon.get(tags::TARGET_COMP_ID) != Some(self.comp_id.as_str()) {
+            return Err("wrong TargetCompID".to_string());
+        }
+        let target_comp_id = logon.get(tags::SENDER_COMP_ID).unwrap_or_default().to_string();
+        if !self.sessions.contains_key(&target_comp_id) {
+            return Err("unknown SenderCompID".to_string());
+        }
+        if let Some(password) = self.passwords.get(&target_comp_id) {
+            if logon.get(tags::PASSWORD) != Some(password.as_str()) {
+                return Err("wrong password".to_string());
+            }
+        }
+        match logon.get(tags::HEART_BT_INT).and_then(|h| h.parse::<u64>().ok()) {
+            Some(secs) if secs > 0 => Ok((target_comp_id, Duration::from_secs(secs))),
+            _ => Err("HeartBtInt must be positive".to_string()),
+        }
+    }
+}
+
+/// Hand every complete message to the session. Returns false once the
+/// connection should close.
+async fn drain(decoder: &mut Decoder, session: &mut Session, application: &mut Application) -> bool {
+    loop {
+        let message = match decoder.next_message() {
+            Ok(Some(message)) => message,
+            Ok(None) => return true,
+            Err(DecodeError::Garbled(reason)) => {
+                warn!("Dropped a message from {}: {}", session.target_comp_id(), reason);
+                continue;
+            }
+            Err(error) => {
+                warn!("Disconnecting {}: {}", session.target_comp_id(), error);
+                return false;
+            }
+        };
+        debug!("{} -> {}", session.target_comp_id(), message);
+        match session.receive(message, Instant::now()) {
+            Received::App(message) => {
+                for reply in application.on_message(&message).await {
+                    session.send(reply, Instant::now());
+                }
+            }
+            Received::Handled => {}
+            Received::Disconnect => return false,
+        }
+    }
+}
+
+async fn read_message(reader: &mut OwnedReadHalf, decoder: &mut Decoder) -> Option<Message> {
+    let mut buf = [0u8; 1024];
+    loop {
+        match decoder.next_message() {
+            Ok(Some(message)) => return Some(message),
+            Ok(None) => {}
+            Err(_) => return None,
+        }
+        match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => decoder.extend(&buf[..n]),
+        }
+    }
+}
+
+/// Write out what the session queued; false if the connection is gone
+async fn flush(writer: &mut OwnedWriteHalf, session: &mut Session) -> bool {
+    for bytes in session.take_outbox() {
+        if writer.write_all(&bytes).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
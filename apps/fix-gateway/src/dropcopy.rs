@@ -0,0 +1,78 @@
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+use crate::engine::{decimal_from_proto, pb};
+use crate::message::{msg_type, tags, Message};
+use crate::orders::{average, business_reject, ord_status, outcome_tag, side_tag, transact_time, UNSUPPORTED_MESSAGE_TYPE};
+
+/// A read-only session copying every fill for a firm's users, however the
+/// orders were entered
+pub struct DropCopy {
+    user_ids: Vec<String>,
+    // Filled notional per working order since the gateway started, for AvgPx
+    notional: HashMap<String, Decimal>,
+}
+
+impl DropCopy {
+    pub fn new(user_ids: &[String]) -> Self {
+        Self { user_ids: user_ids.to_vec(), notional: HashMap::new() }
+    }
+
+    pub fn user_ids(&self) -> &[String] {
+        &self.user_ids
+    }
+
+    pub fn on_message(&self, message: &Message) -> Vec<Message> {
+        vec![business_reject(message, UNSUPPORTED_MESSAGE_TYPE, "Drop copy sessions don't take orders")]
+    }
+
+    pub fn on_execution(&mut self, report: pb::ExecutionReport) -> Vec<Message> {
+        let done = matches!(report.status(), pb::OrderStatus::Filled | pb::OrderStatus::Cancelled | pb::OrderStatus::Rejected);
+        if !matches!(report.exec_type(), pb::ExecType::PartiallyFilled | pb::ExecType::Filled) {
+            if done {
+                self.notional.remove(&report.order_id);
+            }
+            return Vec::new();
+        }
+
+        let last_qty = decimal_from_proto(report.last_quantity.as_ref());
+        let last_px = decimal_from_proto(report.last_price.as_ref());
+        let filled = decimal_from_proto(report.filled_quantity.as_ref());
+        let quantity = decimal_from_proto(report.quantity.as_ref());
+        let notional = match done {
+            true => self.notional.remove(&report.order_id).unwrap_or_default() + last_qty * last_px,
+            false => {
+                let notional = self.notional.entry(report.order_id.clone()).or_default();
+                *notional += last_qty * last_px;
+                *notional
+            }
+        };
+        let leaves_qty = match done {
+            true => Decimal::ZERO,
+            false => quantity - filled,
+        };
+        let trade_id = report.trade_id.clone().unwrap_or_default();
+
+        let message = Message::new(msg_type::EXECUTION_REPORT)
+            .with(tags::ORDER_ID, &report.order_id)
+            // The same fill always gets the same ExecID, so a consumer can
+            // drop what it sees twice after a reconnect
+            .with(tags::EXEC_ID, format!("{}-{}", trade_id, report.order_id))
+            .with(tags::EXEC_TYPE, "F")
+            .with(tags::ORD_STATUS, ord_status(report.status()))
+            .with(tags::ACCOUNT, &report.user_id)
+            .with(tags::SYMBOL, &report.market_id)
+            .with(tags::SECURITY_SUB_TYPE, outcome_tag(report.outcome(), report.outcome_index))
+            .with(tags::SIDE, side_tag(report.side()))
+            .with(tags::ORDER_QTY, quantity)
+            .with(tags::PRICE, decimal_from_proto(report.price.as_ref()))
+            .with(tags::LAST_QTY, last_qty)
+            .with(tags::LAST_PX, last_px)
+            .with(tags::TRD_MATCH_ID, trade_id)
+            .with(tags::CUM_QTY, filled)
+            .with(tags::LEAVES_QTY, leaves_qty)
+            .with(tags::AVG_PX, average(notional, filled))
+            .with(tags::TRANSACT_TIME, transact_time(&report));
+        vec![message]
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status, Streaming};
+use tracing::{info, warn};
+
+pub mod pb {
+    tonic::include_proto!("matching_engine.v2");
+}
+
+use pb::matching_engine_client::MatchingEngineClient;
+
+const NANOS_PER_UNIT: i64 = 1_000_000_000;
+// Wait before resubscribing after the stream drops
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+pub type EngineClient = MatchingEngineClient<InterceptedService<Channel, BearerToken>>;
+
+/// Sends MATCHING_ENGINE_TOKEN on every call when one is configured
+#[derive(Clone)]
+pub struct BearerToken(Option<MetadataValue<Ascii>>);
+
+impl Interceptor for BearerToken {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.0 {
+            request.metadata_mut().insert("authorization", token.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Connects on first use, so the gateway can start before the engine
+pub fn connect(url: &str, token: Option<&str>) -> Result<EngineClient> {
+    let channel = Channel::from_shared(url.to_string())?.connect_lazy();
+    let token = token.map(|t| format!("Bearer {}", t).parse()).transpose()?;
+    Ok(MatchingEngineClient::with_interceptor(channel, BearerToken(token)))
+}
+
+/// Execution streams for a session's users, forwarded into one channel for
+/// as long as the gateway runs. Dropping it ends them.
+pub struct Subscriptions(Vec<JoinHandle<()>>);
+
+impl Subscriptions {
+    /// Subscribe to every user, retrying until the engine answers. A stream
+    /// that drops later is reopened; reports from while it was down are lost.
+    pub fn open(client: &EngineClient, user_ids: &[String], sender: mpsc::Sender<pb::ExecutionReport>) -> Self {
+        let tasks = user_ids
+            .iter()
+            .map(|user_id| tokio::spawn(forward(client.clone(), user_id.clone(), sender.clone())))
+            .collect();
+        Self(tasks)
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+async fn subscribe(client: &mut EngineClient, user_id: &str) -> Result<Streaming<pb::ExecutionReport>, Status> {
+    let request = pb::SubscribeExecutionsRequest { user_id: user_id.to_string() };
+    Ok(client.subscribe_executions(request).await?.into_inner())
+}
+
+async fn forward(mut client: EngineClient, user_id: String, sender: mpsc::Sender<pb::ExecutionReport>) {
+    loop {
+        let mut stream = match subscribe(&mut client, &user_id).await {
+            Ok(stream) => stream,
+            Err(status) => {
+                warn!("Subscribing to executions for {} failed: {}", user_id, status);
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                continue;
+            }
+        };
+        info!("Subscribed to executions for {}", user_id);
+        let ended = loop {
+            match stream.message().await {
+                Ok(Some(report)) => {
+                    if sender.send(report).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => break "ended".to_string(),
+                Err(status) if status.code() == Code::DataLoss => break "fell behind".to_string(),
+                Err(status) => break format!("failed: {}", status),
+            }
+        };
+        warn!("Execution stream for {} {}", user_id, ended);
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+pub fn decimal_to_proto(value: Decimal) -> pb::Decimal {
+    let units = value.trunc();
+    let nanos = ((value - units) * Decimal::from(NANOS_PER_UNIT)).trunc();
+    pb::Decimal {
+        units: units.to_i64().unwrap_or(if value.is_sign_negative() { i64::MIN } else { i64::MAX }),
+        nanos: nanos.to_i32().unwrap_or_default(),
+    }
+}
+
+pub fn decimal_from_proto(value: Option<&pb::Decimal>) -> Decimal {
+    value.map_or(Decimal::ZERO, |value| Decimal::from(value.units) + Decimal::new(value.nanos as i64, 9)).normalize()
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+mod config;
+mod connection;
+mod dropcopy;
+mod engine;
+mod message;
+mod orders;
+mod session;
+
+use config::Config;
+use connection::Gateway;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Initialize logging
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    info!("🚀 Starting FIX Gateway");
+
+    let config = Config::from_env()?;
+    let client = engine::connect(&config.matching_engine_url, config.matching_engine_token.as_deref())?;
+    info!("✅ Matching engine: {}", config.matching_engine_url);
+
+    let listener = TcpListener::bind(("0.0.0.0", config.port)).await?;
+    info!(
+        "✅ Accepting {} as {} on port {} for {} sessions",
+        message::BEGIN_STRING,
+        config.comp_id,
+        config.port,
+        config.sessions.len()
+    );
+
+    let gateway = Arc::new(Gateway::start(config, client));
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let gateway = gateway.clone();
+                tokio::spawn(async move { gateway.serve(stream, peer).await });
+            }
+            Err(e) => warn!("Accept failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+use thiserror::Error;
+
+pub const BEGIN_STRING: &str = "FIX.4.4";
+const SOH: u8 = 0x01;
+
+/// Tags this gateway reads or writes
+pub mod tags {
+    pub const ACCOUNT: u32 = 1;
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECK_SUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const CUM_QTY: u32 = 14;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const EXEC_ID: u32 = 17;
+    pub const EXEC_INST: u32 = 18;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_QTY: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const PRICE: u32 = 44;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const MAX_FLOOR: u32 = 111;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const ORIG_SENDING_TIME: u32 = 122;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const REF_TAG_ID: u32 = 371;
+    pub const REF_MSG_TYPE: u32 = 372;
+    pub const BUSINESS_REJECT_REASON: u32 = 380;
+    pub const SESSION_REJECT_REASON: u32 = 373;
+    pub const CXL_REJ_RESPONSE_TO: u32 = 434;
+    pub const PASSWORD: u32 = 554;
+    pub const SECURITY_SUB_TYPE: u32 = 762;
+    pub const TRD_MATCH_ID: u32 = 880;
+}
+
+/// MsgType values this gateway knows
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+    pub const ORDER_CANCEL_REPLACE_REQUEST: &str = "G";
+    pub const BUSINESS_MESSAGE_REJECT: &str = "j";
+
+    /// Session-level messages, which are gap-filled rather than resent
+    pub fn is_admin(msg_type: &str) -> bool {
+        matches!(msg_type, HEARTBEAT | TEST_REQUEST | RESEND_REQUEST | REJECT | SEQUENCE_RESET | LOGOUT | LOGON)
+    }
+}
+
+// Written by the encoder itself
+const FRAMING: [u32; 3] = [tags::BEGIN_STRING, tags::BODY_LENGTH, tags::CHECK_SUM];
+
+// Standard header fields, which go ahead of the body in this order
+const HEADER: [u32; 7] = [
+    tags::MSG_TYPE,
+    tags::SENDER_COMP_ID,
+    tags::TARGET_COMP_ID,
+    tags::MSG_SEQ_NUM,
+    tags::POSS_DUP_FLAG,
+    tags::SENDING_TIME,
+    tags::ORIG_SENDING_TIME,
+];
+
+/// A FIX message as tag=value pairs in wire order. BeginString, BodyLength
+/// and CheckSum are added on encoding and dropped on decoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    fields: Vec<(u32, String)>,
+}
+
+impl Message {
+    pub fn new(msg_type: &str) -> Self {
+        Self { fields: vec![(tags::MSG_TYPE, msg_type.to_string())] }
+    }
+
+    pub fn msg_type(&self) -> &str {
+        self.get(tags::MSG_TYPE).unwrap_or_default()
+    }
+
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields.iter().find(|(t, _)| *t == tag).map(|(_, v)| v.as_str())
+    }
+
+    /// A Y/N field; absent is N
+    pub fn flag(&self, tag: u32) -> bool {
+        self.get(tag) == Some("Y")
+    }
+
+    pub fn seq_num(&self) -> Option<u64> {
+        self.get(tags::MSG_SEQ_NUM)?.parse().ok()
+    }
+
+    /// Replace the field if present, otherwise append it
+    pub fn set(&mut self, tag: u32, value: impl ToString) -> &mut Self {
+        let value = value.to_string();
+        match self.fields.iter_mut().find(|(t, _)| *t == tag) {
+            Some(field) => field.1 = value,
+            None => self.fields.push((tag, value)),
+        }
+        self
+    }
+
+    pub fn with(mut self, tag: u32, value: impl ToString) -> Self {
+        self.set(tag, value);
+        self
+    }
+
+    pub fn with_opt(self, tag: u32, value: Option<impl ToString>) -> Self {
+        match value {
+            Some(value) => self.with(tag, value),
+            None => self,
+        }
+    }
+
+    /// The wire form. Header fields are moved to the front wherever they
+    /// were set.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        let header = HEADER.iter().filter_map(|tag| self.fields.iter().find(|(t, _)| t == tag));
+        let rest = self.fields.iter().filter(|(tag, _)| !HEADER.contains(tag) && !FRAMING.contains(tag));
+        for (tag, value) in header.chain(rest) {
+            body.extend_from_slice(format!("{}={}", tag, value).as_bytes());
+            body.push(SOH);
+        }
+
+        let mut out = format!("8={}\x019={}\x01", BEGIN_STRING, body.len()).into_bytes();
+        out.extend_from_slice(&body);
+        let checksum = checksum(&out);
+        out.extend_from_slice(format!("10={:03}\x01", checksum).as_bytes());
+        out
+    }
+}
+
+/// `|` for SOH, the way FIX logs are usually written
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (tag, value) in &self.fields {
+            write!(f, "{}={}|", tag, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The stream can't be resynchronized; the connection should be dropped
+    #[error("Expected BeginString {}", BEGIN_STRING)]
+    BadBeginString,
+    /// One message was unreadable and has been skipped. Its sequence number
+    /// is not consumed, so the gap will be resent.
+    #[error("Garbled message: {0}")]
+    Garbled(String),
+}
+
+/// Splits a byte stream into messages
+#[derive(Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// The next complete message, or None until more bytes arrive
+    pub fn next_message(&mut self) -> Result<Option<Message>, DecodeError> {
+        let prefix = format!("8={}\x019=", BEGIN_STRING);
+        if self.buf.len() < prefix.len() {
+            return Ok(None);
+        }
+        if !self.buf.starts_with(prefix.as_bytes()) {
+            return Err(DecodeError::BadBeginString);
+        }
+        let Some(length_end) = self.buf[prefix.len()..].iter().position(|b| *b == SOH) else {
+            return Ok(None);
+        };
+        let length_end = prefix.len() + length_end;
+        let body_length: usize = std::str::from_utf8(&self.buf[prefix.len()..length_end])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(DecodeError::BadBeginString)?;
+        let body_end = length_end + 1 + body_length;
+        // "10=NNN" and its SOH
+        let total = body_end + 7;
+        if self.buf.len() < total {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self.buf.drain(..total).collect();
+        let trailer = &frame[body_end..];
+        let expected = format!("10={:03}\x01", checksum(&frame[..body_end]));
+        if trailer != expected.as_bytes() {
+            return Err(DecodeError::Garbled(format!(
+                "Bad checksum {}",
+                String::from_utf8_lossy(trailer).trim_end_matches('\x01')
+            )));
+        }
+
+        let mut fields = Vec::new();
+        for field in frame[length_end + 1..body_end].split(|b| *b == SOH).filter(|f| !f.is_empty()) {
+            let field = std::str::from_utf8(field).map_err(|_| DecodeError::Garbled("Not UTF-8".to_string()))?;
+            let (tag, value) = field
+                .split_once('=')
+                .ok_or_else(|| DecodeError::Garbled(format!("Field without a tag: {}", field)))?;
+            let tag = tag.parse().map_err(|_| DecodeError::Garbled(format!("Bad tag: {}", tag)))?;
+            fields.push((tag, value.to_string()));
+        }
+        if fields.first().map(|(tag, _)| *tag) != Some(tags::MSG_TYPE) {
+            return Err(DecodeError::Garbled("MsgType must be the third field".to_string()));
+        }
+        Ok(Some(Message { fields }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_then_decode_in_pieces() {
+        let logon = Message::new(msg_type::LOGON)
+            .with(tags::SENDER_COMP_ID, "MM1")
+            .with(tags::MSG_SEQ_NUM, 1)
+            .with(tags::HEART_BT_INT, 30);
+        let heartbeat = Message::new(msg_type::HEARTBEAT).with(tags::MSG_SEQ_NUM, 2);
+        let mut wire = logon.encode();
+        assert!(String::from_utf8_lossy(&wire).starts_with("8=FIX.4.4\x019=24\x0135=A\x01"));
+        wire.extend(heartbeat.encode());
+
+        let mut decoder = Decoder::default();
+        let (first, rest) = wire.split_at(20);
+        decoder.extend(first);
+        assert_eq!(decoder.next_message(), Ok(None));
+        decoder.extend(rest);
+        assert_eq!(decoder.next_message(), Ok(Some(logon)));
+        assert_eq!(decoder.next_message(), Ok(Some(heartbeat)));
+        assert_eq!(decoder.next_message(), Ok(None));
+
+        let mut corrupt = Message::new(msg_type::HEARTBEAT).encode();
+        // The MsgType value, just ahead of the trailer
+        let at = corrupt.len() - 9;
+        corrupt[at] = b'1';
+        decoder.extend(&corrupt);
+        assert!(matches!(decoder.next_message(), Err(DecodeError::Garbled(_))));
+    }
+}
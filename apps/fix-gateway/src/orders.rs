@@ -0,0 +1,585 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::engine::{decimal_from_proto, decimal_to_proto, pb, EngineClient};
+use crate::message::{msg_type, tags, Message};
+use crate::session::timestamp;
+
+// Finished orders remembered so late cancels get "too late" rather than "unknown"
+const FINISHED_LIMIT: usize = 10_000;
+
+// SessionRejectReason (373)
+const REQUIRED_TAG_MISSING: u32 = 1;
+const VALUE_INCORRECT: u32 = 5;
+// BusinessRejectReason (380)
+pub const UNSUPPORTED_MESSAGE_TYPE: u32 = 3;
+// CxlRejReason (102)
+const TOO_LATE_TO_CANCEL: u32 = 0;
+const UNKNOWN_ORDER: u32 = 1;
+const ALREADY_PENDING: u32 = 3;
+const DUPLICATE_CL_ORD_ID: u32 = 6;
+const OTHER: u32 = 99;
+// OrdRejReason (103); OTHER is shared
+const DUPLICATE_ORDER: u32 = 6;
+
+/// A field that stops a message being acted on, answered with a session Reject
+#[derive(Debug, PartialEq)]
+pub struct FieldError {
+    tag: u32,
+    reason: u32,
+    text: String,
+}
+
+fn required(message: &Message, tag: u32) -> Result<&str, FieldError> {
+    message.get(tag).filter(|v| !v.is_empty()).ok_or_else(|| FieldError {
+        tag,
+        reason: REQUIRED_TAG_MISSING,
+        text: format!("Tag {} is required", tag),
+    })
+}
+
+fn incorrect(tag: u32, text: &str) -> FieldError {
+    FieldError { tag, reason: VALUE_INCORRECT, text: text.to_string() }
+}
+
+fn positive(message: &Message, tag: u32) -> Result<Decimal, FieldError> {
+    Decimal::from_str(required(message, tag)?)
+        .ok()
+        .filter(|d| d.is_sign_positive() && !d.is_zero())
+        .ok_or_else(|| incorrect(tag, "Must be a positive number"))
+}
+
+pub fn session_reject(message: &Message, error: FieldError) -> Message {
+    Message::new(msg_type::REJECT)
+        .with_opt(tags::REF_SEQ_NUM, message.get(tags::MSG_SEQ_NUM))
+        .with(tags::REF_TAG_ID, error.tag)
+        .with(tags::REF_MSG_TYPE, message.msg_type())
+        .with(tags::SESSION_REJECT_REASON, error.reason)
+        .with(tags::TEXT, error.text)
+}
+
+pub fn business_reject(message: &Message, reason: u32, text: &str) -> Message {
+    Message::new(msg_type::BUSINESS_MESSAGE_REJECT)
+        .with_opt(tags::REF_SEQ_NUM, message.get(tags::MSG_SEQ_NUM))
+        .with(tags::REF_MSG_TYPE, message.msg_type())
+        .with(tags::BUSINESS_REJECT_REASON, reason)
+        .with(tags::TEXT, text)
+}
+
+/// SecuritySubType (762): YES or NO in binary markets, the outcome index in
+/// categorical ones
+pub fn outcome_tag(outcome: pb::Outcome, outcome_index: u32) -> String {
+    match outcome {
+        pb::Outcome::Yes => "YES".to_string(),
+        pb::Outcome::No => "NO".to_string(),
+        pb::Outcome::Unspecified => outcome_index.to_string(),
+    }
+}
+
+pub fn side_tag(side: pb::Side) -> &'static str {
+    match side {
+        pb::Side::Sell => "2",
+        pb::Side::Buy | pb::Side::Unspecified => "1",
+    }
+}
+
+pub fn ord_status(status: pb::OrderStatus) -> &'static str {
+    match status {
+        pb::OrderStatus::Partial => "1",
+        pb::OrderStatus::Filled => "2",
+        pb::OrderStatus::Cancelled => "4",
+        pb::OrderStatus::Rejected => "8",
+        pb::OrderStatus::Open | pb::OrderStatus::Untriggered | pb::OrderStatus::Unspecified => "0",
+    }
+}
+
+pub fn transact_time(report: &pb::ExecutionReport) -> String {
+    report
+        .timestamp
+        .as_ref()
+        .and_then(|t| DateTime::<Utc>::from_timestamp(t.seconds, t.nanos as u32))
+        .map_or_else(timestamp, |t| t.format("%Y%m%d-%H:%M:%S%.3f").to_string())
+}
+
+pub fn average(notional: Decimal, quantity: Decimal) -> Decimal {
+    match quantity.is_zero() {
+        true => Decimal::ZERO,
+        false => (notional / quantity).round_dp(9).normalize(),
+    }
+}
+
+fn ord_rej_reason(reason: pb::RejectReason) -> u32 {
+    match reason {
+        pb::RejectReason::UnknownMarket => 1,
+        pb::RejectReason::MarketHalted | pb::RejectReason::MarketClosed => 2,
+        pb::RejectReason::RiskLimit => 3,
+        pb::RejectReason::ClientOrderIdReused => DUPLICATE_ORDER,
+        pb::RejectReason::InvalidQuantity => 13,
+        pb::RejectReason::InvalidPrice => 18,
+        _ => OTHER,
+    }
+}
+
+/// The client's view of one engine order
+#[derive(Debug, Clone)]
+struct Order {
+    order_id: Option<String>,
+    account: String,
+    cl_ord_id: String,
+    // The order this one replaced
+    orig_cl_ord_id: Option<String>,
+    symbol: String,
+    security_sub_type: String,
+    side: pb::Side,
+    outcome: pb::Outcome,
+    outcome_index: Option<u32>,
+    ord_type: String,
+    order_type: pb::OrderType,
+    price: Option<Decimal>,
+    display_qty: Option<Decimal>,
+    // OrderQty as the client sent it, counting what replaced orders filled
+    order_qty: Decimal,
+    // Filled by the orders this one replaced
+    prior_qty: Decimal,
+    prior_notional: Decimal,
+    filled: Decimal,
+    notional: Decimal,
+    // Its acceptance still has to go out as Replaced
+    replacing: bool,
+    pending: Option<Pending>,
+}
+
+#[derive(Debug, Clone)]
+enum Pending {
+    Cancel { cl_ord_id: String },
+    Replace { replacement: Box<Order> },
+}
+
+impl Order {
+    /// The order described by a NewOrderSingle or OrderCancelReplaceRequest
+    fn parse(message: &Message, account: &str) -> Result<Self, FieldError> {
+        let security_sub_type = required(message, tags::SECURITY_SUB_TYPE)?;
+        let (outcome, outcome_index) = match security_sub_type {
+            "YES" => (pb::Outcome::Yes, None),
+            "NO" => (pb::Outcome::No, None),
+            index => match index.parse::<u32>() {
+                Ok(index) => (pb::Outcome::Unspecified, Some(index)),
+                Err(_) => return Err(incorrect(tags::SECURITY_SUB_TYPE, "Must be YES, NO or an outcome index")),
+            },
+        };
+        let side = match required(message, tags::SIDE)? {
+            "1" => pb::Side::Buy,
+            "2" => pb::Side::Sell,
+            _ => return Err(incorrect(tags::SIDE, "Must be 1 (buy) or 2 (sell)")),
+        };
+        let ord_type = required(message, tags::ORD_TYPE)?;
+        let post_only = message.get(tags::EXEC_INST).is_some_and(|inst| inst.split(' ').any(|i| i == "6"));
+        let display_qty = match message.get(tags::MAX_FLOOR) {
+            Some(_) => Some(positive(message, tags::MAX_FLOOR)?),
+            None => None,
+        };
+        let (order_type, price) = match (ord_type, post_only, display_qty) {
+            ("1", false, None) => (pb::OrderType::Market, message.get(tags::PRICE).is_some().then(|| positive(message, tags::PRICE)).transpose()?),
+            ("2", false, None) => (pb::OrderType::Limit, Some(positive(message, tags::PRICE)?)),
+            ("2", true, None) => (pb::OrderType::PostOnly, Some(positive(message, tags::PRICE)?)),
+            ("2", false, Some(_)) => (pb::OrderType::Iceberg, Some(positive(message, tags::PRICE)?)),
+            ("1" | "2", _, _) => return Err(incorrect(tags::EXEC_INST, "Participate-don't-initiate and MaxFloor are for limit orders, one at a time")),
+            _ => return Err(incorrect(tags::ORD_TYPE, "Must be 1 (market) or 2 (limit)")),
+        };
+
+        Ok(Self {
+            order_id: None,
+            account: account.to_string(),
+            cl_ord_id: required(message, tags::CL_ORD_ID)?.to_string(),
+            orig_cl_ord_id: None,
+            symbol: required(message, tags::SYMBOL)?.to_string(),
+            security_sub_type: security_sub_type.to_string(),
+            side,
+            outcome,
+            outcome_index,
+            ord_type: ord_type.to_string(),
+            order_type,
+            price,
+            display_qty,
+            order_qty: positive(message, tags::ORDER_QTY)?,
+            prior_qty: Decimal::ZERO,
+            prior_notional: Decimal::ZERO,
+            filled: Decimal::ZERO,
+            notional: Decimal::ZERO,
+            replacing: false,
+            pending: None,
+        })
+    }
+
+    fn request(&self, quantity: Decimal) -> pb::PlaceOrderRequest {
+        pb::PlaceOrderRequest {
+            user_id: self.account.clone(),
+            market_id: self.symbol.clone(),
+            side: self.side.into(),
+            outcome: self.outcome.into(),
+            outcome_index: self.outcome_index,
+            order_type: self.order_type.into(),
+            // Market orders take no limit unless the client gives one
+            price: Some(decimal_to_proto(self.price.unwrap_or_default())),
+            quantity: Some(decimal_to_proto(quantity)),
+            display_quantity: self.display_qty.map(decimal_to_proto),
+            client_order_id: Some(self.cl_ord_id.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn cum_qty(&self) -> Decimal {
+        self.prior_qty + self.filled
+    }
+
+    /// An ExecutionReport with everything known about the order filled in
+    fn report(&self, exec_id: String, exec_type: &str, ord_status: &str) -> Message {
+        let cum_qty = self.cum_qty();
+        let leaves_qty = match ord_status {
+            "0" | "1" => (self.order_qty - cum_qty).max(Decimal::ZERO),
+            _ => Decimal::ZERO,
+        };
+        Message::new(msg_type::EXECUTION_REPORT)
+            .with(tags::ORDER_ID, self.order_id.as_deref().unwrap_or("NONE"))
+            .with(tags::CL_ORD_ID, &self.cl_ord_id)
+            .with_opt(tags::ORIG_CL_ORD_ID, self.orig_cl_ord_id.as_ref())
+            .with(tags::EXEC_ID, exec_id)
+            .with(tags::EXEC_TYPE, exec_type)
+            .with(tags::ORD_STATUS, ord_status)
+            .with(tags::ACCOUNT, &self.account)
+            .with(tags::SYMBOL, &self.symbol)
+            .with(tags::SECURITY_SUB_TYPE, &self.security_sub_type)
+            .with(tags::SIDE, side_tag(self.side))
+            .with(tags::ORD_TYPE, &self.ord_type)
+            .with_opt(tags::PRICE, self.price)
+            .with(tags::ORDER_QTY, self.order_qty)
+            .with(tags::CUM_QTY, cum_qty)
+            .with(tags::LEAVES_QTY, leaves_qty)
+            .with(tags::AVG_PX, average(self.prior_notional + self.notional, cum_qty))
+    }
+
+    /// Apply an acceptance or fill from the engine and report it
+    fn apply(&mut self, report: &pb::ExecutionReport, exec_id: String) -> Message {
+        self.filled = decimal_from_proto(report.filled_quantity.as_ref());
+        let status = ord_status(report.status());
+        let message = match report.exec_type() {
+            pb::ExecType::PartiallyFilled | pb::ExecType::Filled => {
+                let last_qty = decimal_from_proto(report.last_quantity.as_ref());
+                let last_px = decimal_from_proto(report.last_price.as_ref());
+                self.notional += last_qty * last_px;
+                self.report(exec_id, "F", status)
+                    .with(tags::LAST_QTY, last_qty)
+                    .with(tags::LAST_PX, last_px)
+                    .with_opt(tags::TRD_MATCH_ID, report.trade_id.as_ref())
+            }
+            _ if self.replacing => self.report(exec_id, "5", status),
+            _ => self.report(exec_id, "0", status),
+        };
+        self.replacing = false;
+        message
+    }
+}
+
+/// Order entry for one user: NewOrderSingle, OrderCancelRequest and
+/// OrderCancelReplaceRequest go to the engine, and what the engine does with
+/// the orders comes back as ExecutionReports. Lives as long as the session,
+/// across reconnects.
+pub struct OrderEntry {
+    user_id: String,
+    client: EngineClient,
+    // By engine order id
+    orders: HashMap<String, Order>,
+    // Every ClOrdID that named an order, to the engine order id
+    by_cl_ord_id: HashMap<String, String>,
+    finished: VecDeque<String>,
+    // ExecIDs are <when this started>-<count>, unique across restarts
+    exec_prefix: i64,
+    exec_count: u64,
+}
+
+impl OrderEntry {
+    pub fn new(user_id: &str, client: EngineClient) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            client,
+            orders: HashMap::new(),
+            by_cl_ord_id: HashMap::new(),
+            finished: VecDeque::new(),
+            exec_prefix: Utc::now().timestamp_millis(),
+            exec_count: 0,
+        }
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    fn exec_id(&mut self) -> String {
+        self.exec_count += 1;
+        format!("{}-{}", self.exec_prefix, self.exec_count)
+    }
+
+    pub async fn on_message(&mut self, message: &Message) -> Vec<Message> {
+        match message.msg_type() {
+            msg_type::NEW_ORDER_SINGLE => self.new_order(message).await,
+            msg_type::ORDER_CANCEL_REQUEST => self.cancel(message, false).await,
+            msg_type::ORDER_CANCEL_REPLACE_REQUEST => self.cancel(message, true).await,
+            other => vec![business_reject(message, UNSUPPORTED_MESSAGE_TYPE, &format!("MsgType {} is not supported", other))],
+        }
+    }
+
+    async fn new_order(&mut self, message: &Message) -> Vec<Message> {
+        let order = match Order::parse(message, &self.user_id) {
+            Ok(order) => order,
+            Err(error) => return vec![session_reject(message, error)],
+        };
+        if self.by_cl_ord_id.contains_key(&order.cl_ord_id) {
+            let exec_id = self.exec_id();
+            return vec![rejected(&order, exec_id, DUPLICATE_ORDER, "Duplicate ClOrdID")];
+        }
+        let quantity = order.order_qty;
+        match self.place(order.clone(), quantity).await {
+            Ok(()) => Vec::new(),
+            Err((reason, text)) => {
+                let exec_id = self.exec_id();
+                vec![rejected(&order, exec_id, reason, &text)]
+            }
+        }
+    }
+
+    /// Send the order to the engine. Its reports arrive on the execution
+    /// stream, which is read after this returns.
+    async fn place(&mut self, mut order: Order, quantity: Decimal) -> Result<(), (u32, String)> {
+        let response = match self.client.place_order(order.request(quantity)).await {
+            Ok(response) => response.into_inner(),
+            Err(status) => return Err((OTHER, status.message().to_string())),
+        };
+        if response.reject_reason() != pb::RejectReason::Unspecified {
+            return Err((ord_rej_reason(response.reject_reason()), response.reject_message));
+        }
+        order.order_id = Some(response.order_id.clone());
+        self.by_cl_ord_id.insert(order.cl_ord_id.clone(), response.order_id.clone());
+        self.orders.insert(response.order_id, order);
+        Ok(())
+    }
+
+    /// OrderCancelRequest, or the cancel half of an OrderCancelReplaceRequest.
+    /// The engine has no amend, so a replacement is placed once the stream
+    /// confirms the cancel and says how much had filled; it loses the
+    /// original's queue position.
+    async fn cancel(&mut self, message: &Message, replace: bool) -> Vec<Message> {
+        let fields = required(message, tags::CL_ORD_ID)
+            .and_then(|cl_ord_id| Ok((cl_ord_id.to_string(), required(message, tags::ORIG_CL_ORD_ID)?.to_string())));
+        let (cl_ord_id, orig_cl_ord_id) = match fields {
+            Ok(fields) => fields,
+            Err(error) => return vec![session_reject(message, error)],
+        };
+        let replacement = match replace {
+            true => match Order::parse(message, &self.user_id) {
+                Ok(replacement) => Some(replacement),
+                Err(error) => return vec![session_reject(message, error)],
+            },
+            false => None,
+        };
+        let reject = |reason: u32, text: &str| vec![cancel_reject(message, replace, reason, text)];
+
+        let Some(order_id) = self.by_cl_ord_id.get(&orig_cl_ord_id).cloned() else {
+            return reject(UNKNOWN_ORDER, "Unknown order");
+        };
+        if self.by_cl_ord_id.contains_key(&cl_ord_id) {
+            return reject(DUPLICATE_CL_ORD_ID, "Duplicate ClOrdID");
+        }
+        let order = &self.orders[&order_id];
+        if order.pending.is_some() {
+            return reject(ALREADY_PENDING, "A cancel or replace is already pending");
+        }
+        if let Some(replacement) = &replacement {
+            let same = replacement.symbol == order.symbol
+                && replacement.security_sub_type == order.security_sub_type
+                && replacement.side == order.side;
+            if !same {
+                return reject(OTHER, "Symbol, SecuritySubType and Side can't be replaced");
+            }
+        }
+
+        let request = pb::CancelOrderRequest {
+            user_id: self.user_id.clone(),
+            market_id: order.symbol.clone(),
+            order_id: order_id.clone(),
+        };
+        match self.client.cancel_order(request).await {
+            Ok(response) if response.get_ref().cancelled => {
+                let pending = match replacement {
+                    Some(replacement) => Pending::Replace { replacement: Box::new(replacement) },
+                    None => Pending::Cancel { cl_ord_id },
+                };
+                if let Some(order) = self.orders.get_mut(&order_id) {
+                    order.pending = Some(pending);
+                }
+                Vec::new()
+            }
+            Ok(_) => reject(TOO_LATE_TO_CANCEL, "Too late to cancel"),
+            Err(status) => reject(OTHER, status.message()),
+        }
+    }
+
+    pub async fn on_execution(&mut self, report: pb::ExecutionReport) -> Vec<Message> {
+        // Orders placed some other way
+        if !self.orders.contains_key(&report.order_id) {
+            return Vec::new();
+        }
+        let exec_id = self.exec_id();
+        let order = self.orders.get_mut(&report.order_id).unwrap();
+        let message = match report.exec_type() {
+            pb::ExecType::Cancelled | pb::ExecType::Expired => {
+                order.filled = decimal_from_proto(report.filled_quantity.as_ref());
+                let (exec_type, status) = match report.exec_type() {
+                    pb::ExecType::Expired => ("C", "C"),
+                    _ => ("4", "4"),
+                };
+                match order.pending.take() {
+                    Some(Pending::Replace { replacement }) => {
+                        let order = order.clone();
+                        self.finish(&report.order_id);
+                        return self.replace(order, *replacement, exec_id).await;
+                    }
+                    Some(Pending::Cancel { cl_ord_id }) => order
+                        .report(exec_id, exec_type, status)
+                        .with(tags::CL_ORD_ID, cl_ord_id)
+                        .with(tags::ORIG_CL_ORD_ID, &order.cl_ord_id),
+                    // Unsolicited: the rest of a group, an expired market, a
+                    // dropped trading session
+                    None => order.report(exec_id, exec_type, status),
+                }
+            }
+            _ => order.apply(&report, exec_id),
+        };
+        if matches!(report.status(), pb::OrderStatus::Filled | pb::OrderStatus::Cancelled | pb::OrderStatus::Rejected) {
+            self.finish(&report.order_id);
+        }
+        vec![message.with(tags::TRANSACT_TIME, transact_time(&report))]
+    }
+
+    /// Place what is left of `replacement` now that `original` is cancelled
+    async fn replace(&mut self, original: Order, mut replacement: Order, exec_id: String) -> Vec<Message> {
+        replacement.orig_cl_ord_id = Some(original.cl_ord_id.clone());
+        replacement.prior_qty = original.cum_qty();
+        replacement.prior_notional = original.prior_notional + original.notional;
+        replacement.replacing = true;
+        // Reports the original as cancelled under the replace's ClOrdID
+        let cancelled = |text: &str| {
+            original
+                .report(exec_id.clone(), "4", "4")
+                .with(tags::CL_ORD_ID, &replacement.cl_ord_id)
+                .with(tags::ORIG_CL_ORD_ID, &original.cl_ord_id)
+                .with(tags::TEXT, text)
+                .with(tags::TRANSACT_TIME, timestamp())
+        };
+
+        let remaining = replacement.order_qty - replacement.prior_qty;
+        if remaining <= Decimal::ZERO {
+            return vec![cancelled(&format!("Replacement OrderQty is not above the {} already filled", replacement.prior_qty))];
+        }
+        info!("Replacing {} with {} for {}", original.cl_ord_id, replacement.cl_ord_id, remaining);
+        match self.place(replacement.clone(), remaining).await {
+            Ok(()) => Vec::new(),
+            Err((_, text)) => {
+                warn!("Replacement {} rejected after cancelling {}: {}", replacement.cl_ord_id, original.cl_ord_id, text);
+                vec![cancelled(&format!("Replacement rejected: {}", text))]
+            }
+        }
+    }
+
+    fn finish(&mut self, order_id: &str) {
+        self.finished.push_back(order_id.to_string());
+        while self.finished.len() > FINISHED_LIMIT {
+            let Some(order_id) = self.finished.pop_front() else { break };
+            if self.orders.remove(&order_id).is_some() {
+                self.by_cl_ord_id.retain(|_, id| *id != order_id);
+            }
+        }
+    }
+}
+
+fn rejected(order: &Order, exec_id: String, reason: u32, text: &str) -> Message {
+    order
+        .report(exec_id, "8", "8")
+        .with(tags::ORD_REJ_REASON, reason)
+        .with(tags::TEXT, text)
+        .with(tags::TRANSACT_TIME, timestamp())
+}
+
+fn cancel_reject(message: &Message, replace: bool, reason: u32, text: &str) -> Message {
+    Message::new(msg_type::ORDER_CANCEL_REJECT)
+        .with(tags::ORDER_ID, "NONE")
+        .with_opt(tags::CL_ORD_ID, message.get(tags::CL_ORD_ID))
+        .with_opt(tags::ORIG_CL_ORD_ID, message.get(tags::ORIG_CL_ORD_ID))
+        .with(tags::ORD_STATUS, "8")
+        .with(tags::CXL_REJ_RESPONSE_TO, if replace { 2 } else { 1 })
+        .with(tags::CXL_REJ_REASON, reason)
+        .with(tags::TEXT, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_order_single() -> Message {
+        Message::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tags::CL_ORD_ID, "c2")
+            .with(tags::SYMBOL, "election")
+            .with(tags::SECURITY_SUB_TYPE, "YES")
+            .with(tags::SIDE, "1")
+            .with(tags::ORD_TYPE, "2")
+            .with(tags::ORDER_QTY, "10")
+            .with(tags::PRICE, "0.4")
+            .with(tags::MAX_FLOOR, "2")
+    }
+
+    fn execution(exec_type: pb::ExecType, status: pb::OrderStatus, filled: i64, last: Option<(i64, i32)>) -> pb::ExecutionReport {
+        pb::ExecutionReport {
+            order_id: "o2".to_string(),
+            exec_type: exec_type.into(),
+            status: status.into(),
+            filled_quantity: Some(pb::Decimal { units: filled, nanos: 0 }),
+            last_quantity: last.map(|(qty, _)| pb::Decimal { units: qty, nanos: 0 }),
+            last_price: last.map(|(_, nanos)| pb::Decimal { units: 0, nanos }),
+            trade_id: last.map(|_| "t1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_replacement_reports_carry_what_the_original_filled() {
+        let mut order = Order::parse(&new_order_single(), "user-1").unwrap();
+        assert_eq!(order.order_type, pb::OrderType::Iceberg);
+        assert_eq!(order.request(Decimal::from(6)).quantity, Some(pb::Decimal { units: 6, nanos: 0 }));
+        // What replace() sets up after the original filled 4 at 0.5
+        order.order_id = Some("o2".to_string());
+        order.orig_cl_ord_id = Some("c1".to_string());
+        order.prior_qty = Decimal::from(4);
+        order.prior_notional = Decimal::from(2);
+        order.replacing = true;
+
+        let accepted = order.apply(&execution(pb::ExecType::Accepted, pb::OrderStatus::Open, 0, None), "e1".to_string());
+        assert_eq!(accepted.get(tags::EXEC_TYPE), Some("5"));
+        assert_eq!(accepted.get(tags::ORIG_CL_ORD_ID), Some("c1"));
+        assert_eq!(accepted.get(tags::LEAVES_QTY), Some("6"));
+
+        let fill = execution(pb::ExecType::PartiallyFilled, pb::OrderStatus::Partial, 2, Some((2, 200_000_000)));
+        let filled = order.apply(&fill, "e2".to_string());
+        let fields: Vec<_> = [tags::EXEC_TYPE, tags::ORD_STATUS, tags::CUM_QTY, tags::LEAVES_QTY, tags::AVG_PX, tags::LAST_PX]
+            .iter()
+            .map(|tag| filled.get(*tag).unwrap())
+            .collect();
+        assert_eq!(fields, ["F", "1", "6", "4", "0.4", "0.2"]);
+
+        let bad = new_order_single().with(tags::EXEC_INST, "6");
+        assert_eq!(Order::parse(&bad, "user-1").unwrap_err().tag, tags::EXEC_INST);
+        let bad = new_order_single().with(tags::SECURITY_SUB_TYPE, "MAYBE");
+        assert_eq!(Order::parse(&bad, "user-1").unwrap_err().reason, VALUE_INCORRECT);
+    }
+}
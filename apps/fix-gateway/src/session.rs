@@ -0,0 +1,427 @@
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::message::{msg_type, tags, Message};
+
+// Application messages kept for resends; older ones are gap-filled instead
+const STORE_LIMIT: usize = 10_000;
+
+/// Sequence numbers and sent messages. Outlives the connection, so a
+/// counterparty that reconnects carries on where it left off.
+#[derive(Debug)]
+pub struct SessionState {
+    next_out: u64,
+    next_in: u64,
+    // Application messages by sequence number
+    sent: BTreeMap<u64, Message>,
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self { next_out: 1, next_in: 1, sent: BTreeMap::new() }
+    }
+}
+
+impl SessionState {
+    /// Sequence an application message for a counterparty that is not
+    /// connected. It goes out when they ask for a resend after logging on.
+    pub fn store(&mut self, comp_id: &str, target_comp_id: &str, mut message: Message) {
+        self.stamp(comp_id, target_comp_id, &mut message);
+    }
+
+    fn stamp(&mut self, comp_id: &str, target_comp_id: &str, message: &mut Message) {
+        let seq = self.next_out;
+        self.next_out += 1;
+        message
+            .set(tags::SENDER_COMP_ID, comp_id)
+            .set(tags::TARGET_COMP_ID, target_comp_id)
+            .set(tags::MSG_SEQ_NUM, seq)
+            .set(tags::SENDING_TIME, timestamp());
+        if !msg_type::is_admin(message.msg_type()) {
+            self.sent.insert(seq, message.clone());
+            while self.sent.len() > STORE_LIMIT {
+                self.sent.pop_first();
+            }
+        }
+    }
+}
+
+/// What the connection should do after a message came in
+#[derive(Debug, PartialEq)]
+pub enum Received {
+    /// An application message, in sequence
+    App(Message),
+    /// Dealt with at the session level, or dropped until it is resent
+    Handled,
+    /// Write out what is queued and close the connection
+    Disconnect,
+}
+
+/// FIX session layer for one logged-on counterparty: sequence numbers,
+/// heartbeats and test requests, resends and sequence resets. Does no I/O;
+/// whatever it sends is queued until `take_outbox`.
+pub struct Session {
+    comp_id: String,
+    target_comp_id: String,
+    state: SessionState,
+    heartbeat: Duration,
+    last_sent: Instant,
+    last_received: Instant,
+    // TestReqID we are waiting on a Heartbeat for
+    test_request: Option<String>,
+    // We asked for a resend up to this sequence number and are waiting on it
+    awaiting_resend: Option<u64>,
+    logging_out: bool,
+    outbox: Vec<Vec<u8>>,
+}
+
+impl Session {
+    pub fn new(comp_id: &str, target_comp_id: &str, state: SessionState, heartbeat: Duration, now: Instant) -> Self {
+        Self {
+            comp_id: comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+            state,
+            heartbeat,
+            last_sent: now,
+            last_received: now,
+            test_request: None,
+            awaiting_resend: None,
+            logging_out: false,
+            outbox: Vec::new(),
+        }
+    }
+
+    pub fn target_comp_id(&self) -> &str {
+        &self.target_comp_id
+    }
+
+    /// Give back the state to resume from on the next logon
+    pub fn into_state(self) -> SessionState {
+        self.state
+    }
+
+    pub fn take_outbox(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Stamp the header with the next sequence number and queue the message
+    pub fn send(&mut self, mut message: Message, now: Instant) {
+        self.state.stamp(&self.comp_id, &self.target_comp_id, &mut message);
+        self.outbox.push(message.encode());
+        self.last_sent = now;
+    }
+
+    /// Answer the Logon that opened the connection. The counterparty's
+    /// ResetSeqNumFlag starts both directions again from 1.
+    pub fn logon(&mut self, logon: &Message, now: Instant) -> Received {
+        self.last_received = now;
+        let reset = logon.flag(tags::RESET_SEQ_NUM_FLAG);
+        if reset {
+            info!("{} reset sequence numbers", self.target_comp_id);
+            self.state = SessionState::default();
+        }
+        let Some(seq) = logon.seq_num() else {
+            return self.logout_now("MsgSeqNum missing", now);
+        };
+        if seq < self.state.next_in {
+            let text = format!("MsgSeqNum too low, expecting {} but received {}", self.state.next_in, seq);
+            return self.logout_now(&text, now);
+        }
+
+        let reply = Message::new(msg_type::LOGON)
+            .with(tags::ENCRYPT_METHOD, 0)
+            .with(tags::HEART_BT_INT, self.heartbeat.as_secs())
+            .with_opt(tags::RESET_SEQ_NUM_FLAG, reset.then_some("Y"));
+        self.send(reply, now);
+        if seq > self.state.next_in {
+            self.request_resend(seq, now);
+        } else {
+            self.state.next_in += 1;
+        }
+        Received::Handled
+    }
+
+    pub fn receive(&mut self, message: Message, now: Instant) -> Received {
+        self.last_received = now;
+        self.test_request = None;
+        let Some(seq) = message.seq_num() else {
+            return self.logout_now("MsgSeqNum missing", now);
+        };
+        let kind = message.msg_type().to_string();
+
+        // Reset mode moves the expected number whatever the message's own is
+        if kind == msg_type::SEQUENCE_RESET && !message.flag(tags::GAP_FILL_FLAG) {
+            return self.sequence_reset(&message, now);
+        }
+        if seq > self.state.next_in {
+            // Answer resend requests even mid-gap, or both sides could wait forever
+            if kind == msg_type::RESEND_REQUEST {
+                self.resend(&message, now);
+            }
+            if self.awaiting_resend.is_none() {
+                self.request_resend(seq, now);
+            }
+            return Received::Handled;
+        }
+        if seq < self.state.next_in {
+            if message.flag(tags::POSS_DUP_FLAG) {
+                return Received::Handled;
+            }
+            let text = format!("MsgSeqNum too low, expecting {} but received {}", self.state.next_in, seq);
+            return self.logout_now(&text, now);
+        }
+
+        self.state.next_in += 1;
+        if self.awaiting_resend.is_some_and(|until| self.state.next_in > until) {
+            self.awaiting_resend = None;
+        }
+        match kind.as_str() {
+            msg_type::HEARTBEAT | msg_type::LOGON => Received::Handled,
+            msg_type::TEST_REQUEST => {
+                let reply = Message::new(msg_type::HEARTBEAT)
+                    .with_opt(tags::TEST_REQ_ID, message.get(tags::TEST_REQ_ID));
+                self.send(reply, now);
+                Received::Handled
+            }
+            msg_type::RESEND_REQUEST => {
+                self.resend(&message, now);
+                Received::Handled
+            }
+            msg_type::REJECT => {
+                warn!("{} rejected our message {}: {}", self.target_comp_id, message.get(tags::REF_SEQ_NUM).unwrap_or("?"), message);
+                Received::Handled
+            }
+            msg_type::SEQUENCE_RESET => self.sequence_reset(&message, now),
+            msg_type::LOGOUT => {
+                if !self.logging_out {
+                    self.send(Message::new(msg_type::LOGOUT), now);
+                }
+                Received::Disconnect
+            }
+            _ => Received::App(message),
+        }
+    }
+
+    /// Heartbeat when we have been quiet, probe a quiet counterparty with a
+    /// TestRequest, and give up on it if that goes unanswered. Returns false
+    /// once the connection should be dropped.
+    pub fn on_timer(&mut self, now: Instant) -> bool {
+        // Some slack for transmission time, as the spec suggests
+        let grace = self.heartbeat / 5;
+        let silent = now.saturating_duration_since(self.last_received);
+        if self.test_request.is_some() && silent > self.heartbeat * 2 + grace {
+            warn!("{} stopped responding", self.target_comp_id);
+            return false;
+        }
+        if self.test_request.is_none() && silent > self.heartbeat + grace {
+            let id = Utc::now().timestamp_millis().to_string();
+            self.send(Message::new(msg_type::TEST_REQUEST).with(tags::TEST_REQ_ID, &id), now);
+            self.test_request = Some(id);
+        }
+        if now.saturating_duration_since(self.last_sent) >= self.heartbeat {
+            self.send(Message::new(msg_type::HEARTBEAT), now);
+        }
+        true
+    }
+
+    /// Start an orderly logout; the connection closes once it is answered
+    pub fn logout(&mut self, text: &str, now: Instant) {
+        self.logging_out = true;
+        self.send(Message::new(msg_type::LOGOUT).with(tags::TEXT, text), now);
+    }
+
+    fn logout_now(&mut self, text: &str, now: Instant) -> Received {
+        warn!("Logging out {}: {}", self.target_comp_id, text);
+        self.logout(text, now);
+        Received::Disconnect
+    }
+
+    fn request_resend(&mut self, received: u64, now: Instant) {
+        info!("Gap from {}: expected {}, got {}", self.target_comp_id, self.state.next_in, received);
+        let request = Message::new(msg_type::RESEND_REQUEST)
+            .with(tags::BEGIN_SEQ_NO, self.state.next_in)
+            .with(tags::END_SEQ_NO, 0);
+        self.send(request, now);
+        self.awaiting_resend = Some(received);
+    }
+
+    fn sequence_reset(&mut self, message: &Message, now: Instant) -> Received {
+        let new_seq = message.get(tags::NEW_SEQ_NO).and_then(|n| n.parse::<u64>().ok());
+        match new_seq {
+            Some(new_seq) if new_seq >= self.state.next_in => {
+                self.state.next_in = new_seq;
+                if self.awaiting_resend.is_some_and(|until| new_seq > until) {
+                    self.awaiting_resend = None;
+                }
+            }
+            // Gap fills can't move the expected number backwards
+            _ => {
+                let reject = Message::new(msg_type::REJECT)
+                    .with_opt(tags::REF_SEQ_NUM, message.get(tags::MSG_SEQ_NUM))
+                    .with(tags::SESSION_REJECT_REASON, 5)
+                    .with(tags::TEXT, "NewSeqNo must not be lower than the expected MsgSeqNum");
+                self.send(reject, now);
+            }
+        }
+        Received::Handled
+    }
+
+    /// Replay application messages in the requested range as possible
+    /// duplicates, gap-filling over session messages and anything no longer
+    /// stored
+    fn resend(&mut self, request: &Message, now: Instant) {
+        let last_sent = self.state.next_out - 1;
+        let begin = request.get(tags::BEGIN_SEQ_NO).and_then(|n| n.parse::<u64>().ok()).unwrap_or(1).max(1);
+        let end = match request.get(tags::END_SEQ_NO).and_then(|n| n.parse::<u64>().ok()) {
+            Some(0) | None => last_sent,
+            Some(end) => end.min(last_sent),
+        };
+        info!("{} asked for {}..={}", self.target_comp_id, begin, end);
+
+        let stored: Vec<(u64, Message)> =
+            self.state.sent.range(begin..=end).map(|(seq, m)| (*seq, m.clone())).collect();
+        let mut next = begin;
+        for (seq, mut message) in stored {
+            if seq > next {
+                self.outbox.push(self.gap_fill(next, seq).encode());
+            }
+            let original_time = message.get(tags::SENDING_TIME).unwrap_or_default().to_string();
+            message
+                .set(tags::POSS_DUP_FLAG, "Y")
+                .set(tags::ORIG_SENDING_TIME, original_time)
+                .set(tags::SENDING_TIME, timestamp());
+            self.outbox.push(message.encode());
+            next = seq + 1;
+        }
+        if next <= end {
+            self.outbox.push(self.gap_fill(next, end + 1).encode());
+        }
+        self.last_sent = now;
+    }
+
+    fn gap_fill(&self, seq: u64, new_seq: u64) -> Message {
+        Message::new(msg_type::SEQUENCE_RESET)
+            .with(tags::SENDER_COMP_ID, &self.comp_id)
+            .with(tags::TARGET_COMP_ID, &self.target_comp_id)
+            .with(tags::MSG_SEQ_NUM, seq)
+            .with(tags::POSS_DUP_FLAG, "Y")
+            .with(tags::SENDING_TIME, timestamp())
+            .with(tags::GAP_FILL_FLAG, "Y")
+            .with(tags::NEW_SEQ_NO, new_seq)
+    }
+}
+
+/// UTCTimestamp with milliseconds
+pub fn timestamp() -> String {
+    Utc::now().format("%Y%m%d-%H:%M:%S%.3f").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Decoder;
+
+    fn inbound(kind: &str, seq: u64) -> Message {
+        Message::new(kind).with(tags::SENDER_COMP_ID, "MM1").with(tags::MSG_SEQ_NUM, seq)
+    }
+
+    fn sent(session: &mut Session) -> Vec<Message> {
+        let mut decoder = Decoder::default();
+        for bytes in session.take_outbox() {
+            decoder.extend(&bytes);
+        }
+        std::iter::from_fn(|| decoder.next_message().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_gap_asks_for_a_resend_and_recovers_on_gap_fill() {
+        let now = Instant::now();
+        let mut session = Session::new("EXCHANGE", "MM1", SessionState::default(), Duration::from_secs(30), now);
+        assert_eq!(session.logon(&inbound(msg_type::LOGON, 1), now), Received::Handled);
+
+        let order = inbound(msg_type::NEW_ORDER_SINGLE, 4);
+        assert_eq!(session.receive(order.clone(), now), Received::Handled);
+        let out = sent(&mut session);
+        assert_eq!(out[0].msg_type(), msg_type::LOGON);
+        assert_eq!(out[1].msg_type(), msg_type::RESEND_REQUEST);
+        assert_eq!(out[1].get(tags::BEGIN_SEQ_NO), Some("2"));
+        // Only asked once while the gap is open
+        assert_eq!(session.receive(inbound(msg_type::HEARTBEAT, 5), now), Received::Handled);
+        assert!(sent(&mut session).is_empty());
+
+        let gap_fill = inbound(msg_type::SEQUENCE_RESET, 2)
+            .with(tags::POSS_DUP_FLAG, "Y")
+            .with(tags::GAP_FILL_FLAG, "Y")
+            .with(tags::NEW_SEQ_NO, 4);
+        assert_eq!(session.receive(gap_fill, now), Received::Handled);
+        let resent = order.with(tags::POSS_DUP_FLAG, "Y");
+        assert_eq!(session.receive(resent.clone(), now), Received::App(resent));
+
+        let stale = inbound(msg_type::HEARTBEAT, 3);
+        assert_eq!(session.receive(stale, now), Received::Disconnect);
+        assert_eq!(sent(&mut session)[0].msg_type(), msg_type::LOGOUT);
+    }
+
+    #[test]
+    fn test_reports_stored_while_disconnected_are_resent_after_logon() {
+        let now = Instant::now();
+        let mut session = Session::new("EXCHANGE", "MM1", SessionState::default(), Duration::from_secs(30), now);
+        session.logon(&inbound(msg_type::LOGON, 1), now);
+        sent(&mut session);
+        let mut state = session.into_state();
+        state.store("EXCHANGE", "MM1", Message::new(msg_type::EXECUTION_REPORT).with(tags::EXEC_ID, "e1"));
+        state.store("EXCHANGE", "MM1", Message::new(msg_type::EXECUTION_REPORT).with(tags::EXEC_ID, "e2"));
+
+        // Our Logon is 4, so the counterparty asks for 2 and 3
+        let mut session = Session::new("EXCHANGE", "MM1", state, Duration::from_secs(30), now);
+        session.logon(&inbound(msg_type::LOGON, 2), now);
+        assert_eq!(sent(&mut session)[0].get(tags::MSG_SEQ_NUM), Some("4"));
+        let request = inbound(msg_type::RESEND_REQUEST, 3).with(tags::BEGIN_SEQ_NO, 2).with(tags::END_SEQ_NO, 3);
+        session.receive(request, now);
+        let resent: Vec<(Option<String>, Option<String>)> = sent(&mut session)
+            .iter()
+            .map(|m| (m.get(tags::MSG_SEQ_NUM).map(str::to_string), m.get(tags::EXEC_ID).map(str::to_string)))
+            .collect();
+        assert_eq!(
+            resent,
+            vec![(Some("2".to_string()), Some("e1".to_string())), (Some("3".to_string()), Some("e2".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_resend_replays_application_messages_and_gap_fills_the_rest() {
+        let now = Instant::now();
+        let mut session = Session::new("EXCHANGE", "MM1", SessionState::default(), Duration::from_secs(30), now);
+        session.logon(&inbound(msg_type::LOGON, 1), now);
+        session.send(Message::new(msg_type::EXECUTION_REPORT).with(tags::EXEC_ID, "e1"), now);
+        session.send(Message::new(msg_type::HEARTBEAT), now);
+        session.send(Message::new(msg_type::HEARTBEAT), now);
+        session.send(Message::new(msg_type::EXECUTION_REPORT).with(tags::EXEC_ID, "e2"), now);
+        sent(&mut session);
+
+        let request = inbound(msg_type::RESEND_REQUEST, 2).with(tags::BEGIN_SEQ_NO, 1).with(tags::END_SEQ_NO, 0);
+        session.receive(request, now);
+        let replayed: Vec<(String, Option<String>, Option<String>)> = sent(&mut session)
+            .iter()
+            .map(|m| {
+                (
+                    m.msg_type().to_string(),
+                    m.get(tags::MSG_SEQ_NUM).map(str::to_string),
+                    m.get(tags::NEW_SEQ_NO).or(m.get(tags::EXEC_ID)).map(str::to_string),
+                )
+            })
+            .collect();
+        let expect = |kind: &str, seq: &str, detail: &str| (kind.to_string(), Some(seq.to_string()), Some(detail.to_string()));
+        assert_eq!(
+            replayed,
+            vec![
+                expect(msg_type::SEQUENCE_RESET, "1", "2"),
+                expect(msg_type::EXECUTION_REPORT, "2", "e1"),
+                expect(msg_type::SEQUENCE_RESET, "3", "5"),
+                expect(msg_type::EXECUTION_REPORT, "5", "e2"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::engine::{decimal_from_proto, pb};
+
+/// Displayed quantity and order count at one price
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Level {
+    pub quantity: Decimal,
+    pub orders: u32,
+}
+
+#[derive(Default)]
+struct Sides {
+    bids: BTreeMap<Decimal, Level>,
+    asks: BTreeMap<Decimal, Level>,
+}
+
+/// Level-2 view of one market, built from the engine's snapshot and kept up
+/// to date with its updates
+pub struct Book {
+    // Indexed by outcome
+    outcomes: Vec<Sides>,
+}
+
+impl Book {
+    pub fn new(outcome_count: usize) -> Self {
+        Self { outcomes: (0..outcome_count).map(|_| Sides::default()).collect() }
+    }
+
+    pub fn outcome_count(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    /// Set one level as the engine reports it; zero quantity removes it
+    pub fn apply(&mut self, level: &pb::BookLevel) {
+        let Some(sides) = self.outcomes.get_mut(level.outcome_index as usize) else {
+            return;
+        };
+        let side = match level.side() {
+            pb::Side::Buy => &mut sides.bids,
+            pb::Side::Sell => &mut sides.asks,
+            pb::Side::Unspecified => return,
+        };
+        let price = decimal_from_proto(level.price.as_ref());
+        let quantity = decimal_from_proto(level.quantity.as_ref());
+        if quantity.is_zero() {
+            side.remove(&price);
+        } else {
+            side.insert(price, Level { quantity, orders: level.order_count });
+        }
+    }
+
+    pub fn best_bid(&self, outcome: usize) -> Option<(Decimal, Level)> {
+        self.outcomes.get(outcome)?.bids.iter().next_back().map(|(p, l)| (*p, *l))
+    }
+
+    pub fn best_ask(&self, outcome: usize) -> Option<(Decimal, Level)> {
+        self.outcomes.get(outcome)?.asks.iter().next().map(|(p, l)| (*p, *l))
+    }
+
+    /// Every level of every outcome as `[price, quantity, orders]`, best
+    /// price first on both sides
+    pub fn to_json(&self) -> Value {
+        let row = |(price, level): (&Decimal, &Level)| json!([price, level.quantity, level.orders]);
+        let outcomes: Vec<Value> = self
+            .outcomes
+            .iter()
+            .enumerate()
+            .map(|(outcome, sides)| {
+                json!({
+                    "outcome": outcome,
+                    "bids": sides.bids.iter().rev().map(row).collect::<Vec<_>>(),
+                    "asks": sides.asks.iter().map(row).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        Value::Array(outcomes)
+    }
+}
+
+/// One entry of a book update's `changes`
+pub fn change_json(level: &pb::BookLevel) -> Value {
+    json!({
+        "outcome": level.outcome_index,
+        "side": side_name(level.side()),
+        "price": decimal_from_proto(level.price.as_ref()),
+        "quantity": decimal_from_proto(level.quantity.as_ref()),
+        "orders": level.order_count,
+    })
+}
+
+pub fn side_name(side: pb::Side) -> Option<&'static str> {
+    match side {
+        pb::Side::Buy => Some("buy"),
+        pb::Side::Sell => Some("sell"),
+        pb::Side::Unspecified => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(side: pb::Side, price: i32, quantity: i64, orders: u32) -> pb::BookLevel {
+        pb::BookLevel {
+            side: side.into(),
+            outcome_index: 0,
+            price: Some(pb::Decimal { units: 0, nanos: price * 10_000_000 }),
+            quantity: Some(pb::Decimal { units: quantity, nanos: 0 }),
+            order_count: orders,
+        }
+    }
+
+    #[test]
+    fn test_levels_replace_and_remove() {
+        let mut book = Book::new(2);
+        book.apply(&level(pb::Side::Buy, 40, 10, 1));
+        book.apply(&level(pb::Side::Buy, 45, 5, 2));
+        book.apply(&level(pb::Side::Sell, 60, 7, 1));
+        assert_eq!(book.best_bid(0).map(|(p, l)| (p.to_string(), l.orders)), Some(("0.45".to_string(), 2)));
+
+        book.apply(&level(pb::Side::Buy, 45, 0, 0));
+        book.apply(&level(pb::Side::Sell, 60, 3, 1));
+        assert_eq!(book.best_bid(0).map(|(p, _)| p.to_string()), Some("0.4".to_string()));
+        assert_eq!(book.best_ask(0).map(|(_, l)| l.quantity), Some(Decimal::from(3)));
+        assert_eq!(book.best_bid(1), None);
+        assert_eq!(
+            book.to_json()[0],
+            json!({"outcome": 0, "bids": [["0.4", "10", 1]], "asks": [["0.6", "3", 1]]})
+        );
+    }
+}
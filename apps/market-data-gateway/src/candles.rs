@@ -0,0 +1,182 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+
+// Candles kept per outcome and interval: a day of one-minute candles, which
+// the ticker's 24h figures are taken from
+const HISTORY: usize = 1440;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Interval {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    FourHours,
+    OneDay,
+}
+
+impl Interval {
+    pub const ALL: [Interval; 6] = [
+        Interval::OneMinute,
+        Interval::FiveMinutes,
+        Interval::FifteenMinutes,
+        Interval::OneHour,
+        Interval::FourHours,
+        Interval::OneDay,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|interval| interval.as_str() == value)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Interval::OneMinute => "1m",
+            Interval::FiveMinutes => "5m",
+            Interval::FifteenMinutes => "15m",
+            Interval::OneHour => "1h",
+            Interval::FourHours => "4h",
+            Interval::OneDay => "1d",
+        }
+    }
+
+    fn seconds(self) -> i64 {
+        match self {
+            Interval::OneMinute => 60,
+            Interval::FiveMinutes => 300,
+            Interval::FifteenMinutes => 900,
+            Interval::OneHour => 3600,
+            Interval::FourHours => 14400,
+            Interval::OneDay => 86400,
+        }
+    }
+
+    /// Start of the candle `at` falls in; days start at midnight UTC
+    pub fn start(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let secs = at.timestamp();
+        Utc.timestamp_opt(secs - secs.rem_euclid(self.seconds()), 0).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Candle {
+    pub outcome: u32,
+    pub start: DateTime<Utc>,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+}
+
+/// Open, high, low and volume over the last 24 hours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DayStats {
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub volume: Decimal,
+}
+
+/// OHLCV candles for every outcome and interval, built from the trades the
+/// gateway has seen since it started following the market. Intervals without
+/// trades have no candle.
+#[derive(Default)]
+pub struct Candles {
+    series: BTreeMap<(u32, Interval), VecDeque<Candle>>,
+}
+
+impl Candles {
+    /// Add a trade to each interval's candle, returning those candles as
+    /// they now stand
+    pub fn record(&mut self, outcome: u32, price: Decimal, quantity: Decimal, at: DateTime<Utc>) -> Vec<(Interval, Candle)> {
+        Interval::ALL
+            .into_iter()
+            .map(|interval| {
+                let series = self.series.entry((outcome, interval)).or_default();
+                let start = interval.start(at);
+                match series.back_mut() {
+                    // A trade stamped a moment before the one ahead of it
+                    // still lands in the latest candle
+                    Some(candle) if candle.start >= start => {
+                        candle.high = candle.high.max(price);
+                        candle.low = candle.low.min(price);
+                        candle.close = price;
+                        candle.volume += quantity;
+                    }
+                    _ => {
+                        if series.len() == HISTORY {
+                            series.pop_front();
+                        }
+                        series.push_back(Candle {
+                            outcome,
+                            start,
+                            open: price,
+                            high: price,
+                            low: price,
+                            close: price,
+                            volume: quantity,
+                        });
+                    }
+                }
+                (interval, series.back().unwrap().clone())
+            })
+            .collect()
+    }
+
+    /// Every candle for `interval`, by outcome, oldest first
+    pub fn history(&self, interval: Interval) -> Vec<Candle> {
+        self.series
+            .iter()
+            .filter(|((_, i), _)| *i == interval)
+            .flat_map(|(_, series)| series.iter().cloned())
+            .collect()
+    }
+
+    /// The 24 hours to `now`, to the minute; None if nothing traded
+    pub fn day(&self, outcome: u32, now: DateTime<Utc>) -> Option<DayStats> {
+        let since = Interval::OneMinute.start(now - Duration::days(1));
+        let series = self.series.get(&(outcome, Interval::OneMinute))?;
+        let mut candles = series.iter().filter(|candle| candle.start > since);
+        let first = candles.next()?;
+        let stats = DayStats { open: first.open, high: first.high, low: first.low, volume: first.volume };
+        Some(candles.fold(stats, |stats, candle| DayStats {
+            open: stats.open,
+            high: stats.high.max(candle.high),
+            low: stats.low.min(candle.low),
+            volume: stats.volume + candle.volume,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(h: u32, m: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 2, h, m, s).unwrap()
+    }
+
+    #[test]
+    fn test_trades_roll_into_candles_and_day_stats() {
+        let mut candles = Candles::default();
+        let d = |v: i64| Decimal::new(v, 2);
+        candles.record(0, d(50), d(100), at(9, 0, 10));
+        candles.record(0, d(55), d(100), at(9, 0, 50));
+        let latest = candles.record(0, d(45), d(200), at(9, 3, 0));
+
+        let five = latest.iter().find(|(i, _)| *i == Interval::FiveMinutes).map(|(_, c)| c.clone()).unwrap();
+        assert_eq!((five.start, five.open, five.high, five.low, five.close), (at(9, 0, 0), d(50), d(55), d(45), d(45)));
+        assert_eq!(five.volume, d(400));
+        assert_eq!(candles.history(Interval::OneMinute).len(), 2);
+
+        let day = candles.day(0, at(9, 5, 0)).unwrap();
+        assert_eq!((day.open, day.high, day.low, day.volume), (d(50), d(55), d(45), d(400)));
+        // The 09:00 candle has rolled out of the window
+        let later = candles.day(0, at(9, 0, 30) + Duration::days(1)).unwrap();
+        assert_eq!((later.open, later.volume), (d(45), d(200)));
+        assert_eq!(candles.day(1, at(9, 5, 0)), None);
+    }
+}
@@ -0,0 +1,45 @@
+use anyhow::Result;
+use std::env;
+
+pub struct Config {
+    // WebSocket server
+    pub port: u16,
+    // Connections beyond this are turned away with 503
+    pub max_connections: usize,
+    // Channels one connection may be subscribed to at once
+    pub max_subscriptions: usize,
+    // Messages queued for a connection before it is dropped as a slow consumer
+    pub outbox_size: usize,
+
+    // Matching engine
+    pub matching_engine_url: String,
+    pub matching_engine_token: Option<String>,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        Ok(Self {
+            port: env::var("WS_PORT")
+                .unwrap_or_else(|_| "8090".to_string())
+                .parse()
+                .unwrap_or(8090),
+            max_connections: env::var("WS_MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "10000".to_string())
+                .parse()
+                .unwrap_or(10000),
+            max_subscriptions: env::var("WS_MAX_SUBSCRIPTIONS")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()
+                .unwrap_or(50),
+            outbox_size: env::var("WS_OUTBOX_SIZE")
+                .unwrap_or_else(|_| "256".to_string())
+                .parse()
+                .unwrap_or(256),
+            matching_engine_url: env::var("MATCHING_ENGINE_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:50052".to_string()),
+            matching_engine_token: env::var("MATCHING_ENGINE_TOKEN").ok().filter(|t| !t.is_empty()),
+        })
+    }
+}
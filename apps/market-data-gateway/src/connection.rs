@@ -0,0 +1,235 @@
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures_util::{Sink, SinkExt, StreamExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc, Notify};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::feed::Markets;
+use crate::market::{Channel, Frame};
+use crate::protocol::{self, Request};
+
+// Policy Violation, the closest standard code to "you read too slowly"
+const SLOW_CONSUMER: u16 = 1008;
+// A slow consumer's socket may not take the close frame either
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Serves WebSocket connections from the markets being followed
+pub struct Gateway {
+    markets: Arc<Markets>,
+    connections: AtomicUsize,
+    max_connections: usize,
+    max_subscriptions: usize,
+    outbox_size: usize,
+}
+
+/// Holds one of the gateway's connection slots until dropped
+pub struct Slot(Arc<Gateway>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Gateway {
+    pub fn new(config: &Config, markets: Arc<Markets>) -> Self {
+        Self {
+            markets,
+            connections: AtomicUsize::new(0),
+            max_connections: config.max_connections,
+            max_subscriptions: config.max_subscriptions,
+            outbox_size: config.outbox_size,
+        }
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// A slot for one more connection, if there is room
+    pub fn admit(self: &Arc<Self>) -> Option<Slot> {
+        let admitted = self
+            .connections
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| (n < self.max_connections).then_some(n + 1))
+            .is_ok();
+        admitted.then(|| Slot(self.clone()))
+    }
+
+    pub async fn serve(&self, socket: WebSocket, _slot: Slot) {
+        let (sink, mut incoming) = socket.split();
+        let (outbox, queued) = mpsc::channel(self.outbox_size);
+        let slow = Arc::new(Notify::new());
+        let mut writer = tokio::spawn(write(sink, queued, slow.clone()));
+        let mut connection = Connection { outbox, slow, subscriptions: HashMap::new() };
+
+        loop {
+            tokio::select! {
+                message = incoming.next() => match message {
+                    Some(Ok(Message::Text(text))) => self.handle(&mut connection, &text).await,
+                    Some(Ok(Message::Binary(_))) => connection.send(protocol::error(None, "Send requests as text")),
+                    // Pings are answered for us
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => {}
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                },
+                // The socket failed, or the client was too slow
+                _ = &mut writer => break,
+            }
+        }
+        for (_, task) in connection.subscriptions.drain() {
+            task.abort();
+        }
+        writer.abort();
+    }
+
+    async fn handle(&self, connection: &mut Connection, text: &str) {
+        let request = match serde_json::from_str::<Request>(text) {
+            Ok(request) => request,
+            Err(e) => return connection.send(protocol::error(None, &format!("Invalid request: {}", e))),
+        };
+        match request {
+            Request::Ping => connection.send(protocol::pong()),
+            Request::Subscribe(target) => {
+                let channel = match target.channel() {
+                    Ok(channel) => channel,
+                    Err(message) => return connection.send(protocol::error(Some(&target), &message)),
+                };
+                let key = (channel, target.market_id.clone());
+                if connection.subscriptions.contains_key(&key) {
+                    return connection.send(protocol::error(Some(&target), "Already subscribed"));
+                }
+                if connection.subscriptions.len() >= self.max_subscriptions {
+                    return connection.send(protocol::error(Some(&target), "Too many subscriptions"));
+                }
+                let (snapshot, receiver) = match self.markets.subscribe(&target.market_id, channel).await {
+                    Ok(subscribed) => subscribed,
+                    Err(status) => return connection.send(protocol::error(Some(&target), status.message())),
+                };
+                debug!("Subscribed to {:?} in {}", channel, target.market_id);
+                connection.send(protocol::subscribed(&target));
+                connection.queue(snapshot);
+                let task = tokio::spawn(forward(receiver, connection.outbox.clone(), connection.slow.clone()));
+                connection.subscriptions.insert(key, task);
+            }
+            Request::Unsubscribe(target) => {
+                let removed = target
+                    .channel()
+                    .ok()
+                    .and_then(|channel| connection.subscriptions.remove(&(channel, target.market_id.clone())));
+                match removed {
+                    Some(task) => {
+                        task.abort();
+                        connection.send(protocol::unsubscribed(&target));
+                    }
+                    None => connection.send(protocol::error(Some(&target), "Not subscribed")),
+                }
+            }
+        }
+    }
+}
+
+struct Connection {
+    outbox: mpsc::Sender<Frame>,
+    // Told when the outbox overflows, and the connection should go
+    slow: Arc<Notify>,
+    subscriptions: HashMap<(Channel, String), JoinHandle<()>>,
+}
+
+impl Connection {
+    fn send(&self, reply: Value) {
+        self.queue(reply.to_string().into());
+    }
+
+    fn queue(&self, frame: Frame) {
+        enqueue(&self.outbox, frame, &self.slow);
+    }
+}
+
+/// Put a frame in the outbox without waiting; false if the connection is
+/// done for
+fn enqueue(outbox: &mpsc::Sender<Frame>, frame: Frame, slow: &Notify) -> bool {
+    match outbox.try_send(frame) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_)) => {
+            slow.notify_one();
+            false
+        }
+        Err(TrySendError::Closed(_)) => false,
+    }
+}
+
+/// Move one subscription's frames into the connection's outbox
+async fn forward(mut receiver: broadcast::Receiver<Frame>, outbox: mpsc::Sender<Frame>, slow: Arc<Notify>) {
+    loop {
+        match receiver.recv().await {
+            Ok(frame) => {
+                if !enqueue(&outbox, frame, &slow) {
+                    return;
+                }
+            }
+            Err(RecvError::Lagged(_)) => return slow.notify_one(),
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Write out the outbox until the socket goes or the client can't keep up.
+/// A client that stops reading stalls the send, so that races the signal too.
+async fn write(mut sink: impl Sink<Message> + Unpin, mut queued: mpsc::Receiver<Frame>, slow: Arc<Notify>) {
+    loop {
+        let frame = tokio::select! {
+            frame = queued.recv() => frame,
+            _ = slow.notified() => break,
+        };
+        let Some(frame) = frame else { return };
+        tokio::select! {
+            sent = sink.send(Message::Text(frame.to_string())) => {
+                if sent.is_err() {
+                    return;
+                }
+            }
+            _ = slow.notified() => break,
+        }
+    }
+    let close = Message::Close(Some(CloseFrame { code: SLOW_CONSUMER, reason: "slow consumer".into() }));
+    let _ = tokio::time::timeout(CLOSE_TIMEOUT, sink.send(close)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_overflowing_the_outbox_closes_with_1008() {
+        let (outbox, queued) = mpsc::channel(1);
+        let slow = Arc::new(Notify::new());
+        assert!(enqueue(&outbox, "first".into(), &slow));
+        assert!(!enqueue(&outbox, "second".into(), &slow));
+
+        let (sent, mut written) = mpsc::unbounded_channel();
+        let sink = futures_util::sink::unfold(sent, |sent, message: Message| async move {
+            sent.send(message).map(|_| sent)
+        });
+        write(Box::pin(sink), queued, slow).await;
+
+        let mut messages = Vec::new();
+        while let Ok(message) = written.try_recv() {
+            messages.push(message);
+        }
+        assert!(!messages.iter().any(|m| matches!(m, Message::Text(text) if text == "second")));
+        match messages.last() {
+            Some(Message::Close(Some(close))) => {
+                assert_eq!(close.code, SLOW_CONSUMER);
+                assert_eq!(close.reason, "slow consumer");
+            }
+            other => panic!("Expected a close frame, got {:?}", other),
+        }
+    }
+}
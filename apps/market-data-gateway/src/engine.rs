@@ -0,0 +1,53 @@
+use anyhow::Result;
+use rust_decimal::Decimal;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::Channel;
+use tonic::{Request, Status, Streaming};
+
+pub mod pb {
+    tonic::include_proto!("matching_engine.v2");
+}
+
+use pb::matching_engine_client::MatchingEngineClient;
+
+pub type EngineClient = MatchingEngineClient<InterceptedService<Channel, BearerToken>>;
+
+/// Sends MATCHING_ENGINE_TOKEN on every call when one is configured
+#[derive(Clone)]
+pub struct BearerToken(Option<MetadataValue<Ascii>>);
+
+impl Interceptor for BearerToken {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.0 {
+            request.metadata_mut().insert("authorization", token.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Connects on first use, so the gateway can start before the engine
+pub fn connect(url: &str, token: Option<&str>) -> Result<EngineClient> {
+    let channel = Channel::from_shared(url.to_string())?.connect_lazy();
+    let token = token.map(|t| format!("Bearer {}", t).parse()).transpose()?;
+    Ok(MatchingEngineClient::with_interceptor(channel, BearerToken(token)))
+}
+
+/// A market's data stream along with the snapshot it opens with
+pub async fn subscribe(
+    client: &mut EngineClient,
+    market_id: &str,
+) -> Result<(pb::MarketDataUpdate, Streaming<pb::MarketDataUpdate>), Status> {
+    let request = pb::SubscribeMarketDataRequest { market_id: market_id.to_string() };
+    let mut stream = client.subscribe_market_data(request).await?.into_inner();
+    match stream.message().await? {
+        Some(snapshot) if snapshot.snapshot => Ok((snapshot, stream)),
+        Some(_) => Err(Status::internal("Market data stream did not open with a snapshot")),
+        None => Err(Status::unavailable("Market data stream ended")),
+    }
+}
+
+pub fn decimal_from_proto(value: Option<&pb::Decimal>) -> Decimal {
+    value.map_or(Decimal::ZERO, |value| Decimal::from(value.units) + Decimal::new(value.nanos as i64, 9)).normalize()
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+use tonic::{Code, Status, Streaming};
+use tracing::{info, warn};
+
+use crate::engine::{self, pb, EngineClient};
+use crate::market::{Channel, Frame, Market};
+
+// Wait before resubscribing after the stream drops
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+// How often a followed market checks whether anyone is still watching it
+// and rolls its ticker forward
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(60);
+// Other subscriptions wait while a new market's stream opens, so don't let
+// that take long
+const OPEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The markets connections are watching. Each is followed over one engine
+/// stream, opened for its first subscriber and closed once nobody has
+/// watched it for a while.
+pub struct Markets {
+    client: EngineClient,
+    // Subscribing and retiring both hold this, so a market can't be retired
+    // between being looked up and subscribed to
+    markets: Mutex<HashMap<String, Arc<Market>>>,
+}
+
+impl Markets {
+    pub fn new(client: EngineClient) -> Arc<Self> {
+        Arc::new(Self { client, markets: Mutex::new(HashMap::new()) })
+    }
+
+    /// The snapshot for `channel` and a receiver for what follows it
+    pub async fn subscribe(self: &Arc<Self>, market_id: &str, channel: Channel) -> Result<(Frame, broadcast::Receiver<Frame>), Status> {
+        let mut markets = self.markets.lock().await;
+        if let Some(market) = markets.get(market_id) {
+            return Ok(market.subscribe(channel));
+        }
+
+        let (snapshot, stream) = tokio::time::timeout(OPEN_TIMEOUT, engine::subscribe(&mut self.client.clone(), market_id))
+            .await
+            .map_err(|_| Status::unavailable("Matching engine did not answer in time"))??;
+        let market = Arc::new(Market::new(market_id, &snapshot));
+        info!("Following {}", market_id);
+        markets.insert(market_id.to_string(), market.clone());
+        let subscribed = market.subscribe(channel);
+        tokio::spawn(self.clone().follow(market, stream));
+        Ok(subscribed)
+    }
+
+    /// Stop following `market` if nobody is watching it
+    async fn retire(&self, market: &Market) -> bool {
+        let mut markets = self.markets.lock().await;
+        if !market.is_idle() {
+            return false;
+        }
+        markets.remove(market.market_id());
+        info!("Stopped following {}", market.market_id());
+        true
+    }
+
+    async fn follow(self: Arc<Self>, market: Arc<Market>, mut stream: Streaming<pb::MarketDataUpdate>) {
+        let market_id = market.market_id().to_string();
+        let mut housekeeping = tokio::time::interval(HOUSEKEEPING_INTERVAL);
+        housekeeping.tick().await;
+        loop {
+            let failure = tokio::select! {
+                message = stream.message() => match message {
+                    Ok(Some(update)) => match market.apply(&update) {
+                        Ok(()) => continue,
+                        Err(gap) => format!("skipped from seq {} to {}", gap.expected, gap.received),
+                    },
+                    Ok(None) => "ended".to_string(),
+                    Err(status) if status.code() == Code::DataLoss => "fell behind".to_string(),
+                    Err(status) => format!("failed: {}", status),
+                },
+                _ = housekeeping.tick() => {
+                    if self.retire(&market).await {
+                        return;
+                    }
+                    market.tick();
+                    continue;
+                }
+            };
+            warn!("Market data stream for {} {}", market_id, failure);
+
+            loop {
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                if self.retire(&market).await {
+                    return;
+                }
+                match engine::subscribe(&mut self.client.clone(), &market_id).await {
+                    Ok((snapshot, resubscribed)) => {
+                        info!("Resubscribed to market data for {}", market_id);
+                        let _ = market.apply(&snapshot);
+                        stream = resubscribed;
+                        break;
+                    }
+                    Err(status) => warn!("Resubscribing to {} failed: {}", market_id, status),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+mod book;
+mod candles;
+mod config;
+mod connection;
+mod engine;
+mod feed;
+mod market;
+mod protocol;
+
+use config::Config;
+use connection::Gateway;
+use feed::Markets;
+
+// Client requests are small; anything bigger is a mistake or an attack
+const MAX_MESSAGE_SIZE: usize = 16 * 1024;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Initialize logging
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    info!("🚀 Starting Market Data Gateway");
+
+    let config = Config::from_env()?;
+    let client = engine::connect(&config.matching_engine_url, config.matching_engine_token.as_deref())?;
+    info!("✅ Matching engine: {}", config.matching_engine_url);
+
+    let gateway = Arc::new(Gateway::new(&config, Markets::new(client)));
+    let app = Router::new()
+        .route("/ws", get(upgrade))
+        .route("/health", get(|| async { "OK" }))
+        .with_state(gateway);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    info!("✅ Serving WebSocket market data on {} (up to {} connections)", addr, config.max_connections);
+    axum::Server::bind(&addr).serve(app.into_make_service()).await?;
+    Ok(())
+}
+
+async fn upgrade(ws: WebSocketUpgrade, State(gateway): State<Arc<Gateway>>) -> Response {
+    let Some(slot) = gateway.admit() else {
+        warn!("Turned a connection away at {} connections", gateway.connections());
+        return (StatusCode::SERVICE_UNAVAILABLE, "Too many connections").into_response();
+    };
+    ws.max_message_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| async move { gateway.serve(socket, slot).await })
+}
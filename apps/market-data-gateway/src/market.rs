@@ -0,0 +1,322 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::book::{change_json, side_name, Book};
+use crate::candles::{Candles, Interval};
+use crate::engine::{decimal_from_proto, pb};
+
+/// A serialized JSON message, shared by every connection it goes to
+pub type Frame = Arc<str>;
+
+// Frames a channel holds for its slowest subscriber. Subscribers only move
+// frames into their own outbox, so this rarely fills.
+const CHANNEL_BUFFER: usize = 256;
+// Trades a new trades subscriber starts with
+const RECENT_TRADES: usize = 100;
+
+/// What a connection can subscribe to in one market
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Book,
+    Trades,
+    Ticker,
+    Candles(Interval),
+}
+
+/// An update that does not follow the last one. Something was lost, so the
+/// book is only right again after a fresh snapshot.
+#[derive(Debug, PartialEq)]
+pub struct Gap {
+    pub expected: u64,
+    pub received: u64,
+}
+
+struct State {
+    seq: u64,
+    book: Book,
+    trades: VecDeque<Value>,
+    candles: Candles,
+    // Per outcome; kept even once it is more than a day old
+    last_prices: Vec<Option<Decimal>>,
+    ticker: Value,
+}
+
+/// One market followed from the engine, with a broadcast channel per
+/// client channel
+pub struct Market {
+    market_id: String,
+    state: Mutex<State>,
+    book: broadcast::Sender<Frame>,
+    trades: broadcast::Sender<Frame>,
+    ticker: broadcast::Sender<Frame>,
+    candles: HashMap<Interval, broadcast::Sender<Frame>>,
+}
+
+impl Market {
+    pub fn new(market_id: &str, snapshot: &pb::MarketDataUpdate) -> Self {
+        let outcome_count = snapshot.outcome_count as usize;
+        let market = Self {
+            market_id: market_id.to_string(),
+            state: Mutex::new(State {
+                seq: 0,
+                book: Book::new(outcome_count),
+                trades: VecDeque::new(),
+                candles: Candles::default(),
+                last_prices: vec![None; outcome_count],
+                ticker: Value::Null,
+            }),
+            book: broadcast::channel(CHANNEL_BUFFER).0,
+            trades: broadcast::channel(CHANNEL_BUFFER).0,
+            ticker: broadcast::channel(CHANNEL_BUFFER).0,
+            candles: Interval::ALL.into_iter().map(|i| (i, broadcast::channel(CHANNEL_BUFFER).0)).collect(),
+        };
+        let _ = market.apply(snapshot);
+        market
+    }
+
+    pub fn market_id(&self) -> &str {
+        &self.market_id
+    }
+
+    /// Take in a message from the engine and send out what it changed. A
+    /// snapshot replaces the book, and book subscribers get the new one. An
+    /// update after a gap is still sent out, but the stream has to be
+    /// reopened for a snapshot.
+    pub fn apply(&self, update: &pb::MarketDataUpdate) -> Result<(), Gap> {
+        let mut state = self.state.lock().unwrap();
+        if update.snapshot {
+            state.seq = update.seq;
+            state.book = Book::new(update.outcome_count as usize);
+            state.last_prices.resize(update.outcome_count as usize, None);
+            for level in &update.levels {
+                state.book.apply(level);
+            }
+            let _ = self.book.send(self.book_snapshot(&state));
+            self.refresh_ticker(&mut state);
+            return Ok(());
+        }
+
+        let gap = (update.seq != state.seq + 1).then_some(Gap { expected: state.seq + 1, received: update.seq });
+        state.seq = update.seq;
+        for level in &update.levels {
+            state.book.apply(level);
+        }
+        let changes: Vec<Value> = update.levels.iter().map(change_json).collect();
+        // Sent even without changes so book subscribers see every seq
+        let _ = self.book.send(frame(json!({
+            "channel": "book",
+            "type": "update",
+            "market_id": self.market_id,
+            "seq": update.seq,
+            "changes": changes,
+        })));
+
+        if !update.trades.is_empty() {
+            let mut trades = Vec::new();
+            for trade in &update.trades {
+                let price = decimal_from_proto(trade.price.as_ref());
+                let quantity = decimal_from_proto(trade.quantity.as_ref());
+                let at = trade_time(trade);
+                if let Some(last) = state.last_prices.get_mut(trade.outcome_index as usize) {
+                    *last = Some(price);
+                }
+                for (interval, candle) in state.candles.record(trade.outcome_index, price, quantity, at) {
+                    let _ = self.candles[&interval].send(frame(json!({
+                        "channel": "candles",
+                        "type": "update",
+                        "market_id": self.market_id,
+                        "interval": interval.as_str(),
+                        "candle": candle,
+                    })));
+                }
+                let trade = json!({
+                    "trade_id": trade.trade_id,
+                    "outcome": trade.outcome_index,
+                    "price": price,
+                    "quantity": quantity,
+                    "side": side_name(trade.taker_side()),
+                    "timestamp": at,
+                });
+                if state.trades.len() == RECENT_TRADES {
+                    state.trades.pop_front();
+                }
+                state.trades.push_back(trade.clone());
+                trades.push(trade);
+            }
+            let _ = self.trades.send(frame(json!({
+                "channel": "trades",
+                "type": "update",
+                "market_id": self.market_id,
+                "seq": update.seq,
+                "trades": trades,
+            })));
+        }
+        self.refresh_ticker(&mut state);
+        gap.map_or(Ok(()), Err)
+    }
+
+    /// Roll the ticker's 24h figures forward when nothing has traded for a
+    /// while
+    pub fn tick(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.refresh_ticker(&mut state);
+    }
+
+    /// The messages a new subscriber starts with, and a receiver for
+    /// everything after them
+    pub fn subscribe(&self, channel: Channel) -> (Frame, broadcast::Receiver<Frame>) {
+        let state = self.state.lock().unwrap();
+        match channel {
+            Channel::Book => (self.book_snapshot(&state), self.book.subscribe()),
+            Channel::Trades => {
+                let snapshot = frame(json!({
+                    "channel": "trades",
+                    "type": "snapshot",
+                    "market_id": self.market_id,
+                    "seq": state.seq,
+                    "trades": state.trades,
+                }));
+                (snapshot, self.trades.subscribe())
+            }
+            Channel::Ticker => (self.ticker_frame(&state.ticker, "snapshot"), self.ticker.subscribe()),
+            Channel::Candles(interval) => {
+                let snapshot = frame(json!({
+                    "channel": "candles",
+                    "type": "snapshot",
+                    "market_id": self.market_id,
+                    "interval": interval.as_str(),
+                    "candles": state.candles.history(interval),
+                }));
+                (snapshot, self.candles[&interval].subscribe())
+            }
+        }
+    }
+
+    /// No connection is subscribed to anything here
+    pub fn is_idle(&self) -> bool {
+        [&self.book, &self.trades, &self.ticker]
+            .into_iter()
+            .chain(self.candles.values())
+            .all(|sender| sender.receiver_count() == 0)
+    }
+
+    fn book_snapshot(&self, state: &State) -> Frame {
+        frame(json!({
+            "channel": "book",
+            "type": "snapshot",
+            "market_id": self.market_id,
+            "seq": state.seq,
+            "outcomes": state.book.to_json(),
+        }))
+    }
+
+    fn ticker_frame(&self, outcomes: &Value, kind: &str) -> Frame {
+        frame(json!({
+            "channel": "ticker",
+            "type": kind,
+            "market_id": self.market_id,
+            "outcomes": outcomes,
+        }))
+    }
+
+    /// Work out the ticker and send it if it has changed
+    fn refresh_ticker(&self, state: &mut State) {
+        let now = Utc::now();
+        let outcomes: Vec<Value> = (0..state.book.outcome_count())
+            .map(|outcome| {
+                let day = state.candles.day(outcome as u32, now);
+                json!({
+                    "outcome": outcome,
+                    "last": state.last_prices.get(outcome).copied().flatten(),
+                    "best_bid": state.book.best_bid(outcome).map(|(price, _)| price),
+                    "best_ask": state.book.best_ask(outcome).map(|(price, _)| price),
+                    "open_24h": day.map(|d| d.open),
+                    "high_24h": day.map(|d| d.high),
+                    "low_24h": day.map(|d| d.low),
+                    "volume_24h": day.map_or(Decimal::ZERO, |d| d.volume),
+                })
+            })
+            .collect();
+        let ticker = Value::Array(outcomes);
+        if ticker != state.ticker {
+            let _ = self.ticker.send(self.ticker_frame(&ticker, "update"));
+            state.ticker = ticker;
+        }
+    }
+}
+
+fn frame(value: Value) -> Frame {
+    value.to_string().into()
+}
+
+fn trade_time(trade: &pb::PublicTrade) -> DateTime<Utc> {
+    trade
+        .timestamp
+        .as_ref()
+        .and_then(|ts| DateTime::from_timestamp(ts.seconds, ts.nanos as u32))
+        .unwrap_or_else(Utc::now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(price: i32, quantity: i64) -> pb::BookLevel {
+        pb::BookLevel {
+            side: pb::Side::Buy.into(),
+            outcome_index: 0,
+            price: Some(pb::Decimal { units: 0, nanos: price * 10_000_000 }),
+            quantity: Some(pb::Decimal { units: quantity, nanos: 0 }),
+            order_count: 1,
+        }
+    }
+
+    fn update(seq: u64, levels: Vec<pb::BookLevel>, trades: Vec<pb::PublicTrade>) -> pb::MarketDataUpdate {
+        pb::MarketDataUpdate { market_id: "m1".to_string(), seq, levels, trades, ..Default::default() }
+    }
+
+    fn next(receiver: &mut broadcast::Receiver<Frame>) -> Value {
+        serde_json::from_str(&receiver.try_recv().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_apply_sends_changes_and_reports_gaps() {
+        let snapshot = pb::MarketDataUpdate { snapshot: true, outcome_count: 2, ..update(5, vec![level(40, 10)], vec![]) };
+        let market = Market::new("m1", &snapshot);
+        let (opening, mut book) = market.subscribe(Channel::Book);
+        let opening: Value = serde_json::from_str(&opening).unwrap();
+        assert_eq!(opening["seq"], 5);
+        assert_eq!(opening["outcomes"][0]["bids"], json!([["0.4", "10", 1]]));
+        let (_, mut trades) = market.subscribe(Channel::Trades);
+
+        let trade = pb::PublicTrade {
+            trade_id: "t1".to_string(),
+            price: Some(pb::Decimal { units: 0, nanos: 400_000_000 }),
+            quantity: Some(pb::Decimal { units: 4, nanos: 0 }),
+            taker_side: pb::Side::Sell.into(),
+            ..Default::default()
+        };
+        assert_eq!(market.apply(&update(6, vec![level(40, 6)], vec![trade])), Ok(()));
+        let changed = next(&mut book);
+        assert_eq!((changed["type"].clone(), changed["seq"].clone()), (json!("update"), json!(6)));
+        assert_eq!(changed["changes"][0]["quantity"], "6");
+        let printed = next(&mut trades);
+        assert_eq!(printed["trades"][0]["trade_id"], "t1");
+        assert_eq!(printed["trades"][0]["side"], "sell");
+
+        // Still sent, so subscribers see the gap too
+        assert_eq!(market.apply(&update(8, vec![], vec![])), Err(Gap { expected: 7, received: 8 }));
+        assert_eq!(next(&mut book)["seq"], 8);
+
+        let fresh = pb::MarketDataUpdate { snapshot: true, outcome_count: 2, ..update(20, vec![level(45, 3)], vec![]) };
+        assert_eq!(market.apply(&fresh), Ok(()));
+        let resnapshot = next(&mut book);
+        assert_eq!((resnapshot["type"].clone(), resnapshot["seq"].clone()), (json!("snapshot"), json!(20)));
+        assert_eq!(resnapshot["outcomes"][0]["bids"], json!([["0.45", "3", 1]]));
+        assert_eq!(market.apply(&update(21, vec![], vec![])), Ok(()));
+    }
+}
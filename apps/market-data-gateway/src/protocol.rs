@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::candles::Interval;
+use crate::market::Channel;
+
+/// What a client sends
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Request {
+    Subscribe(Target),
+    Unsubscribe(Target),
+    Ping,
+}
+
+/// A channel in one market, as the client names it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Target {
+    pub channel: String,
+    pub market_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+}
+
+impl Target {
+    pub fn channel(&self) -> Result<Channel, String> {
+        if self.market_id.is_empty() {
+            return Err("market_id is required".to_string());
+        }
+        match (self.channel.as_str(), self.interval.as_deref()) {
+            ("book", None) => Ok(Channel::Book),
+            ("trades", None) => Ok(Channel::Trades),
+            ("ticker", None) => Ok(Channel::Ticker),
+            ("candles", Some(interval)) => Interval::parse(interval)
+                .map(Channel::Candles)
+                .ok_or_else(|| format!("Unknown interval {}; use 1m, 5m, 15m, 1h, 4h or 1d", interval)),
+            ("candles", None) => Err("candles needs an interval".to_string()),
+            ("book" | "trades" | "ticker", Some(_)) => Err(format!("{} takes no interval", self.channel)),
+            (channel, _) => Err(format!("Unknown channel {}; use book, trades, ticker or candles", channel)),
+        }
+    }
+}
+
+pub fn subscribed(target: &Target) -> Value {
+    json!({ "type": "subscribed", "target": target })
+}
+
+pub fn unsubscribed(target: &Target) -> Value {
+    json!({ "type": "unsubscribed", "target": target })
+}
+
+pub fn error(target: Option<&Target>, message: &str) -> Value {
+    json!({ "type": "error", "target": target, "message": message })
+}
+
+pub fn pong() -> Value {
+    json!({ "type": "pong" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_parse_to_channels() {
+        let request: Request =
+            serde_json::from_str(r#"{"op":"subscribe","channel":"candles","market_id":"m1","interval":"5m"}"#).unwrap();
+        let Request::Subscribe(target) = request else { panic!("expected a subscribe") };
+        assert_eq!(target.channel(), Ok(Channel::Candles(Interval::FiveMinutes)));
+
+        let request: Request = serde_json::from_str(r#"{"op":"unsubscribe","channel":"book","market_id":"m1"}"#).unwrap();
+        let Request::Unsubscribe(target) = request else { panic!("expected an unsubscribe") };
+        assert_eq!(target.channel(), Ok(Channel::Book));
+
+        assert!(matches!(serde_json::from_str(r#"{"op":"ping"}"#), Ok(Request::Ping)));
+        assert!(serde_json::from_str::<Request>(r#"{"op":"subscribe","channel":"book"}"#).is_err());
+        let target = Target { channel: "candles".to_string(), market_id: "m1".to_string(), interval: Some("2m".to_string()) };
+        assert!(target.channel().is_err());
+    }
+}
@@ -190,6 +190,9 @@ export class MarketService {
         86400 // 24 Hours
       );
 
+      // Let the matching engine pick up the new market
+      await redis.publish("markets:updated", market.id);
+
       console.log("✅ Market saved to database:", market.id);
 
       return {
@@ -0,0 +1,121 @@
+use chrono::Utc;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use uuid::Uuid;
+
+use matching_engine::market_spec::MarketSpec;
+use matching_engine::matcher::Matcher;
+use matching_engine::order::{Lots, Order, OrderSide, OrderStatus, OrderType, Outcome, Ticks};
+use matching_engine::orderbook::OrderBook;
+
+const LEVEL_DEPTHS: [usize; 3] = [100, 1_000, 10_000];
+
+fn order(user: &str, side: OrderSide, price: Ticks, qty: Lots) -> Order {
+    Order {
+        order_id: Uuid::new_v4(),
+        user_id: user.to_string(),
+        market_id: "bench".to_string(),
+        side,
+        outcome: Outcome::YES,
+        order_type: OrderType::LIMIT,
+        price,
+        quantity: qty,
+        filled: 0,
+        order_status: OrderStatus::PENDING,
+        reservation_id: None,
+        display_quantity: None,
+        reduce_only: false,
+        created_at: Utc::now(),
+    }
+}
+
+/// The previous layout: a clone per price-level queue plus a clone in the
+/// id map, with cancels scanning the level. Kept here as the baseline.
+#[derive(Default)]
+struct VecDequeBook {
+    asks: BTreeMap<Ticks, VecDeque<Order>>,
+    orders: HashMap<Uuid, Order>,
+}
+
+impl VecDequeBook {
+    fn add_order(&mut self, order: Order) {
+        self.asks.entry(order.price).or_default().push_back(order.clone());
+        self.orders.insert(order.order_id, order);
+    }
+
+    fn remove_order(&mut self, order_id: Uuid) -> Option<Order> {
+        let order = self.orders.remove(&order_id)?;
+        if let Some(queue) = self.asks.get_mut(&order.price) {
+            queue.retain(|o| o.order_id != order_id);
+            if queue.is_empty() {
+                self.asks.remove(&order.price);
+            }
+        }
+        Some(order)
+    }
+}
+
+/// Cancel the order in the middle of one deep price level, then re-add it
+fn bench_cancel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancel_mid_level");
+
+    for depth in LEVEL_DEPTHS {
+        let resting: Vec<Order> = (0..depth)
+            .map(|i| order(&format!("mm{}", i), OrderSide::SELL, 5500, 10))
+            .collect();
+        let victim = resting[depth / 2].clone();
+
+        let mut slab_book = OrderBook::new("bench".to_string(), MarketSpec::default());
+        resting.iter().cloned().for_each(|o| {
+            slab_book.add_order(o);
+        });
+        group.bench_with_input(BenchmarkId::new("slab", depth), &victim, |b, victim| {
+            b.iter(|| {
+                let removed = slab_book.remove_order(black_box(victim.order_id)).unwrap();
+                slab_book.add_order(removed);
+            })
+        });
+
+        let mut deque_book = VecDequeBook::default();
+        resting.iter().cloned().for_each(|o| deque_book.add_order(o));
+        group.bench_with_input(BenchmarkId::new("vecdeque", depth), &victim, |b, victim| {
+            b.iter(|| {
+                let removed = deque_book.remove_order(black_box(victim.order_id)).unwrap();
+                deque_book.add_order(removed);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// One aggressive BUY sweeping a level of partially-filled makers
+fn bench_sweep(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sweep_level");
+
+    for depth in LEVEL_DEPTHS {
+        group.throughput(Throughput::Elements(depth as u64));
+        group.bench_function(BenchmarkId::from_parameter(depth), |b| {
+            b.iter_batched(
+                || {
+                    let mut book = OrderBook::new("bench".to_string(), MarketSpec::default());
+                    for i in 0..depth {
+                        book.add_order(order(&format!("mm{}", i), OrderSide::SELL, 5500, 10));
+                    }
+                    let taker = order("taker", OrderSide::BUY, 5500, depth as Lots * 10 - 5);
+                    (book, taker)
+                },
+                |(mut book, taker)| {
+                    let result = Matcher::new(&mut book).place_order(taker).unwrap();
+                    black_box(result.trades.len())
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_cancel, bench_sweep);
+criterion_main!(benches);
@@ -1,4 +1,8 @@
 fn main() {
-    tonic_build::compile_protos("proto/matching_engine.proto")
+    tonic_build::configure()
+        .compile(
+            &["proto/matching_engine.proto", "proto/matching_engine_v2.proto"],
+            &["proto"],
+        )
         .unwrap_or_else(|e| panic!("Failed to compile protos: {:?}", e));
 }
\ No newline at end of file
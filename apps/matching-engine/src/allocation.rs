@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::order::Lots;
+
+const BPS: u128 = 10_000;
+
+#[derive(Debug, Error)]
+#[error("Unknown allocation strategy: {0}")]
+pub struct ParseAllocationError(String);
+
+/// How an aggressor's quantity is shared among the makers resting at one price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Allocation {
+    /// Strict time priority
+    FIFO,
+    /// In proportion to each maker's remaining size
+    PRORATA,
+    /// The front of the queue takes up to `priority_bps` of the incoming
+    /// quantity, everything left is shared pro-rata (front order included)
+    HYBRID { priority_bps: u32 },
+}
+
+impl Allocation {
+    /// Split `quantity` across makers given in time priority.
+    ///
+    /// Shares sum to `min(quantity, sizes.sum())` and never exceed a maker's
+    /// size. Lots lost to flooring go one each to the largest remainders,
+    /// earlier orders first on a tie, so the split only depends on the queue.
+    pub fn allocate(&self, sizes: &[Lots], quantity: Lots) -> Vec<Lots> {
+        match self {
+            Allocation::FIFO => fifo(sizes, quantity),
+            Allocation::PRORATA => pro_rata(sizes, quantity),
+            Allocation::HYBRID { priority_bps } => {
+                let Some(&front) = sizes.first() else {
+                    return Vec::new();
+                };
+                let top = ((quantity as u128 * *priority_bps as u128 / BPS) as Lots).min(front);
+
+                let mut rest = sizes.to_vec();
+                rest[0] -= top;
+                let mut shares = pro_rata(&rest, quantity - top);
+                shares[0] += top;
+                shares
+            }
+        }
+    }
+}
+
+fn fifo(sizes: &[Lots], mut quantity: Lots) -> Vec<Lots> {
+    sizes
+        .iter()
+        .map(|size| {
+            let share = quantity.min(*size);
+            quantity -= share;
+            share
+        })
+        .collect()
+}
+
+fn pro_rata(sizes: &[Lots], quantity: Lots) -> Vec<Lots> {
+    let total: u128 = sizes.iter().map(|s| *s as u128).sum();
+    if quantity as u128 >= total {
+        return sizes.to_vec();
+    }
+
+    let exact: Vec<(Lots, u128)> = sizes
+        .iter()
+        .map(|size| {
+            let product = quantity as u128 * *size as u128;
+            ((product / total) as Lots, product % total)
+        })
+        .collect();
+    let mut shares: Vec<Lots> = exact.iter().map(|(share, _)| *share).collect();
+
+    // Fewer leftover lots than makers, and a floored share is always below
+    // the maker's size while quantity < total, so +1 never overfills
+    let leftover = quantity - shares.iter().sum::<Lots>();
+    let mut by_remainder: Vec<usize> = (0..sizes.len()).collect();
+    by_remainder.sort_by_key(|&i| std::cmp::Reverse(exact[i].1));
+    for &i in by_remainder.iter().take(leftover as usize) {
+        shares[i] += 1;
+    }
+    shares
+}
+
+impl fmt::Display for Allocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Allocation::FIFO => write!(f, "fifo"),
+            Allocation::PRORATA => write!(f, "pro_rata"),
+            Allocation::HYBRID { priority_bps } => write!(f, "hybrid:{}", priority_bps),
+        }
+    }
+}
+
+/// `fifo`, `pro_rata`, or `hybrid:<priority_bps>`
+impl FromStr for Allocation {
+    type Err = ParseAllocationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "fifo" => Ok(Allocation::FIFO),
+            "pro_rata" => Ok(Allocation::PRORATA),
+            other => other
+                .strip_prefix("hybrid:")
+                .and_then(|bps| bps.parse().ok())
+                .filter(|bps| *bps as u128 <= BPS)
+                .map(|priority_bps| Allocation::HYBRID { priority_bps })
+                .ok_or_else(|| ParseAllocationError(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pro_rata_rounding_is_deterministic() {
+        // 7 over 3:3:3 -> 2.33 each, the spare lot goes to the oldest order
+        assert_eq!(Allocation::PRORATA.allocate(&[3, 3, 3], 7), vec![3, 2, 2]);
+        // 7 over 5:3:2 -> 3.5, 2.1, 1.4; the largest remainder wins
+        assert_eq!(Allocation::PRORATA.allocate(&[5, 3, 2], 7), vec![4, 2, 1]);
+        // Enough to clear the level fills everyone
+        assert_eq!(Allocation::PRORATA.allocate(&[5, 3, 2], 50), vec![5, 3, 2]);
+    }
+
+    #[test]
+    fn test_hybrid_gives_front_a_priority_slice() {
+        let hybrid: Allocation = "hybrid:5000".parse().unwrap();
+        // Front takes 5 of 10 first, then 5 is split 5:10:5 -> 1.25, 2.5, 1.25
+        assert_eq!(hybrid.allocate(&[10, 10, 5], 10), vec![6, 3, 1]);
+        assert_eq!(Allocation::FIFO.allocate(&[10, 10, 5], 15), vec![10, 5, 0]);
+        assert!("hybrid:20000".parse::<Allocation>().is_err());
+        assert_eq!(hybrid.to_string(), "hybrid:5000");
+    }
+}
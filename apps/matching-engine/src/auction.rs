@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+use crate::order::{Lots, Outcome, Ticks};
+use crate::orderbook::{OrderBook, PriceLevelSummary};
+
+/// Whether a market's book matches on arrival or collects orders for an uncross
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TradingPhase {
+    AUCTION,
+    CONTINUOUS,
+}
+
+impl TradingPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradingPhase::AUCTION => "AUCTION",
+            TradingPhase::CONTINUOUS => "CONTINUOUS",
+        }
+    }
+}
+
+/// Price (in YES ticks) the auction would uncross at if it ended now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClearingPrice {
+    pub price: Ticks,
+    /// Secondary YES + secondary NO + minted pairs executed at `price`
+    pub volume: Lots,
+    /// Eligible quantity left unexecuted at `price`
+    pub imbalance: Lots,
+}
+
+/// Eligible quantity on each of the four sides at one YES price
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Eligible {
+    pub yes_bids: Lots,
+    pub yes_asks: Lots,
+    pub no_bids: Lots,
+    pub no_asks: Lots,
+}
+
+impl Eligible {
+    /// Secondary volume per outcome first, then leftover YES and NO bids mint
+    /// pairs. Sellers can only trade against their own outcome, so filling
+    /// them first never lowers the total.
+    pub fn split(&self) -> (Lots, Lots, Lots) {
+        let yes = self.yes_bids.min(self.yes_asks);
+        let no = self.no_bids.min(self.no_asks);
+        let minted = (self.yes_bids - yes).min(self.no_bids - no);
+        (yes, no, minted)
+    }
+
+    fn volume(&self) -> Lots {
+        let (yes, no, minted) = self.split();
+        yes + no + minted
+    }
+
+    fn imbalance(&self) -> Lots {
+        let (yes, no, minted) = self.split();
+        self.yes_bids + self.yes_asks + self.no_bids + self.no_asks - 2 * (yes + no + minted)
+    }
+}
+
+/// Cumulative quantity along one book side, sorted by price ascending
+struct Ladder(Vec<(Ticks, Lots)>);
+
+impl Ladder {
+    fn new(levels: Vec<PriceLevelSummary>) -> Self {
+        let mut levels: Vec<(Ticks, Lots)> =
+            levels.into_iter().map(|l| (l.price, l.quantity)).collect();
+        levels.sort_unstable_by_key(|(price, _)| *price);
+        let mut total = 0;
+        for level in levels.iter_mut() {
+            total += level.1;
+            level.1 = total;
+        }
+        Self(levels)
+    }
+
+    fn total(&self) -> Lots {
+        self.0.last().map(|(_, q)| *q).unwrap_or(0)
+    }
+
+    fn at_or_below(&self, price: Ticks) -> Lots {
+        let idx = self.0.partition_point(|(p, _)| *p <= price);
+        if idx == 0 { 0 } else { self.0[idx - 1].1 }
+    }
+
+    fn at_or_above(&self, price: Ticks) -> Lots {
+        let idx = self.0.partition_point(|(p, _)| *p < price);
+        if idx == 0 { self.total() } else { self.total() - self.0[idx - 1].1 }
+    }
+}
+
+/// Everything the uncross needs to price the book, in YES terms
+pub struct AuctionBook {
+    one: Ticks,
+    yes_bids: Ladder,
+    yes_asks: Ladder,
+    no_bids: Ladder,
+    no_asks: Ladder,
+}
+
+impl AuctionBook {
+    pub fn new(book: &OrderBook) -> Self {
+        let yes = book.get_full_depth(Outcome::YES, usize::MAX);
+        let no = book.get_full_depth(Outcome::NO, usize::MAX);
+        Self {
+            one: book.spec.one(),
+            yes_bids: Ladder::new(yes.bids),
+            yes_asks: Ladder::new(yes.asks),
+            no_bids: Ladder::new(no.bids),
+            no_asks: Ladder::new(no.asks),
+        }
+    }
+
+    /// A NO price q trades against YES at `one - q`, so NO bids count as
+    /// YES supply (minting) and the NO book clears at the mirrored price
+    pub fn eligible(&self, yes_price: Ticks) -> Eligible {
+        let no_price = self.one - yes_price;
+        Eligible {
+            yes_bids: self.yes_bids.at_or_above(yes_price),
+            yes_asks: self.yes_asks.at_or_below(yes_price),
+            no_bids: self.no_bids.at_or_above(no_price),
+            no_asks: self.no_asks.at_or_below(no_price),
+        }
+    }
+
+    /// Single price maximizing executed volume. Ties go to the smallest
+    /// imbalance, then to the middle of the remaining candidates (lower one
+    /// on an even count), so the result only depends on the book.
+    pub fn clearing_price(&self) -> Option<ClearingPrice> {
+        let mut candidates: Vec<Ticks> = self
+            .yes_bids
+            .0
+            .iter()
+            .chain(self.yes_asks.0.iter())
+            .map(|(p, _)| *p)
+            .chain(
+                self.no_bids
+                    .0
+                    .iter()
+                    .chain(self.no_asks.0.iter())
+                    .map(|(p, _)| self.one - *p),
+            )
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut best: Vec<ClearingPrice> = Vec::new();
+        for price in candidates {
+            let eligible = self.eligible(price);
+            let candidate = ClearingPrice {
+                price,
+                volume: eligible.volume(),
+                imbalance: eligible.imbalance(),
+            };
+            if candidate.volume == 0 {
+                continue;
+            }
+            match best.first() {
+                Some(b) if (candidate.volume, std::cmp::Reverse(candidate.imbalance))
+                    < (b.volume, std::cmp::Reverse(b.imbalance)) => {}
+                Some(b) if (candidate.volume, candidate.imbalance) == (b.volume, b.imbalance) => {
+                    best.push(candidate)
+                }
+                _ => best = vec![candidate],
+            }
+        }
+
+        best.get(best.len().saturating_sub(1) / 2).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_spec::MarketSpec;
+    use crate::order::{Order, OrderSide, OrderStatus, OrderType};
+    use chrono::Utc;
+    use rust_decimal_macros::dec;
+    use uuid::Uuid;
+
+    fn add(book: &mut OrderBook, side: OrderSide, outcome: Outcome, price: Ticks, qty: Lots) {
+        book.add_order(Order {
+            order_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4().to_string(),
+            market_id: "m".to_string(),
+            side,
+            outcome,
+            order_type: OrderType::LIMIT,
+            price,
+            quantity: qty,
+            filled: 0,
+            order_status: OrderStatus::OPEN,
+            reservation_id: None,
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+        });
+    }
+
+    fn book() -> OrderBook {
+        OrderBook::new("m".to_string(), MarketSpec::new(dec!(0.01), dec!(1)).unwrap())
+    }
+
+    #[test]
+    fn test_clearing_price_maximizes_secondary_volume() {
+        let mut book = book();
+        add(&mut book, OrderSide::BUY, Outcome::YES, 60, 10);
+        add(&mut book, OrderSide::BUY, Outcome::YES, 55, 10);
+        add(&mut book, OrderSide::SELL, Outcome::YES, 50, 5);
+        add(&mut book, OrderSide::SELL, Outcome::YES, 55, 10);
+        add(&mut book, OrderSide::SELL, Outcome::YES, 65, 10);
+
+        // At 55: bids 20, asks 15 -> 15. At 60: bids 10 -> 10. At 50: asks 5.
+        let clearing = AuctionBook::new(&book).clearing_price().unwrap();
+        assert_eq!(clearing.price, 55);
+        assert_eq!(clearing.volume, 15);
+        assert_eq!(clearing.imbalance, 5);
+    }
+
+    #[test]
+    fn test_no_bids_supply_yes_through_minting() {
+        let mut book = book();
+        add(&mut book, OrderSide::BUY, Outcome::YES, 60, 10);
+        // NO bid at 0.45 mints with any YES bid at >= 0.55
+        add(&mut book, OrderSide::BUY, Outcome::NO, 45, 10);
+
+        let auction = AuctionBook::new(&book);
+        let clearing = auction.clearing_price().unwrap();
+        assert_eq!(clearing.volume, 10);
+        assert_eq!(clearing.imbalance, 0);
+        // Candidates 0.55 and 0.60 tie; the lower middle wins
+        assert_eq!(clearing.price, 55);
+        assert_eq!(auction.eligible(clearing.price).split(), (0, 0, 10));
+    }
+
+    #[test]
+    fn test_no_cross_has_no_clearing_price() {
+        let mut book = book();
+        add(&mut book, OrderSide::BUY, Outcome::YES, 40, 10);
+        add(&mut book, OrderSide::SELL, Outcome::YES, 60, 10);
+        add(&mut book, OrderSide::BUY, Outcome::NO, 30, 10);
+
+        assert!(AuctionBook::new(&book).clearing_price().is_none());
+    }
+}
@@ -0,0 +1,288 @@
+#![allow(clippy::result_large_err)] // tonic::Status is the error type throughout this module
+
+use anyhow::{anyhow, bail, Result};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tonic::service::Interceptor;
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use tonic::{Request, Status};
+use tracing::warn;
+
+use crate::config::Config;
+
+// Denials and admin interventions go to this target so they can be shipped
+// apart from the rest of the log
+pub const AUDIT: &str = "audit";
+
+/// What a caller may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Trades for any user (the order service)
+    SERVICE,
+    /// Trades for its own user id only
+    BOT,
+    /// Everything, including the admin RPCs
+    ADMIN,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::SERVICE => write!(f, "service"),
+            Role::BOT => write!(f, "bot"),
+            Role::ADMIN => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "service" => Ok(Role::SERVICE),
+            "bot" => Ok(Role::BOT),
+            "admin" => Ok(Role::ADMIN),
+            other => bail!("Unknown role: {}", other),
+        }
+    }
+}
+
+/// Who is making a request. The interceptor puts one in every request it lets through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    /// A user id for bots, a service or operator name otherwise
+    pub subject: String,
+    pub role: Role,
+}
+
+impl Caller {
+    fn may_trade_for(&self, user_id: &str) -> bool {
+        match self.role {
+            Role::SERVICE | Role::ADMIN => true,
+            Role::BOT => self.subject == user_id,
+        }
+    }
+}
+
+/// `<role>:<subject>`, e.g. `bot:user-123`
+impl FromStr for Caller {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (role, subject) = s.split_once(':').ok_or_else(|| anyhow!("Expected <role>:<subject>, got {}", s))?;
+        Ok(Caller { subject: subject.to_string(), role: role.parse()? })
+    }
+}
+
+/// What an RPC needs from its caller
+#[derive(Debug, Clone, Copy)]
+pub enum Access<'a> {
+    /// Market data; any authenticated caller
+    Read,
+    /// Acting on this user's orders
+    Trade(&'a str),
+    Admin,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    role: String,
+}
+
+/// Authenticates every call from a bearer token or the client certificate.
+/// With neither configured every call is refused, unless auth was turned off
+/// outright, which lets everything through as an anonymous admin.
+#[derive(Clone)]
+pub struct Authenticator {
+    enforced: bool,
+    tokens: Option<Arc<(DecodingKey, Validation)>>,
+    // Keyed by the SHA-256 of the certificate's DER, lowercase hex
+    client_certs: Arc<HashMap<String, Caller>>,
+}
+
+impl Authenticator {
+    pub fn new(token_secret: Option<&str>, client_certs: HashMap<String, Caller>) -> Self {
+        let tokens = token_secret
+            .map(|secret| Arc::new((DecodingKey::from_secret(secret.as_bytes()), Validation::new(Algorithm::HS256))));
+        Self {
+            enforced: true,
+            tokens,
+            client_certs: Arc::new(client_certs),
+        }
+    }
+
+    /// Let every call through as an anonymous admin
+    pub fn disabled() -> Self {
+        Self { enforced: false, tokens: None, client_certs: Arc::new(HashMap::new()) }
+    }
+
+    /// Client certificates are only trusted once TLS has verified them
+    /// against the client CA, so mapping some without one is refused.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        if config.auth_disabled {
+            return Ok(Self::disabled());
+        }
+        if !config.client_certs.is_empty() && config.tls_client_ca_path.is_none() {
+            bail!("AUTH_CLIENT_CERTS needs TLS_CLIENT_CA_PATH to verify the certificates against");
+        }
+        Ok(Self::new(config.auth_token_secret.as_deref(), config.client_certs.clone()))
+    }
+
+    pub fn is_enforced(&self) -> bool {
+        self.enforced
+    }
+
+    fn authenticate<T>(&self, request: &Request<T>) -> Result<Caller, String> {
+        if let Some(header) = request.metadata().get("authorization") {
+            let token = header
+                .to_str()
+                .ok()
+                .and_then(|h| h.strip_prefix("Bearer "))
+                .ok_or("Malformed authorization header")?;
+            let (key, validation) = self.tokens.as_deref().ok_or("Bearer tokens are not accepted")?;
+            let claims = decode::<Claims>(token, key, validation).map_err(|e| format!("Invalid token: {}", e))?.claims;
+            let role = claims.role.parse().map_err(|e| format!("Invalid token: {}", e))?;
+            return Ok(Caller { subject: claims.sub, role });
+        }
+        let certs = request.peer_certs().ok_or("No credentials")?;
+        let cert = certs.first().ok_or("No client certificate")?;
+        let fingerprint = fingerprint(cert.as_ref());
+        self.client_certs
+            .get(&fingerprint)
+            .cloned()
+            .ok_or_else(|| format!("Unknown client certificate {}", fingerprint))
+    }
+}
+
+impl Interceptor for Authenticator {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let caller = match self.enforced {
+            true => self.authenticate(&request).map_err(|reason| {
+                let peer = request.remote_addr().map_or("unknown".to_string(), |addr| addr.to_string());
+                warn!(target: AUDIT, "Rejected call from {}: {}", peer, reason);
+                Status::unauthenticated(reason)
+            })?,
+            false => Caller { subject: "anonymous".to_string(), role: Role::ADMIN },
+        };
+        request.extensions_mut().insert(caller);
+        Ok(request)
+    }
+}
+
+/// Check the caller the interceptor attached against what `method` needs
+pub fn authorize<T>(request: &Request<T>, method: &str, access: Access) -> Result<(), Status> {
+    let Some(caller) = request.extensions().get::<Caller>() else {
+        warn!(target: AUDIT, "{} called without an authenticated caller", method);
+        return Err(Status::unauthenticated("No credentials"));
+    };
+    let allowed = match access {
+        Access::Read => true,
+        Access::Trade(user_id) => caller.may_trade_for(user_id),
+        Access::Admin => caller.role == Role::ADMIN,
+    };
+    if allowed {
+        return Ok(());
+    }
+    warn!(target: AUDIT, "Denied {} to {} ({}): {:?}", method, caller.subject, caller.role, access);
+    Err(Status::permission_denied(format!("{} may not call {}", caller.role, method)))
+}
+
+/// `<sha256>=<role>:<subject>` entries, comma-separated
+pub fn parse_client_certs(value: &str) -> Result<HashMap<String, Caller>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (fingerprint, caller) =
+                entry.split_once('=').ok_or_else(|| anyhow!("Expected <sha256>=<role>:<subject>, got {}", entry))?;
+            Ok((fingerprint.to_lowercase().replace(':', ""), caller.parse()?))
+        })
+        .collect()
+}
+
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Server TLS from the configured PEM files, verifying clients when a CA is set
+pub fn tls_config(config: &Config) -> Result<Option<ServerTlsConfig>> {
+    let (cert, key) = match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert), Some(key)) => (std::fs::read(cert)?, std::fs::read(key)?),
+        (None, None) if config.tls_client_ca_path.is_none() => return Ok(None),
+        _ => bail!("TLS needs both TLS_CERT_PATH and TLS_KEY_PATH"),
+    };
+    let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+    if let Some(ca) = &config.tls_client_ca_path {
+        tls = tls.client_ca_root(Certificate::from_pem(std::fs::read(ca)?));
+    }
+    Ok(Some(tls))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct TestClaims<'a> {
+        sub: &'a str,
+        role: &'a str,
+        exp: i64,
+    }
+
+    fn request_with_token(secret: &str, sub: &str, role: &str) -> Request<()> {
+        let claims = TestClaims { sub, role, exp: chrono::Utc::now().timestamp() + 60 };
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap();
+        let mut request = Request::new(());
+        request.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        request
+    }
+
+    #[test]
+    fn test_bot_token_trades_only_for_itself() {
+        let mut auth = Authenticator::new(Some("secret"), HashMap::new());
+
+        let request = auth.call(request_with_token("secret", "user-1", "bot")).unwrap();
+        assert!(authorize(&request, "PlaceOrder", Access::Trade("user-1")).is_ok());
+        assert!(authorize(&request, "GetOrderbook", Access::Read).is_ok());
+        let denied = authorize(&request, "PlaceOrder", Access::Trade("user-2")).unwrap_err();
+        assert_eq!(denied.code(), tonic::Code::PermissionDenied);
+        assert!(authorize(&request, "CreateMarket", Access::Admin).is_err());
+
+        let service = auth.call(request_with_token("secret", "order-service", "service")).unwrap();
+        assert!(authorize(&service, "PlaceOrder", Access::Trade("user-2")).is_ok());
+        assert!(authorize(&service, "CreateMarket", Access::Admin).is_err());
+
+        let forged = auth.call(request_with_token("other", "user-1", "admin")).unwrap_err();
+        assert_eq!(forged.code(), tonic::Code::Unauthenticated);
+        assert_eq!(auth.call(Request::new(())).unwrap_err().code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn test_unconfigured_auth_refuses_everything_unless_disabled() {
+        let mut auth = Authenticator::new(None, HashMap::new());
+        assert_eq!(auth.call(Request::new(())).unwrap_err().code(), tonic::Code::Unauthenticated);
+        let token = auth.call(request_with_token("secret", "ops", "admin")).unwrap_err();
+        assert_eq!(token.code(), tonic::Code::Unauthenticated);
+
+        let request = Authenticator::disabled().call(Request::new(())).unwrap();
+        assert!(authorize(&request, "CreateMarket", Access::Admin).is_ok());
+    }
+
+    #[test]
+    fn test_client_certs_parse_by_fingerprint() {
+        let certs = parse_client_certs("AB:cd:01=service:order-service, ef02=admin:ops").unwrap();
+        assert_eq!(certs["abcd01"], Caller { subject: "order-service".to_string(), role: Role::SERVICE });
+        assert_eq!(certs["ef02"].role, Role::ADMIN);
+        assert!(parse_client_certs("ef02=root:ops").is_err());
+    }
+}
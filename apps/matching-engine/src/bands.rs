@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::order::{Outcome, Ticks};
+
+#[derive(Debug, Error)]
+#[error("Unknown circuit breaker action: {0}")]
+pub struct ParseBreakerActionError(String);
+
+/// What a tripped circuit breaker does to the market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerAction {
+    /// Refuse new orders until the cooldown has passed
+    HALT,
+    /// Move the book into an auction, to be uncrossed by an operator
+    AUCTION,
+}
+
+impl fmt::Display for BreakerAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakerAction::HALT => write!(f, "halt"),
+            BreakerAction::AUCTION => write!(f, "auction"),
+        }
+    }
+}
+
+impl FromStr for BreakerAction {
+    type Err = ParseBreakerActionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "halt" => Ok(BreakerAction::HALT),
+            "auction" => Ok(BreakerAction::AUCTION),
+            other => Err(ParseBreakerActionError(other.to_string())),
+        }
+    }
+}
+
+/// Trips when one outcome trades more than `max_move` apart within `window`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerConfig {
+    pub max_move: Ticks,
+    pub window: Duration,
+    /// HALT only: how long new orders are refused
+    pub cooldown: Duration,
+    pub action: BreakerAction,
+}
+
+/// Price protection for one market. Both parts are off unless set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BandConfig {
+    /// Orders may not trade further than this from the reference price
+    pub width: Option<Ticks>,
+    pub breaker: Option<BreakerConfig>,
+}
+
+/// A breaker that tripped on an outcome's price move
+#[derive(Debug, Clone)]
+pub struct BreakerTripped {
+    pub market_id: String,
+    pub outcome: Outcome,
+    pub action: BreakerAction,
+    /// Lowest and highest price traded within the window
+    pub low: Ticks,
+    pub high: Ticks,
+    /// HALT only
+    pub halted_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub struct PriceBands {
+    config: BandConfig,
+    // Indexed by Outcome: recent trade prices inside the breaker window
+    history: Vec<VecDeque<(Instant, Ticks)>>,
+    halted_until: Option<Instant>,
+}
+
+impl PriceBands {
+    pub fn new(outcome_count: usize) -> Self {
+        Self {
+            config: BandConfig::default(),
+            history: vec![VecDeque::new(); outcome_count],
+            halted_until: None,
+        }
+    }
+
+    pub fn config(&self) -> BandConfig {
+        self.config
+    }
+
+    pub fn configure(&mut self, config: BandConfig) {
+        self.config = config;
+        self.history.iter_mut().for_each(VecDeque::clear);
+    }
+
+    /// Lowest and highest price an order may trade at, given the reference
+    /// price (last trade, or mid when nothing has traded). None means no band.
+    pub fn limits(&self, reference: Option<Ticks>, one: Ticks) -> Option<(Ticks, Ticks)> {
+        let width = self.config.width?;
+        let reference = reference?;
+        Some((reference.saturating_sub(width), (reference + width).min(one)))
+    }
+
+    pub fn is_halted(&self, now: Instant) -> bool {
+        self.halted_until.is_some_and(|until| now < until)
+    }
+
+    pub fn halted_until(&self) -> Option<Instant> {
+        self.halted_until.filter(|until| Instant::now() < *until)
+    }
+
+    /// Lift a halt before its cooldown is up. Returns whether one was in force.
+    pub fn resume(&mut self, now: Instant) -> bool {
+        let was_halted = self.is_halted(now);
+        self.halted_until = None;
+        self.history.iter_mut().for_each(VecDeque::clear);
+        was_halted
+    }
+
+    /// Feed a trade price to the breaker. Returns the low and high of the
+    /// window if it tripped; the window starts afresh afterwards.
+    pub fn record_trade(&mut self, outcome: Outcome, price: Ticks, now: Instant) -> Option<(BreakerAction, Ticks, Ticks)> {
+        let breaker = self.config.breaker?;
+        let history = &mut self.history[outcome.index()];
+        while history.front().is_some_and(|&(at, _)| now.duration_since(at) >= breaker.window) {
+            history.pop_front();
+        }
+        history.push_back((now, price));
+
+        let low = history.iter().map(|&(_, p)| p).min()?;
+        let high = history.iter().map(|&(_, p)| p).max()?;
+        if high - low <= breaker.max_move {
+            return None;
+        }
+        history.clear();
+        if breaker.action == BreakerAction::HALT {
+            self.halted_until = Some(now + breaker.cooldown);
+        }
+        Some((breaker.action, low, high))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_trips_on_move_within_window_and_cools_down() {
+        let mut bands = PriceBands::new(2);
+        bands.configure(BandConfig {
+            width: Some(10),
+            breaker: Some(BreakerConfig {
+                max_move: 15,
+                window: Duration::from_secs(60),
+                cooldown: Duration::from_secs(30),
+                action: BreakerAction::HALT,
+            }),
+        });
+        assert_eq!(bands.limits(Some(95), 100), Some((85, 100)));
+        assert_eq!(bands.limits(None, 100), None);
+
+        let start = Instant::now();
+        assert!(bands.record_trade(Outcome::YES, 50, start).is_none());
+        // The old print has left the window by the time the price gets here
+        assert!(bands.record_trade(Outcome::YES, 70, start + Duration::from_secs(61)).is_none());
+        assert!(bands.record_trade(Outcome::NO, 30, start + Duration::from_secs(62)).is_none());
+
+        let tripped = bands.record_trade(Outcome::YES, 54, start + Duration::from_secs(62));
+        assert_eq!(tripped, Some((BreakerAction::HALT, 54, 70)));
+        assert!(bands.is_halted(start + Duration::from_secs(91)));
+        assert!(!bands.is_halted(start + Duration::from_secs(92)));
+    }
+}
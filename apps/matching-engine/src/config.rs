@@ -1,21 +1,163 @@
 use anyhow::Result;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
 use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::allocation::Allocation;
+use crate::auth::{parse_client_certs, Caller};
+use crate::bands::{BandConfig, BreakerConfig};
+use crate::market_spec::MarketSpec;
+use crate::ratelimit::{CallClass, RateLimit, Scope};
 
 pub struct Config {
     pub redis_url: String,
+    // Markets and positions are loaded from here at startup; orders for
+    // unlisted markets are refused and SELLs checked against positions.
+    // Unset accepts any market id and leaves SELLs unchecked.
+    pub database_url: Option<String>,
+    // Rebuild the books from the database's OPEN/PARTIAL orders at startup;
+    // needs DATABASE_URL
+    pub recover_orders: bool,
     pub grpc_port: u16,
+    pub metrics_port: u16,
+    // Serve gRPC over TLS with this certificate and key (PEM)
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    // Require client certificates signed by this CA (PEM)
+    pub tls_client_ca_path: Option<String>,
+    // Callers known by client certificate, as comma-separated
+    // "<sha256 of the DER>=<role>:<subject>" entries; needs the client CA
+    pub client_certs: HashMap<String, Caller>,
+    // HS256 key for bearer tokens carrying sub and role claims. With neither
+    // this nor a client CA every call is refused.
+    pub auth_token_secret: Option<String>,
+    // AUTH_DISABLED=true lets every call through as an admin, for local
+    // development only
+    pub auth_disabled: bool,
+    // Token buckets from RATE_LIMIT_<USER|CALLER>_<PLACE|CANCEL|QUERY>, each
+    // "<per second>[:<burst>]"; unset ones are unlimited. SetRateLimit
+    // changes them at runtime.
+    pub rate_limits: HashMap<(Scope, CallClass), RateLimit>,
+    // Liveness watchdog behind grpc.health.v1
+    pub health_check_interval_ms: u64,
+    pub health_check_timeout_ms: u64,
+    // Tick/lot grid for books created on the fly
+    pub default_spec: MarketSpec,
+    // Level allocation for new books; SetAllocation overrides per market
+    pub default_allocation: Allocation,
+    // How long a client_order_id is remembered for retries
+    pub client_order_id_ttl_secs: u64,
+    // Cancel-on-disconnect: a session's orders go after this long without a heartbeat
+    pub session_timeout_ms: u64,
+    // Price bands and circuit breaker for new books; SetPriceBands overrides per market
+    pub default_bands: BandConfig,
+    // How long after printing a trade can still be busted or repriced; 0
+    // turns corrections off
+    pub trade_correction_window_secs: u64,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self> {
         dotenv::dotenv().ok();
         
-        Ok(Self {
+        Self {
             redis_url: env::var("REDIS_URL")
                 .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string()),
+            database_url: env::var("DATABASE_URL").ok().filter(|url| !url.is_empty()),
+            recover_orders: env::var("RECOVER_ORDERS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
             grpc_port: env::var("GRPC_PORT")
                 .unwrap_or_else(|_| "50052".to_string())
                 .parse()?,
-        })
+            metrics_port: env::var("METRICS_PORT")
+                .unwrap_or_else(|_| "9464".to_string())
+                .parse()?,
+            tls_cert_path: env::var("TLS_CERT_PATH").ok().filter(|path| !path.is_empty()),
+            tls_key_path: env::var("TLS_KEY_PATH").ok().filter(|path| !path.is_empty()),
+            tls_client_ca_path: env::var("TLS_CLIENT_CA_PATH").ok().filter(|path| !path.is_empty()),
+            client_certs: parse_client_certs(&env::var("AUTH_CLIENT_CERTS").unwrap_or_default())?,
+            auth_token_secret: env::var("AUTH_TOKEN_SECRET").ok().filter(|secret| !secret.is_empty()),
+            auth_disabled: env::var("AUTH_DISABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            rate_limits: rate_limits_from_env()?,
+            health_check_interval_ms: env::var("HEALTH_CHECK_INTERVAL_MS")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()?,
+            health_check_timeout_ms: env::var("HEALTH_CHECK_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()?,
+            default_spec: MarketSpec::new(
+                Decimal::from_str(&env::var("TICK_SIZE").unwrap_or_else(|_| "0.0001".to_string()))?,
+                Decimal::from_str(&env::var("LOT_SIZE").unwrap_or_else(|_| "0.000001".to_string()))?,
+            )?,
+            default_allocation: env::var("ALLOCATION")
+                .unwrap_or_else(|_| "fifo".to_string())
+                .parse()?,
+            client_order_id_ttl_secs: env::var("CLIENT_ORDER_ID_TTL_SECS")
+                .unwrap_or_else(|_| "600".to_string())
+                .parse()?,
+            session_timeout_ms: env::var("SESSION_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()?,
+            default_bands: BandConfig::default(),
+            trade_correction_window_secs: env::var("TRADE_CORRECTION_WINDOW_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()?,
+        }
+        .with_default_bands()
+    }
+
+    // Band widths are prices, so they go on the default tick grid once it is known
+    fn with_default_bands(mut self) -> Result<Self> {
+        let ticks = |var: &str| -> Result<Option<_>> {
+            match env::var(var) {
+                Ok(price) if !price.is_empty() => {
+                    Ok(Some(self.default_spec.price_to_ticks(Decimal::from_str(&price)?)?))
+                }
+                _ => Ok(None),
+            }
+        };
+        let width = ticks("PRICE_BAND_WIDTH")?;
+        let breaker = match ticks("CIRCUIT_BREAKER_MOVE")? {
+            Some(max_move) => Some(BreakerConfig {
+                max_move,
+                window: Duration::from_millis(
+                    env::var("CIRCUIT_BREAKER_WINDOW_MS")
+                        .unwrap_or_else(|_| "60000".to_string())
+                        .parse()?,
+                ),
+                cooldown: Duration::from_millis(
+                    env::var("CIRCUIT_BREAKER_COOLDOWN_MS")
+                        .unwrap_or_else(|_| "300000".to_string())
+                        .parse()?,
+                ),
+                action: env::var("CIRCUIT_BREAKER_ACTION")
+                    .unwrap_or_else(|_| "halt".to_string())
+                    .parse()?,
+            }),
+            None => None,
+        };
+        self.default_bands = BandConfig { width, breaker };
+        Ok(self)
     }
-}
\ No newline at end of file
+}
+
+fn rate_limits_from_env() -> Result<HashMap<(Scope, CallClass), RateLimit>> {
+    let mut limits = HashMap::new();
+    for scope in Scope::ALL {
+        for class in CallClass::ALL {
+            let var = format!("RATE_LIMIT_{}_{}", scope, class).to_uppercase();
+            match env::var(&var) {
+                Ok(limit) if !limit.is_empty() => {
+                    limits.insert((scope, class), limit.parse()?);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(limits)
+}
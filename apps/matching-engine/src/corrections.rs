@@ -0,0 +1,405 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::db::{delete_correction, insert_correction, load_corrections, CorrectionRow};
+use crate::inventory::Inventory;
+use crate::order::Ticks;
+use crate::trade::{CompleteSetMatch, ComplementaryMatch, Trade};
+
+/// One print as the matcher made it
+#[derive(Debug, Clone)]
+pub enum Printed {
+    Trade(Trade),
+    Complementary(ComplementaryMatch),
+    CompleteSet(CompleteSetMatch),
+}
+
+impl Printed {
+    pub fn trade_id(&self) -> Uuid {
+        match self {
+            Printed::Trade(t) => t.trade_id,
+            Printed::Complementary(m) => m.trade_id,
+            Printed::CompleteSet(m) => m.trade_id,
+        }
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            Printed::Trade(t) => t.timestamp,
+            Printed::Complementary(m) => m.timestamp,
+            Printed::CompleteSet(m) => m.timestamp,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Printed::Trade(_) => "trade",
+            Printed::Complementary(_) => "complementary_match",
+            Printed::CompleteSet(_) => "complete_set_match",
+        }
+    }
+
+}
+
+impl Trade {
+    /// Give the tokens back to the seller. Only done once `check_unwind`
+    /// has passed, so the buyer is not left short or with SELLs it cannot
+    /// cover.
+    fn unwind(&self, inventory: &mut Inventory) {
+        inventory.sold(&self.buyer_id, self.outcome, self.quantity);
+        inventory.bought(&self.seller_id, self.outcome, self.quantity);
+    }
+
+    /// The buyer must still hold the tokens, and not have offered them in a
+    /// working SELL. Nothing is checked unless positions were loaded.
+    fn check_unwind(&self, inventory: &Inventory) -> Result<(), CorrectionError> {
+        if inventory.enforced && inventory.available(&self.buyer_id, self.outcome) < self.quantity {
+            return Err(CorrectionError::PositionMoved(self.trade_id, self.buyer_id.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeStatus {
+    ACTIVE,
+    BUSTED,
+    /// Repriced at least once
+    CORRECTED,
+}
+
+impl TradeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeStatus::ACTIVE => "active",
+            TradeStatus::BUSTED => "busted",
+            TradeStatus::CORRECTED => "corrected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorrectionKind {
+    BUST,
+    PRICE,
+}
+
+impl fmt::Display for CorrectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorrectionKind::BUST => write!(f, "bust"),
+            CorrectionKind::PRICE => write!(f, "price"),
+        }
+    }
+}
+
+/// Who changed a trade after the fact, how and why
+#[derive(Debug, Clone)]
+pub struct Correction {
+    pub correction_id: Uuid,
+    pub kind: CorrectionKind,
+    /// PRICE only: the price before and after
+    pub price: Option<(Ticks, Ticks)>,
+    /// The authenticated caller
+    pub actor: String,
+    pub reason: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A trade and everything done to it since it printed
+#[derive(Debug, Clone)]
+pub struct TradeRecord {
+    /// As it stands now, so a corrected trade carries its new price
+    pub printed: Printed,
+    pub status: TradeStatus,
+    /// Oldest first
+    pub corrections: Vec<Correction>,
+}
+
+impl TradeRecord {
+    /// The correction just made
+    pub fn last_correction(&self) -> &Correction {
+        self.corrections.last().expect("a corrected trade has a correction")
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CorrectionError {
+    #[error("Market not found")]
+    UnknownMarket,
+    #[error("No trade {0} within the correction window")]
+    NotFound(Uuid),
+    #[error("Trade {0} is past the correction window")]
+    Expired(Uuid),
+    #[error("Trade {0} has already been busted")]
+    Busted(Uuid),
+    #[error("Only secondary trades can be repriced; bust trade {0} instead")]
+    NotRepriceable(Uuid),
+    #[error("Trade {0} minted tokens on-chain; mints cannot be busted")]
+    NotBustable(Uuid),
+    #[error("Trade {0} is already at that price")]
+    SamePrice(Uuid),
+    #[error("Invalid price: {0}")]
+    InvalidPrice(String),
+    #[error("Trade {0} cannot be busted: {1} has since sold or offered the tokens")]
+    PositionMoved(Uuid, String),
+    #[error("Trade {0} was corrected by someone else meanwhile; try again")]
+    Changed(Uuid),
+    #[error("Trade corrections need a database to record them in")]
+    NoDatabase,
+    #[error("Failed to record the correction: {0}")]
+    Unavailable(String),
+}
+
+/// Where corrections are recorded before they take effect
+#[async_trait]
+pub trait CorrectionStore: Send + Sync {
+    async fn insert(&self, row: &CorrectionRow) -> Result<()>;
+    /// Take back a correction the book refused. One already applied
+    /// downstream is left alone.
+    async fn delete(&self, id: &str) -> Result<()>;
+    /// Every correction made to a trade, oldest first, however long ago
+    async fn load(&self, trade_id: &str) -> Result<Vec<CorrectionRow>>;
+}
+
+/// The `trade_corrections` table
+pub struct PostgresCorrections {
+    database_url: String,
+}
+
+impl PostgresCorrections {
+    pub fn new(database_url: String) -> Self {
+        Self { database_url }
+    }
+}
+
+#[async_trait]
+impl CorrectionStore for PostgresCorrections {
+    async fn insert(&self, row: &CorrectionRow) -> Result<()> {
+        insert_correction(&self.database_url, row).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        delete_correction(&self.database_url, id).await
+    }
+
+    async fn load(&self, trade_id: &str) -> Result<Vec<CorrectionRow>> {
+        load_corrections(&self.database_url, trade_id).await
+    }
+}
+
+/// Trades one market printed within the correction window, busted and
+/// corrected ones included, so they can still be looked up and undone
+#[derive(Debug, Default)]
+pub struct TradeLog {
+    /// Zero keeps nothing, which turns corrections off
+    pub window: Duration,
+    records: HashMap<Uuid, TradeRecord>,
+    // Oldest first, for pruning
+    printed: VecDeque<(DateTime<Utc>, Uuid)>,
+}
+
+impl TradeLog {
+    /// Keep everything one matching call printed, dropping whatever has
+    /// aged out of the window
+    pub fn record(&mut self, trades: &[Trade], complementary: &[ComplementaryMatch], complete_sets: &[CompleteSetMatch]) {
+        if self.window.is_zero() {
+            return;
+        }
+        let printed = trades
+            .iter()
+            .cloned()
+            .map(Printed::Trade)
+            .chain(complementary.iter().cloned().map(Printed::Complementary))
+            .chain(complete_sets.iter().cloned().map(Printed::CompleteSet));
+        for printed in printed {
+            self.printed.push_back((printed.timestamp(), printed.trade_id()));
+            self.records.insert(
+                printed.trade_id(),
+                TradeRecord { printed, status: TradeStatus::ACTIVE, corrections: Vec::new() },
+            );
+        }
+
+        let now = Utc::now();
+        while let Some(&(at, trade_id)) = self.printed.front() {
+            if !expired(self.window, at, now) {
+                break;
+            }
+            self.printed.pop_front();
+            self.records.remove(&trade_id);
+        }
+    }
+
+    pub fn get(&self, trade_id: &Uuid) -> Option<&TradeRecord> {
+        self.records.get(trade_id)
+    }
+
+    /// Cancel a secondary trade outright, giving the tokens back to the
+    /// seller. Refused if the buyer no longer has them to give back. Mints
+    /// are refused too: nothing downstream can reverse them yet. Returns the
+    /// trade as it will stand; nothing changes until the correction is
+    /// applied.
+    pub fn bust(
+        &self,
+        trade_id: &Uuid,
+        inventory: &Inventory,
+        actor: &str,
+        reason: &str,
+        now: DateTime<Utc>,
+    ) -> Result<TradeRecord, CorrectionError> {
+        let mut record = self.open(trade_id, now)?.clone();
+        let Printed::Trade(trade) = &record.printed else {
+            return Err(CorrectionError::NotBustable(*trade_id));
+        };
+        trade.check_unwind(inventory)?;
+        record.status = TradeStatus::BUSTED;
+        record.corrections.push(correction(CorrectionKind::BUST, None, actor, reason, now));
+        Ok(record)
+    }
+
+    /// Reprice a secondary trade. The tokens stay where they are; only the
+    /// cash that changed hands is different. Nothing changes until the
+    /// correction is applied.
+    pub fn reprice(
+        &self,
+        trade_id: &Uuid,
+        price: Ticks,
+        actor: &str,
+        reason: &str,
+        now: DateTime<Utc>,
+    ) -> Result<TradeRecord, CorrectionError> {
+        let mut record = self.open(trade_id, now)?.clone();
+        let Printed::Trade(trade) = &mut record.printed else {
+            return Err(CorrectionError::NotRepriceable(*trade_id));
+        };
+        if trade.price == price {
+            return Err(CorrectionError::SamePrice(*trade_id));
+        }
+        let was = std::mem::replace(&mut trade.price, price);
+        record.status = TradeStatus::CORRECTED;
+        record.corrections.push(correction(CorrectionKind::PRICE, Some((was, price)), actor, reason, now));
+        Ok(record)
+    }
+
+    /// Put a correction from `bust` or `reprice` into effect. Refused if the
+    /// trade has been corrected since, or a bust's tokens have moved since.
+    pub fn apply(&mut self, corrected: TradeRecord, inventory: &mut Inventory) -> Result<&TradeRecord, CorrectionError> {
+        let trade_id = corrected.printed.trade_id();
+        let record = self.records.get_mut(&trade_id).ok_or(CorrectionError::NotFound(trade_id))?;
+        if record.corrections.len() + 1 != corrected.corrections.len() {
+            return Err(CorrectionError::Changed(trade_id));
+        }
+        if let (TradeStatus::BUSTED, Printed::Trade(trade)) = (corrected.status, &record.printed) {
+            trade.check_unwind(inventory)?;
+            trade.unwind(inventory);
+        }
+        *record = corrected;
+        Ok(record)
+    }
+
+    // A trade that can still be changed
+    fn open(&self, trade_id: &Uuid, now: DateTime<Utc>) -> Result<&TradeRecord, CorrectionError> {
+        let record = self.records.get(trade_id).ok_or(CorrectionError::NotFound(*trade_id))?;
+        if expired(self.window, record.printed.timestamp(), now) {
+            return Err(CorrectionError::Expired(*trade_id));
+        }
+        if record.status == TradeStatus::BUSTED {
+            return Err(CorrectionError::Busted(*trade_id));
+        }
+        Ok(record)
+    }
+}
+
+fn expired(window: Duration, printed_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    (now - printed_at).to_std().is_ok_and(|age| age > window)
+}
+
+fn correction(
+    kind: CorrectionKind,
+    price: Option<(Ticks, Ticks)>,
+    actor: &str,
+    reason: &str,
+    timestamp: DateTime<Utc>,
+) -> Correction {
+    Correction {
+        correction_id: Uuid::new_v4(),
+        kind,
+        price,
+        actor: actor.to_string(),
+        reason: reason.to_string(),
+        timestamp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_spec::MarketSpec;
+    use crate::matcher::Matcher;
+    use crate::order::{Order, OrderSide, Outcome};
+    use crate::orderbook::OrderBook;
+
+    #[test]
+    fn test_bust_unwinds_positions_and_reprice_keeps_them() {
+        let mut book = OrderBook::new("market_test".to_string(), MarketSpec::default());
+        book.trades.window = Duration::from_secs(60);
+        book.inventory.enforced = true;
+        book.inventory.set("a", Outcome::YES, 50);
+        Matcher::new(&mut book).place_order(Order::limit("a", OrderSide::SELL, Outcome::YES, 6000, 30)).unwrap();
+        let secondary = Matcher::new(&mut book).place_order(Order::limit("b", OrderSide::BUY, Outcome::YES, 6000, 30)).unwrap();
+        Matcher::new(&mut book).place_order(Order::limit("c", OrderSide::BUY, Outcome::YES, 5000, 10)).unwrap();
+        let mint = Matcher::new(&mut book).place_order(Order::limit("d", OrderSide::BUY, Outcome::NO, 5000, 10)).unwrap();
+        let trade_id = secondary.trades[0].trade_id;
+        let mint_id = mint.complementary_matches[0].trade_id;
+        let now = Utc::now();
+
+        // Nothing changes until the correction is applied
+        let repriced = book.trades.reprice(&trade_id, 5500, "ops", "stale quote", now).unwrap();
+        let raced = book.trades.reprice(&trade_id, 5800, "ops", "stale quote", now).unwrap();
+        assert_eq!(book.trades.get(&trade_id).unwrap().status, TradeStatus::ACTIVE);
+        let record = book.trades.apply(repriced, &mut book.inventory).unwrap();
+        assert_eq!(record.status, TradeStatus::CORRECTED);
+        assert_eq!(record.last_correction().price, Some((6000, 5500)));
+        assert_eq!(book.trades.apply(raced, &mut book.inventory).unwrap_err(), CorrectionError::Changed(trade_id));
+        assert_eq!((book.inventory.held("a", Outcome::YES), book.inventory.held("b", Outcome::YES)), (20, 30));
+        assert_eq!(
+            book.trades.reprice(&mint_id, 4000, "ops", "stale quote", now).unwrap_err(),
+            CorrectionError::NotRepriceable(mint_id)
+        );
+
+        // b has offered ten of the tokens again, so they can't be taken back
+        let busted = book.trades.bust(&trade_id, &book.inventory, "ops", "fat finger", now).unwrap();
+        let offer = Matcher::new(&mut book).place_order(Order::limit("b", OrderSide::SELL, Outcome::YES, 7000, 10)).unwrap();
+        assert_eq!(
+            book.trades.apply(busted.clone(), &mut book.inventory).unwrap_err(),
+            CorrectionError::PositionMoved(trade_id, "b".to_string())
+        );
+        book.cancel(offer.order.order_id).unwrap();
+        let record = book.trades.apply(busted, &mut book.inventory).unwrap();
+        assert_eq!(record.status, TradeStatus::BUSTED);
+        assert_eq!(record.corrections.len(), 2);
+        assert_eq!((book.inventory.held("a", Outcome::YES), book.inventory.held("b", Outcome::YES)), (50, 0));
+        assert_eq!(
+            book.trades.bust(&trade_id, &book.inventory, "ops", "again", now).unwrap_err(),
+            CorrectionError::Busted(trade_id)
+        );
+
+        let later = now + chrono::Duration::seconds(61);
+        assert_eq!(
+            book.trades.bust(&mint_id, &book.inventory, "ops", "late", later).unwrap_err(),
+            CorrectionError::Expired(mint_id)
+        );
+        // Minted on-chain, so the tokens stay
+        assert_eq!(
+            book.trades.bust(&mint_id, &book.inventory, "ops", "fat finger", now).unwrap_err(),
+            CorrectionError::NotBustable(mint_id)
+        );
+        assert_eq!((book.inventory.held("c", Outcome::YES), book.inventory.held("d", Outcome::NO)), (10, 10));
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rust_decimal::Decimal;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{NoTls, Row};
+use tracing::{info, warn};
+
+/// One row of the `positions` table
+#[derive(Debug, Clone)]
+pub struct PositionRow {
+    pub user_id: String,
+    pub market_id: String,
+    pub yes_tokens: Decimal,
+    pub no_tokens: Decimal,
+}
+
+/// An OPEN or PARTIAL row of the `orders` table. Its id is the reservation_id
+/// the order was placed with.
+#[derive(Debug, Clone)]
+pub struct OrderRow {
+    pub id: String,
+    pub user_id: String,
+    pub market_id: String,
+    /// "BUY" / "SELL"
+    pub side: String,
+    /// "YES" / "NO"
+    pub outcome: String,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub filled_quantity: Decimal,
+    pub created_at: DateTime<Utc>,
+    /// False when no row in `markets` has this id
+    pub market_exists: bool,
+}
+
+/// One row of the `markets` table; `id` is the market id the engine trades under
+#[derive(Debug, Clone)]
+pub struct MarketRow {
+    pub id: String,
+    /// "OPEN", "CLOSE", "PAUSED", ...
+    pub state: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// One row of the `trade_corrections` table: a bust or reprice, with the
+/// trade as it stood afterwards. `applied_at` is the ledger's to set.
+#[derive(Debug, Clone)]
+pub struct CorrectionRow {
+    pub id: String,
+    pub trade_id: String,
+    pub market_id: String,
+    /// "trade", "complementary_match" or "complete_set_match"
+    pub trade_kind: String,
+    /// "bust" / "price"
+    pub action: String,
+    pub old_price: Option<Decimal>,
+    pub new_price: Option<Decimal>,
+    pub actor: String,
+    pub reason: String,
+    /// JSON, as published with the original trade
+    pub trade: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrderRow {
+    pub fn remaining(&self) -> Decimal {
+        self.quantity - self.filled_quantity
+    }
+}
+
+/// Read every unclaimed position. The connection is only held for the load;
+/// from then on the engine tracks positions from its own fills.
+pub async fn load_positions(database_url: &str) -> Result<Vec<PositionRow>> {
+    let rows = query(
+        database_url,
+        r#"
+        SELECT user_id, market_id, yes_tokens, no_tokens
+        FROM positions
+        WHERE NOT is_claimed AND (yes_tokens > 0 OR no_tokens > 0)
+        "#,
+        &[],
+    )
+    .await?;
+
+    let positions: Vec<PositionRow> = rows
+        .iter()
+        .map(|row| PositionRow {
+            user_id: row.get(0),
+            market_id: row.get(1),
+            yes_tokens: row.get(2),
+            no_tokens: row.get(3),
+        })
+        .collect();
+    info!("Loaded {} positions", positions.len());
+    Ok(positions)
+}
+
+/// Every order the database has as resting, oldest first, which is the
+/// time priority they are rebuilt in
+pub async fn load_open_orders(database_url: &str) -> Result<Vec<OrderRow>> {
+    let rows = query(
+        database_url,
+        r#"
+        SELECT o.id, o.user_id, o.market_id, o.side::text, o.outcome::text,
+               o.price, o.quantity, o.filled_quantity, o.created_at, m.id IS NOT NULL
+        FROM orders o
+        LEFT JOIN markets m ON m.id = o.market_id
+        WHERE o.status IN ('OPEN', 'PARTIAL')
+        ORDER BY o.created_at, o.id
+        "#,
+        &[],
+    )
+    .await?;
+
+    let orders: Vec<OrderRow> = rows
+        .iter()
+        .map(|row| OrderRow {
+            id: row.get(0),
+            user_id: row.get(1),
+            market_id: row.get(2),
+            side: row.get(3),
+            outcome: row.get(4),
+            price: row.get(5),
+            quantity: row.get(6),
+            filled_quantity: row.get(7),
+            // TIMESTAMP(3) without a zone; Prisma writes UTC
+            created_at: row.get::<_, NaiveDateTime>(8).and_utc(),
+            market_exists: row.get(9),
+        })
+        .collect();
+    info!("Loaded {} open orders", orders.len());
+    Ok(orders)
+}
+
+/// Every market, whatever its state
+pub async fn load_markets(database_url: &str) -> Result<Vec<MarketRow>> {
+    let rows = query(database_url, "SELECT id, state::text, expires_at FROM markets", &[]).await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| MarketRow {
+            id: row.get(0),
+            state: row.get(1),
+            expires_at: row.get::<_, NaiveDateTime>(2).and_utc(),
+        })
+        .collect())
+}
+
+/// Store a correction before it takes effect
+pub async fn insert_correction(database_url: &str, row: &CorrectionRow) -> Result<()> {
+    query(
+        database_url,
+        r#"
+        INSERT INTO trade_corrections
+            (id, trade_id, market_id, trade_kind, action, old_price, new_price, actor, reason, trade, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#,
+        &[
+            &row.id,
+            &row.trade_id,
+            &row.market_id,
+            &row.trade_kind,
+            &row.action,
+            &row.old_price,
+            &row.new_price,
+            &row.actor,
+            &row.reason,
+            &row.trade,
+            &row.created_at.naive_utc(),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Withdraw a stored correction that could not take effect after all
+pub async fn delete_correction(database_url: &str, id: &str) -> Result<()> {
+    query(database_url, "DELETE FROM trade_corrections WHERE id = $1 AND applied_at IS NULL", &[&id]).await?;
+    Ok(())
+}
+
+/// Every correction made to a trade, oldest first, however long ago
+pub async fn load_corrections(database_url: &str, trade_id: &str) -> Result<Vec<CorrectionRow>> {
+    let rows = query(
+        database_url,
+        r#"
+        SELECT id, trade_id, market_id, trade_kind, action, old_price, new_price, actor, reason, trade, created_at
+        FROM trade_corrections
+        WHERE trade_id = $1
+        ORDER BY created_at, id
+        "#,
+        &[&trade_id],
+    )
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| CorrectionRow {
+            id: row.get(0),
+            trade_id: row.get(1),
+            market_id: row.get(2),
+            trade_kind: row.get(3),
+            action: row.get(4),
+            old_price: row.get(5),
+            new_price: row.get(6),
+            actor: row.get(7),
+            reason: row.get(8),
+            trade: row.get(9),
+            created_at: row.get::<_, NaiveDateTime>(10).and_utc(),
+        })
+        .collect())
+}
+
+// One short-lived connection per call. The engine only writes corrections.
+async fn query(database_url: &str, sql: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>> {
+    let (client, connection) = tokio_postgres::connect(database_url, NoTls).await?;
+    let connection = tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            warn!("Database connection error: {}", e);
+        }
+    });
+
+    let rows = client.query(sql, params).await?;
+    drop(client);
+    connection.await?;
+    Ok(rows)
+}
@@ -0,0 +1,1304 @@
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::allocation::Allocation;
+use crate::auction::{AuctionBook, TradingPhase};
+use crate::auth::AUDIT;
+use crate::bands::{BandConfig, BreakerTripped};
+use crate::config::Config;
+use crate::corrections::{CorrectionError, CorrectionStore, PostgresCorrections, Printed, TradeRecord};
+use crate::db::{CorrectionRow, PositionRow};
+use crate::executions::{cancel_execution, match_executions, ExecType, Execution, ExecutionHub};
+use crate::fired::{FiredMessage, FiredQueue};
+use crate::idempotency::{Claim, IdempotencyCache};
+use crate::market_spec::{MarketSpec, SpecError};
+use crate::marketdata::MarketDataHub;
+use crate::matcher::{FiredTrigger, GroupResult, MatchResult, Matcher, RejectReason};
+use crate::metrics::Metrics;
+use crate::mmp::MmpTriggered;
+use crate::order::{Order, OrderSide, OrderStatus, OrderType, Outcome};
+use crate::orderbook::{OrderBook, SharedOrderBook};
+use crate::ratelimit::RateLimiter;
+use crate::redis_client::RedisClient;
+use crate::reservations::ReservationIndex;
+use crate::registry::{MarketInfo, MarketRegistry, MarketSource, MarketState};
+use crate::session::{ClosedSession, SessionRegistry};
+use crate::trade::{CompleteSetMatch, ComplementaryMatch, Trade};
+use crate::triggers::{Trigger, TriggerKind};
+
+/// An order as submitted, after the wire format has been decoded but before
+/// it is put on the market's tick/lot grid
+#[derive(Debug, Clone)]
+pub struct NewOrder {
+    pub user_id: String,
+    pub market_id: String,
+    pub side: OrderSide,
+    pub outcome: Outcome,
+    pub order_type: OrderType,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    /// ICEBERG only
+    pub display_quantity: Option<Decimal>,
+    pub reservation_id: Option<String>,
+    pub client_order_id: Option<String>,
+    /// Cancel the order if this session stops heartbeating
+    pub session_id: Option<String>,
+    /// Conditional orders: held until the last trade reaches this price
+    pub trigger: Option<(TriggerKind, Decimal)>,
+    /// SELL only: trim to the seller's position instead of rejecting
+    pub reduce_only: bool,
+}
+
+impl NewOrder {
+    /// Identifies the order body, so a client_order_id reused for a
+    /// different order is caught regardless of which API version sent it
+    fn fingerprint(&self) -> String {
+        format!(
+            "{}|{:?}|{:?}|{:?}|{}|{}|{}|{}|{:?}|{}",
+            self.market_id,
+            self.side,
+            self.outcome,
+            self.order_type,
+            self.price.normalize(),
+            self.quantity.normalize(),
+            self.display_quantity.map(|d| d.normalize().to_string()).unwrap_or_default(),
+            self.reservation_id.as_deref().unwrap_or_default(),
+            self.trigger.map(|(kind, price)| (kind, price.normalize())),
+            self.reduce_only
+        )
+    }
+
+    fn into_order(self, spec: &MarketSpec) -> Result<(Order, Option<Trigger>), PlaceError> {
+        let price = spec.price_to_ticks(self.price).map_err(price_rejected)?;
+        let trigger = self
+            .trigger
+            .map(|(kind, price)| spec.price_to_ticks(price).map(|price| Trigger { kind, price }))
+            .transpose()
+            .map_err(price_rejected)?;
+        let quantity = spec
+            .quantity_to_lots(self.quantity)
+            .map_err(|e| PlaceError::rejected(RejectReason::InvalidQuantity, e))?;
+        let display_quantity = self
+            .display_quantity
+            .map(|d| spec.quantity_to_lots(d))
+            .transpose()
+            .map_err(|e| PlaceError::rejected(RejectReason::InvalidQuantity, e))?;
+
+        let order = Order {
+            order_id: Uuid::new_v4(),
+            user_id: self.user_id,
+            market_id: self.market_id,
+            side: self.side,
+            outcome: self.outcome,
+            order_type: self.order_type,
+            price,
+            quantity,
+            filled: 0,
+            order_status: OrderStatus::PENDING,
+            reservation_id: self.reservation_id,
+            display_quantity,
+            reduce_only: self.reduce_only,
+            created_at: Utc::now(),
+        };
+        Ok((order, trigger))
+    }
+}
+
+fn price_rejected(e: SpecError) -> PlaceError {
+    let reason = match e {
+        SpecError::OutOfRange(_) => RejectReason::PriceOutOfRange,
+        _ => RejectReason::InvalidPrice,
+    };
+    PlaceError::rejected(reason, e)
+}
+
+/// The exits of a bracket order. Both are sized to the entry and sit on the
+/// opposite side of the same outcome.
+#[derive(Debug, Clone)]
+pub struct BracketExits {
+    /// Limit price the position is closed at in profit
+    pub take_profit_price: Decimal,
+    /// Last trade price that sets off the stop
+    pub stop_price: Decimal,
+    /// The stop goes in as a LIMIT at this price, or as a MARKET order if unset
+    pub stop_limit_price: Option<Decimal>,
+    pub take_profit_reservation_id: Option<String>,
+    pub stop_reservation_id: Option<String>,
+}
+
+impl BracketExits {
+    fn fingerprint(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.take_profit_price.normalize(),
+            self.stop_price.normalize(),
+            self.stop_limit_price.map(|p| p.normalize().to_string()).unwrap_or_default(),
+            self.take_profit_reservation_id.as_deref().unwrap_or_default(),
+            self.stop_reservation_id.as_deref().unwrap_or_default()
+        )
+    }
+
+    fn into_orders(self, spec: &MarketSpec, entry: &Order) -> Result<(Order, Order, Trigger), PlaceError> {
+        let side = match entry.side {
+            OrderSide::BUY => OrderSide::SELL,
+            OrderSide::SELL => OrderSide::BUY,
+        };
+        let exit = |order_type, price, reservation_id| Order {
+            order_id: Uuid::new_v4(),
+            side,
+            order_type,
+            price,
+            filled: 0,
+            order_status: OrderStatus::PENDING,
+            reservation_id,
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+            ..entry.clone()
+        };
+
+        let take_profit_price = spec.price_to_ticks(self.take_profit_price).map_err(price_rejected)?;
+        let stop_trigger = Trigger {
+            kind: TriggerKind::STOP,
+            price: spec.price_to_ticks(self.stop_price).map_err(price_rejected)?,
+        };
+        let (stop_type, stop_price) = match self.stop_limit_price {
+            Some(price) => (OrderType::LIMIT, spec.price_to_ticks(price).map_err(price_rejected)?),
+            None => (OrderType::MARKET, 0),
+        };
+
+        Ok((
+            exit(OrderType::LIMIT, take_profit_price, self.take_profit_reservation_id),
+            exit(stop_type, stop_price, self.stop_reservation_id),
+            stop_trigger,
+        ))
+    }
+}
+
+/// Everything a successful placement produced. Kept as-is for
+/// client_order_id replays, so both API versions can re-render it.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    pub spec: MarketSpec,
+    pub outcome_count: usize,
+    pub order: Order,
+    pub trades: Vec<Trade>,
+    pub complementary_matches: Vec<ComplementaryMatch>,
+    pub complete_set_matches: Vec<CompleteSetMatch>,
+}
+
+/// Every leg of an OCO pair or bracket as placed
+#[derive(Debug, Clone)]
+pub struct GroupPlacement {
+    pub group_id: Uuid,
+    /// OCO: both legs in the order given. Bracket: entry, take-profit, stop.
+    pub legs: Vec<Placement>,
+}
+
+/// What a client_order_id is remembered with. Orders and groups share one
+/// cache, so an id used for one cannot be replayed as the other.
+#[derive(Debug, Clone)]
+enum Placed {
+    Order(Box<Placement>),
+    Group(GroupPlacement),
+}
+
+impl From<Placement> for Placed {
+    fn from(placement: Placement) -> Self {
+        Placed::Order(Box::new(placement))
+    }
+}
+
+impl From<GroupPlacement> for Placed {
+    fn from(placement: GroupPlacement) -> Self {
+        Placed::Group(placement)
+    }
+}
+
+impl TryFrom<Placed> for Placement {
+    type Error = ();
+
+    fn try_from(placed: Placed) -> Result<Self, ()> {
+        match placed {
+            Placed::Order(placement) => Ok(*placement),
+            Placed::Group(_) => Err(()),
+        }
+    }
+}
+
+impl TryFrom<Placed> for GroupPlacement {
+    type Error = ();
+
+    fn try_from(placed: Placed) -> Result<Self, ()> {
+        match placed {
+            Placed::Group(placement) => Ok(placement),
+            Placed::Order(_) => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PlaceError {
+    /// The order was refused for a business reason
+    #[error("{message}")]
+    Rejected { reason: RejectReason, message: String },
+    #[error("Order with this client_order_id is still being placed")]
+    InFlight,
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl PlaceError {
+    fn rejected(reason: RejectReason, detail: impl ToString) -> Self {
+        PlaceError::Rejected { reason, message: detail.to_string() }
+    }
+}
+
+impl From<RejectReason> for PlaceError {
+    fn from(reason: RejectReason) -> Self {
+        PlaceError::rejected(reason, reason)
+    }
+}
+
+/// Books and shared state behind every API version
+pub struct Engine {
+    pub orderbooks: Arc<DashMap<String, SharedOrderBook>>,
+    pub metrics: Arc<Metrics>,
+    redis: Arc<RedisClient>,
+    default_spec: MarketSpec,
+    default_allocation: Allocation,
+    default_bands: BandConfig,
+    correction_window: Duration,
+    /// Where trade corrections are recorded; there are none without it
+    pub corrections: Option<Arc<dyn CorrectionStore>>,
+    enforce_positions: bool,
+    placed: IdempotencyCache<Placed>,
+    pub reservations: ReservationIndex,
+    /// Fired triggers waiting to be queued for settlement
+    pub fired: FiredQueue,
+    pub sessions: SessionRegistry,
+    /// Markets orders are accepted for; enforced when there is a database
+    pub markets: MarketRegistry,
+    pub executions: ExecutionHub,
+    /// Level-2 book changes and public trades per market
+    pub market_data: MarketDataHub,
+    pub rate_limits: RateLimiter,
+}
+
+impl Engine {
+    pub fn new(
+        orderbooks: Arc<DashMap<String, SharedOrderBook>>,
+        redis: Arc<RedisClient>,
+        metrics: Arc<Metrics>,
+        config: &Config,
+    ) -> Self {
+        Self {
+            orderbooks,
+            metrics,
+            redis,
+            default_spec: config.default_spec,
+            default_allocation: config.default_allocation,
+            default_bands: config.default_bands,
+            correction_window: Duration::from_secs(config.trade_correction_window_secs),
+            corrections: config
+                .database_url
+                .clone()
+                .map(|url| Arc::new(PostgresCorrections::new(url)) as Arc<dyn CorrectionStore>),
+            enforce_positions: config.database_url.is_some(),
+            placed: IdempotencyCache::new(Duration::from_secs(config.client_order_id_ttl_secs)),
+            reservations: ReservationIndex::default(),
+            fired: FiredQueue::default(),
+            sessions: SessionRegistry::new(Duration::from_millis(config.session_timeout_ms)),
+            markets: MarketRegistry::new(config.database_url.is_some()),
+            executions: ExecutionHub::default(),
+            market_data: MarketDataHub::default(),
+            rate_limits: RateLimiter::new(config.rate_limits.clone()),
+        }
+    }
+
+    pub fn book(&self, market_id: &str) -> Option<SharedOrderBook> {
+        self.orderbooks.get(market_id).map(|b| b.clone())
+    }
+
+    /// Existing book, or an empty one for a listed market nobody has traded yet
+    pub fn market_book(&self, market_id: &str) -> Option<SharedOrderBook> {
+        match self.markets.get(market_id) {
+            Some(_) => self.listed_book(market_id),
+            None => self.book(market_id),
+        }
+    }
+
+    /// Book for a listed market, created the first time it is needed. None
+    /// if the registry is enforced and does not have the market.
+    pub fn listed_book(&self, market_id: &str) -> Option<SharedOrderBook> {
+        let spec = match self.markets.get(market_id) {
+            Some(market) => market.spec,
+            None if self.markets.is_enforced() => return None,
+            None => self.default_spec,
+        };
+        let orderbook = self
+            .orderbooks
+            .entry(market_id.to_string())
+            .or_insert_with(|| {
+                let mut book = OrderBook::new(market_id.to_string(), spec);
+                book.allocation = self.default_allocation;
+                book.bands.configure(self.default_bands);
+                book.inventory.enforced = self.enforce_positions;
+                book.trades.window = self.correction_window;
+                // A market closed before anything was placed in it
+                book.closed = self.markets.is_closed(market_id);
+                Arc::new(RwLock::new(book))
+            })
+            .clone();
+        Some(orderbook)
+    }
+
+    /// Reload the registry from `source`
+    pub async fn refresh_markets(&self, source: &dyn MarketSource) -> anyhow::Result<()> {
+        let listed = self.markets.replace(source.load().await?);
+        info!("Market registry now lists {} markets", listed);
+        Ok(())
+    }
+
+    // The book to place into, if the market is listed and open
+    fn open_book(&self, market_id: &str) -> Result<SharedOrderBook, PlaceError> {
+        if let Some(market) = self.markets.get(market_id) {
+            let detail = if market.expires_at.is_some_and(|at| at <= Utc::now()) {
+                Some(format!("Market {} has expired", market_id))
+            } else if !market.is_tradable() {
+                Some(format!("Market {} is {}", market_id, market.state))
+            } else {
+                None
+            };
+            if let Some(detail) = detail {
+                return Err(self.rejected(market_id, PlaceError::rejected(RejectReason::MarketClosed, detail)));
+            }
+        }
+        self.listed_book(market_id)
+            .ok_or_else(|| self.rejected(market_id, RejectReason::UnknownMarket.into()))
+    }
+
+    /// Create a market with `outcome_count` outcomes. An existing market is
+    /// returned as-is if it has the same outcome count; otherwise its actual
+    /// count comes back as the error.
+    pub fn create_market(&self, market_id: &str, outcome_count: usize) -> Result<SharedOrderBook, usize> {
+        let orderbook = self
+            .orderbooks
+            .entry(market_id.to_string())
+            .or_insert_with(|| {
+                let mut book = OrderBook::with_outcomes(market_id.to_string(), self.default_spec, outcome_count);
+                book.allocation = self.default_allocation;
+                book.bands.configure(self.default_bands);
+                book.inventory.enforced = self.enforce_positions;
+                book.trades.window = self.correction_window;
+                info!("Created market {} with {} outcomes", market_id, outcome_count);
+                Arc::new(RwLock::new(book))
+            })
+            .clone();
+        if self.markets.get(market_id).is_none() {
+            self.markets.add(MarketInfo {
+                market_id: market_id.to_string(),
+                state: MarketState::OPEN,
+                expires_at: None,
+                spec: self.default_spec,
+            });
+        }
+
+        let existing = orderbook.read().unwrap().outcome_count();
+        if existing == outcome_count {
+            Ok(orderbook)
+        } else {
+            Err(existing)
+        }
+    }
+
+    /// Load holdings from the `positions` table. Rows off the market's lot
+    /// grid are skipped with a warning.
+    pub fn seed_positions(&self, positions: Vec<PositionRow>) {
+        for position in positions {
+            let Some(orderbook) = self.listed_book(&position.market_id) else {
+                warn!("Skipping position of {} in unknown market {}", position.user_id, position.market_id);
+                continue;
+            };
+            let mut book = orderbook.write().unwrap();
+            for (outcome, tokens) in [(Outcome::YES, position.yes_tokens), (Outcome::NO, position.no_tokens)] {
+                match book.spec.quantity_to_lots(tokens) {
+                    Ok(lots) => book.inventory.set(&position.user_id, outcome, lots),
+                    Err(e) => warn!(
+                        "Skipping {:?} position of {} in {}: {}",
+                        outcome, position.user_id, position.market_id, e
+                    ),
+                }
+            }
+        }
+    }
+
+    pub fn purge_expired_client_order_ids(&self) {
+        self.placed.purge_expired();
+    }
+
+    /// Forget reservations whose orders have all gone
+    pub fn purge_reservations(&self) {
+        self.reservations.purge(|market_id, reservation_id| self.holds_reservation(market_id, reservation_id));
+    }
+
+    /// Whether a working order in `market_id` is backed by `reservation_id`
+    fn holds_reservation(&self, market_id: &str, reservation_id: &str) -> bool {
+        self.book(market_id).is_some_and(|orderbook| {
+            let book = orderbook.read().unwrap();
+            book.has_reservation(reservation_id)
+                || book.triggers.has_reservation(reservation_id)
+                || book.groups.has_reservation(reservation_id)
+        })
+    }
+
+    /// Close every market whose expiry has passed: it stops taking orders,
+    /// everything working in it is cancelled on `orders:cancelled`, and
+    /// `markets:closed` announces it for settlement and resolution
+    pub fn close_expired_markets(&self, now: DateTime<Utc>) {
+        for market in self.markets.expired(now) {
+            if !self.markets.close(&market.market_id) {
+                continue;
+            }
+            let cancelled = match self.book(&market.market_id) {
+                Some(orderbook) => {
+                    let mut book = orderbook.write().unwrap();
+                    book.closed = true;
+                    let orders = book.cancel_all();
+                    self.metrics.observe_book(&book);
+                    self.market_data.publish(&mut book);
+                    self.publish_pulled(&book, &orders, ExecType::EXPIRED);
+                    orders.iter().map(|order| cancelled_json(&book.spec, order)).collect()
+                }
+                None => Vec::new(),
+            };
+            info!("Market {} expired, cancelled {} orders", market.market_id, cancelled.len());
+
+            let count = cancelled.len();
+            if !cancelled.is_empty() {
+                self.publish_cancellations(serde_json::json!({
+                    "reason": "market_expired",
+                    "market_id": market.market_id,
+                    "orders": cancelled,
+                }));
+            }
+            let payload = serde_json::json!({
+                "market_id": market.market_id,
+                "expires_at": market.expires_at,
+                "closed_at": now,
+                "cancelled_orders": count,
+            })
+            .to_string();
+            let redis = self.redis.clone();
+            tokio::spawn(async move {
+                if let Err(e) = redis.publish("markets:closed", &payload).await {
+                    warn!("Failed to publish market closed: {}", e);
+                }
+            });
+        }
+    }
+
+    /// Cancel the orders of every session that missed its heartbeat window
+    pub fn expire_sessions(&self) {
+        for session in self.sessions.expire(Instant::now()) {
+            warn!("Session {} of {} timed out", session.session_id, session.user_id);
+            self.cancel_session_orders(session);
+        }
+    }
+
+    /// Stop tracking a session's orders once they have filled or been
+    /// cancelled, so a long-lived session doesn't hold on to every order it
+    /// ever placed. Bracket entries stay while their exits are held.
+    pub fn prune_session(&self, session_id: &str) {
+        let finished: Vec<(String, Uuid)> = self
+            .sessions
+            .orders(session_id)
+            .into_iter()
+            .filter(|(market_id, order_id)| {
+                !self.book(market_id).is_some_and(|orderbook| {
+                    let book = orderbook.read().unwrap();
+                    book.working_order(order_id).is_some() || book.groups.group_of(order_id).is_some()
+                })
+            })
+            .collect();
+        self.sessions.forget(session_id, &finished);
+    }
+
+    /// Pull whatever a closed session still has resting, along with the rest
+    /// of any group its orders belong to, and publish the cancellations on
+    /// `orders:cancelled` so reservations can be released. Returns the number
+    /// of orders cancelled.
+    pub fn cancel_session_orders(&self, session: ClosedSession) -> usize {
+        let mut cancelled = Vec::new();
+        for (market_id, order_id) in &session.orders {
+            let Some(orderbook) = self.book(market_id) else { continue };
+            let mut book = orderbook.write().unwrap();
+            let orders = match book.groups.group_of(order_id) {
+                Some(group_id) => book.cancel_group(&group_id),
+                None => book.cancel(*order_id).into_iter().collect(),
+            };
+            if orders.is_empty() {
+                continue;
+            }
+            self.metrics.observe_book(&book);
+            self.market_data.publish(&mut book);
+            self.publish_pulled(&book, &orders, ExecType::CANCELLED);
+            cancelled.extend(orders.iter().map(|order| cancelled_json(&book.spec, order)));
+        }
+        info!("Session {} closed, cancelled {} orders", session.session_id, cancelled.len());
+        if cancelled.is_empty() {
+            return 0;
+        }
+
+        let count = cancelled.len();
+        let payload = serde_json::json!({
+            "reason": "session_closed",
+            "session_id": session.session_id,
+            "user_id": session.user_id,
+            "orders": cancelled,
+        });
+        self.publish_cancellations(payload);
+        count
+    }
+
+    /// Publish OCO partners and bracket exits that were cancelled because
+    /// another order in their group filled
+    pub fn publish_group_cancellations(&self, spec: &MarketSpec, orders: &[Order]) {
+        if orders.is_empty() {
+            return;
+        }
+        let payload = serde_json::json!({
+            "reason": "order_group",
+            "orders": orders.iter().map(|order| cancelled_json(spec, order)).collect::<Vec<_>>(),
+        });
+        self.publish_cancellations(payload);
+    }
+
+    /// Execution reports for orders taken out of `book` other than by matching
+    fn publish_pulled(&self, book: &OrderBook, orders: &[Order], exec_type: ExecType) {
+        let outcome_count = book.outcome_count();
+        self.executions.publish(
+            orders
+                .iter()
+                .map(|order| cancel_execution(book.spec, outcome_count, order, exec_type))
+                .collect(),
+        );
+    }
+
+    fn publish_cancellations(&self, payload: serde_json::Value) {
+        let payload = payload.to_string();
+        let redis = self.redis.clone();
+        tokio::spawn(async move {
+            if let Err(e) = redis.publish("orders:cancelled", &payload).await {
+                warn!("Failed to publish cancellations: {}", e);
+            }
+        });
+    }
+
+    /// Push the price the auction would uncross at right now to
+    /// `auction:indicative:{market_id}`
+    pub fn publish_indicative(&self, book: &OrderBook) {
+        let indicative = AuctionBook::new(book).clearing_price();
+        let payload = serde_json::json!({
+            "market_id": book.market_id,
+            "price": indicative.map(|c| book.spec.ticks_to_price(c.price).to_string()),
+            "volume": book.spec.lots_to_quantity(indicative.map_or(0, |c| c.volume)).to_string(),
+        })
+        .to_string();
+
+        let channel = format!("auction:indicative:{}", book.market_id);
+        let redis = self.redis.clone();
+        tokio::spawn(async move {
+            if let Err(e) = redis.publish(&channel, &payload).await {
+                warn!("Failed to publish indicative price on {}: {}", channel, e);
+            }
+        });
+    }
+
+    /// Tell the quoting user their orders were pulled, on `mmp:{market_id}`
+    fn publish_mmp_triggered(&self, spec: &MarketSpec, event: &MmpTriggered) {
+        self.metrics.mmp_triggers_total.with_label_values(&[&event.market_id]).inc();
+        let payload = serde_json::json!({
+            "market_id": event.market_id,
+            "user_id": event.user_id,
+            "filled_quantity": spec.lots_to_quantity(event.filled_quantity).to_string(),
+            "fill_count": event.fill_count,
+            "cancelled": event.cancelled.iter().map(|o| serde_json::json!({
+                "order_id": o.order_id,
+                "reservation_id": o.reservation_id,
+                "remaining": spec.lots_to_quantity(o.remaining()).to_string(),
+            })).collect::<Vec<_>>(),
+        })
+        .to_string();
+
+        let channel = format!("mmp:{}", event.market_id);
+        let redis = self.redis.clone();
+        tokio::spawn(async move {
+            if let Err(e) = redis.publish(&channel, &payload).await {
+                warn!("Failed to publish MMP trigger on {}: {}", channel, e);
+            }
+        });
+    }
+
+    /// Announce a tripped circuit breaker on `circuit_breaker:{market_id}`
+    fn publish_breaker_tripped(&self, spec: &MarketSpec, event: &BreakerTripped) {
+        self.metrics
+            .circuit_breaker_trips_total
+            .with_label_values(&[&event.market_id, &event.action.to_string()])
+            .inc();
+        let payload = serde_json::json!({
+            "market_id": event.market_id,
+            "outcome": event.outcome.index(),
+            "action": event.action.to_string(),
+            "low": spec.ticks_to_price(event.low).to_string(),
+            "high": spec.ticks_to_price(event.high).to_string(),
+            "cooldown_ms": event.halted_until.map(|until| until.saturating_duration_since(Instant::now()).as_millis() as u64),
+        })
+        .to_string();
+
+        let channel = format!("circuit_breaker:{}", event.market_id);
+        let redis = self.redis.clone();
+        tokio::spawn(async move {
+            if let Err(e) = redis.publish(&channel, &payload).await {
+                warn!("Failed to publish circuit breaker on {}: {}", channel, e);
+            }
+        });
+    }
+
+    /// Report conditional orders that fired on `triggers:{market_id}`, one
+    /// message each in firing order, with everything they traded. The same
+    /// messages go on the `triggers:fired` list for the order service to
+    /// settle, since their trades are in no placement response. `placed_by`
+    /// is the reservation_id of the placement that set them off, whose own
+    /// fills have to be settled first.
+    pub fn publish_triggered(&self, spec: &MarketSpec, placed_by: Option<&str>, fired: &[FiredTrigger]) {
+        for fired in fired {
+            let order = &fired.order;
+            let mut payload = serde_json::json!({
+                "market_id": order.market_id,
+                "order_id": order.order_id,
+                "user_id": order.user_id,
+                "reservation_id": order.reservation_id,
+                "side": format!("{:?}", order.side),
+                "placed_by": placed_by,
+                // Both null for a bracket take-profit released by its entry
+                "trigger_type": fired.trigger.map(|t| t.kind.as_str()),
+                "trigger_price": fired.trigger.map(|t| spec.ticks_to_price(t.price).to_string()),
+            });
+            match &fired.result {
+                Ok(result) => {
+                    for event in &result.mmp_triggered {
+                        self.publish_mmp_triggered(spec, event);
+                    }
+                    for event in &result.breakers_tripped {
+                        self.publish_breaker_tripped(spec, event);
+                    }
+                    self.publish_group_cancellations(spec, &result.cancelled);
+                    payload["status"] = format!("{:?}", result.order.order_status).into();
+                    payload["filled"] = spec.lots_to_quantity(result.order.filled).to_string().into();
+                    payload["trades"] = result.trades.iter().map(|t| trade_json(spec, t)).collect();
+                    payload["complementary_matches"] =
+                        result.complementary_matches.iter().map(|m| complementary_match_json(spec, m)).collect();
+                    payload["complete_set_matches"] =
+                        result.complete_set_matches.iter().map(|m| complete_set_match_json(spec, m)).collect();
+                }
+                Err(e) => {
+                    payload["status"] = "REJECTED".into();
+                    payload["reject_reason"] =
+                        e.downcast_ref::<RejectReason>().map_or("internal", |r| r.as_str()).into();
+                }
+            }
+
+            self.fired.push(FiredMessage {
+                channel: format!("triggers:{}", order.market_id),
+                payload: payload.to_string(),
+            });
+        }
+    }
+
+    /// Deduplicate on (user_id, client_order_id) before placing. Only
+    /// successful placements are remembered; a failed one can be retried.
+    pub fn place_order(&self, req: NewOrder) -> Result<Placement, PlaceError> {
+        let (user_id, client_id, market_id) =
+            (req.user_id.clone(), req.client_order_id.clone(), req.market_id.clone());
+        let fingerprint = req.fingerprint();
+        let reservations = [req.reservation_id.clone()];
+        self.deduplicated(&user_id, client_id.as_deref(), &market_id, fingerprint, || {
+            self.reserved(&market_id, &reservations, || self.place(req))
+        })
+    }
+
+    /// Hold the reservations of a placement against every other market
+    /// while it runs. One that backs an order in another market is refused.
+    fn reserved<T>(
+        &self,
+        market_id: &str,
+        reservations: &[Option<String>],
+        place: impl FnOnce() -> Result<T, PlaceError>,
+    ) -> Result<T, PlaceError> {
+        let mut claimed: Vec<&str> = Vec::new();
+        for reservation_id in reservations.iter().flatten() {
+            let free = self.reservations.claim(reservation_id, market_id, |holder| {
+                self.holds_reservation(holder, reservation_id)
+            });
+            if !free {
+                for reservation_id in claimed {
+                    self.reservations.finish(reservation_id);
+                }
+                let detail = format!("Reservation {} backs an order in another market", reservation_id);
+                return Err(self.rejected(market_id, PlaceError::rejected(RejectReason::DuplicateReservation, detail)));
+            }
+            claimed.push(reservation_id);
+        }
+        let result = place();
+        for reservation_id in claimed {
+            self.reservations.finish(reservation_id);
+        }
+        result
+    }
+
+    fn deduplicated<T>(
+        &self,
+        user_id: &str,
+        client_id: Option<&str>,
+        market_id: &str,
+        fingerprint: String,
+        place: impl FnOnce() -> Result<T, PlaceError>,
+    ) -> Result<T, PlaceError>
+    where
+        T: Clone + Into<Placed> + TryFrom<Placed>,
+    {
+        let client_id = match client_id {
+            Some(id) if !id.is_empty() => id,
+            _ => return place(),
+        };
+
+        match self.placed.claim(user_id, client_id, fingerprint) {
+            Claim::Fresh => {}
+            Claim::Replay(placed) => {
+                if let Ok(placement) = T::try_from(placed) {
+                    info!("↩️ Replaying placement for {} / {}", user_id, client_id);
+                    return Ok(placement);
+                }
+                self.metrics.record_reject(market_id, RejectReason::ClientOrderIdReused.as_str());
+                return Err(RejectReason::ClientOrderIdReused.into());
+            }
+            Claim::InFlight => return Err(PlaceError::InFlight),
+            Claim::Conflict => {
+                self.metrics.record_reject(market_id, RejectReason::ClientOrderIdReused.as_str());
+                return Err(RejectReason::ClientOrderIdReused.into());
+            }
+        }
+
+        let result = place();
+        match &result {
+            Ok(placement) => self.placed.complete(user_id, client_id, placement.clone().into()),
+            Err(_) => self.placed.release(user_id, client_id),
+        }
+        result
+    }
+
+    fn place(&self, req: NewOrder) -> Result<Placement, PlaceError> {
+        info!("📥 PlaceOrder: {}, {}", req.user_id, req.market_id);
+        let market_id = req.market_id.clone();
+        let session_id = req.session_id.clone();
+        self.check_session(&req)?;
+
+        let orderbook = self.open_book(&req.market_id)?;
+        let (spec, outcome_count) = {
+            let book = orderbook.read().unwrap();
+            (book.spec, book.outcome_count())
+        };
+
+        let result = req.into_order(&spec).and_then(|(order, trigger)| {
+            self.count_order(&order, outcome_count);
+            self.with_matcher(
+                &orderbook,
+                |matcher| match trigger {
+                    Some(trigger) => matcher.place_conditional(order, trigger),
+                    None => matcher.place_order(order),
+                },
+                |result, makers| match_executions(spec, outcome_count, &[result], makers),
+            )
+        });
+
+        match result {
+            Ok(result) => {
+                if let Some(session_id) = &session_id {
+                    let rests = matches!(
+                        result.order.order_status,
+                        OrderStatus::OPEN | OrderStatus::PARTIAL | OrderStatus::UNTRIGGERED
+                    );
+                    if rests {
+                        self.track_session(session_id, &result.order.user_id, &market_id, &[result.order.order_id]);
+                    }
+                }
+                info!("✅ Matched: trades={}, cmatch={}", result.trades.len(), result.complementary_matches.len());
+                Ok(self.settle(spec, outcome_count, result))
+            }
+            Err(e) => Err(self.rejected(&market_id, e)),
+        }
+    }
+
+    /// Place two orders for the same user and market so that the first fill
+    /// on either cancels the other. Deduplicated on the first leg's
+    /// client_order_id.
+    pub fn place_oco(&self, legs: [NewOrder; 2]) -> Result<GroupPlacement, PlaceError> {
+        let [first, second] = &legs;
+        let (user_id, client_id, market_id) =
+            (first.user_id.clone(), first.client_order_id.clone(), first.market_id.clone());
+        let fingerprint = format!("oco|{}|{}", first.fingerprint(), second.fingerprint());
+        let reservations = [first.reservation_id.clone(), second.reservation_id.clone()];
+        self.deduplicated(&user_id, client_id.as_deref(), &market_id, fingerprint, || {
+            self.reserved(&market_id, &reservations, || self.place_oco_once(legs))
+        })
+    }
+
+    fn place_oco_once(&self, legs: [NewOrder; 2]) -> Result<GroupPlacement, PlaceError> {
+        let [first, second] = legs;
+        info!("📥 PlaceOco: {}, {}", first.user_id, first.market_id);
+        if first.user_id != second.user_id || first.market_id != second.market_id {
+            return Err(anyhow::anyhow!("OCO legs must share a user and market").into());
+        }
+        let market_id = first.market_id.clone();
+        let session_id = first.session_id.clone().or_else(|| second.session_id.clone());
+        self.check_session(&first)?;
+        self.check_session(&second)?;
+
+        let orderbook = self.open_book(&market_id)?;
+        let (spec, outcome_count) = {
+            let book = orderbook.read().unwrap();
+            (book.spec, book.outcome_count())
+        };
+
+        let result = first.into_order(&spec).and_then(|first| {
+            let second = second.into_order(&spec)?;
+            self.count_order(&first.0, outcome_count);
+            self.count_order(&second.0, outcome_count);
+            self.with_matcher(&orderbook, |matcher| matcher.place_oco([first, second]), |result, makers| {
+                group_executions(spec, outcome_count, result, makers)
+            })
+        });
+        match result {
+            Ok(result) => Ok(self.settle_group(spec, outcome_count, session_id.as_deref(), &market_id, result)),
+            Err(e) => Err(self.rejected(&market_id, e)),
+        }
+    }
+
+    /// Place an entry order whose take-profit and stop are released once it
+    /// has completely filled. Deduplicated on the entry's client_order_id.
+    pub fn place_bracket(&self, entry: NewOrder, exits: BracketExits) -> Result<GroupPlacement, PlaceError> {
+        let (user_id, client_id, market_id) =
+            (entry.user_id.clone(), entry.client_order_id.clone(), entry.market_id.clone());
+        let fingerprint = format!("bracket|{}|{}", entry.fingerprint(), exits.fingerprint());
+        let reservations = [
+            entry.reservation_id.clone(),
+            exits.take_profit_reservation_id.clone(),
+            exits.stop_reservation_id.clone(),
+        ];
+        self.deduplicated(&user_id, client_id.as_deref(), &market_id, fingerprint, || {
+            self.reserved(&market_id, &reservations, || self.place_bracket_once(entry, exits))
+        })
+    }
+
+    fn place_bracket_once(&self, entry: NewOrder, exits: BracketExits) -> Result<GroupPlacement, PlaceError> {
+        info!("📥 PlaceBracket: {}, {}", entry.user_id, entry.market_id);
+        let market_id = entry.market_id.clone();
+        let session_id = entry.session_id.clone();
+        self.check_session(&entry)?;
+
+        let orderbook = self.open_book(&market_id)?;
+        let (spec, outcome_count) = {
+            let book = orderbook.read().unwrap();
+            (book.spec, book.outcome_count())
+        };
+
+        let result = entry.into_order(&spec).and_then(|(entry, entry_trigger)| {
+            let (take_profit, stop, stop_trigger) = exits.into_orders(&spec, &entry)?;
+            self.count_order(&entry, outcome_count);
+            self.with_matcher(
+                &orderbook,
+                |matcher| matcher.place_bracket((entry, entry_trigger), take_profit, stop, stop_trigger),
+                |result, makers| group_executions(spec, outcome_count, result, makers),
+            )
+        });
+        match result {
+            Ok(result) => Ok(self.settle_group(spec, outcome_count, session_id.as_deref(), &market_id, result)),
+            Err(e) => Err(self.rejected(&market_id, e)),
+        }
+    }
+
+    /// Cancel every working order of a group. Returns None if the market or
+    /// group does not exist, or the group belongs to someone else.
+    pub fn cancel_group(&self, market_id: &str, group_id: &Uuid, user_id: &str) -> Option<Vec<Order>> {
+        let orderbook = self.book(market_id)?;
+        let mut book = orderbook.write().unwrap();
+        if book.groups.owner(group_id) != Some(user_id) {
+            return None;
+        }
+        let cancelled = book.cancel_group(group_id);
+        self.metrics.observe_book(&book);
+        self.market_data.publish(&mut book);
+        self.publish_pulled(&book, &cancelled, ExecType::CANCELLED);
+        info!("Cancelled group {} in {}: {} orders", group_id, market_id, cancelled.len());
+        Some(cancelled)
+    }
+
+    /// Cancel one working order, along with the rest of its group if it is
+    /// in one, and publish the cancellations on `orders:cancelled`. Returns
+    /// None if there is no such order or it belongs to someone else.
+    pub fn cancel_order(&self, market_id: &str, order_id: &Uuid, user_id: &str) -> Option<Vec<Order>> {
+        let orderbook = self.book(market_id)?;
+        let mut book = orderbook.write().unwrap();
+        if book.working_order(order_id)?.user_id != user_id {
+            return None;
+        }
+        let cancelled = match book.groups.group_of(order_id) {
+            Some(group_id) => book.cancel_group(&group_id),
+            None => book.cancel(*order_id).into_iter().collect(),
+        };
+        self.metrics.observe_book(&book);
+        self.market_data.publish(&mut book);
+        self.publish_pulled(&book, &cancelled, ExecType::CANCELLED);
+        info!("Cancelled order {} in {}: {} orders", order_id, market_id, cancelled.len());
+
+        let payload = serde_json::json!({
+            "reason": "cancelled",
+            "user_id": user_id,
+            "orders": cancelled.iter().map(|order| cancelled_json(&book.spec, order)).collect::<Vec<_>>(),
+        });
+        self.publish_cancellations(payload);
+        Some(cancelled)
+    }
+
+    /// Bust a secondary trade printed within the correction window: the
+    /// tokens go back to the seller, and the ledger reverses the cash and
+    /// positions. Mints are refused. Comes back
+    /// with the market's spec for rendering prices.
+    pub async fn bust_trade(
+        &self,
+        market_id: &str,
+        trade_id: &Uuid,
+        actor: &str,
+        reason: &str,
+    ) -> Result<(MarketSpec, TradeRecord), CorrectionError> {
+        let orderbook = self.book(market_id).ok_or(CorrectionError::UnknownMarket)?;
+        let (spec, corrected) = {
+            let book = orderbook.read().unwrap();
+            (book.spec, book.trades.bust(trade_id, &book.inventory, actor, reason, Utc::now())?)
+        };
+        self.correct(&orderbook, spec, corrected).await
+    }
+
+    /// Reprice a secondary trade printed within the correction window, e.g.
+    /// one that went through at a stale price. Positions stand; the ledger
+    /// settles the difference in cash.
+    pub async fn reprice_trade(
+        &self,
+        market_id: &str,
+        trade_id: &Uuid,
+        price: Decimal,
+        actor: &str,
+        reason: &str,
+    ) -> Result<(MarketSpec, TradeRecord), CorrectionError> {
+        let orderbook = self.book(market_id).ok_or(CorrectionError::UnknownMarket)?;
+        let (spec, corrected) = {
+            let book = orderbook.read().unwrap();
+            let price = book.spec.price_to_ticks(price).map_err(|e| CorrectionError::InvalidPrice(e.to_string()))?;
+            (book.spec, book.trades.reprice(trade_id, price, actor, reason, Utc::now())?)
+        };
+        self.correct(&orderbook, spec, corrected).await
+    }
+
+    /// Every correction ever made to a trade, from the database, for trades
+    /// that have left the correction window
+    pub async fn stored_corrections(&self, trade_id: &Uuid) -> Result<Vec<CorrectionRow>, CorrectionError> {
+        let store = self.corrections.as_ref().ok_or(CorrectionError::NoDatabase)?;
+        store
+            .load(&trade_id.to_string())
+            .await
+            .map_err(|e| CorrectionError::Unavailable(e.to_string()))
+    }
+
+    // Store a checked correction in `trade_corrections`, then put it into
+    // effect. If the trade moved on in between, the row is taken out again.
+    async fn correct(
+        &self,
+        orderbook: &SharedOrderBook,
+        spec: MarketSpec,
+        corrected: TradeRecord,
+    ) -> Result<(MarketSpec, TradeRecord), CorrectionError> {
+        let store = self.corrections.as_ref().ok_or(CorrectionError::NoDatabase)?;
+        let market_id = orderbook.read().unwrap().market_id.clone();
+        let row = correction_row(&spec, &market_id, &corrected);
+        store
+            .insert(&row)
+            .await
+            .map_err(|e| CorrectionError::Unavailable(e.to_string()))?;
+
+        let applied = {
+            let mut book = orderbook.write().unwrap();
+            let book = &mut *book;
+            book.trades.apply(corrected, &mut book.inventory).cloned()
+        };
+        match applied {
+            Ok(record) => {
+                self.publish_correction(&row);
+                Ok((spec, record))
+            }
+            Err(e) => {
+                if let Err(db_error) = store.delete(&row.id).await {
+                    error!("Correction {} was refused but is still stored: {}", row.id, db_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// A trade still within the correction window, with every correction
+    /// made to it
+    pub fn trade_record(&self, market_id: &str, trade_id: &Uuid) -> Result<(MarketSpec, TradeRecord), CorrectionError> {
+        let orderbook = self.book(market_id).ok_or(CorrectionError::UnknownMarket)?;
+        let book = orderbook.read().unwrap();
+        let record = book.trades.get(trade_id).cloned().ok_or(CorrectionError::NotFound(*trade_id))?;
+        Ok((book.spec, record))
+    }
+
+    /// Record a correction that has taken effect in the audit log and queue
+    /// it on `trades:corrections` for the ledger, which reads the stored row
+    fn publish_correction(&self, row: &CorrectionRow) {
+        self.metrics.trade_corrections_total.with_label_values(&[&row.market_id, &row.action]).inc();
+        match (row.old_price, row.new_price) {
+            (Some(old_price), Some(new_price)) => info!(
+                target: AUDIT,
+                "{} repriced trade {} in {} from {} to {}: {}",
+                row.actor, row.trade_id, row.market_id, old_price, new_price, row.reason
+            ),
+            _ => info!(
+                target: AUDIT,
+                "{} busted trade {} in {}: {}",
+                row.actor, row.trade_id, row.market_id, row.reason
+            ),
+        }
+
+        let payload = serde_json::json!({
+            "correction_id": row.id,
+            "action": row.action,
+            "market_id": row.market_id,
+            "trade_id": row.trade_id,
+        })
+        .to_string();
+        let redis = self.redis.clone();
+        tokio::spawn(async move {
+            if let Err(e) = redis.enqueue("trades:corrections", &payload).await {
+                warn!("Failed to queue trade correction: {}", e);
+            }
+        });
+    }
+
+    fn check_session(&self, req: &NewOrder) -> Result<(), PlaceError> {
+        if let Some(session_id) = &req.session_id {
+            if !self.sessions.is_open(session_id, &req.user_id) {
+                self.metrics.record_reject(&req.market_id, RejectReason::UnknownSession.as_str());
+                return Err(RejectReason::UnknownSession.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Tie orders to a session, cancelling them at once if it has closed in
+    /// the meantime
+    fn track_session(&self, session_id: &str, user_id: &str, market_id: &str, order_ids: &[Uuid]) {
+        let closed = order_ids.iter().any(|id| !self.sessions.track(session_id, market_id, *id));
+        if closed {
+            // The session expired while we were matching
+            self.cancel_session_orders(ClosedSession {
+                session_id: session_id.to_string(),
+                user_id: user_id.to_string(),
+                orders: order_ids.iter().map(|id| (market_id.to_string(), *id)).collect(),
+            });
+        }
+    }
+
+    fn count_order(&self, order: &Order, outcome_count: usize) {
+        self.metrics
+            .orders_total
+            .with_label_values(&[
+                &order.market_id,
+                order.side.as_str(),
+                &order.outcome.label(outcome_count),
+                order.order_type.as_str(),
+            ])
+            .inc();
+    }
+
+    /// Run `f` under the market's write lock, which serializes all matching
+    /// within a market. `reports` turns what it did, and the resting orders
+    /// it filled, into execution reports; they go out before the lock is
+    /// released so each market's reports stay in the order things happened.
+    fn with_matcher<T>(
+        &self,
+        orderbook: &SharedOrderBook,
+        f: impl FnOnce(&mut Matcher) -> anyhow::Result<T>,
+        reports: impl FnOnce(&T, Vec<Order>) -> Vec<Execution>,
+    ) -> Result<T, PlaceError> {
+        let mut book = orderbook.write().unwrap();
+        let started = Instant::now();
+        let result = f(&mut Matcher::new(&mut book));
+        let makers = book.take_fills();
+        if let Ok(result) = &result {
+            self.executions.publish(reports(result, makers));
+        }
+        self.metrics
+            .match_latency_seconds
+            .with_label_values(&[&book.market_id])
+            .observe(started.elapsed().as_secs_f64());
+        self.metrics.observe_book(&book);
+        self.market_data.publish(&mut book);
+        if book.phase == TradingPhase::AUCTION {
+            self.publish_indicative(&book);
+        }
+
+        result.map_err(|e| match e.downcast::<RejectReason>() {
+            Ok(reason) => reason.into(),
+            Err(e) => PlaceError::Internal(e),
+        })
+    }
+
+    /// Publish what a placement set off and keep what the caller reports
+    fn settle(&self, spec: MarketSpec, outcome_count: usize, result: MatchResult) -> Placement {
+        for event in &result.mmp_triggered {
+            self.publish_mmp_triggered(&spec, event);
+        }
+        for event in &result.breakers_tripped {
+            self.publish_breaker_tripped(&spec, event);
+        }
+        self.publish_triggered(&spec, result.order.reservation_id.as_deref(), &result.triggered);
+        self.publish_group_cancellations(&spec, &result.cancelled);
+        Placement {
+            spec,
+            outcome_count,
+            order: result.order,
+            trades: result.trades,
+            complementary_matches: result.complementary_matches,
+            complete_set_matches: result.complete_set_matches,
+        }
+    }
+
+    fn settle_group(
+        &self,
+        spec: MarketSpec,
+        outcome_count: usize,
+        session_id: Option<&str>,
+        market_id: &str,
+        result: GroupResult,
+    ) -> GroupPlacement {
+        if let Some(session_id) = session_id {
+            // Every leg is tracked: held bracket exits are reached through
+            // their group once released
+            let order_ids: Vec<Uuid> = result.legs.iter().map(|leg| leg.order.order_id).collect();
+            let user_id = &result.legs[0].order.user_id;
+            self.track_session(session_id, user_id, market_id, &order_ids);
+        }
+        GroupPlacement {
+            group_id: result.group_id,
+            legs: result.legs.into_iter().map(|leg| self.settle(spec, outcome_count, leg)).collect(),
+        }
+    }
+
+    fn rejected(&self, market_id: &str, e: PlaceError) -> PlaceError {
+        if let PlaceError::Rejected { reason, .. } = &e {
+            self.metrics.record_reject(market_id, reason.as_str());
+        }
+        e
+    }
+}
+
+fn group_executions(spec: MarketSpec, outcome_count: usize, result: &GroupResult, makers: Vec<Order>) -> Vec<Execution> {
+    let legs: Vec<&MatchResult> = result.legs.iter().collect();
+    match_executions(spec, outcome_count, &legs, makers)
+}
+
+fn cancelled_json(spec: &MarketSpec, order: &Order) -> serde_json::Value {
+    serde_json::json!({
+        "market_id": order.market_id,
+        "order_id": order.order_id,
+        "user_id": order.user_id,
+        "reservation_id": order.reservation_id,
+        "remaining": spec.lots_to_quantity(order.remaining()).to_string(),
+    })
+}
+
+// The correction just made to `record`, as stored
+fn correction_row(spec: &MarketSpec, market_id: &str, record: &TradeRecord) -> CorrectionRow {
+    let correction = record.last_correction();
+    let trade = match &record.printed {
+        Printed::Trade(t) => trade_json(spec, t),
+        Printed::Complementary(m) => complementary_match_json(spec, m),
+        Printed::CompleteSet(m) => complete_set_match_json(spec, m),
+    };
+    CorrectionRow {
+        id: correction.correction_id.to_string(),
+        trade_id: record.printed.trade_id().to_string(),
+        market_id: market_id.to_string(),
+        trade_kind: record.printed.kind().to_string(),
+        action: correction.kind.to_string(),
+        old_price: correction.price.map(|(was, _)| spec.ticks_to_price(was)),
+        new_price: correction.price.map(|(_, now)| spec.ticks_to_price(now)),
+        actor: correction.actor.clone(),
+        reason: correction.reason.clone(),
+        trade: trade.to_string(),
+        created_at: correction.timestamp,
+    }
+}
+
+fn trade_json(spec: &MarketSpec, t: &Trade) -> serde_json::Value {
+    serde_json::json!({
+        "trade_id": t.trade_id,
+        "outcome": t.outcome.index(),
+        "trade_type": format!("{:?}", t.trade_type),
+        "buyer_id": t.buyer_id,
+        "seller_id": t.seller_id,
+        "buyer_order_id": t.buyer_order_id,
+        "seller_order_id": t.seller_order_id,
+        "buyer_reservation_id": t.buyer_reservation_id,
+        "seller_reservation_id": t.seller_reservation_id,
+        "price": spec.ticks_to_price(t.price).to_string(),
+        "quantity": spec.lots_to_quantity(t.quantity).to_string(),
+    })
+}
+
+fn complementary_match_json(spec: &MarketSpec, m: &ComplementaryMatch) -> serde_json::Value {
+    serde_json::json!({
+        "trade_id": m.trade_id,
+        "yes_buyer_id": m.yes_buyer_id,
+        "no_buyer_id": m.no_buyer_id,
+        "yes_order_id": m.yes_order_id,
+        "no_order_id": m.no_order_id,
+        "yes_reservation_id": m.yes_reservation_id,
+        "no_reservation_id": m.no_reservation_id,
+        "yes_price": spec.ticks_to_price(m.yes_price).to_string(),
+        "no_price": spec.ticks_to_price(m.no_price).to_string(),
+        "quantity": spec.lots_to_quantity(m.quantity).to_string(),
+    })
+}
+
+fn complete_set_match_json(spec: &MarketSpec, m: &CompleteSetMatch) -> serde_json::Value {
+    serde_json::json!({
+        "trade_id": m.trade_id,
+        "quantity": spec.lots_to_quantity(m.quantity).to_string(),
+        "legs": m.legs.iter().map(|leg| serde_json::json!({
+            "outcome": leg.outcome.index(),
+            "buyer_id": leg.buyer_id,
+            "order_id": leg.order_id,
+            "reservation_id": leg.reservation_id,
+            "price": spec.ticks_to_price(leg.price).to_string(),
+        })).collect::<Vec<_>>(),
+    })
+}
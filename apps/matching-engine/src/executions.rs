@@ -0,0 +1,298 @@
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::fmt;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::market_spec::MarketSpec;
+use crate::matcher::{MatchResult, UncrossResult};
+use crate::order::{Lots, Order, OrderStatus, Ticks};
+use crate::trade::{CompleteSetMatch, ComplementaryMatch, Trade};
+
+// Reports a subscriber may fall behind by before its stream is ended
+const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// What happened to an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecType {
+    ACCEPTED,
+    PARTIAL,
+    FILLED,
+    CANCELLED,
+    /// Cancelled because its market expired
+    EXPIRED,
+}
+
+impl fmt::Display for ExecType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecType::ACCEPTED => write!(f, "accepted"),
+            ExecType::PARTIAL => write!(f, "partial"),
+            ExecType::FILLED => write!(f, "filled"),
+            ExecType::CANCELLED => write!(f, "cancelled"),
+            ExecType::EXPIRED => write!(f, "expired"),
+        }
+    }
+}
+
+/// One fill of one order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastFill {
+    pub trade_id: Uuid,
+    pub price: Ticks,
+    pub quantity: Lots,
+}
+
+/// An execution report for the order's owner
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub exec_type: ExecType,
+    /// The order as it stood right after this event; `filled` is cumulative
+    pub order: Order,
+    /// Fills only
+    pub last_fill: Option<LastFill>,
+    pub spec: MarketSpec,
+    pub outcome_count: usize,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Execution {
+    fn new(exec_type: ExecType, order: Order, last_fill: Option<LastFill>, spec: MarketSpec, outcome_count: usize) -> Self {
+        Self { exec_type, order, last_fill, spec, outcome_count, timestamp: Utc::now() }
+    }
+}
+
+/// Reports for everything one matching call did, in the order it happened:
+/// the new orders being accepted, every fill on either side, then whatever
+/// was cancelled along the way. `makers` is what the book recorded of its
+/// resting orders as they filled.
+pub fn match_executions(
+    spec: MarketSpec,
+    outcome_count: usize,
+    results: &[&MatchResult],
+    makers: Vec<Order>,
+) -> Vec<Execution> {
+    let mut executions: Vec<Execution> = results
+        .iter()
+        .map(|result| {
+            let mut order = result.order.clone();
+            order.filled = 0;
+            if order.order_status != OrderStatus::UNTRIGGERED {
+                order.order_status = OrderStatus::OPEN;
+            }
+            Execution::new(ExecType::ACCEPTED, order, None, spec, outcome_count)
+        })
+        .collect();
+    executions.extend(follow_on_executions(spec, outcome_count, results, Vec::new(), &[], makers));
+    executions
+}
+
+/// Reports for an auction uncross: every resting order it filled, then
+/// whatever those fills set off
+pub fn uncross_executions(
+    spec: MarketSpec,
+    outcome_count: usize,
+    result: &UncrossResult,
+    makers: Vec<Order>,
+) -> Vec<Execution> {
+    let fired: Vec<&MatchResult> = result.triggered.iter().filter_map(|f| f.result.as_ref().ok()).collect();
+    let prints = prints_of(&result.trades, &result.complementary_matches, &[]);
+    follow_on_executions(spec, outcome_count, &fired, prints, &result.cancelled, makers)
+}
+
+// Fills and cancellations from `results` and the orders they set off, after
+// `prints` that came before any of them
+fn follow_on_executions(
+    spec: MarketSpec,
+    outcome_count: usize,
+    results: &[&MatchResult],
+    mut prints: Vec<(Uuid, LastFill)>,
+    cancelled: &[Order],
+    makers: Vec<Order>,
+) -> Vec<Execution> {
+    let mut all: Vec<&MatchResult> = Vec::new();
+    for result in results {
+        all.push(result);
+        all.extend(result.triggered.iter().filter_map(|fired| fired.result.as_ref().ok()));
+    }
+
+    // A taker that went on to rest can be filled again by an order its own
+    // fills set off, so the book's record is the later one where there is one
+    let mut latest: HashMap<Uuid, Order> =
+        all.iter().map(|result| (result.order.order_id, result.order.clone())).collect();
+    latest.extend(makers.into_iter().map(|o| (o.order_id, o)));
+    for result in &all {
+        prints.extend(prints_of(&result.trades, &result.complementary_matches, &result.complete_set_matches));
+    }
+    let mut executions = fill_executions(spec, outcome_count, &prints, &latest);
+
+    let mut pulled: Vec<&Order> = cancelled.iter().collect();
+    for result in &all {
+        if result.order.order_status == OrderStatus::CANCELLED {
+            pulled.push(&result.order);
+        }
+        pulled.extend(&result.cancelled);
+        pulled.extend(result.mmp_triggered.iter().flat_map(|event| &event.cancelled));
+    }
+    executions.extend(
+        pulled
+            .into_iter()
+            .map(|order| cancel_execution(spec, outcome_count, order, ExecType::CANCELLED)),
+    );
+    executions
+}
+
+/// A fill for each print, with the order's cumulative filled quantity
+/// worked back from `latest`, its state after the last of them
+pub fn fill_executions(
+    spec: MarketSpec,
+    outcome_count: usize,
+    prints: &[(Uuid, LastFill)],
+    latest: &HashMap<Uuid, Order>,
+) -> Vec<Execution> {
+    let mut filled_before: HashMap<Uuid, Lots> = HashMap::new();
+    for (order_id, fill) in prints {
+        if let Some(order) = latest.get(order_id) {
+            let before = filled_before.entry(*order_id).or_insert(order.filled);
+            *before = before.saturating_sub(fill.quantity);
+        }
+    }
+
+    prints
+        .iter()
+        .filter_map(|(order_id, fill)| {
+            let mut order = latest.get(order_id)?.clone();
+            let filled = filled_before.get_mut(order_id)?;
+            *filled += fill.quantity;
+            order.filled = *filled;
+            let exec_type = if order.is_filled() { ExecType::FILLED } else { ExecType::PARTIAL };
+            order.order_status = if order.is_filled() { OrderStatus::FILLED } else { OrderStatus::PARTIAL };
+            Some(Execution::new(exec_type, order, Some(*fill), spec, outcome_count))
+        })
+        .collect()
+}
+
+pub fn cancel_execution(spec: MarketSpec, outcome_count: usize, order: &Order, exec_type: ExecType) -> Execution {
+    let mut order = order.clone();
+    order.order_status = OrderStatus::CANCELLED;
+    Execution::new(exec_type, order, None, spec, outcome_count)
+}
+
+/// Every order on either side of these fills, with what it got
+pub fn prints_of(
+    trades: &[Trade],
+    complementary: &[ComplementaryMatch],
+    complete_sets: &[CompleteSetMatch],
+) -> Vec<(Uuid, LastFill)> {
+    let mut prints = Vec::new();
+    for cmatch in complementary {
+        let fill = |price| LastFill { trade_id: cmatch.trade_id, price, quantity: cmatch.quantity };
+        prints.push((cmatch.yes_order_id, fill(cmatch.yes_price)));
+        prints.push((cmatch.no_order_id, fill(cmatch.no_price)));
+    }
+    for set in complete_sets {
+        prints.extend(set.legs.iter().map(|leg| {
+            (leg.order_id, LastFill { trade_id: set.trade_id, price: leg.price, quantity: set.quantity })
+        }));
+    }
+    for trade in trades {
+        let fill = LastFill { trade_id: trade.trade_id, price: trade.price, quantity: trade.quantity };
+        prints.push((trade.buyer_order_id, fill));
+        prints.push((trade.seller_order_id, fill));
+    }
+    prints
+}
+
+/// Per-user fan-out of execution reports. Nothing is kept for users
+/// without a subscriber.
+#[derive(Default)]
+pub struct ExecutionHub {
+    subscribers: DashMap<String, broadcast::Sender<Execution>>,
+}
+
+impl ExecutionHub {
+    pub fn subscribe(&self, user_id: &str) -> broadcast::Receiver<Execution> {
+        self.subscribers
+            .entry(user_id.to_string())
+            .or_insert_with(|| broadcast::channel(SUBSCRIBER_BUFFER).0)
+            .subscribe()
+    }
+
+    pub fn publish(&self, executions: Vec<Execution>) {
+        for execution in executions {
+            let user_id = execution.order.user_id.clone();
+            let delivered = match self.subscribers.get(&user_id) {
+                Some(sender) => sender.send(execution).is_ok(),
+                None => continue,
+            };
+            if !delivered {
+                // Every stream for this user has gone away
+                self.subscribers.remove_if(&user_id, |_, sender| sender.receiver_count() == 0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::Matcher;
+    use crate::order::{OrderSide, OrderType, Outcome};
+    use crate::orderbook::OrderBook;
+
+    fn order(user: &str, side: OrderSide, price: Ticks, quantity: Lots) -> Order {
+        Order {
+            order_id: Uuid::new_v4(),
+            user_id: user.to_string(),
+            market_id: "market_test".to_string(),
+            side,
+            outcome: Outcome::YES,
+            order_type: OrderType::LIMIT,
+            price,
+            quantity,
+            filled: 0,
+            order_status: OrderStatus::PENDING,
+            reservation_id: None,
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_fills_report_cumulative_quantity_on_both_sides() {
+        let mut book = OrderBook::new("market_test".to_string(), MarketSpec::default());
+        let mut maker = order("maker", OrderSide::SELL, 6000, 30);
+        maker.filled = 5;
+        Matcher::new(&mut book).place_order(maker).unwrap();
+        book.take_fills();
+
+        let hub = ExecutionHub::default();
+        let mut maker_stream = hub.subscribe("maker");
+        let mut taker_stream = hub.subscribe("taker");
+
+        let first = Matcher::new(&mut book).place_order(order("taker", OrderSide::BUY, 6000, 10)).unwrap();
+        hub.publish(match_executions(MarketSpec::default(), 2, &[&first], book.take_fills()));
+        let second = Matcher::new(&mut book).place_order(order("taker", OrderSide::BUY, 6000, 40)).unwrap();
+        hub.publish(match_executions(MarketSpec::default(), 2, &[&second], book.take_fills()));
+
+        let maker_reports: Vec<(ExecType, Lots)> = std::iter::from_fn(|| maker_stream.try_recv().ok())
+            .map(|e| (e.exec_type, e.order.filled))
+            .collect();
+        assert_eq!(maker_reports, vec![(ExecType::PARTIAL, 15), (ExecType::FILLED, 30)]);
+
+        let taker_reports: Vec<(ExecType, Lots, Option<Lots>)> = std::iter::from_fn(|| taker_stream.try_recv().ok())
+            .map(|e| (e.exec_type, e.order.filled, e.last_fill.map(|f| f.quantity)))
+            .collect();
+        assert_eq!(
+            taker_reports,
+            vec![
+                (ExecType::ACCEPTED, 0, None),
+                (ExecType::FILLED, 10, Some(10)),
+                (ExecType::ACCEPTED, 0, None),
+                (ExecType::PARTIAL, 15, Some(15)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::{error, warn};
+
+use crate::redis_client::RedisClient;
+
+/// List the order service pops fired triggers from to settle them
+pub const FIRED_TRIGGERS_QUEUE: &str = "triggers:fired";
+
+/// A fired trigger's report, for `triggers:fired` and its market's channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiredMessage {
+    pub channel: String,
+    pub payload: String,
+}
+
+/// Fired triggers on their way out. One task sends them all, so they reach
+/// the queue in the order they fired: a stop selling what an earlier one
+/// bought has to be settled after it.
+pub struct FiredQueue {
+    sender: mpsc::UnboundedSender<FiredMessage>,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<FiredMessage>>>,
+}
+
+impl Default for FiredQueue {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self { sender, receiver: Mutex::new(Some(receiver)) }
+    }
+}
+
+impl FiredQueue {
+    pub fn push(&self, message: FiredMessage) {
+        // Only fails once the receiver is gone, when nothing would send it
+        let _ = self.sender.send(message);
+    }
+
+    /// The receiving end, for `deliver`. Only the first caller gets it.
+    pub fn take(&self) -> Option<mpsc::UnboundedReceiver<FiredMessage>> {
+        self.receiver.lock().unwrap().take()
+    }
+}
+
+/// Queue each fired trigger on `triggers:fired`, then publish it, one at a
+/// time in the order they were pushed
+pub async fn deliver(mut receiver: mpsc::UnboundedReceiver<FiredMessage>, redis: Arc<RedisClient>) {
+    while let Some(FiredMessage { channel, payload }) = receiver.recv().await {
+        if let Err(e) = redis.enqueue(FIRED_TRIGGERS_QUEUE, &payload).await {
+            error!("Failed to queue fired trigger {}: {}", payload, e);
+        }
+        if let Err(e) = redis.publish(&channel, &payload).await {
+            warn!("Failed to publish fired trigger on {}: {}", channel, e);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::order::{Lots, Order};
+use crate::triggers::Trigger;
+
+/// What the matcher must do after a grouped order fills
+#[derive(Debug, Clone)]
+pub enum GroupAction {
+    /// Take the order out of the book or trigger book
+    Cancel(Uuid),
+    /// Shrink the order by this much, keeping its priority
+    Reduce(Uuid, Lots),
+    /// A bracket's entry has filled: rest the take-profit, hold the stop
+    Activate(Box<HeldExits>),
+}
+
+/// A bracket's exits as submitted, before the entry has filled
+#[derive(Debug, Clone)]
+pub struct HeldExits {
+    pub take_profit: Order,
+    pub stop: Order,
+    pub stop_trigger: Trigger,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Leg {
+    order_id: Uuid,
+    quantity: Lots,
+    filled: Lots,
+}
+
+impl Leg {
+    fn new(order: &Order) -> Self {
+        Self { order_id: order.order_id, quantity: order.quantity, filled: 0 }
+    }
+}
+
+#[derive(Debug)]
+enum Exits {
+    /// Not in any book until the entry has completely filled
+    Held(Box<HeldExits>),
+    /// Each exit is kept at the open position: a fill on one shrinks the other
+    Live { take_profit: Leg, stop: Leg },
+}
+
+#[derive(Debug)]
+enum Group {
+    Oco { legs: [Uuid; 2] },
+    Bracket { entry: Leg, exits: Exits },
+}
+
+/// Orders linked into OCO pairs and brackets within one market
+#[derive(Debug, Default)]
+pub struct OrderGroups {
+    groups: HashMap<Uuid, (String, Group)>,
+    // Live leg order_id -> group_id
+    by_order: HashMap<Uuid, Uuid>,
+    // Held bracket exits don't rest anywhere else, so their reservations are kept here
+    reservations: HashMap<String, Uuid>,
+}
+
+/// Everything a cancelled group still had working
+#[derive(Debug)]
+pub struct CancelledGroup {
+    /// Legs that may be resting in the book or trigger book
+    pub live: Vec<Uuid>,
+    /// Bracket exits that were never activated
+    pub held: Vec<Order>,
+}
+
+impl OrderGroups {
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn group_of(&self, order_id: &Uuid) -> Option<Uuid> {
+        self.by_order.get(order_id).copied()
+    }
+
+    pub fn group_ids(&self) -> Vec<Uuid> {
+        self.groups.keys().copied().collect()
+    }
+
+    pub fn owner(&self, group_id: &Uuid) -> Option<&str> {
+        self.groups.get(group_id).map(|(user_id, _)| user_id.as_str())
+    }
+
+    pub fn has_reservation(&self, reservation_id: &str) -> bool {
+        self.reservations.contains_key(reservation_id)
+    }
+
+    /// The other leg of an OCO pair, or the other exit of a released
+    /// bracket: the one order that cannot fill alongside this one
+    pub fn partner(&self, order_id: &Uuid) -> Option<Uuid> {
+        let (_, group) = self.groups.get(&self.group_of(order_id)?)?;
+        match group {
+            Group::Oco { legs } => legs.iter().copied().find(|leg| leg != order_id),
+            Group::Bracket { exits: Exits::Live { take_profit, stop }, .. } => {
+                if take_profit.order_id == *order_id {
+                    Some(stop.order_id)
+                } else if stop.order_id == *order_id {
+                    Some(take_profit.order_id)
+                } else {
+                    None
+                }
+            }
+            Group::Bracket { .. } => None,
+        }
+    }
+
+    /// Link two orders so the first fill on either cancels the other
+    pub fn add_oco(&mut self, user_id: &str, legs: [&Order; 2]) -> Uuid {
+        let group_id = Uuid::new_v4();
+        let legs = [legs[0].order_id, legs[1].order_id];
+        for leg in legs {
+            self.by_order.insert(leg, group_id);
+        }
+        self.groups.insert(group_id, (user_id.to_string(), Group::Oco { legs }));
+        group_id
+    }
+
+    /// Hold a take-profit and a stop until `entry` has completely filled
+    pub fn add_bracket(&mut self, user_id: &str, entry: &Order, take_profit: Order, stop: Order, stop_trigger: Trigger) -> Uuid {
+        let group_id = Uuid::new_v4();
+        for reservation_id in [&take_profit.reservation_id, &stop.reservation_id].into_iter().flatten() {
+            self.reservations.insert(reservation_id.clone(), group_id);
+        }
+        self.by_order.insert(entry.order_id, group_id);
+        let exits = Exits::Held(Box::new(HeldExits { take_profit, stop, stop_trigger }));
+        self.groups.insert(group_id, (user_id.to_string(), Group::Bracket { entry: Leg::new(entry), exits }));
+        group_id
+    }
+
+    /// Account for a fill on `order_id`, returning what must happen to the
+    /// rest of its group, if it has one
+    pub fn on_fill(&mut self, order_id: Uuid, quantity: Lots) -> Vec<GroupAction> {
+        let Some(group_id) = self.group_of(&order_id) else {
+            return Vec::new();
+        };
+        let (_, group) = self.groups.get_mut(&group_id).expect("indexed group exists");
+
+        match group {
+            Group::Oco { legs } => {
+                let partner = if legs[0] == order_id { legs[1] } else { legs[0] };
+                self.remove(&group_id);
+                vec![GroupAction::Cancel(partner)]
+            }
+            Group::Bracket { entry, exits } if entry.order_id == order_id => {
+                entry.filled += quantity;
+                if entry.filled < entry.quantity {
+                    return Vec::new();
+                }
+                let Exits::Held(held) = exits else {
+                    return Vec::new();
+                };
+                let held = held.clone();
+                *exits = Exits::Live { take_profit: Leg::new(&held.take_profit), stop: Leg::new(&held.stop) };
+
+                self.by_order.remove(&order_id);
+                self.by_order.insert(held.take_profit.order_id, group_id);
+                self.by_order.insert(held.stop.order_id, group_id);
+                for reservation_id in [&held.take_profit.reservation_id, &held.stop.reservation_id].into_iter().flatten() {
+                    self.reservations.remove(reservation_id);
+                }
+                vec![GroupAction::Activate(held)]
+            }
+            Group::Bracket { exits: Exits::Live { take_profit, stop }, .. } => {
+                let (filled, other) = if take_profit.order_id == order_id {
+                    (take_profit, stop)
+                } else {
+                    (stop, take_profit)
+                };
+                filled.filled += quantity;
+                if filled.filled >= filled.quantity {
+                    // Position closed
+                    let other = other.order_id;
+                    self.remove(&group_id);
+                    return vec![GroupAction::Cancel(other)];
+                }
+                other.quantity -= quantity;
+                vec![GroupAction::Reduce(other.order_id, quantity)]
+            }
+            Group::Bracket { .. } => Vec::new(),
+        }
+    }
+
+    /// Forget a group, handing back what it still had working
+    pub fn cancel(&mut self, group_id: &Uuid) -> Option<CancelledGroup> {
+        let group = self.remove(group_id)?;
+        Some(match group {
+            Group::Oco { legs } => CancelledGroup { live: legs.to_vec(), held: Vec::new() },
+            Group::Bracket { entry, exits: Exits::Held(held) } => CancelledGroup {
+                live: vec![entry.order_id],
+                held: vec![held.take_profit, held.stop],
+            },
+            Group::Bracket { entry, exits: Exits::Live { take_profit, stop } } => CancelledGroup {
+                live: vec![entry.order_id, take_profit.order_id, stop.order_id],
+                held: Vec::new(),
+            },
+        })
+    }
+
+    fn remove(&mut self, group_id: &Uuid) -> Option<Group> {
+        let (_, group) = self.groups.remove(group_id)?;
+        self.by_order.retain(|_, g| g != group_id);
+        self.reservations.retain(|_, g| g != group_id);
+        Some(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::{OrderSide, OrderStatus, OrderType, Outcome};
+    use crate::triggers::TriggerKind;
+    use chrono::Utc;
+
+    fn order(side: OrderSide, quantity: Lots) -> Order {
+        Order {
+            order_id: Uuid::new_v4(),
+            user_id: "trader".to_string(),
+            market_id: "market_test".to_string(),
+            side,
+            outcome: Outcome::YES,
+            order_type: OrderType::LIMIT,
+            price: 50,
+            quantity,
+            filled: 0,
+            order_status: OrderStatus::OPEN,
+            reservation_id: None,
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_bracket_activates_on_full_entry_fill_and_tracks_position() {
+        let mut groups = OrderGroups::default();
+        let entry = order(OrderSide::BUY, 10);
+        let (take_profit, stop) = (order(OrderSide::SELL, 10), order(OrderSide::SELL, 10));
+        let stop_trigger = Trigger { kind: TriggerKind::STOP, price: 40 };
+        let group_id = groups.add_bracket("trader", &entry, take_profit.clone(), stop.clone(), stop_trigger);
+
+        // A partial entry fill leaves the exits held
+        assert!(groups.on_fill(entry.order_id, 4).is_empty());
+        let activated = groups.on_fill(entry.order_id, 6);
+        assert!(matches!(&activated[..], [GroupAction::Activate(_)]));
+
+        // Exits shrink each other until one closes the position
+        let reduced = groups.on_fill(take_profit.order_id, 3);
+        assert!(matches!(&reduced[..], [GroupAction::Reduce(id, 3)] if *id == stop.order_id));
+        let closed = groups.on_fill(stop.order_id, 7);
+        assert!(matches!(&closed[..], [GroupAction::Cancel(id)] if *id == take_profit.order_id));
+        assert!(groups.cancel(&group_id).is_none());
+        assert!(groups.is_empty());
+    }
+
+    #[test]
+    fn test_oco_first_fill_cancels_partner() {
+        let mut groups = OrderGroups::default();
+        let (a, b) = (order(OrderSide::SELL, 10), order(OrderSide::SELL, 10));
+        groups.add_oco("trader", [&a, &b]);
+
+        let actions = groups.on_fill(b.order_id, 1);
+        assert!(matches!(&actions[..], [GroupAction::Cancel(id)] if *id == a.order_id));
+        assert!(groups.on_fill(b.order_id, 1).is_empty());
+        assert!(groups.is_empty());
+    }
+}
@@ -1,19 +1,41 @@
+#![allow(clippy::result_large_err)] // tonic::Status is the error type throughout this module
+
 use anyhow::Result;
 use dashmap::DashMap;
+use futures_util::StreamExt;
 use rust_decimal::Decimal;
 use std::str::FromStr;
 use std::sync::Arc;
-use tonic::{Request, Response, Status};
-use tracing::info;
+use std::time::{Duration, Instant};
+use tonic::{Code, Request, Response, Status};
+use tracing::{info, warn};
 use uuid::Uuid;
-use chrono::Utc;
 
 use matching_engine::Trade;
-use crate::matcher::Matcher;
-use crate::order::{Order, OrderSide, OrderStatus, OrderType, Outcome};
-use crate::orderbook::OrderBook;
+use crate::allocation::Allocation;
+use crate::auction::{AuctionBook, TradingPhase};
+use crate::auth::{authorize, tls_config, Access, Authenticator, Caller};
+use crate::bands::{BandConfig, BreakerAction, BreakerConfig};
+use crate::config::Config;
+use crate::corrections::{Correction, CorrectionError, CorrectionKind, TradeRecord, TradeStatus};
+use crate::db::{load_open_orders, load_positions, CorrectionRow};
+use crate::engine::{BracketExits, Engine, GroupPlacement, NewOrder, PlaceError, Placement};
+use crate::executions::uncross_executions;
+use crate::fired;
+use crate::grpc_server_v2::{self, matching_engine_v2::matching_engine_server::MatchingEngineServer as MatchingEngineV2Server};
+use crate::health::watch_books;
+use crate::market_spec::MarketSpec;
+use crate::matcher::{Matcher, RejectReason};
+use crate::metrics::Metrics;
+use crate::mmp::MmpConfig;
+use crate::order::{OrderSide, OrderStatus, OrderType, Outcome};
+use crate::orderbook::{PriceLevelSummary, SharedOrderBook};
+use crate::ratelimit::{CallClass, RateLimit, Scope};
+use crate::recovery;
 use crate::redis_client::RedisClient;
-use crate::trade::TradeType;
+use crate::registry::{MarketSource, PostgresMarkets, MARKETS_RELOAD_INTERVAL, MARKETS_UPDATED_CHANNEL};
+use crate::triggers::TriggerKind;
+use crate::trade::{self, TradeType};
 
 pub mod matching_engine {
     tonic::include_proto!("matching_engine");
@@ -22,9 +44,15 @@ pub mod matching_engine {
 use matching_engine::matching_engine_server::{MatchingEngine, MatchingEngineServer};
 use matching_engine::*;
 
+/// v1 API: string-typed fields, every failure surfaced as a gRPC status
 pub struct MatchingEngineService {
-    orderbooks: Arc<DashMap<String, Arc<OrderBook>>>,
-    redis: Arc<RedisClient>,
+    engine: Arc<Engine>,
+    // For CheckConsistency; unset when the engine runs without a database
+    database_url: Option<String>,
+}
+
+fn parse_decimal(value: &str, what: &str) -> Result<Decimal, Status> {
+    Decimal::from_str(value).map_err(|_| Status::invalid_argument(format!("Invalid {}", what)))
 }
 
 #[tonic::async_trait]
@@ -33,59 +61,804 @@ impl MatchingEngine for MatchingEngineService {
         &self,
         request: Request<PlaceOrderRequest>,
     ) -> Result<Response<PlaceOrderResponse>, Status> {
-        let req = request.into_inner();
-        
-        info!("📥 PlaceOrder: {}, {}", req.user_id, req.market_id);
-        
-        // Get/create orderbook
-        let orderbook = self.orderbooks
-            .entry(req.market_id.clone())
-            .or_insert_with(|| Arc::new(OrderBook::new(req.market_id.clone())))
-            .clone();
-        
-        // Parse order
-        let order = Order {
-            order_id: Uuid::new_v4(),
-            user_id: req.user_id,
+        let access = Access::Trade(&request.get_ref().user_id);
+        let result = authorize(&request, "PlaceOrder", access)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::PLACE, access).map_err(Status::from))
+            .and_then(|_| self.handle_place_order(request.into_inner()));
+        self.record("PlaceOrder", &result);
+        result.map(Response::new)
+    }
+    
+    async fn get_orderbook(
+        &self,
+        request: Request<GetOrderbookRequest>,
+    ) -> Result<Response<GetOrderbookResponse>, Status> {
+        let result = authorize(&request, "GetOrderbook", Access::Read)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::QUERY, Access::Read).map_err(Status::from))
+            .and_then(|_| self.handle_get_orderbook(request.into_inner()));
+        self.record("GetOrderbook", &result);
+        result.map(Response::new)
+    }
+
+    async fn start_auction(
+        &self,
+        request: Request<StartAuctionRequest>,
+    ) -> Result<Response<StartAuctionResponse>, Status> {
+        let result = authorize(&request, "StartAuction", Access::Admin)
+            .and_then(|_| self.handle_start_auction(request.into_inner()));
+        self.record("StartAuction", &result);
+        result.map(Response::new)
+    }
+
+    async fn uncross(
+        &self,
+        request: Request<UncrossRequest>,
+    ) -> Result<Response<UncrossResponse>, Status> {
+        let result = authorize(&request, "Uncross", Access::Admin)
+            .and_then(|_| self.handle_uncross(request.into_inner()));
+        self.record("Uncross", &result);
+        result.map(Response::new)
+    }
+
+    async fn set_allocation(
+        &self,
+        request: Request<SetAllocationRequest>,
+    ) -> Result<Response<SetAllocationResponse>, Status> {
+        let result = authorize(&request, "SetAllocation", Access::Admin)
+            .and_then(|_| self.handle_set_allocation(request.into_inner()));
+        self.record("SetAllocation", &result);
+        result.map(Response::new)
+    }
+
+    async fn create_market(
+        &self,
+        request: Request<CreateMarketRequest>,
+    ) -> Result<Response<CreateMarketResponse>, Status> {
+        let result = authorize(&request, "CreateMarket", Access::Admin)
+            .and_then(|_| self.handle_create_market(request.into_inner()));
+        self.record("CreateMarket", &result);
+        result.map(Response::new)
+    }
+
+    async fn set_mmp(
+        &self,
+        request: Request<SetMmpRequest>,
+    ) -> Result<Response<SetMmpResponse>, Status> {
+        let result = authorize(&request, "SetMmp", Access::Trade(&request.get_ref().user_id))
+            .and_then(|_| self.handle_set_mmp(request.into_inner()));
+        self.record("SetMmp", &result);
+        result.map(Response::new)
+    }
+
+    async fn reset_mmp(
+        &self,
+        request: Request<ResetMmpRequest>,
+    ) -> Result<Response<ResetMmpResponse>, Status> {
+        let result = authorize(&request, "ResetMmp", Access::Trade(&request.get_ref().user_id))
+            .and_then(|_| self.handle_reset_mmp(request.into_inner()));
+        self.record("ResetMmp", &result);
+        result.map(Response::new)
+    }
+
+    async fn heartbeat(
+        &self,
+        request: Request<HeartbeatRequest>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        let result = authorize(&request, "Heartbeat", Access::Trade(&request.get_ref().user_id))
+            .and_then(|_| self.handle_heartbeat(request.into_inner()));
+        self.record("Heartbeat", &result);
+        result.map(Response::new)
+    }
+
+    async fn close_session(
+        &self,
+        request: Request<CloseSessionRequest>,
+    ) -> Result<Response<CloseSessionResponse>, Status> {
+        let access = Access::Trade(&request.get_ref().user_id);
+        let result = authorize(&request, "CloseSession", access)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::CANCEL, access).map_err(Status::from))
+            .and_then(|_| self.handle_close_session(request.into_inner()));
+        self.record("CloseSession", &result);
+        result.map(Response::new)
+    }
+
+    async fn place_oco(
+        &self,
+        request: Request<PlaceOcoRequest>,
+    ) -> Result<Response<PlaceGroupResponse>, Status> {
+        let access = Access::Trade(request.get_ref().first.as_ref().map_or("", |leg| &leg.user_id));
+        let result = authorize(&request, "PlaceOco", access)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::PLACE, access).map_err(Status::from))
+            .and_then(|_| self.handle_place_oco(request.into_inner()));
+        self.record("PlaceOco", &result);
+        result.map(Response::new)
+    }
+
+    async fn place_bracket(
+        &self,
+        request: Request<PlaceBracketRequest>,
+    ) -> Result<Response<PlaceGroupResponse>, Status> {
+        let access = Access::Trade(request.get_ref().entry.as_ref().map_or("", |entry| &entry.user_id));
+        let result = authorize(&request, "PlaceBracket", access)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::PLACE, access).map_err(Status::from))
+            .and_then(|_| self.handle_place_bracket(request.into_inner()));
+        self.record("PlaceBracket", &result);
+        result.map(Response::new)
+    }
+
+    async fn cancel_group(
+        &self,
+        request: Request<CancelGroupRequest>,
+    ) -> Result<Response<CancelGroupResponse>, Status> {
+        let access = Access::Trade(&request.get_ref().user_id);
+        let result = authorize(&request, "CancelGroup", access)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::CANCEL, access).map_err(Status::from))
+            .and_then(|_| self.handle_cancel_group(request.into_inner()));
+        self.record("CancelGroup", &result);
+        result.map(Response::new)
+    }
+
+    async fn set_price_bands(
+        &self,
+        request: Request<SetPriceBandsRequest>,
+    ) -> Result<Response<SetPriceBandsResponse>, Status> {
+        let result = authorize(&request, "SetPriceBands", Access::Admin)
+            .and_then(|_| self.handle_set_price_bands(request.into_inner()));
+        self.record("SetPriceBands", &result);
+        result.map(Response::new)
+    }
+
+    async fn resume_market(
+        &self,
+        request: Request<ResumeMarketRequest>,
+    ) -> Result<Response<ResumeMarketResponse>, Status> {
+        let result = authorize(&request, "ResumeMarket", Access::Admin)
+            .and_then(|_| self.handle_resume_market(request.into_inner()));
+        self.record("ResumeMarket", &result);
+        result.map(Response::new)
+    }
+
+    async fn set_position(
+        &self,
+        request: Request<SetPositionRequest>,
+    ) -> Result<Response<SetPositionResponse>, Status> {
+        let result = authorize(&request, "SetPosition", Access::Admin)
+            .and_then(|_| self.handle_set_position(request.into_inner()));
+        self.record("SetPosition", &result);
+        result.map(Response::new)
+    }
+
+    async fn check_consistency(
+        &self,
+        request: Request<CheckConsistencyRequest>,
+    ) -> Result<Response<CheckConsistencyResponse>, Status> {
+        let result = match authorize(&request, "CheckConsistency", Access::Admin) {
+            Ok(()) => self.handle_check_consistency().await,
+            Err(status) => Err(status),
+        };
+        self.record("CheckConsistency", &result);
+        result.map(Response::new)
+    }
+
+    async fn set_rate_limit(
+        &self,
+        request: Request<SetRateLimitRequest>,
+    ) -> Result<Response<SetRateLimitResponse>, Status> {
+        let result = authorize(&request, "SetRateLimit", Access::Admin)
+            .and_then(|_| self.handle_set_rate_limit(request.into_inner()));
+        self.record("SetRateLimit", &result);
+        result.map(Response::new)
+    }
+
+    async fn bust_trade(
+        &self,
+        request: Request<BustTradeRequest>,
+    ) -> Result<Response<TradeCorrectionResponse>, Status> {
+        let result = match authorize(&request, "BustTrade", Access::Admin) {
+            Ok(()) => {
+                let actor = actor(&request);
+                self.handle_bust_trade(&actor, request.into_inner()).await
+            }
+            Err(status) => Err(status),
+        };
+        self.record("BustTrade", &result);
+        result.map(Response::new)
+    }
+
+    async fn correct_trade(
+        &self,
+        request: Request<CorrectTradeRequest>,
+    ) -> Result<Response<TradeCorrectionResponse>, Status> {
+        let result = match authorize(&request, "CorrectTrade", Access::Admin) {
+            Ok(()) => {
+                let actor = actor(&request);
+                self.handle_correct_trade(&actor, request.into_inner()).await
+            }
+            Err(status) => Err(status),
+        };
+        self.record("CorrectTrade", &result);
+        result.map(Response::new)
+    }
+
+    async fn get_trade_corrections(
+        &self,
+        request: Request<GetTradeCorrectionsRequest>,
+    ) -> Result<Response<GetTradeCorrectionsResponse>, Status> {
+        let result = match authorize(&request, "GetTradeCorrections", Access::Admin) {
+            Ok(()) => self.handle_get_trade_corrections(request.into_inner()).await,
+            Err(status) => Err(status),
+        };
+        self.record("GetTradeCorrections", &result);
+        result.map(Response::new)
+    }
+}
+
+impl MatchingEngineService {
+    fn record<T>(&self, method: &str, result: &Result<T, Status>) {
+        let code = match result {
+            Ok(_) => Code::Ok,
+            Err(status) => status.code(),
+        };
+        self.engine.metrics.record_grpc(method, code);
+    }
+
+    fn handle_place_order(&self, req: PlaceOrderRequest) -> Result<PlaceOrderResponse, Status> {
+        let new_order = parse_order(&req);
+        if new_order.is_err() {
+            self.engine.metrics.record_reject(&req.market_id, "invalid_request");
+        }
+
+        let placement = self.engine.place_order(new_order?).map_err(place_error_to_status)?;
+        Ok(placement_to_proto(placement))
+    }
+
+    fn handle_place_oco(&self, req: PlaceOcoRequest) -> Result<PlaceGroupResponse, Status> {
+        let (Some(first), Some(second)) = (req.first, req.second) else {
+            return Err(Status::invalid_argument("Both OCO legs are required"));
+        };
+        if first.user_id != second.user_id || first.market_id != second.market_id {
+            return Err(Status::invalid_argument("OCO legs must have the same user_id and market_id"));
+        }
+        let legs = [parse_order(&first), parse_group_leg(&second)];
+        if legs.iter().any(|leg| leg.is_err()) {
+            self.engine.metrics.record_reject(&first.market_id, "invalid_request");
+        }
+        let [first, second] = legs;
+
+        let placement = self.engine.place_oco([first?, second?]).map_err(place_error_to_status)?;
+        Ok(group_placement_to_proto(placement))
+    }
+
+    fn handle_place_bracket(&self, req: PlaceBracketRequest) -> Result<PlaceGroupResponse, Status> {
+        let entry = req.entry.ok_or(Status::invalid_argument("Bracket entry is required"))?;
+        let parsed = parse_order(&entry).and_then(|order| {
+            let exits = BracketExits {
+                take_profit_price: parse_decimal(&req.take_profit_price, "take_profit_price")?,
+                stop_price: parse_decimal(&req.stop_price, "stop_price")?,
+                stop_limit_price: req
+                    .stop_limit_price
+                    .as_deref()
+                    .map(|p| parse_decimal(p, "stop_limit_price"))
+                    .transpose()?,
+                take_profit_reservation_id: req.take_profit_reservation_id,
+                stop_reservation_id: req.stop_reservation_id,
+            };
+            Ok((order, exits))
+        });
+        if parsed.is_err() {
+            self.engine.metrics.record_reject(&entry.market_id, "invalid_request");
+        }
+
+        let (order, exits) = parsed?;
+        let placement = self.engine.place_bracket(order, exits).map_err(place_error_to_status)?;
+        Ok(group_placement_to_proto(placement))
+    }
+
+    fn handle_cancel_group(&self, req: CancelGroupRequest) -> Result<CancelGroupResponse, Status> {
+        let group_id = Uuid::parse_str(&req.group_id).map_err(|_| Status::invalid_argument("Invalid group_id"))?;
+        let cancelled = self
+            .engine
+            .cancel_group(&req.market_id, &group_id, &req.user_id)
+            .ok_or(Status::not_found("Group not found"))?;
+
+        Ok(CancelGroupResponse {
+            group_id: req.group_id,
+            cancelled_order_ids: cancelled.iter().map(|o| o.order_id.to_string()).collect(),
+        })
+    }
+
+    fn handle_get_orderbook(&self, req: GetOrderbookRequest) -> Result<GetOrderbookResponse, Status> {
+        let orderbook = self.engine.market_book(&req.market_id).ok_or(Status::not_found("Market not found"))?;
+        let book = orderbook.read().unwrap();
+        let outcome = Outcome::parse(&req.outcome)
+            .filter(|o| o.index() < book.outcome_count())
+            .ok_or(Status::invalid_argument("Invalid outcome"))?;
+        let depth = book.get_depth(outcome, 10);
+        let to_proto = |levels: Vec<PriceLevelSummary>| {
+            levels
+                .into_iter()
+                .map(|l| PriceLevel {
+                    price: book.spec.ticks_to_price(l.price).to_string(),
+                    quantity: book.spec.lots_to_quantity(l.quantity).to_string(),
+                })
+                .collect()
+        };
+        let indicative = match book.phase {
+            TradingPhase::AUCTION => AuctionBook::new(&book).clearing_price(),
+            TradingPhase::CONTINUOUS => None,
+        };
+        Ok(GetOrderbookResponse {
+            bids: to_proto(depth.bids),
+            asks: to_proto(depth.asks),
+            phase: book.phase.as_str().to_string(),
+            indicative_price: indicative.map(|c| book.spec.ticks_to_price(c.price).to_string()),
+            indicative_volume: indicative.map(|c| book.spec.lots_to_quantity(c.volume).to_string()),
+            allocation: book.allocation.to_string(),
+        })
+    }
+
+    fn handle_set_allocation(&self, req: SetAllocationRequest) -> Result<SetAllocationResponse, Status> {
+        let allocation = Allocation::from_str(&req.allocation)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let orderbook = self.engine.listed_book(&req.market_id).ok_or(Status::not_found("Market not found"))?;
+        let mut book = orderbook.write().unwrap();
+        book.allocation = allocation;
+        info!("Market {} now allocates {}", req.market_id, allocation);
+
+        Ok(SetAllocationResponse {
             market_id: req.market_id,
-            side: match req.side.as_str() {
-                "BUY" => OrderSide::BUY,
-                "SELL" => OrderSide::SELL,
-                _ => return Err(Status::invalid_argument("Invalid side")),
-            },
-            outcome: match req.outcome.as_str() {
-                "YES" => Outcome::YES,
-                "NO" => Outcome::NO,
-                _ => return Err(Status::invalid_argument("Invalid outcome")),
-            },
-            order_type: match req.order_type.as_str() {
-                "LIMIT" => OrderType::LIMIT,
-                "MARKET" => OrderType::MARKET,
-                "POSTONLY" => OrderType::POSTONLY,
-                _ => return Err(Status::invalid_argument("Invalid order type")),
+            allocation: allocation.to_string(),
+        })
+    }
+
+    fn handle_start_auction(&self, req: StartAuctionRequest) -> Result<StartAuctionResponse, Status> {
+        let orderbook = self.engine.listed_book(&req.market_id).ok_or(Status::not_found("Market not found"))?;
+        let mut book = orderbook.write().unwrap();
+        if !book.is_binary() {
+            return Err(Status::failed_precondition("Auctions are only supported for binary markets"));
+        }
+        book.phase = TradingPhase::AUCTION;
+        info!("Market {} entered auction with {} resting orders", req.market_id, book.len());
+        self.engine.publish_indicative(&book);
+
+        Ok(StartAuctionResponse {
+            market_id: req.market_id,
+            phase: book.phase.as_str().to_string(),
+        })
+    }
+
+    fn handle_uncross(&self, req: UncrossRequest) -> Result<UncrossResponse, Status> {
+        let orderbook = self
+            .engine
+            .book(&req.market_id)
+            .ok_or(Status::not_found("Market not found"))?;
+        let mut book = orderbook.write().unwrap();
+        if book.phase != TradingPhase::AUCTION {
+            return Err(Status::failed_precondition("Market is not in auction"));
+        }
+
+        let result = Matcher::new(&mut book).uncross();
+        let makers = book.take_fills();
+        self.engine.metrics.observe_book(&book);
+        self.engine.market_data.publish(&mut book);
+        self.engine
+            .executions
+            .publish(uncross_executions(book.spec, book.outcome_count(), &result, makers));
+        self.engine.publish_triggered(&book.spec, None, &result.triggered);
+        self.engine.publish_group_cancellations(&book.spec, &result.cancelled);
+
+        let spec = book.spec;
+        Ok(UncrossResponse {
+            market_id: req.market_id,
+            clearing_price: result.clearing.map(|c| spec.ticks_to_price(c.price).to_string()),
+            volume: spec.lots_to_quantity(result.clearing.map_or(0, |c| c.volume)).to_string(),
+            trades: trades_to_proto(&spec, book.outcome_count(), &result.trades),
+            complementary_matches: complementary_matches_to_proto(&spec, &result.complementary_matches),
+        })
+    }
+
+    fn handle_create_market(&self, req: CreateMarketRequest) -> Result<CreateMarketResponse, Status> {
+        let outcome_count = req.outcome_count as usize;
+        if !(2..=256).contains(&outcome_count) {
+            return Err(Status::invalid_argument("outcome_count must be between 2 and 256"));
+        }
+        self.engine
+            .create_market(&req.market_id, outcome_count)
+            .map_err(|existing| {
+                Status::already_exists(format!("Market already exists with {} outcomes", existing))
+            })?;
+
+        Ok(CreateMarketResponse {
+            market_id: req.market_id,
+            outcome_count: req.outcome_count,
+        })
+    }
+
+    fn handle_set_mmp(&self, req: SetMmpRequest) -> Result<SetMmpResponse, Status> {
+        let orderbook = self.engine.listed_book(&req.market_id).ok_or(Status::not_found("Market not found"))?;
+        let mut book = orderbook.write().unwrap();
+
+        if req.window_ms == 0 {
+            book.mmp.remove(&req.user_id);
+            info!("MMP off for {} in {}", req.user_id, req.market_id);
+            return Ok(SetMmpResponse {
+                market_id: req.market_id,
+                user_id: req.user_id,
+                enabled: false,
+            });
+        }
+
+        let max_quantity = req
+            .max_quantity
+            .as_deref()
+            .map(|q| {
+                let q = Decimal::from_str(q).map_err(|e| Status::invalid_argument(format!("Invalid max_quantity: {}", e)))?;
+                book.spec
+                    .quantity_to_lots(q)
+                    .map_err(|e| Status::invalid_argument(format!("Invalid max_quantity: {}", e)))
+            })
+            .transpose()?;
+        let max_fills = req.max_fills.map(|n| n as usize);
+        if max_quantity.is_none() && max_fills.is_none() {
+            return Err(Status::invalid_argument("Set max_quantity, max_fills or both"));
+        }
+
+        book.mmp.configure(
+            &req.user_id,
+            MmpConfig {
+                window: Duration::from_millis(req.window_ms),
+                max_quantity,
+                max_fills,
             },
-            price: Decimal::from_str(&req.price).map_err(|_| Status::invalid_argument("Invalid price"))?,
-            quantity: Decimal::from_str(&req.quantity).map_err(|_| Status::invalid_argument("Invalid quantity"))?,
-            filled: Decimal::ZERO,
-            order_status: OrderStatus::PENDING,
-            reservation_id: req.reservation_id,
-            created_at: Utc::now(),
-        };
-        
-        // Match
-        let matcher = Matcher::new((*orderbook).clone());
-        let result = matcher.place_order(order).map_err(|e| Status::internal(e.to_string()))?;
-        
-        info!("✅ Matched: trades={}, cmatch={}", result.trades.len(), result.complementary_matches.len());
-        
-        let trades = result
-        .trades
+        );
+        info!("MMP on for {} in {}: {}ms window", req.user_id, req.market_id, req.window_ms);
+
+        Ok(SetMmpResponse {
+            market_id: req.market_id,
+            user_id: req.user_id,
+            enabled: true,
+        })
+    }
+
+    fn handle_heartbeat(&self, req: HeartbeatRequest) -> Result<HeartbeatResponse, Status> {
+        if req.user_id.is_empty() {
+            return Err(Status::invalid_argument("user_id is required"));
+        }
+        let session_id = if req.session_id.is_empty() {
+            Uuid::new_v4().to_string()
+        } else {
+            req.session_id
+        };
+        if !self.engine.sessions.heartbeat(&session_id, &req.user_id, Instant::now()) {
+            return Err(Status::permission_denied("Session belongs to another user"));
+        }
+        self.engine.prune_session(&session_id);
+
+        Ok(HeartbeatResponse {
+            session_id,
+            timeout_ms: self.engine.sessions.timeout().as_millis() as u64,
+        })
+    }
+
+    fn handle_close_session(&self, req: CloseSessionRequest) -> Result<CloseSessionResponse, Status> {
+        if !self.engine.sessions.is_open(&req.session_id, &req.user_id) {
+            return Err(Status::not_found("Session not found"));
+        }
+        let cancelled = self
+            .engine
+            .sessions
+            .close(&req.session_id)
+            .map_or(0, |session| self.engine.cancel_session_orders(session));
+
+        Ok(CloseSessionResponse {
+            session_id: req.session_id,
+            cancelled: cancelled as u32,
+        })
+    }
+
+    fn handle_set_price_bands(&self, req: SetPriceBandsRequest) -> Result<SetPriceBandsResponse, Status> {
+        let orderbook = self.engine.listed_book(&req.market_id).ok_or(Status::not_found("Market not found"))?;
+        let mut book = orderbook.write().unwrap();
+        let spec = book.spec;
+        let ticks = |value: &Option<String>, what: &str| {
+            value
+                .as_deref()
+                .map(|p| {
+                    spec.price_to_ticks(parse_decimal(p, what)?)
+                        .map_err(|e| Status::invalid_argument(format!("Invalid {}: {}", what, e)))
+                })
+                .transpose()
+        };
+
+        let width = ticks(&req.band_width, "band_width")?;
+        let breaker = match ticks(&req.breaker_move, "breaker_move")? {
+            Some(max_move) => {
+                if req.breaker_window_ms == 0 {
+                    return Err(Status::invalid_argument("breaker_window_ms is required with breaker_move"));
+                }
+                Some(BreakerConfig {
+                    max_move,
+                    window: Duration::from_millis(req.breaker_window_ms),
+                    cooldown: Duration::from_millis(req.breaker_cooldown_ms),
+                    action: BreakerAction::from_str(&req.breaker_action)
+                        .map_err(|e| Status::invalid_argument(e.to_string()))?,
+                })
+            }
+            None => None,
+        };
+        book.bands.configure(BandConfig { width, breaker });
+        info!("Price bands for {}: width {:?}, breaker {:?}", req.market_id, width, breaker);
+
+        Ok(SetPriceBandsResponse {
+            market_id: req.market_id,
+            band_width: width.map(|w| spec.ticks_to_price(w).to_string()),
+            breaker_enabled: breaker.is_some(),
+        })
+    }
+
+    fn handle_set_rate_limit(&self, req: SetRateLimitRequest) -> Result<SetRateLimitResponse, Status> {
+        let scope: Scope = req.scope.parse().map_err(|e: anyhow::Error| Status::invalid_argument(e.to_string()))?;
+        let class: CallClass = req.call_class.parse().map_err(|e: anyhow::Error| Status::invalid_argument(e.to_string()))?;
+        let limit = match (req.reset, req.rate) {
+            (true, _) | (false, 0) => None,
+            (false, rate) => Some(
+                RateLimit::new(rate, if req.burst == 0 { rate } else { req.burst })
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?,
+            ),
+        };
+        let limits = &self.engine.rate_limits;
+        match (&req.subject, req.reset) {
+            (Some(subject), true) => limits.clear_override(scope, subject, class),
+            (Some(subject), false) => limits.set_override(scope, subject, class, limit),
+            (None, true) => return Err(Status::invalid_argument("reset needs a subject")),
+            (None, false) => limits.set_default(scope, class, limit),
+        }
+        info!(
+            "Rate limit for {} {} calls{}: {}",
+            scope,
+            class,
+            req.subject.as_deref().map(|s| format!(" by {}", s)).unwrap_or_default(),
+            limit.map_or("none".to_string(), |l| format!("{}/s, burst {}", l.rate, l.burst)),
+        );
+
+        Ok(SetRateLimitResponse {
+            scope: scope.to_string(),
+            call_class: class.to_string(),
+            subject: req.subject,
+            rate: limit.map(|l| l.rate),
+            burst: limit.map(|l| l.burst),
+        })
+    }
+
+    fn handle_resume_market(&self, req: ResumeMarketRequest) -> Result<ResumeMarketResponse, Status> {
+        let orderbook = self
+            .engine
+            .book(&req.market_id)
+            .ok_or(Status::not_found("Market not found"))?;
+        let was_halted = orderbook.write().unwrap().bands.resume(Instant::now());
+        info!("Market {} resumed (was halted: {})", req.market_id, was_halted);
+
+        Ok(ResumeMarketResponse {
+            market_id: req.market_id,
+            was_halted,
+        })
+    }
+
+    fn handle_set_position(&self, req: SetPositionRequest) -> Result<SetPositionResponse, Status> {
+        let outcome = Outcome::parse(&req.outcome).ok_or(Status::invalid_argument("Invalid outcome"))?;
+        let quantity = parse_decimal(&req.quantity, "quantity")?;
+        let orderbook = self.engine.listed_book(&req.market_id).ok_or(Status::not_found("Market not found"))?;
+        let mut book = orderbook.write().unwrap();
+        if outcome.index() >= book.outcome_count() {
+            return Err(Status::invalid_argument("Invalid outcome"));
+        }
+        let lots = book
+            .spec
+            .quantity_to_lots(quantity)
+            .map_err(|e| Status::invalid_argument(format!("Invalid quantity: {}", e)))?;
+        book.inventory.set(&req.user_id, outcome, lots);
+        info!("Position of {} in {} {} set to {}", req.user_id, req.market_id, req.outcome, quantity);
+
+        Ok(SetPositionResponse {
+            available: book.spec.lots_to_quantity(book.inventory.available(&req.user_id, outcome)).to_string(),
+            market_id: req.market_id,
+            user_id: req.user_id,
+            outcome: req.outcome,
+            quantity: book.spec.lots_to_quantity(lots).to_string(),
+        })
+    }
+
+    async fn handle_check_consistency(&self) -> Result<CheckConsistencyResponse, Status> {
+        let database_url = self
+            .database_url
+            .as_deref()
+            .ok_or(Status::failed_precondition("No database configured"))?;
+        let rows = load_open_orders(database_url)
+            .await
+            .map_err(|e| Status::unavailable(format!("Failed to load orders: {}", e)))?;
+        let mismatches = recovery::check_consistency(&self.engine, &rows);
+        info!("Consistency check: {} database orders, {} mismatches", rows.len(), mismatches.len());
+
+        Ok(CheckConsistencyResponse {
+            orders_checked: rows.len() as u32,
+            mismatches: mismatches
+                .into_iter()
+                .map(|m| OrderMismatch {
+                    order_id: m.order_id,
+                    market_id: m.market_id,
+                    engine_remaining: m.engine_remaining.map(|q| q.to_string()),
+                    db_remaining: m.db_remaining.map(|q| q.to_string()),
+                })
+                .collect(),
+        })
+    }
+
+    fn handle_reset_mmp(&self, req: ResetMmpRequest) -> Result<ResetMmpResponse, Status> {
+        let orderbook = self
+            .engine
+            .book(&req.market_id)
+            .ok_or(Status::not_found("Market not found"))?;
+        let was_frozen = orderbook.write().unwrap().mmp.reset(&req.user_id);
+        info!("MMP reset for {} in {} (was frozen: {})", req.user_id, req.market_id, was_frozen);
+
+        Ok(ResetMmpResponse {
+            market_id: req.market_id,
+            user_id: req.user_id,
+            was_frozen,
+        })
+    }
+
+    async fn handle_bust_trade(&self, actor: &str, req: BustTradeRequest) -> Result<TradeCorrectionResponse, Status> {
+        let trade_id = Uuid::parse_str(&req.trade_id).map_err(|_| Status::invalid_argument("Invalid trade_id"))?;
+        let reason = required_reason(&req.reason)?;
+        let (spec, record) = self
+            .engine
+            .bust_trade(&req.market_id, &trade_id, actor, reason)
+            .await
+            .map_err(correction_error_to_status)?;
+        Ok(correction_response(&spec, req.market_id, &record))
+    }
+
+    async fn handle_correct_trade(&self, actor: &str, req: CorrectTradeRequest) -> Result<TradeCorrectionResponse, Status> {
+        let trade_id = Uuid::parse_str(&req.trade_id).map_err(|_| Status::invalid_argument("Invalid trade_id"))?;
+        let price = parse_decimal(&req.price, "price")?;
+        let reason = required_reason(&req.reason)?;
+        let (spec, record) = self
+            .engine
+            .reprice_trade(&req.market_id, &trade_id, price, actor, reason)
+            .await
+            .map_err(correction_error_to_status)?;
+        Ok(correction_response(&spec, req.market_id, &record))
+    }
+
+    // From memory within the correction window; after that, from what was
+    // stored when the trade was corrected
+    async fn handle_get_trade_corrections(&self, req: GetTradeCorrectionsRequest) -> Result<GetTradeCorrectionsResponse, Status> {
+        let trade_id = Uuid::parse_str(&req.trade_id).map_err(|_| Status::invalid_argument("Invalid trade_id"))?;
+        let (spec, record) = match self.engine.trade_record(&req.market_id, &trade_id) {
+            Err(CorrectionError::NotFound(_)) => return self.stored_trade_corrections(req, &trade_id).await,
+            result => result.map_err(correction_error_to_status)?,
+        };
+
+        Ok(GetTradeCorrectionsResponse {
+            market_id: req.market_id,
+            trade_id: req.trade_id,
+            trade_kind: record.printed.kind().to_string(),
+            status: record.status.as_str().to_string(),
+            corrections: record.corrections.iter().map(|c| correction_to_proto(&spec, c)).collect(),
+        })
+    }
+
+    async fn stored_trade_corrections(
+        &self,
+        req: GetTradeCorrectionsRequest,
+        trade_id: &Uuid,
+    ) -> Result<GetTradeCorrectionsResponse, Status> {
+        let rows = match self.engine.stored_corrections(trade_id).await {
+            Err(CorrectionError::NoDatabase) => Vec::new(),
+            result => result.map_err(correction_error_to_status)?,
+        };
+        let rows: Vec<CorrectionRow> = rows.into_iter().filter(|row| row.market_id == req.market_id).collect();
+        let Some(last) = rows.last() else {
+            return Err(correction_error_to_status(CorrectionError::NotFound(*trade_id)));
+        };
+        let status = match rows.iter().any(|row| row.action == CorrectionKind::BUST.to_string()) {
+            true => TradeStatus::BUSTED,
+            false => TradeStatus::CORRECTED,
+        };
+
+        Ok(GetTradeCorrectionsResponse {
+            trade_kind: last.trade_kind.clone(),
+            status: status.as_str().to_string(),
+            corrections: rows.iter().map(correction_row_to_proto).collect(),
+            market_id: req.market_id,
+            trade_id: req.trade_id,
+        })
+    }
+}
+
+// Who is making an admin call, for the audit trail; authorize has already
+// checked there is someone
+fn actor<T>(request: &Request<T>) -> String {
+    request.extensions().get::<Caller>().map_or_else(String::new, |caller| caller.subject.clone())
+}
+
+fn required_reason(reason: &str) -> Result<&str, Status> {
+    match reason.trim() {
+        "" => Err(Status::invalid_argument("A reason is required")),
+        reason => Ok(reason),
+    }
+}
+
+fn correction_response(spec: &MarketSpec, market_id: String, record: &TradeRecord) -> TradeCorrectionResponse {
+    TradeCorrectionResponse {
+        market_id,
+        trade_id: record.printed.trade_id().to_string(),
+        status: record.status.as_str().to_string(),
+        correction: Some(correction_to_proto(spec, record.last_correction())),
+    }
+}
+
+fn correction_to_proto(spec: &MarketSpec, correction: &Correction) -> TradeCorrection {
+    TradeCorrection {
+        correction_id: correction.correction_id.to_string(),
+        action: correction.kind.to_string(),
+        old_price: correction.price.map(|(was, _)| spec.ticks_to_price(was).to_string()),
+        new_price: correction.price.map(|(_, now)| spec.ticks_to_price(now).to_string()),
+        actor: correction.actor.clone(),
+        reason: correction.reason.clone(),
+        timestamp: correction.timestamp.to_string(),
+    }
+}
+
+fn correction_row_to_proto(row: &CorrectionRow) -> TradeCorrection {
+    TradeCorrection {
+        correction_id: row.id.clone(),
+        action: row.action.clone(),
+        old_price: row.old_price.map(|p| p.to_string()),
+        new_price: row.new_price.map(|p| p.to_string()),
+        actor: row.actor.clone(),
+        reason: row.reason.clone(),
+        timestamp: row.created_at.to_string(),
+    }
+}
+
+fn placement_to_proto(placement: Placement) -> PlaceOrderResponse {
+    let Placement { spec, outcome_count, order, trades, complementary_matches, complete_set_matches } = placement;
+
+    let status = match order.order_status {
+        OrderStatus::PENDING => "OPEN",
+        OrderStatus::OPEN => "OPEN",
+        OrderStatus::FILLED => "FILLED",
+        OrderStatus::PARTIAL => "PARTIAL",
+        OrderStatus::CANCELLED => "CANCELLED",
+        OrderStatus::UNTRIGGERED => "UNTRIGGERED",
+    };
+    for t in &trades {
+        info!("TRADE FIELDS: id={} market={} outcome={:?} type={:?}", 
+            t.trade_id, t.market_id, t.outcome, t.trade_type);
+    }
+    PlaceOrderResponse {
+        order_id: order.order_id.to_string(),
+        status: status.to_string(),
+        trades: trades_to_proto(&spec, outcome_count, &trades),
+        complementary_matches: complementary_matches_to_proto(&spec, &complementary_matches),
+        complete_set_matches: complete_set_matches_to_proto(&spec, outcome_count, &complete_set_matches),
+    }
+}
+
+fn group_placement_to_proto(placement: GroupPlacement) -> PlaceGroupResponse {
+    PlaceGroupResponse {
+        group_id: placement.group_id.to_string(),
+        legs: placement.legs.into_iter().map(placement_to_proto).collect(),
+    }
+}
+
+fn trades_to_proto(spec: &MarketSpec, outcome_count: usize, trades: &[trade::Trade]) -> Vec<Trade> {
+    trades
         .iter()
         .map(|t| {
-            let outcome_str = match t.outcome {
-                Outcome::YES => "YES".to_string(),
-                Outcome::NO => "NO".to_string(),
-            };
+            let outcome_str = t.outcome.label(outcome_count);
     
             let trade_type_str = match t.trade_type {
                 TradeType::SECONDARY => "SECONDARY".to_string(),
@@ -106,27 +879,30 @@ impl MatchingEngine for MatchingEngineService {
                 trade_id: t.trade_id.to_string(),
                 buyer_id: t.buyer_id.clone(),
                 seller_id: t.seller_id.clone(),
-                quantity: t.quantity.to_string(),
-                price: t.price.to_string(),
+                quantity: spec.lots_to_quantity(t.quantity).to_string(),
+                price: spec.ticks_to_price(t.price).to_string(),
                 market_id: t.market_id.clone(),
                 outcome: outcome_str,
                 trade_type: trade_type_str,
                 timestamp: t.timestamp.to_string(),
             }
         })
-        .collect();
-    
-    // ✅ FIX: Actually build the complementary_matches array
-    let complementary_matches = result
-        .complementary_matches
+        .collect()
+}
+
+fn complementary_matches_to_proto(
+    spec: &MarketSpec,
+    matches: &[trade::ComplementaryMatch],
+) -> Vec<ComplementaryMatch> {
+    matches
         .iter()
         .map(|c| ComplementaryMatch {
             trade_id: c.trade_id.to_string(),
             yes_buyer_id: c.yes_buyer_id.clone(),
             no_buyer_id: c.no_buyer_id.clone(),
-            quantity: c.quantity.to_string(),
-            yes_price: c.yes_price.to_string(),
-            no_price: c.no_price.to_string(),
+            quantity: spec.lots_to_quantity(c.quantity).to_string(),
+            yes_price: spec.ticks_to_price(c.yes_price).to_string(),
+            no_price: spec.ticks_to_price(c.no_price).to_string(),
             market_id : c.market_id.to_string(),
             timestamp : c.timestamp.to_string(),
             yes_order_id: c.yes_order_id.to_string(),
@@ -134,51 +910,266 @@ impl MatchingEngine for MatchingEngineService {
             yes_reservation_id: c.yes_reservation_id.clone(),
             no_reservation_id: c.no_reservation_id.clone(),
         })
-        .collect();
-        
-        let status = match  result.order.order_status {
-            OrderStatus::PENDING => "OPEN",
-            OrderStatus::OPEN => "OPEN",
-            OrderStatus::FILLED => "FILLED",
-            OrderStatus::PARTIAL => "PARTIAL",
-            OrderStatus::CANCELLED => "CANCELLED"
-        };
-        for t in &result.trades {
-            info!("TRADE FIELDS: id={} market={} outcome={:?} type={:?}", 
-                t.trade_id, t.market_id, t.outcome, t.trade_type);
-        }
-        Ok(Response::new(PlaceOrderResponse {
-            order_id: result.order.order_id.to_string(),
-            status: status.to_string(),
-            trades,                      // ✅ Now populated
-            complementary_matches,       // ✅ Now populated
-        }))
+        .collect()
+}
+
+fn complete_set_matches_to_proto(
+    spec: &MarketSpec,
+    outcome_count: usize,
+    matches: &[trade::CompleteSetMatch],
+) -> Vec<CompleteSetMatch> {
+    matches
+        .iter()
+        .map(|m| CompleteSetMatch {
+            trade_id: m.trade_id.to_string(),
+            market_id: m.market_id.clone(),
+            quantity: spec.lots_to_quantity(m.quantity).to_string(),
+            legs: m
+                .legs
+                .iter()
+                .map(|leg| MintLeg {
+                    outcome: leg.outcome.label(outcome_count),
+                    buyer_id: leg.buyer_id.clone(),
+                    order_id: leg.order_id.to_string(),
+                    reservation_id: leg.reservation_id.clone(),
+                    price: spec.ticks_to_price(leg.price).to_string(),
+                })
+                .collect(),
+            timestamp: m.timestamp.to_string(),
+        })
+        .collect()
+}
+
+fn parse_order(req: &PlaceOrderRequest) -> Result<NewOrder, Status> {
+    if req.reduce_only && req.side != "SELL" {
+        return Err(Status::invalid_argument("reduce_only applies to SELL orders only"));
     }
-    
-    async fn get_orderbook(
-        &self,
-        request: Request<GetOrderbookRequest>,
-    ) -> Result<Response<GetOrderbookResponse>, Status> {
-        let req = request.into_inner();
-        let orderbook = self.orderbooks.get(&req.market_id).ok_or(Status::not_found("Market not found"))?;
-        let outcome = match req.outcome.as_str() {
-            "YES" => Outcome::YES,
-            "NO" => Outcome::NO,
-            _ => return Err(Status::invalid_argument("Invalid outcome")),
-        };
-        let depth = orderbook.get_depth(outcome, 10);
-        Ok(Response::new(GetOrderbookResponse { bids: vec![], asks: vec![] }))
+    Ok(NewOrder {
+        user_id: req.user_id.clone(),
+        market_id: req.market_id.clone(),
+        side: match req.side.as_str() {
+            "BUY" => OrderSide::BUY,
+            "SELL" => OrderSide::SELL,
+            _ => return Err(Status::invalid_argument("Invalid side")),
+        },
+        outcome: Outcome::parse(&req.outcome).ok_or(Status::invalid_argument("Invalid outcome"))?,
+        order_type: match req.order_type.as_str() {
+            "LIMIT" => OrderType::LIMIT,
+            "MARKET" => OrderType::MARKET,
+            "POSTONLY" => OrderType::POSTONLY,
+            "ICEBERG" => OrderType::ICEBERG,
+            _ => return Err(Status::invalid_argument("Invalid order type")),
+        },
+        price: parse_decimal(&req.price, "price")?,
+        quantity: parse_decimal(&req.quantity, "quantity")?,
+        display_quantity: req
+            .display_quantity
+            .as_deref()
+            .map(|d| parse_decimal(d, "display_quantity"))
+            .transpose()?,
+        reservation_id: req.reservation_id.clone(),
+        client_order_id: req.client_order_id.clone(),
+        session_id: req.session_id.clone().filter(|id| !id.is_empty()),
+        trigger: match (&req.trigger_type, &req.trigger_price) {
+            (Some(kind), Some(price)) => Some((
+                TriggerKind::from_str(kind).map_err(Status::invalid_argument)?,
+                parse_decimal(price, "trigger_price")?,
+            )),
+            (None, None) => None,
+            _ => return Err(Status::invalid_argument("trigger_type and trigger_price go together")),
+        },
+        reduce_only: req.reduce_only,
+    })
+}
+
+// An OCO pair is deduplicated on its first leg's client_order_id, so one on
+// the second leg would be ignored
+fn parse_group_leg(req: &PlaceOrderRequest) -> Result<NewOrder, Status> {
+    if req.client_order_id.is_some() {
+        return Err(Status::invalid_argument("client_order_id goes on the first OCO leg"));
+    }
+    parse_order(req)
+}
+
+// v1 clients only ever see rejections as error statuses
+fn correction_error_to_status(e: CorrectionError) -> Status {
+    match e {
+        CorrectionError::UnknownMarket | CorrectionError::NotFound(_) => Status::not_found(e.to_string()),
+        CorrectionError::InvalidPrice(_) => Status::invalid_argument(e.to_string()),
+        CorrectionError::Expired(_)
+        | CorrectionError::Busted(_)
+        | CorrectionError::NotRepriceable(_)
+        | CorrectionError::NotBustable(_)
+        | CorrectionError::SamePrice(_)
+        | CorrectionError::PositionMoved(..)
+        | CorrectionError::NoDatabase => Status::failed_precondition(e.to_string()),
+        CorrectionError::Changed(_) => Status::aborted(e.to_string()),
+        CorrectionError::Unavailable(_) => Status::unavailable(e.to_string()),
+    }
+}
+
+fn place_error_to_status(e: PlaceError) -> Status {
+    match e {
+        PlaceError::Rejected { reason: RejectReason::ClientOrderIdReused, message } => {
+            Status::already_exists(message)
+        }
+        PlaceError::Rejected { reason: RejectReason::UnknownMarket, message } => Status::not_found(message),
+        PlaceError::Rejected {
+            reason: RejectReason::InvalidPrice | RejectReason::PriceOutOfRange | RejectReason::InvalidQuantity,
+            message,
+        } => Status::invalid_argument(message),
+        PlaceError::Rejected {
+            reason: RejectReason::UnknownSession
+                | RejectReason::MarketHalted
+                | RejectReason::MarketClosed
+                | RejectReason::PriceOutsideBand
+                | RejectReason::RiskLimit,
+            message,
+        } => Status::failed_precondition(message),
+        PlaceError::Rejected { message, .. } => Status::internal(message),
+        PlaceError::InFlight => Status::aborted(e.to_string()),
+        PlaceError::Internal(e) => Status::internal(e.to_string()),
+    }
+}
+
+// Reload the registry on every change notification, and every minute for
+// changes made without one. A dropped
+// subscription is retried, reloading once it is back in case something was
+// missed.
+async fn watch_markets(engine: Arc<Engine>, redis: Arc<RedisClient>, source: Arc<dyn MarketSource>) {
+    let mut reload = tokio::time::interval(MARKETS_RELOAD_INTERVAL);
+    loop {
+        match redis.subscribe(MARKETS_UPDATED_CHANNEL).await {
+            Ok(mut pubsub) => {
+                let mut updates = pubsub.on_message();
+                loop {
+                    tokio::select! {
+                        update = updates.next() => if update.is_none() { break },
+                        _ = reload.tick() => {}
+                    }
+                    if let Err(e) = engine.refresh_markets(source.as_ref()).await {
+                        warn!("Failed to reload markets: {}", e);
+                    }
+                }
+                warn!("Lost the {} subscription", MARKETS_UPDATED_CHANNEL);
+            }
+            Err(e) => warn!("Failed to subscribe to {}: {}", MARKETS_UPDATED_CHANNEL, e),
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        if let Err(e) = engine.refresh_markets(source.as_ref()).await {
+            warn!("Failed to reload markets: {}", e);
+        }
     }
 }
 
 pub async fn start_grpc_server(
     addr: std::net::SocketAddr,
-    orderbooks: Arc<DashMap<String, Arc<OrderBook>>>,
+    orderbooks: Arc<DashMap<String, SharedOrderBook>>,
     redis: Arc<RedisClient>,
+    metrics: Arc<Metrics>,
+    config: &Config,
 ) -> Result<()> {
-    let service = MatchingEngineService { orderbooks, redis };
-    tonic::transport::Server::builder()
-        .add_service(MatchingEngineServer::new(service))
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_serving::<MatchingEngineServer<MatchingEngineService>>()
+        .await;
+    health_reporter
+        .set_serving::<MatchingEngineV2Server<grpc_server_v2::MatchingEngineService>>()
+        .await;
+    tokio::spawn(watch_books(
+        health_reporter,
+        orderbooks.clone(),
+        metrics.clone(),
+        Duration::from_millis(config.health_check_interval_ms),
+        Duration::from_millis(config.health_check_timeout_ms),
+    ));
+
+    let engine = Arc::new(Engine::new(orderbooks, redis.clone(), metrics, config));
+    if let Some(receiver) = engine.fired.take() {
+        tokio::spawn(fired::deliver(receiver, redis.clone()));
+    }
+    if let Some(database_url) = &config.database_url {
+        let source: Arc<dyn MarketSource> = Arc::new(PostgresMarkets::new(database_url.clone(), config.default_spec));
+        engine.refresh_markets(source.as_ref()).await?;
+        tokio::spawn(watch_markets(engine.clone(), redis, source));
+        engine.seed_positions(load_positions(database_url).await?);
+    }
+    if config.recover_orders {
+        let Some(database_url) = &config.database_url else {
+            anyhow::bail!("RECOVER_ORDERS needs DATABASE_URL");
+        };
+        // Positions first, so restored SELLs are committed against them
+        let rows = load_open_orders(database_url).await?;
+        let report = recovery::restore(&engine, &rows);
+        let mismatches = recovery::check_consistency(&engine, &rows);
+        if mismatches.is_empty() {
+            info!("Books match the database after restoring {} orders", report.restored);
+        } else {
+            for m in &mismatches {
+                warn!(
+                    "Order {} in {}: engine has {:?} resting, database {:?}",
+                    m.order_id, m.market_id, m.engine_remaining, m.db_remaining
+                );
+            }
+            warn!("{} orders differ between the books and the database", mismatches.len());
+        }
+    }
+    let sweeper = engine.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            ticker.tick().await;
+            sweeper.purge_expired_client_order_ids();
+            sweeper.purge_reservations();
+            sweeper.rate_limits.purge_idle(Instant::now());
+        }
+    });
+    // Sweep often enough that a dead session is noticed within ~1.25x its timeout
+    let reaper = engine.clone();
+    let sweep_every = (engine.sessions.timeout() / 4).max(Duration::from_millis(10));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(sweep_every);
+        loop {
+            ticker.tick().await;
+            reaper.expire_sessions();
+        }
+    });
+
+    // Wake at the next expiry, and at least every second to see newly listed markets
+    let closer = engine.clone();
+    tokio::spawn(async move {
+        loop {
+            let now = chrono::Utc::now();
+            closer.close_expired_markets(now);
+            let wait = closer
+                .markets
+                .next_expiry()
+                .map_or(Duration::from_secs(1), |at| (at - now).to_std().unwrap_or_default())
+                .min(Duration::from_secs(1));
+            tokio::time::sleep(wait).await;
+        }
+    });
+
+    let service = MatchingEngineService {
+        engine: engine.clone(),
+        database_url: config.database_url.clone(),
+    };
+    let service_v2 = grpc_server_v2::MatchingEngineService::new(engine);
+    let auth = Authenticator::from_config(config)?;
+    if !auth.is_enforced() {
+        warn!("gRPC authentication is off (AUTH_DISABLED); every caller is an admin");
+    } else if config.auth_token_secret.is_none() && config.tls_client_ca_path.is_none() {
+        warn!("No AUTH_TOKEN_SECRET or TLS_CLIENT_CA_PATH; every gRPC call will be refused");
+    }
+    let mut server = tonic::transport::Server::builder();
+    if let Some(tls) = tls_config(config)? {
+        server = server.tls_config(tls)?;
+    }
+    // Health checks stay open for probes
+    server
+        .add_service(health_service)
+        .add_service(MatchingEngineServer::with_interceptor(service, auth.clone()))
+        .add_service(MatchingEngineV2Server::with_interceptor(service_v2, auth))
         .serve(addr)
         .await?;
     Ok(())
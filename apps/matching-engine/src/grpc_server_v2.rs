@@ -0,0 +1,876 @@
+#![allow(clippy::result_large_err)] // tonic::Status is the error type throughout this module
+
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use futures_util::{stream, Stream, StreamExt};
+use rust_decimal::Decimal;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::{Code, Request, Response, Status};
+use uuid::Uuid;
+
+use crate::auction::{AuctionBook, TradingPhase};
+use crate::auth::{authorize, Access};
+use crate::engine::{Engine, NewOrder, PlaceError, Placement};
+use crate::executions::{ExecType, Execution};
+use crate::market_spec::MarketSpec;
+use crate::marketdata::{Level, MarketSnapshot, MarketUpdate, OrderEventKind, OrderSnapshot, Print};
+use crate::matcher::RejectReason;
+use crate::order::{OrderSide, OrderStatus, OrderType, Outcome};
+use crate::orderbook::PriceLevelSummary;
+use crate::ratelimit::{CallClass, Throttled};
+use crate::trade::{self, TradeType};
+use crate::triggers::TriggerKind;
+
+pub mod matching_engine_v2 {
+    tonic::include_proto!("matching_engine.v2");
+}
+
+use matching_engine_v2 as pb;
+use matching_engine_v2::matching_engine_server::MatchingEngine;
+
+const NANOS_PER_UNIT: i64 = 1_000_000_000;
+const DEFAULT_DEPTH: usize = 10;
+
+/// v2 API: typed enums and decimals, rejections returned as responses
+pub struct MatchingEngineService {
+    engine: Arc<Engine>,
+}
+
+impl MatchingEngineService {
+    pub fn new(engine: Arc<Engine>) -> Self {
+        Self { engine }
+    }
+}
+
+#[tonic::async_trait]
+impl MatchingEngine for MatchingEngineService {
+    async fn place_order(
+        &self,
+        request: Request<pb::PlaceOrderRequest>,
+    ) -> Result<Response<pb::PlaceOrderResponse>, Status> {
+        let access = Access::Trade(&request.get_ref().user_id);
+        let result = authorize(&request, "v2.PlaceOrder", access)
+            .map(|_| self.engine.rate_limits.admit(&request, CallClass::PLACE, access))
+            .and_then(|admitted| match admitted {
+                Ok(()) => self.handle_place_order(request.into_inner()),
+                Err(throttled) => {
+                    self.engine.metrics.record_reject(&request.get_ref().market_id, "rate_limited");
+                    Ok(throttled_to_proto(throttled))
+                }
+            });
+        self.record("v2.PlaceOrder", &result);
+        result.map(Response::new)
+    }
+
+    async fn get_orderbook(
+        &self,
+        request: Request<pb::GetOrderbookRequest>,
+    ) -> Result<Response<pb::GetOrderbookResponse>, Status> {
+        let result = authorize(&request, "v2.GetOrderbook", Access::Read)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::QUERY, Access::Read).map_err(Status::from))
+            .and_then(|_| self.handle_get_orderbook(request.into_inner()));
+        self.record("v2.GetOrderbook", &result);
+        result.map(Response::new)
+    }
+
+    async fn cancel_order(
+        &self,
+        request: Request<pb::CancelOrderRequest>,
+    ) -> Result<Response<pb::CancelOrderResponse>, Status> {
+        let access = Access::Trade(&request.get_ref().user_id);
+        let result = authorize(&request, "v2.CancelOrder", access)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::CANCEL, access).map_err(Status::from))
+            .and_then(|_| self.handle_cancel_order(request.into_inner()));
+        self.record("v2.CancelOrder", &result);
+        result.map(Response::new)
+    }
+
+    type SubscribeExecutionsStream = Pin<Box<dyn Stream<Item = Result<pb::ExecutionReport, Status>> + Send>>;
+
+    async fn subscribe_executions(
+        &self,
+        request: Request<pb::SubscribeExecutionsRequest>,
+    ) -> Result<Response<Self::SubscribeExecutionsStream>, Status> {
+        let access = Access::Trade(&request.get_ref().user_id);
+        let result = authorize(&request, "v2.SubscribeExecutions", access)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::QUERY, access).map_err(Status::from))
+            .and_then(|_| match request.get_ref().user_id.as_str() {
+                "" => Err(Status::invalid_argument("user_id is required")),
+                user_id => Ok(self.engine.executions.subscribe(user_id)),
+            });
+        self.record("v2.SubscribeExecutions", &result);
+
+        // Ends when the client goes away and drops the receiver
+        let stream = stream::unfold(Some(result?), |receiver| async move {
+            let mut receiver = receiver?;
+            match receiver.recv().await {
+                Ok(execution) => Some((Ok(execution_to_proto(&execution)), Some(receiver))),
+                Err(RecvError::Lagged(missed)) => {
+                    Some((Err(Status::data_loss(format!("Missed {} execution reports", missed))), None))
+                }
+                Err(RecvError::Closed) => None,
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    type SubscribeMarketDataStream = Pin<Box<dyn Stream<Item = Result<pb::MarketDataUpdate, Status>> + Send>>;
+
+    async fn subscribe_market_data(
+        &self,
+        request: Request<pb::SubscribeMarketDataRequest>,
+    ) -> Result<Response<Self::SubscribeMarketDataStream>, Status> {
+        let result = authorize(&request, "v2.SubscribeMarketData", Access::Read)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::QUERY, Access::Read).map_err(Status::from))
+            .and_then(|_| {
+                let market_id = &request.get_ref().market_id;
+                let orderbook = self.engine.market_book(market_id).ok_or(Status::not_found("Market not found"))?;
+                let book = orderbook.read().unwrap();
+                Ok(self.engine.market_data.subscribe(&book))
+            });
+        self.record("v2.SubscribeMarketData", &result);
+
+        let (snapshot, receiver) = result?;
+        let market_id = request.into_inner().market_id;
+        let first = snapshot_to_proto(&market_id, &snapshot);
+        // Ends when the client goes away and drops the receiver
+        let updates = stream::unfold(Some((market_id, receiver)), |state| async move {
+            let (market_id, mut receiver) = state?;
+            match receiver.recv().await {
+                Ok(update) => Some((Ok(update_to_proto(&market_id, &update)), Some((market_id, receiver)))),
+                Err(RecvError::Lagged(missed)) => {
+                    Some((Err(Status::data_loss(format!("Missed {} market data updates", missed))), None))
+                }
+                Err(RecvError::Closed) => None,
+            }
+        });
+        Ok(Response::new(Box::pin(stream::once(async { Ok(first) }).chain(updates))))
+    }
+
+    type SubscribeOrdersStream = Pin<Box<dyn Stream<Item = Result<pb::OrderBookUpdate, Status>> + Send>>;
+
+    async fn subscribe_orders(
+        &self,
+        request: Request<pb::SubscribeOrdersRequest>,
+    ) -> Result<Response<Self::SubscribeOrdersStream>, Status> {
+        let result = authorize(&request, "v2.SubscribeOrders", Access::Read)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::QUERY, Access::Read).map_err(Status::from))
+            .and_then(|_| {
+                let market_id = &request.get_ref().market_id;
+                self.engine.market_book(market_id).ok_or(Status::not_found("Market not found"))?;
+                Ok(self.engine.market_data.updates(market_id))
+            });
+        self.record("v2.SubscribeOrders", &result);
+
+        let market_id = request.into_inner().market_id;
+        // Ends when the client goes away and drops the receiver
+        let stream = stream::unfold(Some((market_id, result?)), |state| async move {
+            let (market_id, mut receiver) = state?;
+            match receiver.recv().await {
+                Ok(update) => Some((Ok(order_update_to_proto(&market_id, &update)), Some((market_id, receiver)))),
+                Err(RecvError::Lagged(missed)) => {
+                    Some((Err(Status::data_loss(format!("Missed {} order book updates", missed))), None))
+                }
+                Err(RecvError::Closed) => None,
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_order_snapshot(
+        &self,
+        request: Request<pb::GetOrderSnapshotRequest>,
+    ) -> Result<Response<pb::OrderSnapshot>, Status> {
+        let result = authorize(&request, "v2.GetOrderSnapshot", Access::Read)
+            .and_then(|_| self.engine.rate_limits.admit(&request, CallClass::QUERY, Access::Read).map_err(Status::from))
+            .and_then(|_| {
+                let market_id = &request.get_ref().market_id;
+                let orderbook = self.engine.market_book(market_id).ok_or(Status::not_found("Market not found"))?;
+                let book = orderbook.read().unwrap();
+                Ok(order_snapshot_to_proto(market_id, &self.engine.market_data.order_snapshot(&book)))
+            });
+        self.record("v2.GetOrderSnapshot", &result);
+        result.map(Response::new)
+    }
+}
+
+impl MatchingEngineService {
+    fn record<T>(&self, method: &str, result: &Result<T, Status>) {
+        let code = match result {
+            Ok(_) => Code::Ok,
+            Err(status) => status.code(),
+        };
+        self.engine.metrics.record_grpc(method, code);
+    }
+
+    fn handle_place_order(&self, req: pb::PlaceOrderRequest) -> Result<pb::PlaceOrderResponse, Status> {
+        let new_order = match parse_order(&req) {
+            Ok(order) => order,
+            Err(message) => {
+                self.engine.metrics.record_reject(&req.market_id, "invalid_request");
+                return Ok(rejected(pb::RejectReason::InvalidRequest, message));
+            }
+        };
+
+        match self.engine.place_order(new_order) {
+            Ok(placement) => Ok(placement_to_proto(placement)),
+            Err(PlaceError::Rejected { reason, message }) => Ok(rejected(reject_reason_to_proto(reason), message)),
+            Err(e @ PlaceError::InFlight) => Err(Status::aborted(e.to_string())),
+            Err(PlaceError::Internal(e)) => Err(Status::internal(e.to_string())),
+        }
+    }
+
+    fn handle_cancel_order(&self, req: pb::CancelOrderRequest) -> Result<pb::CancelOrderResponse, Status> {
+        let order_id = Uuid::parse_str(&req.order_id).map_err(|_| Status::invalid_argument("Invalid order_id"))?;
+        let mut cancelled = self.engine.cancel_order(&req.market_id, &order_id, &req.user_id).unwrap_or_default();
+        // The rest of a group comes back in no particular order
+        cancelled.sort_by_key(|order| order.order_id != order_id);
+        Ok(pb::CancelOrderResponse {
+            cancelled: !cancelled.is_empty(),
+            cancelled_order_ids: cancelled.iter().map(|order| order.order_id.to_string()).collect(),
+        })
+    }
+
+    fn handle_get_orderbook(&self, req: pb::GetOrderbookRequest) -> Result<pb::GetOrderbookResponse, Status> {
+        let outcome = outcome_from_request(req.outcome(), req.outcome_index)
+            .map_err(Status::invalid_argument)?;
+        let orderbook = self.engine.market_book(&req.market_id).ok_or(Status::not_found("Market not found"))?;
+        let depth = match req.depth {
+            0 => DEFAULT_DEPTH,
+            n => n as usize,
+        };
+
+        let book = orderbook.read().unwrap();
+        if outcome.index() >= book.outcome_count() {
+            return Err(Status::invalid_argument("Outcome does not exist in this market"));
+        }
+        let spec = book.spec;
+        let levels = book.get_depth(outcome, depth);
+        let to_proto = |levels: Vec<PriceLevelSummary>| {
+            levels
+                .into_iter()
+                .map(|l| pb::PriceLevel {
+                    price: Some(price(&spec, l.price)),
+                    quantity: Some(quantity(&spec, l.quantity)),
+                    order_count: l.order_count as u32,
+                })
+                .collect()
+        };
+        let (phase, indicative) = match book.phase {
+            TradingPhase::AUCTION => (pb::TradingPhase::Auction, AuctionBook::new(&book).clearing_price()),
+            TradingPhase::CONTINUOUS => (pb::TradingPhase::Continuous, None),
+        };
+
+        Ok(pb::GetOrderbookResponse {
+            bids: to_proto(levels.bids),
+            asks: to_proto(levels.asks),
+            phase: phase.into(),
+            indicative_price: indicative.map(|c| price(&spec, c.price)),
+            indicative_volume: indicative.map(|c| quantity(&spec, c.volume)),
+        })
+    }
+}
+
+fn parse_order(req: &pb::PlaceOrderRequest) -> Result<NewOrder, String> {
+    if req.reduce_only && req.side() != pb::Side::Sell {
+        return Err("reduce_only applies to SIDE_SELL only".to_string());
+    }
+    Ok(NewOrder {
+        user_id: req.user_id.clone(),
+        market_id: req.market_id.clone(),
+        side: match req.side() {
+            pb::Side::Buy => OrderSide::BUY,
+            pb::Side::Sell => OrderSide::SELL,
+            pb::Side::Unspecified => return Err("side is required".to_string()),
+        },
+        outcome: outcome_from_request(req.outcome(), req.outcome_index)?,
+        order_type: match req.order_type() {
+            pb::OrderType::Limit => OrderType::LIMIT,
+            pb::OrderType::Market => OrderType::MARKET,
+            pb::OrderType::PostOnly => OrderType::POSTONLY,
+            pb::OrderType::Iceberg => OrderType::ICEBERG,
+            pb::OrderType::Unspecified => return Err("order_type is required".to_string()),
+        },
+        price: decimal_from_proto(req.price.as_ref().ok_or("price is required")?)?,
+        quantity: decimal_from_proto(req.quantity.as_ref().ok_or("quantity is required")?)?,
+        display_quantity: req.display_quantity.as_ref().map(decimal_from_proto).transpose()?,
+        reservation_id: req.reservation_id.clone(),
+        client_order_id: req.client_order_id.clone(),
+        session_id: req.session_id.clone().filter(|id| !id.is_empty()),
+        trigger: match (req.trigger_type(), &req.trigger_price) {
+            (pb::TriggerType::Unspecified, None) => None,
+            (pb::TriggerType::Stop, Some(price)) => Some((TriggerKind::STOP, decimal_from_proto(price)?)),
+            (pb::TriggerType::TakeProfit, Some(price)) => Some((TriggerKind::TAKEPROFIT, decimal_from_proto(price)?)),
+            _ => return Err("trigger_type and trigger_price go together".to_string()),
+        },
+        reduce_only: req.reduce_only,
+    })
+}
+
+fn throttled_to_proto(throttled: Throttled) -> pb::PlaceOrderResponse {
+    let message = Status::from(throttled).message().to_string();
+    pb::PlaceOrderResponse {
+        retry_after_ms: Some(throttled.retry_after.as_millis() as u64),
+        ..rejected(pb::RejectReason::RateLimited, message)
+    }
+}
+
+fn rejected(reason: pb::RejectReason, message: String) -> pb::PlaceOrderResponse {
+    pb::PlaceOrderResponse {
+        status: pb::OrderStatus::Rejected.into(),
+        reject_reason: reason.into(),
+        reject_message: message,
+        ..Default::default()
+    }
+}
+
+fn status_to_proto(status: OrderStatus) -> pb::OrderStatus {
+    match status {
+        OrderStatus::PENDING | OrderStatus::OPEN => pb::OrderStatus::Open,
+        OrderStatus::PARTIAL => pb::OrderStatus::Partial,
+        OrderStatus::FILLED => pb::OrderStatus::Filled,
+        OrderStatus::CANCELLED => pb::OrderStatus::Cancelled,
+        OrderStatus::UNTRIGGERED => pb::OrderStatus::Untriggered,
+    }
+}
+
+fn placement_to_proto(placement: Placement) -> pb::PlaceOrderResponse {
+    let Placement { spec, outcome_count, order, trades, complementary_matches, complete_set_matches } = placement;
+    pb::PlaceOrderResponse {
+        order_id: order.order_id.to_string(),
+        status: status_to_proto(order.order_status).into(),
+        trades: trades.iter().map(|t| trade_to_proto(&spec, outcome_count, t)).collect(),
+        complementary_matches: complementary_matches
+            .iter()
+            .map(|c| complementary_match_to_proto(&spec, c))
+            .collect(),
+        reject_reason: pb::RejectReason::Unspecified.into(),
+        reject_message: String::new(),
+        complete_set_matches: complete_set_matches
+            .iter()
+            .map(|m| complete_set_match_to_proto(&spec, m))
+            .collect(),
+        retry_after_ms: None,
+    }
+}
+
+fn execution_to_proto(execution: &Execution) -> pb::ExecutionReport {
+    let Execution { exec_type, order, last_fill, spec, outcome_count, timestamp: at } = execution;
+    let outcome = match outcome_count {
+        2 => outcome_to_proto(order.outcome),
+        _ => pb::Outcome::Unspecified,
+    };
+    pb::ExecutionReport {
+        order_id: order.order_id.to_string(),
+        user_id: order.user_id.clone(),
+        market_id: order.market_id.clone(),
+        reservation_id: order.reservation_id.clone(),
+        exec_type: match exec_type {
+            ExecType::ACCEPTED => pb::ExecType::Accepted,
+            ExecType::PARTIAL => pb::ExecType::PartiallyFilled,
+            ExecType::FILLED => pb::ExecType::Filled,
+            ExecType::CANCELLED => pb::ExecType::Cancelled,
+            ExecType::EXPIRED => pb::ExecType::Expired,
+        }
+        .into(),
+        status: status_to_proto(order.order_status).into(),
+        side: side_to_proto(order.side).into(),
+        outcome: outcome.into(),
+        outcome_index: order.outcome.0 as u32,
+        price: Some(price(spec, order.price)),
+        quantity: Some(quantity(spec, order.quantity)),
+        filled_quantity: Some(quantity(spec, order.filled)),
+        trade_id: last_fill.map(|fill| fill.trade_id.to_string()),
+        last_price: last_fill.map(|fill| price(spec, fill.price)),
+        last_quantity: last_fill.map(|fill| quantity(spec, fill.quantity)),
+        timestamp: Some(timestamp(*at)),
+    }
+}
+
+fn snapshot_to_proto(market_id: &str, snapshot: &MarketSnapshot) -> pb::MarketDataUpdate {
+    pb::MarketDataUpdate {
+        market_id: market_id.to_string(),
+        seq: snapshot.seq,
+        snapshot: true,
+        levels: snapshot.levels.iter().map(|l| level_to_proto(&snapshot.spec, l)).collect(),
+        trades: Vec::new(),
+        outcome_count: snapshot.outcome_count as u32,
+        phase: match snapshot.phase {
+            TradingPhase::AUCTION => pb::TradingPhase::Auction,
+            TradingPhase::CONTINUOUS => pb::TradingPhase::Continuous,
+        }
+        .into(),
+        timestamp: Some(timestamp(snapshot.timestamp)),
+    }
+}
+
+fn update_to_proto(market_id: &str, update: &MarketUpdate) -> pb::MarketDataUpdate {
+    let spec = &update.spec;
+    pb::MarketDataUpdate {
+        market_id: market_id.to_string(),
+        seq: update.seq,
+        snapshot: false,
+        levels: update.levels.iter().map(|l| level_to_proto(spec, l)).collect(),
+        trades: update.prints.iter().map(|p| print_to_proto(spec, p)).collect(),
+        outcome_count: 0,
+        phase: pb::TradingPhase::Unspecified.into(),
+        timestamp: Some(timestamp(update.timestamp)),
+    }
+}
+
+fn order_update_to_proto(market_id: &str, update: &MarketUpdate) -> pb::OrderBookUpdate {
+    let spec = &update.spec;
+    pb::OrderBookUpdate {
+        market_id: market_id.to_string(),
+        seq: update.seq,
+        events: update
+            .orders
+            .iter()
+            .map(|e| pb::OrderEvent {
+                event_type: match e.kind {
+                    OrderEventKind::ADDED => pb::OrderEventType::Added,
+                    OrderEventKind::REDUCED => pb::OrderEventType::Reduced,
+                    OrderEventKind::FILLED => pb::OrderEventType::Filled,
+                    OrderEventKind::REMOVED => pb::OrderEventType::Removed,
+                }
+                .into(),
+                order_id: e.public_id,
+                side: side_to_proto(e.side).into(),
+                outcome_index: e.outcome.0 as u32,
+                price: Some(price(spec, e.price)),
+                quantity: Some(quantity(spec, e.quantity)),
+                change: Some(quantity(spec, e.change)),
+            })
+            .collect(),
+        trades: update.prints.iter().map(|p| print_to_proto(spec, p)).collect(),
+        timestamp: Some(timestamp(update.timestamp)),
+    }
+}
+
+fn order_snapshot_to_proto(market_id: &str, snapshot: &OrderSnapshot) -> pb::OrderSnapshot {
+    let spec = &snapshot.spec;
+    pb::OrderSnapshot {
+        market_id: market_id.to_string(),
+        seq: snapshot.seq,
+        outcome_count: snapshot.outcome_count as u32,
+        orders: snapshot
+            .orders
+            .iter()
+            .map(|o| pb::PublicOrder {
+                order_id: o.public_id,
+                side: side_to_proto(o.side).into(),
+                outcome_index: o.outcome.0 as u32,
+                price: Some(price(spec, o.price)),
+                quantity: Some(quantity(spec, o.quantity)),
+            })
+            .collect(),
+        timestamp: Some(timestamp(snapshot.timestamp)),
+    }
+}
+
+fn print_to_proto(spec: &MarketSpec, print: &Print) -> pb::PublicTrade {
+    pb::PublicTrade {
+        trade_id: print.trade_id.to_string(),
+        outcome_index: print.outcome.0 as u32,
+        price: Some(price(spec, print.price)),
+        quantity: Some(quantity(spec, print.quantity)),
+        taker_side: print.taker_side.map_or(pb::Side::Unspecified, side_to_proto).into(),
+        timestamp: Some(timestamp(print.timestamp)),
+    }
+}
+
+fn level_to_proto(spec: &MarketSpec, level: &Level) -> pb::BookLevel {
+    pb::BookLevel {
+        side: side_to_proto(level.side).into(),
+        outcome_index: level.outcome.0 as u32,
+        price: Some(price(spec, level.price)),
+        quantity: Some(quantity(spec, level.quantity)),
+        order_count: level.order_count as u32,
+    }
+}
+
+fn trade_to_proto(spec: &MarketSpec, outcome_count: usize, t: &trade::Trade) -> pb::Trade {
+    let outcome = match outcome_count {
+        2 => outcome_to_proto(t.outcome),
+        _ => pb::Outcome::Unspecified,
+    };
+    pb::Trade {
+        trade_id: t.trade_id.to_string(),
+        market_id: t.market_id.clone(),
+        outcome: outcome.into(),
+        outcome_index: t.outcome.0 as u32,
+        trade_type: match t.trade_type {
+            TradeType::SECONDARY => pb::TradeType::Secondary,
+            TradeType::COMPLEMENTARY => pb::TradeType::Complementary,
+        }
+        .into(),
+        buyer_id: t.buyer_id.clone(),
+        seller_id: t.seller_id.clone(),
+        price: Some(price(spec, t.price)),
+        quantity: Some(quantity(spec, t.quantity)),
+        buyer_order_id: t.buyer_order_id.to_string(),
+        seller_order_id: t.seller_order_id.to_string(),
+        buyer_reservation_id: t.buyer_reservation_id.clone(),
+        seller_reservation_id: t.seller_reservation_id.clone(),
+        timestamp: Some(timestamp(t.timestamp)),
+    }
+}
+
+fn complementary_match_to_proto(spec: &MarketSpec, c: &trade::ComplementaryMatch) -> pb::ComplementaryMatch {
+    pb::ComplementaryMatch {
+        trade_id: c.trade_id.to_string(),
+        market_id: c.market_id.clone(),
+        yes_buyer_id: c.yes_buyer_id.clone(),
+        no_buyer_id: c.no_buyer_id.clone(),
+        yes_price: Some(price(spec, c.yes_price)),
+        no_price: Some(price(spec, c.no_price)),
+        quantity: Some(quantity(spec, c.quantity)),
+        yes_order_id: c.yes_order_id.to_string(),
+        no_order_id: c.no_order_id.to_string(),
+        yes_reservation_id: c.yes_reservation_id.clone(),
+        no_reservation_id: c.no_reservation_id.clone(),
+        timestamp: Some(timestamp(c.timestamp)),
+    }
+}
+
+fn complete_set_match_to_proto(spec: &MarketSpec, m: &trade::CompleteSetMatch) -> pb::CompleteSetMatch {
+    pb::CompleteSetMatch {
+        trade_id: m.trade_id.to_string(),
+        market_id: m.market_id.clone(),
+        quantity: Some(quantity(spec, m.quantity)),
+        legs: m
+            .legs
+            .iter()
+            .map(|leg| pb::MintLeg {
+                outcome_index: leg.outcome.0 as u32,
+                buyer_id: leg.buyer_id.clone(),
+                order_id: leg.order_id.to_string(),
+                reservation_id: leg.reservation_id.clone(),
+                price: Some(price(spec, leg.price)),
+            })
+            .collect(),
+        timestamp: Some(timestamp(m.timestamp)),
+    }
+}
+
+fn reject_reason_to_proto(reason: RejectReason) -> pb::RejectReason {
+    match reason {
+        RejectReason::SelfTrade => pb::RejectReason::SelfTrade,
+        RejectReason::UnknownMarket => pb::RejectReason::UnknownMarket,
+        RejectReason::MarketClosed => pb::RejectReason::MarketClosed,
+        RejectReason::InvalidOutcome => pb::RejectReason::InvalidOutcome,
+        RejectReason::InvalidQuantity => pb::RejectReason::InvalidQuantity,
+        RejectReason::InvalidPrice => pb::RejectReason::InvalidPrice,
+        RejectReason::PriceOutOfRange => pb::RejectReason::PriceOutOfRange,
+        RejectReason::MarketOrderInAuction => pb::RejectReason::MarketOrderInAuction,
+        RejectReason::DuplicateReservation => pb::RejectReason::DuplicateReservation,
+        RejectReason::ClientOrderIdReused => pb::RejectReason::ClientOrderIdReused,
+        RejectReason::MmpTriggered => pb::RejectReason::MmpTriggered,
+        RejectReason::UnknownSession => pb::RejectReason::UnknownSession,
+        RejectReason::MarketHalted => pb::RejectReason::MarketHalted,
+        RejectReason::PriceOutsideBand => pb::RejectReason::PriceOutsideBand,
+        RejectReason::RiskLimit => pb::RejectReason::RiskLimit,
+    }
+}
+
+/// outcome_index (categorical markets) wins over the YES/NO enum
+fn outcome_from_request(outcome: pb::Outcome, outcome_index: Option<u32>) -> Result<Outcome, String> {
+    match (outcome_index, outcome) {
+        (Some(index), _) => u8::try_from(index)
+            .map(Outcome)
+            .map_err(|_| format!("outcome_index {} is out of range", index)),
+        (None, pb::Outcome::Yes) => Ok(Outcome::YES),
+        (None, pb::Outcome::No) => Ok(Outcome::NO),
+        (None, pb::Outcome::Unspecified) => Err("outcome is required".to_string()),
+    }
+}
+
+fn side_to_proto(side: OrderSide) -> pb::Side {
+    match side {
+        OrderSide::BUY => pb::Side::Buy,
+        OrderSide::SELL => pb::Side::Sell,
+    }
+}
+
+// Binary markets only
+fn outcome_to_proto(outcome: Outcome) -> pb::Outcome {
+    match outcome {
+        Outcome::YES => pb::Outcome::Yes,
+        Outcome::NO => pb::Outcome::No,
+        _ => pb::Outcome::Unspecified,
+    }
+}
+
+fn price(spec: &MarketSpec, ticks: u64) -> pb::Decimal {
+    decimal_to_proto(spec.ticks_to_price(ticks))
+}
+
+fn quantity(spec: &MarketSpec, lots: u64) -> pb::Decimal {
+    decimal_to_proto(spec.lots_to_quantity(lots))
+}
+
+fn timestamp(ts: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: ts.timestamp(),
+        nanos: ts.timestamp_subsec_nanos() as i32,
+    }
+}
+
+// Tick and lot sizes are far coarser than 1e-9, so outbound values are exact
+fn decimal_to_proto(value: Decimal) -> pb::Decimal {
+    let units = value.trunc();
+    let nanos = ((value - units) * Decimal::from(NANOS_PER_UNIT)).trunc();
+    pb::Decimal {
+        units: units.to_i64().unwrap_or(if value.is_sign_negative() { i64::MIN } else { i64::MAX }),
+        nanos: nanos.to_i32().unwrap_or_default(),
+    }
+}
+
+fn decimal_from_proto(value: &pb::Decimal) -> Result<Decimal, String> {
+    let in_range = value.nanos.unsigned_abs() < NANOS_PER_UNIT as u32;
+    let same_sign = (value.units >= 0 && value.nanos >= 0) || (value.units <= 0 && value.nanos <= 0);
+    if !in_range || !same_sign {
+        return Err(format!("Invalid decimal: units={} nanos={}", value.units, value.nanos));
+    }
+    Ok(Decimal::from(value.units) + Decimal::new(value.nanos as i64, 9))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocation::Allocation;
+    use crate::bands::BandConfig;
+    use crate::config::Config;
+    use crate::corrections::{CorrectionError, CorrectionStore, TradeStatus};
+    use crate::db::CorrectionRow;
+    use crate::metrics::Metrics;
+    use crate::redis_client::RedisClient;
+    use dashmap::DashMap;
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+
+    // No database, so any market id opens a book and SELLs go unchecked
+    fn engine() -> Engine {
+        let config = Config {
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            database_url: None,
+            recover_orders: false,
+            grpc_port: 0,
+            metrics_port: 0,
+            tls_cert_path: None,
+            tls_key_path: None,
+            tls_client_ca_path: None,
+            client_certs: HashMap::new(),
+            auth_token_secret: None,
+            auth_disabled: false,
+            rate_limits: HashMap::new(),
+            health_check_interval_ms: 1000,
+            health_check_timeout_ms: 5000,
+            default_spec: MarketSpec::new(dec!(0.01), dec!(1)).unwrap(),
+            default_allocation: Allocation::FIFO,
+            client_order_id_ttl_secs: 600,
+            session_timeout_ms: 5000,
+            default_bands: BandConfig::default(),
+            trade_correction_window_secs: 3600,
+        };
+        let redis = Arc::new(RedisClient::new(&config.redis_url).unwrap());
+        let metrics = Arc::new(Metrics::new().unwrap());
+        Engine::new(Arc::new(DashMap::new()), redis, metrics, &config)
+    }
+
+    fn service() -> MatchingEngineService {
+        MatchingEngineService::new(Arc::new(engine()))
+    }
+
+    // trade_corrections in memory. Each write yields first, the way a round
+    // trip to the database would.
+    #[derive(Default)]
+    struct MemoryCorrections {
+        rows: std::sync::Mutex<Vec<CorrectionRow>>,
+        down: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl CorrectionStore for MemoryCorrections {
+        async fn insert(&self, row: &CorrectionRow) -> anyhow::Result<()> {
+            tokio::task::yield_now().await;
+            anyhow::ensure!(!self.down, "database is down");
+            self.rows.lock().unwrap().push(row.clone());
+            Ok(())
+        }
+
+        async fn delete(&self, id: &str) -> anyhow::Result<()> {
+            tokio::task::yield_now().await;
+            self.rows.lock().unwrap().retain(|row| row.id != id);
+            Ok(())
+        }
+
+        async fn load(&self, trade_id: &str) -> anyhow::Result<Vec<CorrectionRow>> {
+            Ok(self.rows.lock().unwrap().iter().filter(|row| row.trade_id == trade_id).cloned().collect())
+        }
+    }
+
+    fn buy(user_id: &str, price: Decimal) -> NewOrder {
+        NewOrder {
+            user_id: user_id.to_string(),
+            market_id: "m1".to_string(),
+            side: OrderSide::BUY,
+            outcome: Outcome::YES,
+            order_type: OrderType::LIMIT,
+            price,
+            quantity: dec!(10),
+            display_quantity: None,
+            reservation_id: None,
+            client_order_id: None,
+            session_id: None,
+            trigger: None,
+            reduce_only: false,
+        }
+    }
+
+    fn cancel(user_id: &str, order_id: Uuid) -> pb::CancelOrderRequest {
+        pb::CancelOrderRequest { user_id: user_id.to_string(), market_id: "m1".to_string(), order_id: order_id.to_string() }
+    }
+
+    #[test]
+    fn test_decimal_round_trip() {
+        for value in [dec!(0), dec!(0.6543), dec!(-1.5), dec!(12345.000001)] {
+            assert_eq!(decimal_from_proto(&decimal_to_proto(value)).unwrap(), value);
+        }
+        assert_eq!(decimal_to_proto(dec!(0.6543)), pb::Decimal { units: 0, nanos: 654_300_000 });
+        assert!(decimal_from_proto(&pb::Decimal { units: 1, nanos: -5 }).is_err());
+        assert!(decimal_from_proto(&pb::Decimal { units: 0, nanos: 1_000_000_000 }).is_err());
+    }
+
+    #[test]
+    fn test_unspecified_enum_is_invalid_request() {
+        let req = pb::PlaceOrderRequest {
+            user_id: "alice".to_string(),
+            market_id: "m1".to_string(),
+            side: pb::Side::Buy.into(),
+            outcome: pb::Outcome::Unspecified.into(),
+            order_type: pb::OrderType::Limit.into(),
+            price: Some(decimal_to_proto(dec!(0.5))),
+            quantity: Some(decimal_to_proto(dec!(10))),
+            ..Default::default()
+        };
+        assert_eq!(parse_order(&req).unwrap_err(), "outcome is required");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_checks_the_owner_and_takes_the_whole_group() {
+        let service = service();
+        let single = service.engine.place_order(buy("alice", dec!(0.20))).unwrap().order.order_id;
+        let oco = service.engine.place_oco([buy("alice", dec!(0.40)), buy("alice", dec!(0.30))]).unwrap();
+        let legs: Vec<Uuid> = oco.legs.iter().map(|leg| leg.order.order_id).collect();
+
+        // Someone else's order is as good as unknown
+        let refused = service.handle_cancel_order(cancel("bob", legs[0])).unwrap();
+        assert!(!refused.cancelled && refused.cancelled_order_ids.is_empty());
+
+        // The leg asked for comes first, then the rest of its group
+        let pulled = service.handle_cancel_order(cancel("alice", legs[1])).unwrap();
+        assert!(pulled.cancelled);
+        assert_eq!(pulled.cancelled_order_ids, vec![legs[1].to_string(), legs[0].to_string()]);
+        let book = service.engine.book("m1").unwrap();
+        assert!(book.read().unwrap().working_order(&legs[0]).is_none());
+        assert!(book.read().unwrap().working_order(&single).is_some());
+
+        let alone = service.handle_cancel_order(cancel("alice", single)).unwrap();
+        assert_eq!(alone.cancelled_order_ids, vec![single.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_a_reservation_backs_one_market_at_a_time() {
+        let service = service();
+        let reserved = |market_id: &str| NewOrder {
+            market_id: market_id.to_string(),
+            reservation_id: Some("r1".to_string()),
+            ..buy("alice", dec!(0.40))
+        };
+        let first = service.engine.place_order(reserved("m1")).unwrap().order.order_id;
+        match service.engine.place_order(reserved("m2")) {
+            Err(PlaceError::Rejected { reason, .. }) => assert_eq!(reason, RejectReason::DuplicateReservation),
+            other => panic!("Expected DuplicateReservation, got {:?}", other.map(|p| p.order.order_id)),
+        }
+
+        // Free again once its order has gone
+        service.handle_cancel_order(cancel("alice", first)).unwrap();
+        assert!(service.engine.place_order(reserved("m2")).is_ok());
+        service.engine.purge_reservations();
+        assert_eq!(service.engine.reservations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fired_triggers_are_queued_in_firing_order() {
+        let service = service();
+        let mut fired = service.engine.fired.take().unwrap();
+        let sell = |user_id: &str, price: Decimal, quantity: Decimal| NewOrder {
+            side: OrderSide::SELL,
+            quantity,
+            ..buy(user_id, price)
+        };
+        let stop = |user_id: &str, trigger: Decimal| NewOrder {
+            order_type: OrderType::MARKET,
+            reservation_id: Some(format!("{}-stop", user_id)),
+            trigger: Some((TriggerKind::STOP, trigger)),
+            ..sell(user_id, dec!(0.01), dec!(10))
+        };
+        service.engine.place_order(buy("bob", dec!(0.45))).unwrap();
+        service.engine.place_order(buy("carol", dec!(0.40))).unwrap();
+        service.engine.place_order(buy("dave", dec!(0.35))).unwrap();
+        service.engine.place_order(stop("alice", dec!(0.45))).unwrap();
+        service.engine.place_order(stop("frank", dec!(0.40))).unwrap();
+
+        // erin's print at 0.45 fires alice, whose sale down to 0.40 fires frank
+        let erin = NewOrder { reservation_id: Some("erin-sell".to_string()), ..sell("erin", dec!(0.45), dec!(5)) };
+        service.engine.place_order(erin).unwrap();
+
+        let mut queued = Vec::new();
+        while let Ok(message) = fired.try_recv() {
+            assert_eq!(message.channel, "triggers:m1");
+            let payload: serde_json::Value = serde_json::from_str(&message.payload).unwrap();
+            queued.push((payload["user_id"].as_str().unwrap().to_string(), payload["placed_by"].clone()));
+        }
+        assert_eq!(
+            queued,
+            vec![("alice".to_string(), "erin-sell".into()), ("frank".to_string(), "erin-sell".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_a_correction_is_stored_before_it_applies_and_removed_if_refused() {
+        let store = Arc::new(MemoryCorrections::default());
+        let mut engine = engine();
+        engine.corrections = Some(store.clone());
+        let sell = NewOrder { side: OrderSide::SELL, ..buy("alice", dec!(0.50)) };
+        engine.place_order(sell).unwrap();
+        let trade_id = engine.place_order(buy("bob", dec!(0.50))).unwrap().trades[0].trade_id;
+
+        // Both are checked against the trade as it was and stored; whichever
+        // reaches the book first wins, and the other's row is taken out
+        let (first, second) = tokio::join!(
+            engine.reprice_trade("m1", &trade_id, dec!(0.45), "ops", "stale quote"),
+            engine.reprice_trade("m1", &trade_id, dec!(0.40), "ops", "staler quote"),
+        );
+        let (record, lost) = match (first, second) {
+            (Ok((_, record)), Err(e)) | (Err(e), Ok((_, record))) => (record, e),
+            (first, second) => panic!("Expected one to win, got {:?} and {:?}", first.is_ok(), second.is_ok()),
+        };
+        assert_eq!(lost, CorrectionError::Changed(trade_id));
+        assert_eq!(record.status, TradeStatus::CORRECTED);
+        let stored = engine.stored_corrections(&trade_id).await.unwrap();
+        let ids: Vec<String> = stored.iter().map(|row| row.id.clone()).collect();
+        assert_eq!(ids, vec![record.last_correction().correction_id.to_string()]);
+
+        // Nothing takes effect unless it was stored
+        let mut engine = engine;
+        engine.corrections = Some(Arc::new(MemoryCorrections { down: true, ..Default::default() }));
+        let refused = engine.bust_trade("m1", &trade_id, "ops", "fat finger").await.unwrap_err();
+        assert!(matches!(refused, CorrectionError::Unavailable(_)));
+        let (_, record) = engine.trade_record("m1", &trade_id).unwrap();
+        assert_eq!((record.status, record.corrections.len()), (TradeStatus::CORRECTED, 1));
+    }
+}
@@ -0,0 +1,74 @@
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tonic::server::NamedService;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tracing::{error, info};
+
+use crate::grpc_server::matching_engine::matching_engine_server::MatchingEngineServer;
+use crate::grpc_server::MatchingEngineService;
+use crate::grpc_server_v2::matching_engine_v2::matching_engine_server::MatchingEngineServer as MatchingEngineV2Server;
+use crate::grpc_server_v2::MatchingEngineService as MatchingEngineV2Service;
+use crate::metrics::Metrics;
+use crate::orderbook::SharedOrderBook;
+
+/// Liveness watchdog behind the gRPC health service.
+///
+/// Every `interval` a blocking task takes a read lock on each book. If that
+/// probe can't finish within `timeout` (a matcher stuck holding a write lock)
+/// or finds a poisoned lock (a matcher panicked mid-update), the engine is
+/// reported NOT_SERVING until a later probe succeeds.
+pub async fn watch_books(
+    mut reporter: HealthReporter,
+    orderbooks: Arc<DashMap<String, SharedOrderBook>>,
+    metrics: Arc<Metrics>,
+    interval: Duration,
+    timeout: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    // A wedged probe is awaited again rather than piling up new blocking threads
+    let mut probe: Option<JoinHandle<bool>> = None;
+    let mut serving = true;
+
+    loop {
+        ticker.tick().await;
+
+        let handle = probe.get_or_insert_with(|| {
+            let books: Vec<SharedOrderBook> =
+                orderbooks.iter().map(|entry| entry.value().clone()).collect();
+            tokio::task::spawn_blocking(move || books.iter().all(|book| book.read().is_ok()))
+        });
+
+        let healthy = match tokio::time::timeout(timeout, handle).await {
+            Ok(result) => {
+                probe = None;
+                result.unwrap_or(false)
+            }
+            Err(_) => false,
+        };
+
+        metrics.engine_up.set(healthy as i64);
+        if healthy == serving {
+            continue;
+        }
+        serving = healthy;
+
+        let status = if healthy {
+            info!("Liveness probe recovered, reporting SERVING");
+            ServingStatus::Serving
+        } else {
+            error!("Liveness probe failed, reporting NOT_SERVING");
+            ServingStatus::NotServing
+        };
+        // "" is the whole-server status most orchestrators probe
+        reporter.set_service_status("", status).await;
+        reporter
+            .set_service_status(<MatchingEngineServer<MatchingEngineService> as NamedService>::NAME, status)
+            .await;
+        reporter
+            .set_service_status(<MatchingEngineV2Server<MatchingEngineV2Service> as NamedService>::NAME, status)
+            .await;
+    }
+}
@@ -0,0 +1,122 @@
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+/// What a second request with a key already seen should do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Claim<T> {
+    /// First time this key is seen; the caller must `complete` or `release` it
+    Fresh,
+    /// The original is still being processed
+    InFlight,
+    /// Same key, same request: hand back the original response
+    Replay(T),
+    /// Same key reused for a different request
+    Conflict,
+}
+
+struct Slot<T> {
+    claimed_at: Instant,
+    fingerprint: String,
+    response: Option<T>,
+}
+
+/// Responses remembered per `(user_id, client_order_id)` for `ttl`, so a
+/// retried PlaceOrder returns the original result instead of placing again
+pub struct IdempotencyCache<T> {
+    slots: DashMap<(String, String), Slot<T>>,
+    ttl: Duration,
+}
+
+impl<T: Clone> IdempotencyCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            slots: DashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Atomically claim `key`. `fingerprint` identifies the request body so a
+    /// reused client id on a different order is caught rather than replayed.
+    pub fn claim(&self, user_id: &str, client_id: &str, fingerprint: String) -> Claim<T> {
+        let now = Instant::now();
+        match self.slots.entry((user_id.to_string(), client_id.to_string())) {
+            Entry::Occupied(mut slot) if now.duration_since(slot.get().claimed_at) >= self.ttl => {
+                slot.insert(Slot { claimed_at: now, fingerprint, response: None });
+                Claim::Fresh
+            }
+            Entry::Occupied(slot) => {
+                let slot = slot.get();
+                if slot.fingerprint != fingerprint {
+                    Claim::Conflict
+                } else {
+                    match &slot.response {
+                        Some(response) => Claim::Replay(response.clone()),
+                        None => Claim::InFlight,
+                    }
+                }
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(Slot { claimed_at: now, fingerprint, response: None });
+                Claim::Fresh
+            }
+        }
+    }
+
+    /// Remember the response for a claimed key
+    pub fn complete(&self, user_id: &str, client_id: &str, response: T) {
+        if let Some(mut slot) = self.slots.get_mut(&(user_id.to_string(), client_id.to_string())) {
+            slot.response = Some(response);
+        }
+    }
+
+    /// Drop a claim whose request failed, so a retry is evaluated afresh
+    pub fn release(&self, user_id: &str, client_id: &str) {
+        self.slots.remove(&(user_id.to_string(), client_id.to_string()));
+    }
+
+    /// Forget everything older than the retention window
+    pub fn purge_expired(&self) {
+        let ttl = self.ttl;
+        self.slots.retain(|_, slot| slot.claimed_at.elapsed() < ttl);
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_replays_and_reuse_conflicts() {
+        let cache: IdempotencyCache<u32> = IdempotencyCache::new(Duration::from_secs(60));
+
+        assert_eq!(cache.claim("alice", "c1", "buy 10".into()), Claim::Fresh);
+        assert_eq!(cache.claim("alice", "c1", "buy 10".into()), Claim::InFlight);
+        cache.complete("alice", "c1", 7);
+        assert_eq!(cache.claim("alice", "c1", "buy 10".into()), Claim::Replay(7));
+        assert_eq!(cache.claim("alice", "c1", "buy 20".into()), Claim::Conflict);
+
+        // Keys are per user, and a released claim can be retried
+        assert_eq!(cache.claim("bob", "c1", "buy 10".into()), Claim::Fresh);
+        cache.release("bob", "c1");
+        assert_eq!(cache.claim("bob", "c1", "buy 10".into()), Claim::Fresh);
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let cache: IdempotencyCache<u32> = IdempotencyCache::new(Duration::ZERO);
+        assert_eq!(cache.claim("alice", "c1", "buy 10".into()), Claim::Fresh);
+        cache.complete("alice", "c1", 7);
+        assert_eq!(cache.claim("alice", "c1", "buy 10".into()), Claim::Fresh);
+        cache.purge_expired();
+        assert!(cache.is_empty());
+    }
+}
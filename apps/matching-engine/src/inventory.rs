@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use crate::order::{Lots, Outcome};
+
+/// Tokens each user holds per outcome in one market, and how much of that
+/// already backs working SELL orders, resting or held for a trigger. Seeded from the `positions` table and
+/// kept current from the engine's own fills.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    /// SELLs beyond what is available are only refused when this is set, i.e.
+    /// when positions were loaded at startup
+    pub enforced: bool,
+    held: HashMap<(String, Outcome), Lots>,
+    committed: HashMap<(String, Outcome), Lots>,
+}
+
+impl Inventory {
+    pub fn held(&self, user_id: &str, outcome: Outcome) -> Lots {
+        self.held.get(&(user_id.to_string(), outcome)).copied().unwrap_or(0)
+    }
+
+    /// Tokens offered by working SELLs
+    pub fn committed(&self, user_id: &str, outcome: Outcome) -> Lots {
+        self.committed.get(&(user_id.to_string(), outcome)).copied().unwrap_or(0)
+    }
+
+    /// Held tokens not already offered by a working SELL
+    pub fn available(&self, user_id: &str, outcome: Outcome) -> Lots {
+        self.held(user_id, outcome).saturating_sub(self.committed(user_id, outcome))
+    }
+
+    /// Replace a user's holding, e.g. from the database
+    pub fn set(&mut self, user_id: &str, outcome: Outcome, quantity: Lots) {
+        self.held.insert((user_id.to_string(), outcome), quantity);
+    }
+
+    pub fn bought(&mut self, user_id: &str, outcome: Outcome, quantity: Lots) {
+        *self.held.entry((user_id.to_string(), outcome)).or_default() += quantity;
+    }
+
+    /// A holding stops at zero rather than going short
+    pub fn sold(&mut self, user_id: &str, outcome: Outcome, quantity: Lots) {
+        if let Some(held) = self.held.get_mut(&(user_id.to_string(), outcome)) {
+            *held = held.saturating_sub(quantity);
+        }
+    }
+
+    /// A SELL has started resting in the book or waiting for its trigger
+    pub fn commit(&mut self, user_id: &str, outcome: Outcome, quantity: Lots) {
+        *self.committed.entry((user_id.to_string(), outcome)).or_default() += quantity;
+    }
+
+    /// A working SELL has filled, shrunk, fired or been cancelled
+    pub fn release(&mut self, user_id: &str, outcome: Outcome, quantity: Lots) {
+        let key = (user_id.to_string(), outcome);
+        if let Some(committed) = self.committed.get_mut(&key) {
+            *committed = committed.saturating_sub(quantity);
+            if *committed == 0 {
+                self.committed.remove(&key);
+            }
+        }
+    }
+}
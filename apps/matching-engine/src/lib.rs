@@ -0,0 +1,31 @@
+pub mod allocation;
+pub mod auction;
+pub mod config;
+pub mod engine;
+pub mod market_spec;
+pub mod marketdata;
+pub mod order;
+pub mod orderbook;
+pub mod matcher;
+pub mod trade;
+pub mod redis_client;
+pub mod grpc_server;
+pub mod grpc_server_v2;
+pub mod health;
+pub mod idempotency;
+pub mod reservations;
+pub mod metrics;
+pub mod mmp;
+pub mod session;
+pub mod triggers;
+pub mod groups;
+pub mod bands;
+pub mod inventory;
+pub mod db;
+pub mod recovery;
+pub mod registry;
+pub mod executions;
+pub mod fired;
+pub mod auth;
+pub mod ratelimit;
+pub mod corrections;
@@ -4,18 +4,11 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use tracing::info;
 
-mod config;
-mod order;
-mod orderbook;
-mod matcher;
-mod trade;
-mod redis_client;
-mod grpc_server;
-
-use config::Config;
-use orderbook::OrderBook;
-use redis_client::RedisClient;
-use grpc_server::start_grpc_server;
+use matching_engine::config::Config;
+use matching_engine::metrics::{start_metrics_server, Metrics};
+use matching_engine::orderbook::SharedOrderBook;
+use matching_engine::redis_client::RedisClient;
+use matching_engine::grpc_server::start_grpc_server;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -35,7 +28,11 @@ async fn main() -> Result<()> {
     info!("✅ Redis connected: {}", config.redis_url);
     
     // Create orderbooks (shared state)
-    let orderbooks: Arc<DashMap<String, Arc<OrderBook>>> = Arc::new(DashMap::new());
+    let orderbooks: Arc<DashMap<String, SharedOrderBook>> = Arc::new(DashMap::new());
+    
+    let metrics = Arc::new(Metrics::new()?);
+    let metrics_addr: SocketAddr = format!("0.0.0.0:{}", config.metrics_port).parse()?;
+    tokio::spawn(start_metrics_server(metrics_addr, metrics.clone()));
     
     info!("✅ Matching engine ready");
     
@@ -43,7 +40,7 @@ async fn main() -> Result<()> {
     let addr: SocketAddr = format!("0.0.0.0:{}", config.grpc_port).parse()?;
     info!("🌐 gRPC server starting on {}", addr);
     
-    start_grpc_server(addr, orderbooks, redis).await?;
+    start_grpc_server(addr, orderbooks, redis, metrics, &config).await?;
     
     Ok(())
 }
\ No newline at end of file
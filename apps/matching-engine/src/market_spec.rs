@@ -0,0 +1,143 @@
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use thiserror::Error;
+
+use crate::order::{Lots, Ticks};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SpecError {
+    #[error("price {0} is not a multiple of tick size {1}")]
+    OffTick(Decimal, Decimal),
+    #[error("quantity {0} is not a multiple of lot size {1}")]
+    OffLot(Decimal, Decimal),
+    #[error("{0} is out of range")]
+    OutOfRange(Decimal),
+    #[error("tick size {0} must be positive and divide 1")]
+    InvalidTickSize(Decimal),
+    #[error("lot size {0} must be positive")]
+    InvalidLotSize(Decimal),
+}
+
+/// Price/quantity grid of a market.
+///
+/// Decimal prices and quantities are converted to integer ticks and lots once,
+/// at the gRPC edge. Everything behind that boundary works on `u64`, and only
+/// outbound messages are converted back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketSpec {
+    pub tick_size: Decimal,
+    pub lot_size: Decimal,
+}
+
+impl Default for MarketSpec {
+    // Matches the DB columns: price Decimal(5, 4), quantity Decimal(20, 6)
+    fn default() -> Self {
+        Self {
+            tick_size: dec!(0.0001),
+            lot_size: dec!(0.000001),
+        }
+    }
+}
+
+impl MarketSpec {
+    pub fn new(tick_size: Decimal, lot_size: Decimal) -> Result<Self, SpecError> {
+        // 1.00 must be a whole number of ticks so YES + NO = 1 stays exact
+        if tick_size <= Decimal::ZERO || !(Decimal::ONE % tick_size).is_zero() {
+            return Err(SpecError::InvalidTickSize(tick_size));
+        }
+        if lot_size <= Decimal::ZERO {
+            return Err(SpecError::InvalidLotSize(lot_size));
+        }
+        Ok(Self { tick_size, lot_size })
+    }
+
+    /// Number of ticks in a price of 1.00 (a complete YES + NO pair)
+    pub fn one(&self) -> Ticks {
+        (Decimal::ONE / self.tick_size)
+            .to_u64()
+            .expect("tick size validated to divide 1")
+    }
+
+    pub fn price_to_ticks(&self, price: Decimal) -> Result<Ticks, SpecError> {
+        let ticks = Self::to_units(price, self.tick_size)
+            .ok_or(SpecError::OffTick(price, self.tick_size))?;
+        ticks.to_u64().ok_or(SpecError::OutOfRange(price))
+    }
+
+    pub fn ticks_to_price(&self, ticks: Ticks) -> Decimal {
+        (Decimal::from(ticks) * self.tick_size).normalize()
+    }
+
+    pub fn quantity_to_lots(&self, quantity: Decimal) -> Result<Lots, SpecError> {
+        let lots = Self::to_units(quantity, self.lot_size)
+            .ok_or(SpecError::OffLot(quantity, self.lot_size))?;
+        lots.to_u64().ok_or(SpecError::OutOfRange(quantity))
+    }
+
+    pub fn lots_to_quantity(&self, lots: Lots) -> Decimal {
+        (Decimal::from(lots) * self.lot_size).normalize()
+    }
+
+    // Exact division; None when the value is not on the grid
+    fn to_units(value: Decimal, unit: Decimal) -> Option<Decimal> {
+        let units = value.checked_div(unit)?;
+        units.fract().is_zero().then_some(units)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_every_price_tick_round_trips() {
+        let spec = MarketSpec::default();
+        assert_eq!(spec.one(), 10_000);
+
+        for ticks in 0..=spec.one() {
+            let price = spec.ticks_to_price(ticks);
+            assert_eq!(spec.price_to_ticks(price), Ok(ticks));
+        }
+    }
+
+    #[test]
+    fn test_wire_strings_round_trip_exactly() {
+        let spec = MarketSpec::default();
+
+        for s in ["0", "0.5", "0.55", "0.5500", "0.0001", "0.9999", "1", "1.0"] {
+            let price = Decimal::from_str(s).unwrap();
+            let back = spec.ticks_to_price(spec.price_to_ticks(price).unwrap());
+            assert_eq!(back, price, "price {}", s);
+        }
+
+        for s in ["0.000001", "1", "100", "123456.789012", "18446744073709.551615"] {
+            let quantity = Decimal::from_str(s).unwrap();
+            let lots = spec.quantity_to_lots(quantity).unwrap();
+            assert_eq!(spec.lots_to_quantity(lots), quantity, "quantity {}", s);
+            assert_eq!(spec.lots_to_quantity(lots).to_string(), s.trim_end_matches(".0"));
+        }
+    }
+
+    #[test]
+    fn test_off_grid_and_out_of_range_are_rejected() {
+        let spec = MarketSpec::default();
+
+        assert!(matches!(spec.price_to_ticks(dec!(0.55001)), Err(SpecError::OffTick(..))));
+        assert!(matches!(spec.quantity_to_lots(dec!(1.0000001)), Err(SpecError::OffLot(..))));
+        assert!(matches!(spec.price_to_ticks(dec!(-0.5)), Err(SpecError::OutOfRange(_))));
+        assert!(matches!(
+            spec.quantity_to_lots(dec!(18446744073709.551616)),
+            Err(SpecError::OutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_tick_size_must_divide_one() {
+        assert!(MarketSpec::new(dec!(0.01), dec!(1)).is_ok());
+        assert!(MarketSpec::new(dec!(0.03), dec!(1)).is_err());
+        assert!(MarketSpec::new(dec!(0), dec!(1)).is_err());
+        assert!(MarketSpec::new(dec!(0.01), dec!(0)).is_err());
+    }
+}
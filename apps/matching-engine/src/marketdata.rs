@@ -0,0 +1,339 @@
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::auction::TradingPhase;
+use crate::market_spec::MarketSpec;
+use crate::order::{Lots, Order, OrderSide, Outcome, Ticks};
+use crate::orderbook::OrderBook;
+use crate::trade::{CompleteSetMatch, ComplementaryMatch, Trade};
+
+// Updates a subscriber may fall behind by before its stream is ended
+const SUBSCRIBER_BUFFER: usize = 4096;
+
+/// A trade as the public sees it: no users, no order ids
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Print {
+    pub trade_id: Uuid,
+    pub outcome: Outcome,
+    pub price: Ticks,
+    pub quantity: Lots,
+    /// Which way the incoming order traded in this outcome's book; None for
+    /// an auction uncross
+    pub taker_side: Option<OrderSide>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// One print per outcome traded, mints first, in the order they executed.
+/// An incoming BUY that mints against resting BUYs of the other outcomes
+/// hits their bids, so those prints are SELLs.
+pub fn public_trades(
+    trades: &[Trade],
+    complementary: &[ComplementaryMatch],
+    complete_sets: &[CompleteSetMatch],
+    taker: Option<&Order>,
+) -> Vec<Print> {
+    let taker_side = |outcome: Outcome| {
+        taker.map(|order| match order.outcome == outcome {
+            true => order.side,
+            false => OrderSide::SELL,
+        })
+    };
+    let mut prints = Vec::new();
+    for cmatch in complementary {
+        for (outcome, price) in [(Outcome::YES, cmatch.yes_price), (Outcome::NO, cmatch.no_price)] {
+            prints.push(Print {
+                trade_id: cmatch.trade_id,
+                outcome,
+                price,
+                quantity: cmatch.quantity,
+                taker_side: taker_side(outcome),
+                timestamp: cmatch.timestamp,
+            });
+        }
+    }
+    for set in complete_sets {
+        prints.extend(set.legs.iter().map(|leg| Print {
+            trade_id: set.trade_id,
+            outcome: leg.outcome,
+            price: leg.price,
+            quantity: set.quantity,
+            taker_side: taker_side(leg.outcome),
+            timestamp: set.timestamp,
+        }));
+    }
+    prints.extend(trades.iter().map(|trade| Print {
+        trade_id: trade.trade_id,
+        outcome: trade.outcome,
+        price: trade.price,
+        quantity: trade.quantity,
+        taker_side: taker.map(|order| order.side),
+        timestamp: trade.timestamp,
+    }));
+    prints
+}
+
+/// What happened to a resting order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderEventKind {
+    ADDED,
+    REDUCED,
+    FILLED,
+    /// Every order leaves the book this way, whether cancelled, filled or
+    /// reduced to nothing
+    REMOVED,
+}
+
+/// One change to one resting order, as the public sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderEvent {
+    pub kind: OrderEventKind,
+    /// Stands in for the order id. An iceberg's refreshed slice gets a new
+    /// one, so slices can't be tied together.
+    pub public_id: u64,
+    pub side: OrderSide,
+    pub outcome: Outcome,
+    pub price: Ticks,
+    /// Displayed quantity left afterwards
+    pub quantity: Lots,
+    /// What this event took off the displayed quantity
+    pub change: Lots,
+}
+
+/// A resting order in the level-3 snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicOrder {
+    pub public_id: u64,
+    pub side: OrderSide,
+    pub outcome: Outcome,
+    pub price: Ticks,
+    pub quantity: Lots,
+}
+
+/// What the book has done since the engine last published it
+#[derive(Debug, Default)]
+pub struct Journal {
+    /// Levels that may look different now
+    pub touched: Vec<(OrderSide, Outcome, Ticks)>,
+    /// In the order they happened
+    pub orders: Vec<OrderEvent>,
+    /// In the order they executed
+    pub prints: Vec<Print>,
+}
+
+/// One price level as clients see it. A quantity of 0 means the level has
+/// emptied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Level {
+    pub side: OrderSide,
+    pub outcome: Outcome,
+    pub price: Ticks,
+    /// Displayed quantity; iceberg reserves are never shown
+    pub quantity: Lots,
+    pub order_count: usize,
+}
+
+/// Everything one write to a book did to its public view
+#[derive(Debug, Clone)]
+pub struct MarketUpdate {
+    /// One more than the update before it
+    pub seq: u64,
+    pub spec: MarketSpec,
+    pub levels: Vec<Level>,
+    pub orders: Vec<OrderEvent>,
+    pub prints: Vec<Print>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Every resting order as of update `seq`
+#[derive(Debug, Clone)]
+pub struct OrderSnapshot {
+    pub seq: u64,
+    pub spec: MarketSpec,
+    pub outcome_count: usize,
+    /// Per outcome, bids best price first then asks, each level in time
+    /// priority
+    pub orders: Vec<PublicOrder>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// The whole visible book as of update `seq`
+#[derive(Debug, Clone)]
+pub struct MarketSnapshot {
+    pub seq: u64,
+    pub spec: MarketSpec,
+    pub outcome_count: usize,
+    pub phase: TradingPhase,
+    pub levels: Vec<Level>,
+    pub timestamp: DateTime<Utc>,
+}
+
+struct Feed {
+    seq: u64,
+    sender: broadcast::Sender<Arc<MarketUpdate>>,
+}
+
+impl Default for Feed {
+    fn default() -> Self {
+        Self { seq: 0, sender: broadcast::channel(SUBSCRIBER_BUFFER).0 }
+    }
+}
+
+/// Per-market fan-out of book changes, order events and public trades, all
+/// in one sequence
+#[derive(Default)]
+pub struct MarketDataHub {
+    feeds: DashMap<String, Feed>,
+}
+
+impl MarketDataHub {
+    /// Send out what changed in `book` since the last call. Call it with the
+    /// write lock still held so updates go out in the order they happened.
+    pub fn publish(&self, book: &mut OrderBook) {
+        let Journal { touched, orders, prints } = book.take_journal();
+        if touched.is_empty() && orders.is_empty() && prints.is_empty() {
+            return;
+        }
+        let mut seen = HashSet::new();
+        let levels = touched
+            .into_iter()
+            .filter(|key| seen.insert(*key))
+            .map(|(side, outcome, price)| {
+                let (quantity, order_count) = book.level(side, outcome, price).map_or((0, 0), |l| (l.quantity, l.order_count));
+                Level { side, outcome, price, quantity, order_count }
+            })
+            .collect();
+
+        let mut feed = self.feeds.entry(book.market_id.clone()).or_default();
+        feed.seq += 1;
+        let update = MarketUpdate { seq: feed.seq, spec: book.spec, levels, orders, prints, timestamp: Utc::now() };
+        // Nobody listening is fine; the sequence still moves on
+        let _ = feed.sender.send(Arc::new(update));
+    }
+
+    /// A snapshot of `book` and every update after it. Call it with the book
+    /// locked so nothing can slip in between the two.
+    pub fn subscribe(&self, book: &OrderBook) -> (MarketSnapshot, broadcast::Receiver<Arc<MarketUpdate>>) {
+        let feed = self.feeds.entry(book.market_id.clone()).or_default();
+        let mut levels = Vec::new();
+        for outcome in book.outcomes() {
+            let depth = book.get_depth(outcome, usize::MAX);
+            for (side, side_levels) in [(OrderSide::BUY, depth.bids), (OrderSide::SELL, depth.asks)] {
+                levels.extend(side_levels.into_iter().map(|l| Level {
+                    side,
+                    outcome,
+                    price: l.price,
+                    quantity: l.quantity,
+                    order_count: l.order_count,
+                }));
+            }
+        }
+        let snapshot = MarketSnapshot {
+            seq: feed.seq,
+            spec: book.spec,
+            outcome_count: book.outcome_count(),
+            phase: book.phase,
+            levels,
+            timestamp: Utc::now(),
+        };
+        (snapshot, feed.sender.subscribe())
+    }
+
+    /// Every resting order in `book`, numbered with the update it follows.
+    /// Call it with the book locked.
+    pub fn order_snapshot(&self, book: &OrderBook) -> OrderSnapshot {
+        let seq = self.feeds.get(&book.market_id).map_or(0, |feed| feed.seq);
+        OrderSnapshot {
+            seq,
+            spec: book.spec,
+            outcome_count: book.outcome_count(),
+            orders: book.public_orders(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Every update from now on, for the level-3 feed
+    pub fn updates(&self, market_id: &str) -> broadcast::Receiver<Arc<MarketUpdate>> {
+        self.feeds.entry(market_id.to_string()).or_default().sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::Matcher;
+    use crate::order::OrderType;
+
+    #[test]
+    fn test_updates_follow_snapshot_with_levels_and_prints() {
+        let mut book = OrderBook::new("market_test".to_string(), MarketSpec::default());
+        let hub = MarketDataHub::default();
+        Matcher::new(&mut book).place_order(Order::limit("a", OrderSide::SELL, Outcome::YES, 6000, 30)).unwrap();
+        Matcher::new(&mut book).place_order(Order::limit("b", OrderSide::BUY, Outcome::NO, 3000, 20)).unwrap();
+        hub.publish(&mut book);
+
+        let (snapshot, mut updates) = hub.subscribe(&book);
+        assert_eq!(snapshot.seq, 1);
+        assert_eq!(snapshot.levels.len(), 2);
+
+        // Lifts part of the ask, then mints against the NO bid
+        Matcher::new(&mut book).place_order(Order::limit("c", OrderSide::BUY, Outcome::YES, 7000, 40)).unwrap();
+        hub.publish(&mut book);
+        let update = updates.try_recv().unwrap();
+        assert_eq!(update.seq, 2);
+
+        let sides: Vec<(Outcome, Option<OrderSide>, Lots)> =
+            update.prints.iter().map(|p| (p.outcome, p.taker_side, p.quantity)).collect();
+        assert_eq!(
+            sides,
+            vec![
+                (Outcome::YES, Some(OrderSide::BUY), 20),
+                (Outcome::NO, Some(OrderSide::SELL), 20),
+                (Outcome::YES, Some(OrderSide::BUY), 20),
+            ]
+        );
+        let level = |side, outcome| update.levels.iter().find(|l| l.side == side && l.outcome == outcome).copied();
+        assert_eq!(level(OrderSide::BUY, Outcome::NO).map(|l| l.quantity), Some(0));
+        assert_eq!(level(OrderSide::SELL, Outcome::YES).map(|l| l.quantity), Some(10));
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_order_events_follow_queue_and_hide_iceberg_slices() {
+        let mut book = OrderBook::new("market_test".to_string(), MarketSpec::default());
+        let hub = MarketDataHub::default();
+        let mut iceberg = Order::limit("a", OrderSide::SELL, Outcome::YES, 6000, 30);
+        iceberg.order_type = OrderType::ICEBERG;
+        iceberg.display_quantity = Some(10);
+        Matcher::new(&mut book).place_order(iceberg).unwrap();
+        Matcher::new(&mut book).place_order(Order::limit("b", OrderSide::SELL, Outcome::YES, 6000, 5)).unwrap();
+        hub.publish(&mut book);
+
+        let snapshot = hub.order_snapshot(&book);
+        let queue = |orders: &[PublicOrder]| orders.iter().map(|o| (o.public_id, o.quantity)).collect::<Vec<_>>();
+        assert_eq!((snapshot.seq, queue(&snapshot.orders)), (1, vec![(1, 10), (2, 5)]));
+
+        let mut updates = hub.updates("market_test");
+        Matcher::new(&mut book).place_order(Order::limit("c", OrderSide::BUY, Outcome::YES, 6000, 12)).unwrap();
+        hub.publish(&mut book);
+        let update = updates.try_recv().unwrap();
+        let events: Vec<(OrderEventKind, u64, Lots, Lots)> =
+            update.orders.iter().map(|e| (e.kind, e.public_id, e.quantity, e.change)).collect();
+        assert_eq!(
+            events,
+            vec![
+                (OrderEventKind::FILLED, 1, 0, 10),
+                (OrderEventKind::REMOVED, 1, 0, 0),
+                (OrderEventKind::ADDED, 3, 10, 0),
+                (OrderEventKind::FILLED, 2, 3, 2),
+            ]
+        );
+        assert_eq!(update.prints.len(), 2);
+
+        let snapshot = hub.order_snapshot(&book);
+        assert_eq!((snapshot.seq, queue(&snapshot.orders)), (update.seq, vec![(2, 3), (3, 10)]));
+    }
+}
@@ -1,24 +1,348 @@
-use anyhow::{Context, Result};
-use chrono::Utc;
-use rust_decimal::Decimal;
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::time::Instant;
+use thiserror::Error;
 use tracing::{info, warn};
 use uuid::Uuid;
 
-use crate::order::{Order, OrderSide, OrderStatus, OrderType, Outcome};
-use crate::orderbook::OrderBook;
-use crate::trade::{ComplementaryMatch, Trade, TradeType};
+use crate::allocation::Allocation;
+use crate::auction::{AuctionBook, ClearingPrice, TradingPhase};
+use crate::bands::{BreakerAction, BreakerTripped};
+use crate::groups::{GroupAction, HeldExits};
+use crate::marketdata::public_trades;
+use crate::mmp::MmpTriggered;
+use crate::order::{Lots, Order, OrderSide, OrderStatus, OrderType, Outcome, Ticks};
+use crate::orderbook::{OrderBook, OrderHandle};
+use crate::trade::{CompleteSetMatch, ComplementaryMatch, Trade, TradeType};
+use crate::triggers::{Conditional, Trigger};
 
-pub struct Matcher {
-    orderbook: OrderBook,
+pub struct Matcher<'a> {
+    orderbook: &'a mut OrderBook,
+    mmp_triggered: Vec<MmpTriggered>,
+    breakers_tripped: Vec<BreakerTripped>,
 }
 
-impl Matcher {
-    pub fn new(orderbook: OrderBook) -> Self {
-        Self { orderbook }
+impl<'a> Matcher<'a> {
+    pub fn new(orderbook: &'a mut OrderBook) -> Self {
+        Self {
+            orderbook,
+            mmp_triggered: Vec::new(),
+            breakers_tripped: Vec::new(),
+        }
     }
     
-    /// Main entry point: place an order and try to match
-    pub fn place_order(&self, mut order: Order) -> Result<MatchResult> {
+    /// Main entry point: place an order and try to match, then work through
+    /// whatever its fills set off
+    pub fn place_order(&mut self, mut order: Order) -> Result<MatchResult> {
+        self.check_position(&mut order)?;
+        let mut result = self.place(order)?;
+        let fills = fills_of(&result.trades, &result.complementary_matches, &result.complete_set_matches);
+        (result.triggered, result.cancelled) = self.follow_up(fills);
+        Ok(result)
+    }
+
+    /// Hold an order in the trigger book until the last trade price crosses
+    /// `trigger`. It fires straight away if the last price already has.
+    pub fn place_conditional(&mut self, mut order: Order, trigger: Trigger) -> Result<MatchResult> {
+        self.check_position(&mut order)?;
+        self.validate_order(&order)?;
+        self.validate_trigger(&trigger)?;
+
+        order.order_status = OrderStatus::UNTRIGGERED;
+        let seq = self.orderbook.hold(order.clone(), trigger);
+        info!(
+            "Holding {} {:?} {:?} until {} @ {} (seq {})",
+            order.order_id, order.side, order.outcome, trigger.kind, trigger.price, seq
+        );
+
+        let mut result = MatchResult::untouched(order);
+        (result.triggered, result.cancelled) = self.follow_up(Vec::new());
+        Ok(result)
+    }
+
+    /// Place two orders, either of which may be conditional, so that the
+    /// first fill on one cancels the other. Both are validated before either
+    /// goes in; if the second is still refused, the first is pulled again.
+    pub fn place_oco(&mut self, mut legs: [(Order, Option<Trigger>); 2]) -> Result<GroupResult> {
+        for (order, trigger) in &mut legs {
+            // Only one leg can fill, so each may use the whole position
+            self.check_position(order)?;
+            self.validate_order(order)?;
+            if let Some(trigger) = trigger {
+                self.validate_trigger(trigger)?;
+            }
+        }
+        if legs[0].0.reservation_id.is_some() && legs[0].0.reservation_id == legs[1].0.reservation_id {
+            return Err(RejectReason::DuplicateReservation.into());
+        }
+
+        let group_id = self.orderbook.groups.add_oco(&legs[0].0.user_id, [&legs[0].0, &legs[1].0]);
+        let mut results: Vec<MatchResult> = Vec::new();
+        for (mut order, trigger) in legs {
+            if self.orderbook.groups.group_of(&order.order_id).is_none() {
+                // The first leg has already filled
+                order.order_status = OrderStatus::CANCELLED;
+                results.push(MatchResult::untouched(order));
+                continue;
+            }
+            match self.place_leg(order.clone(), trigger) {
+                Ok(result) => results.push(result),
+                Err(e) if results.is_empty() => {
+                    self.orderbook.groups.cancel(&group_id);
+                    return Err(e);
+                }
+                Err(e) => {
+                    warn!("OCO leg {} refused after its partner was placed: {}", order.order_id, e);
+                    let pulled = self.orderbook.cancel_group(&group_id);
+                    results[0].cancelled.extend(pulled);
+                    order.order_status = OrderStatus::CANCELLED;
+                    results.push(MatchResult::untouched(order));
+                }
+            }
+        }
+
+        Ok(GroupResult { group_id, legs: results })
+    }
+
+    /// Place an entry order with a take-profit and a stop that are held back
+    /// until the entry has completely filled. The take-profit then rests in
+    /// the book and the stop waits in the trigger book, each sized to the open
+    /// position. The exits come back as submitted; once released they are
+    /// reported in the `triggered` of whatever filled the entry.
+    pub fn place_bracket(
+        &mut self,
+        entry: (Order, Option<Trigger>),
+        mut take_profit: Order,
+        mut stop: Order,
+        stop_trigger: Trigger,
+    ) -> Result<GroupResult> {
+        let (mut entry, entry_trigger) = entry;
+        self.check_position(&mut entry)?;
+        self.validate_order(&entry)?;
+        if let Some(trigger) = &entry_trigger {
+            self.validate_trigger(trigger)?;
+        }
+        self.validate_order(&take_profit)?;
+        self.validate_order(&stop)?;
+        self.validate_trigger(&stop_trigger)?;
+        let reservations: Vec<&String> =
+            [&entry.reservation_id, &take_profit.reservation_id, &stop.reservation_id].into_iter().flatten().collect();
+        if (1..reservations.len()).any(|i| reservations[..i].contains(&reservations[i])) {
+            return Err(RejectReason::DuplicateReservation.into());
+        }
+
+        take_profit.order_status = OrderStatus::UNTRIGGERED;
+        stop.order_status = OrderStatus::UNTRIGGERED;
+        let group_id = self.orderbook.groups.add_bracket(
+            &entry.user_id,
+            &entry,
+            take_profit.clone(),
+            stop.clone(),
+            stop_trigger,
+        );
+        let entry = match self.place_leg(entry, entry_trigger) {
+            Ok(result) => result,
+            Err(e) => {
+                self.orderbook.groups.cancel(&group_id);
+                return Err(e);
+            }
+        };
+
+        Ok(GroupResult {
+            group_id,
+            legs: vec![entry, MatchResult::untouched(take_profit), MatchResult::untouched(stop)],
+        })
+    }
+
+    fn place_leg(&mut self, order: Order, trigger: Option<Trigger>) -> Result<MatchResult> {
+        match trigger {
+            Some(trigger) => self.place_conditional(order, trigger),
+            None => self.place_order(order),
+        }
+    }
+
+    /// Work through what a placement set off until nothing more happens.
+    /// Grouped orders react to each fill first (OCO partners are cancelled,
+    /// bracket exits shrink or are released), then elected stop/take-profit
+    /// orders fire one at a time in sequence order. Anything their fills set
+    /// off queues behind, so a replay of the same input gives the same result.
+    fn follow_up(&mut self, fills: Vec<(Uuid, Lots)>) -> (Vec<FiredTrigger>, Vec<Order>) {
+        let mut fills: VecDeque<(Uuid, Lots)> = fills.into();
+        let mut elected: VecDeque<Conditional> = VecDeque::new();
+        let mut fired = Vec::new();
+        let mut cancelled = Vec::new();
+
+        loop {
+            if let Some((order_id, quantity)) = fills.pop_front() {
+                for action in self.orderbook.groups.on_fill(order_id, quantity) {
+                    match action {
+                        GroupAction::Cancel(id) => {
+                            cancelled.extend(self.orderbook.cancel(id).or_else(|| take_elected(&mut elected, id)));
+                        }
+                        GroupAction::Reduce(id, quantity) => {
+                            let reduced = self
+                                .orderbook
+                                .reduce(id, quantity)
+                                .or_else(|| self.orderbook.reduce_held(&id, quantity))
+                                .or_else(|| reduce_elected(&mut elected, id, quantity));
+                            if let Some(mut order) = reduced.filter(|o| o.remaining() == 0) {
+                                order.order_status = OrderStatus::CANCELLED;
+                                cancelled.push(order);
+                            }
+                        }
+                        GroupAction::Activate(held) => {
+                            let HeldExits { mut take_profit, mut stop, stop_trigger } = *held;
+                            info!("Bracket entry filled, releasing {} and {}", take_profit.order_id, stop.order_id);
+                            match self.check_position(&mut stop) {
+                                Ok(()) => {
+                                    self.orderbook.hold(stop, stop_trigger);
+                                }
+                                Err(e) => {
+                                    warn!("Stop {} was rejected: {}", stop.order_id, e);
+                                    fired.push(FiredTrigger { trigger: Some(stop_trigger), order: stop, result: Err(e) });
+                                }
+                            }
+                            let result = self.check_position(&mut take_profit).and_then(|()| self.place(take_profit.clone()));
+                            match &result {
+                                Ok(r) => fills.extend(fills_of(&r.trades, &r.complementary_matches, &r.complete_set_matches)),
+                                Err(e) => warn!("Take-profit {} was rejected: {}", take_profit.order_id, e),
+                            }
+                            fired.push(FiredTrigger { trigger: None, order: take_profit, result });
+                        }
+                    }
+                }
+                continue;
+            }
+
+            if self.orderbook.phase == TradingPhase::AUCTION || self.orderbook.bands.is_halted(Instant::now()) {
+                break;
+            }
+            elected.extend(self.orderbook.elect());
+            let Some(mut conditional) = elected.pop_front() else {
+                break;
+            };
+            info!(
+                "Trigger {} @ {} elected {} (seq {})",
+                conditional.trigger.kind, conditional.trigger.price, conditional.order.order_id, conditional.seq
+            );
+            // The position may have changed while it was held
+            let result = self
+                .check_position(&mut conditional.order)
+                .and_then(|()| self.place(conditional.order.clone()));
+            match &result {
+                Ok(r) => fills.extend(fills_of(&r.trades, &r.complementary_matches, &r.complete_set_matches)),
+                Err(e) => warn!("Elected order {} was rejected: {}", conditional.order.order_id, e),
+            }
+            fired.push(FiredTrigger {
+                trigger: Some(conditional.trigger),
+                order: conditional.order,
+                result,
+            });
+        }
+
+        (fired, cancelled)
+    }
+
+    fn validate_trigger(&self, trigger: &Trigger) -> Result<()> {
+        if trigger.price > self.orderbook.spec.one() {
+            return Err(RejectReason::PriceOutOfRange.into());
+        }
+        Ok(())
+    }
+
+    /// Last trade price per outcome, which is what triggers watch
+    fn record_last_prices(&mut self, prints: &[(Outcome, Ticks)]) {
+        for &(outcome, price) in prints {
+            self.orderbook.triggers.record_trade(outcome, price);
+        }
+    }
+
+    /// Move tokens from sellers to buyers. Mints create them for every buyer.
+    fn record_positions(&mut self, trades: &[Trade], complementary: &[ComplementaryMatch], complete_sets: &[CompleteSetMatch]) {
+        let inventory = &mut self.orderbook.inventory;
+        for cmatch in complementary {
+            inventory.bought(&cmatch.yes_buyer_id, Outcome::YES, cmatch.quantity);
+            inventory.bought(&cmatch.no_buyer_id, Outcome::NO, cmatch.quantity);
+        }
+        for set in complete_sets {
+            for leg in &set.legs {
+                inventory.bought(&leg.buyer_id, leg.outcome, set.quantity);
+            }
+        }
+        for trade in trades {
+            inventory.bought(&trade.buyer_id, trade.outcome, trade.quantity);
+            inventory.sold(&trade.seller_id, trade.outcome, trade.quantity);
+        }
+    }
+
+    /// A SELL may only offer tokens the seller holds and has not already
+    /// offered elsewhere; a reduce-only SELL is trimmed to that instead. An
+    /// OCO partner or the other bracket exit may offer the same tokens, as
+    /// only one of the two can fill. Nothing is checked unless positions
+    /// were loaded.
+    fn check_position(&self, order: &mut Order) -> Result<()> {
+        let inventory = &self.orderbook.inventory;
+        if order.side != OrderSide::SELL || !inventory.enforced {
+            return Ok(());
+        }
+
+        let shared = self
+            .orderbook
+            .groups
+            .partner(&order.order_id)
+            .and_then(|id| self.orderbook.working_order(&id))
+            .filter(|partner| partner.side == OrderSide::SELL && partner.outcome == order.outcome)
+            .map_or(0, |partner| partner.remaining());
+        let committed = inventory.committed(&order.user_id, order.outcome).saturating_sub(shared);
+        let available = inventory.held(&order.user_id, order.outcome).saturating_sub(committed);
+        if order.reduce_only && available > 0 && order.quantity > available {
+            info!("Reduce-only {} trimmed from {} to {}", order.order_id, order.quantity, available);
+            order.quantity = available;
+            order.display_quantity = order.display_quantity.map(|d| d.min(available));
+        }
+        if order.quantity > available {
+            return Err(RejectReason::RiskLimit.into());
+        }
+        Ok(())
+    }
+
+    /// Feed prints to the circuit breaker, acting on the first that trips it
+    fn check_breaker(&mut self, prints: &[(Outcome, Ticks)]) {
+        let now = Instant::now();
+        for &(outcome, price) in prints {
+            let Some((action, low, high)) = self.orderbook.bands.record_trade(outcome, price, now) else {
+                continue;
+            };
+            warn!(
+                "Circuit breaker tripped in {}: {:?} traded {}..{}, {}",
+                self.orderbook.market_id, outcome, low, high, action
+            );
+            if action == BreakerAction::AUCTION {
+                self.orderbook.phase = TradingPhase::AUCTION;
+            }
+            self.breakers_tripped.push(BreakerTripped {
+                market_id: self.orderbook.market_id.clone(),
+                outcome,
+                action,
+                low,
+                high,
+                halted_until: self.orderbook.bands.halted_until(),
+            });
+            return;
+        }
+    }
+
+    /// Band edges around the last trade, or the mid if nothing has traded
+    fn band_limits(&self, outcome: Outcome) -> Option<(Ticks, Ticks)> {
+        let reference = self.orderbook.triggers.last_price(outcome).or_else(|| {
+            let bid = self.orderbook.best_bid(outcome)?;
+            let ask = self.orderbook.best_ask(outcome)?;
+            Some((bid + ask) / 2)
+        });
+        self.orderbook.bands.limits(reference, self.orderbook.spec.one())
+    }
+
+    fn place(&mut self, mut order: Order) -> Result<MatchResult> {
         info!(
             "Placing order: {} {:?} {:?} @ {} (qty: {})",
             order.user_id, order.side, order.outcome, order.price, order.quantity
@@ -26,8 +350,21 @@ impl Matcher {
         
         // 1. Validate order
         self.validate_order(&order)?;
-        print!("Hi there");
+        if self.orderbook.mmp.is_frozen(&order.user_id) {
+            return Err(RejectReason::MmpTriggered.into());
+        }
+        let in_auction = self.orderbook.phase == TradingPhase::AUCTION;
+        if in_auction && order.order_type == OrderType::MARKET {
+            return Err(RejectReason::MarketOrderInAuction.into());
+        }
 
+        // Bands are fixed at arrival so an order can't drag its own reference
+        let limits = if in_auction { None } else { self.band_limits(order.outcome) };
+        if let Some((low, high)) = limits {
+            if order.order_type != OrderType::MARKET && !(low..=high).contains(&order.price) {
+                return Err(RejectReason::PriceOutsideBand.into());
+            }
+        }
 
         // 2. Check for self-trade
         if self.orderbook.would_self_trade(
@@ -37,21 +374,30 @@ impl Matcher {
             order.price
         ) {
             warn!("Self-trade detected for user {}", order.user_id);
-            return Err(anyhow::anyhow!("Self-trade not allowed"));
+            return Err(RejectReason::SelfTrade.into());
         }
         
         // 3. Try to match order
         let mut trades = Vec::new();
         let mut complementary_matches = Vec::new();
+        let mut complete_set_matches = Vec::new();
         
         match order.order_type {
+            _ if in_auction => {
+                // Orders only accumulate until the uncross
+            }
             OrderType::MARKET => {
                 // Match immediately at best available price
-                self.match_market_order(&mut order, &mut trades)?;
+                self.match_market_order(&mut order, limits, &mut trades)?;
             }
-            OrderType::LIMIT => {
+            OrderType::LIMIT | OrderType::ICEBERG => {
                 // Try to match, add remainder to book
-                self.match_limit_order(&mut order, &mut trades, &mut complementary_matches)?;
+                self.match_limit_order(
+                    &mut order,
+                    &mut trades,
+                    &mut complementary_matches,
+                    &mut complete_set_matches,
+                )?;
             }
             OrderType::POSTONLY => {
                 // Only add to book, never take liquidity
@@ -60,8 +406,8 @@ impl Matcher {
         }
         
         // 4. Add remaining quantity to orderbook
-        if order.remaining() > Decimal::ZERO && !order.is_filled() {
-            order.order_status = if order.filled > Decimal::ZERO {
+        if order.remaining() > 0 && !order.is_filled() {
+            order.order_status = if order.filled > 0 {
                 OrderStatus::PARTIAL
             } else {
                 OrderStatus::OPEN
@@ -71,24 +417,46 @@ impl Matcher {
         } else if order.is_filled() {
             order.order_status = OrderStatus::FILLED;
         }
+        let prints = prints_of(&trades, &complementary_matches, &complete_set_matches);
+        self.record_last_prices(&prints);
+        self.record_positions(&trades, &complementary_matches, &complete_set_matches);
+        self.orderbook.trades.record(&trades, &complementary_matches, &complete_set_matches);
+        self.orderbook.record_prints(public_trades(&trades, &complementary_matches, &complete_set_matches, Some(&order)));
+        self.check_breaker(&prints);
         
         Ok(MatchResult {
             order,
             trades,
             complementary_matches,
+            complete_set_matches,
+            mmp_triggered: std::mem::take(&mut self.mmp_triggered),
+            breakers_tripped: std::mem::take(&mut self.breakers_tripped),
+            triggered: Vec::new(),
+            cancelled: Vec::new(),
         })
     }
     
-    /// Match a MARKET order (execute immediately at best price)
-    fn match_market_order(&self, order: &mut Order, trades: &mut Vec<Trade>) -> Result<()> {
-        while order.remaining() > Decimal::ZERO {
+    /// Match a MARKET order (execute immediately at best price), going no
+    /// further than the edge of the price band
+    fn match_market_order(&mut self, order: &mut Order, limits: Option<(Ticks, Ticks)>, trades: &mut Vec<Trade>) -> Result<()> {
+        while order.remaining() > 0 {
             // ✅ CORRECT: Use best_bid/best_ask methods
             let best_price = match order.side {
                 OrderSide::BUY => self.orderbook.best_ask(order.outcome),
                 OrderSide::SELL => self.orderbook.best_bid(order.outcome),
             };
             
+            let outside_band = |price: Ticks| {
+                limits.is_some_and(|(low, high)| match order.side {
+                    OrderSide::BUY => price > high,
+                    OrderSide::SELL => price < low,
+                })
+            };
             match best_price {
+                Some(price) if outside_band(price) => {
+                    warn!("Market order {} stopped at the price band", order.order_id);
+                    break;
+                }
                 Some(price) => {
                     self.execute_trade_at_price(order, price, trades)?;
                 }
@@ -105,19 +473,20 @@ impl Matcher {
     
     /// Match a LIMIT order (match at price or better, add remainder to book)
     fn match_limit_order(
-        &self,
+        &mut self,
         order: &mut Order,
         trades: &mut Vec<Trade>,
         complementary: &mut Vec<ComplementaryMatch>,
+        complete_sets: &mut Vec<CompleteSetMatch>,
     ) -> Result<()> {
-        // First, try complementary matching (BUY YES + BUY NO)
+        // First, try complementary matching (one BUY per outcome = mint a set)
         if order.side == OrderSide::BUY {
-            self.try_complementary_match(order, complementary)?;
+            self.try_complementary_match(order, complementary, complete_sets)?;
         }
         
         // Then, try secondary matching (existing tokens)
-        while order.remaining() > Decimal::ZERO {
-            // ✅ CORRECT: Compare Decimal to Decimal
+        while order.remaining() > 0 {
+            // Prices are integer ticks, so these comparisons are exact
             let can_match = match order.side {
                 OrderSide::BUY => {
                     // BUY: match with SELL at or below our price
@@ -150,170 +519,194 @@ impl Matcher {
         Ok(())
     }
     
-    /// Try to match with a complementary order (BUY YES + BUY NO = mint pair)
-    /// Try to match with a complementary order (BUY YES + BUY NO = mint pair)
-fn try_complementary_match(
-    &self,
-    order: &mut Order,
-    matches: &mut Vec<ComplementaryMatch>,
-) -> Result<()> {
-    if order.side != OrderSide::BUY {
-        return Ok(());
+    /// End the opening auction: execute everything that crosses at the single
+    /// clearing price, then switch the book to continuous trading.
+    /// Auctions are only run on binary markets.
+    pub fn uncross(&mut self) -> UncrossResult {
+        let clearing = AuctionBook::new(self.orderbook).clearing_price();
+        let mut trades = Vec::new();
+        let mut complementary_matches = Vec::new();
+
+        if let Some(clearing) = clearing {
+            let yes_price = clearing.price;
+            let no_price = self.orderbook.spec.one() - yes_price;
+
+            // Same split as the volume calculation: secondary first, then mint
+            self.cross_at(Outcome::YES, yes_price, &mut trades);
+            self.cross_at(Outcome::NO, no_price, &mut trades);
+            self.mint_at(yes_price, no_price, &mut complementary_matches);
+            self.record_last_prices(&prints_of(&trades, &complementary_matches, &[]));
+            self.record_positions(&trades, &complementary_matches, &[]);
+            self.orderbook.trades.record(&trades, &complementary_matches, &[]);
+            self.orderbook.record_prints(public_trades(&trades, &complementary_matches, &[], None));
+
+            info!(
+                "Uncrossed {} @ {} (volume: {})",
+                self.orderbook.market_id, yes_price, clearing.volume
+            );
+        }
+
+        self.orderbook.phase = TradingPhase::CONTINUOUS;
+        let (triggered, cancelled) = self.follow_up(fills_of(&trades, &complementary_matches, &[]));
+
+        UncrossResult {
+            clearing,
+            trades,
+            complementary_matches,
+            triggered,
+            cancelled,
+        }
     }
-    
-    // Get opposite outcome
-    let opposite_outcome = match order.outcome {
-        Outcome::YES => Outcome::NO,
-        Outcome::NO => Outcome::YES,
-    };
-    
-    // Get RwLock
-    let opposite_bids = match opposite_outcome {
-        Outcome::YES => &self.orderbook.yes_bids,
-        Outcome::NO => &self.orderbook.no_bids,
-    };
-    
-    // Lock for reading
-    let book = opposite_bids.read().unwrap();
-    
-    // Find matching prices (must sum to 1.00)
-    let our_price = order.price;
-    let required_price = Decimal::ONE - our_price;
-    
-    // Collect order IDs to process (not full orders!)
-    let mut order_ids_to_match: Vec<Uuid> = book
-        .range(required_price..)
-        .flat_map(|(_, orders)| orders.iter().map(|o| o.order_id))
-        .collect();
-    
-    // Release read lock IMMEDIATELY
-    drop(book);
-    
-    // ✅ FIX: Process each order ID only once
-    for order_id in order_ids_to_match {
-        if order.remaining() == Decimal::ZERO {
-            break;
+
+    /// Secondary fills in price-time priority while both sides are eligible at `price`
+    fn cross_at(&mut self, outcome: Outcome, price: Ticks, trades: &mut Vec<Trade>) {
+        while let (Some(bid), Some(ask)) = (
+            self.orderbook.best_bid_order(outcome),
+            self.orderbook.best_ask_order(outcome),
+        ) {
+            let (buyer, seller) = (self.orderbook.order(bid), self.orderbook.order(ask));
+            if buyer.price < price || seller.price > price {
+                break;
+            }
+
+            let matched_qty = buyer.remaining().min(seller.remaining());
+            trades.push(Trade::new(buyer, seller, TradeType::SECONDARY, price, matched_qty));
+            self.orderbook.fill(bid, matched_qty);
+            self.orderbook.fill(ask, matched_qty);
         }
-        
-        // Get current state of opposite order
-        let opposite_order = match self.orderbook.orders.get(&order_id) {
-            Some(o) => o.clone(),
-            None => continue, // Order was already removed
-        };
-        
-        if opposite_order.is_filled() {
-            continue; // Skip already filled orders
+    }
+
+    /// Pair leftover YES and NO bids, each paying its side of the clearing price
+    fn mint_at(&mut self, yes_price: Ticks, no_price: Ticks, matches: &mut Vec<ComplementaryMatch>) {
+        while let (Some(yes_handle), Some(no_handle)) = (
+            self.orderbook.best_bid_order(Outcome::YES),
+            self.orderbook.best_bid_order(Outcome::NO),
+        ) {
+            let (yes, no) = (self.orderbook.order(yes_handle), self.orderbook.order(no_handle));
+            if yes.price < yes_price || no.price < no_price {
+                break;
+            }
+
+            let matched_qty = yes.remaining().min(no.remaining());
+            matches.push(ComplementaryMatch::new(yes, no, yes_price, no_price, matched_qty));
+            self.orderbook.fill(yes_handle, matched_qty);
+            self.orderbook.fill(no_handle, matched_qty);
         }
-        
-        // Calculate matched quantity
-        let matched_qty = order.remaining().min(opposite_order.remaining());
-        
-        // Create complementary match
-        let (yes_buyer, no_buyer, yes_order, no_order, yes_price, no_price) = 
-            match order.outcome {
-                Outcome::YES => (
-                    order.user_id.clone(),
-                    opposite_order.user_id.clone(),
-                    order.order_id,
-                    opposite_order.order_id,
-                    order.price,
-                    opposite_order.price,
-                ),
-                Outcome::NO => (
-                    opposite_order.user_id.clone(),
-                    order.user_id.clone(),
-                    opposite_order.order_id,
-                    order.order_id,
-                    opposite_order.price,
-                    order.price,
-                ),
+    }
+
+    /// Try to mint complete outcome sets: this BUY plus the best bid on
+    /// every other outcome, while their prices sum to at least 1.00. In a
+    /// binary market that is BUY YES + BUY NO, reported as a ComplementaryMatch.
+    fn try_complementary_match(
+        &mut self,
+        order: &mut Order,
+        matches: &mut Vec<ComplementaryMatch>,
+        complete_sets: &mut Vec<CompleteSetMatch>,
+    ) -> Result<()> {
+        if order.side != OrderSide::BUY {
+            return Ok(());
+        }
+
+        let others: Vec<Outcome> = self.orderbook.outcomes().filter(|o| *o != order.outcome).collect();
+        let one = self.orderbook.spec.one();
+
+        while order.remaining() > 0 {
+            // Best bid on each other outcome; any empty book means no set
+            let Some(legs) = others
+                .iter()
+                .map(|o| self.orderbook.best_bid_order(*o))
+                .collect::<Option<Vec<OrderHandle>>>()
+            else {
+                break;
             };
-        
-        matches.push(ComplementaryMatch {
-            trade_id: Uuid::new_v4(),
-            market_id: order.market_id.clone(),
-            yes_buyer_id: yes_buyer,
-            no_buyer_id: no_buyer,
-            quantity: matched_qty,
-            yes_price,
-            no_price,
-            yes_order_id: yes_order,
-            no_order_id: no_order,
-            yes_reservation_id: order.reservation_id.clone(),
-            no_reservation_id: opposite_order.reservation_id.clone(),
-            timestamp: Utc::now(),
-        });
-        
-        // ✅ FIX: Update taker order
-        order.filled += matched_qty;
-        
-        // ✅ FIX: Update or remove maker order (simpler approach)
-        if opposite_order.filled + matched_qty >= opposite_order.quantity {
-            // Fully filled - just remove it
-            self.orderbook.remove_order(opposite_order.order_id);
-        } else {
-            // Partially filled - update the filled amount in DashMap
-            // The order stays in the BTreeMap price level
-            if let Some(mut stored) = self.orderbook.orders.get_mut(&opposite_order.order_id) {
-                stored.filled += matched_qty;
+
+            let total: Ticks = order.price + legs.iter().map(|h| self.orderbook.order(*h).price).sum::<Ticks>();
+            if total < one {
+                break;
             }
-            
-            // ✅ IMPORTANT: Also update in the price level itself
-            // We need to find it and update it in place
-            let price = opposite_order.price;
-            let bids = match opposite_outcome {
-                Outcome::YES => &self.orderbook.yes_bids,
-                Outcome::NO => &self.orderbook.no_bids,
-            };
-            
-            let mut book = bids.write().unwrap();
-            if let Some(orders_at_price) = book.get_mut(&price) {
-                // Find the order in the VecDeque and update it
-                for order_in_queue in orders_at_price.iter_mut() {
-                    if order_in_queue.order_id == opposite_order.order_id {
-                        order_in_queue.filled += matched_qty;
-                        break;
-                    }
-                }
-                
-                // ✅ CRITICAL: Remove from queue if now filled
-                if opposite_order.filled + matched_qty >= opposite_order.quantity {
-                    orders_at_price.retain(|o| o.order_id != opposite_order.order_id);
-                    
-                    // Clean up empty price level
-                    if orders_at_price.is_empty() {
-                        book.remove(&price);
-                    }
-                }
+
+            // Calculate matched quantity
+            let matched_qty = legs
+                .iter()
+                .map(|h| self.orderbook.visible(*h))
+                .fold(order.remaining(), Lots::min);
+
+            if self.orderbook.is_binary() {
+                let opposite_order = self.orderbook.order(legs[0]);
+                let (yes, no) = match order.outcome {
+                    Outcome::YES => (&*order, opposite_order),
+                    _ => (opposite_order, &*order),
+                };
+
+                let cmatch = ComplementaryMatch::new(yes, no, yes.price, no.price, matched_qty);
+                info!(
+                    "Complementary match: {} YES + {} NO = {} pairs",
+                    cmatch.yes_price, cmatch.no_price, matched_qty
+                );
+                matches.push(cmatch);
+            } else {
+                let buyers: Vec<&Order> = std::iter::once(&*order)
+                    .chain(legs.iter().map(|h| self.orderbook.order(*h)))
+                    .collect();
+                info!(
+                    "Complete set match: {} outcomes @ {} total = {} sets",
+                    buyers.len(), total, matched_qty
+                );
+                complete_sets.push(CompleteSetMatch::new(&buyers, matched_qty));
+            }
+
+            // The book holds the only copy of each maker, so one fill covers it
+            order.filled += matched_qty;
+            let mut tripped = Vec::new();
+            for handle in legs {
+                let maker = self.orderbook.fill(handle, matched_qty);
+                self.record_maker_fill(&maker, matched_qty, &mut tripped);
             }
-            drop(book);
+            self.pull_quotes(tripped);
         }
-        
-        info!(
-            "Complementary match: {} YES + {} NO = {} pairs",
-            yes_price, no_price, matched_qty
-        );
+
+        Ok(())
     }
-    
-    Ok(())
-}
+
     /// Execute a trade at a specific price
     fn execute_trade_at_price(
-        &self,
+        &mut self,
         taker_order: &mut Order,
-        price: Decimal,
+        price: Ticks,
         trades: &mut Vec<Trade>,
     ) -> Result<()> {
-        // ✅ CORRECT: Use pop_best_ask/pop_best_bid methods
-        let maker_order = match taker_order.side {
-            OrderSide::BUY => self.orderbook.pop_best_ask(taker_order.outcome),
-            OrderSide::SELL => self.orderbook.pop_best_bid(taker_order.outcome),
+        // FIFO only ever touches the front of the level; the other strategies
+        // share the taker's quantity across the whole level at once
+        let makers = match self.orderbook.allocation {
+            Allocation::FIFO => match taker_order.side {
+                OrderSide::BUY => self.orderbook.best_ask_order(taker_order.outcome),
+                OrderSide::SELL => self.orderbook.best_bid_order(taker_order.outcome),
+            }
+            .into_iter()
+            .collect(),
+            _ => {
+                let maker_side = match taker_order.side {
+                    OrderSide::BUY => OrderSide::SELL,
+                    OrderSide::SELL => OrderSide::BUY,
+                };
+                self.orderbook.level_handles(maker_side, taker_order.outcome, price)
+            }
         };
-        
-        if let Some(mut maker_order) = maker_order {
-            // Calculate matched quantity
-            let matched_qty = taker_order.remaining().min(maker_order.remaining());
-            
+
+        // Icebergs only offer their current slice; the caller comes back to
+        // the level for whatever is replenished
+        let sizes: Vec<Lots> = makers.iter().map(|h| self.orderbook.visible(*h)).collect();
+        let shares = self.orderbook.allocation.allocate(&sizes, taker_order.remaining());
+        // Quotes are only pulled once the whole level is allocated, so the
+        // handles above stay valid
+        let mut tripped = Vec::new();
+
+        for (handle, matched_qty) in makers.into_iter().zip(shares) {
+            if matched_qty == 0 {
+                continue;
+            }
+            let maker_order = self.orderbook.order(handle);
+
             // Determine trade type
             let trade_type = TradeType::determine(
                 taker_order.side,
@@ -321,92 +714,305 @@ fn try_complementary_match(
                 maker_order.side,
                 maker_order.outcome,
             );
-            
+
             // Create trade
-            let (buyer_id, seller_id, buyer_order_id, seller_order_id, buyer_res, seller_res) = 
-                match taker_order.side {
-                    OrderSide::BUY => (
-                        taker_order.user_id.clone(),
-                        maker_order.user_id.clone(),
-                        taker_order.order_id,
-                        maker_order.order_id,
-                        taker_order.reservation_id.clone(),
-                        maker_order.reservation_id.clone(),
-                    ),
-                    OrderSide::SELL => (
-                        maker_order.user_id.clone(),
-                        taker_order.user_id.clone(),
-                        maker_order.order_id,
-                        taker_order.order_id,
-                        maker_order.reservation_id.clone(),
-                        taker_order.reservation_id.clone(),
-                    ),
-                };
-            
-            trades.push(Trade {
-                trade_id: Uuid::new_v4(),
-                market_id: taker_order.market_id.clone(),
-                outcome: taker_order.outcome,
-                trade_type,
-                buyer_id,
-                seller_id,
-                quantity: matched_qty,
-                price,
-                buyer_order_id,
-                seller_order_id,
-                buyer_reservation_id: buyer_res,
-                seller_reservation_id: seller_res,
-                timestamp: Utc::now(),
-            });
-            
-            // Update orders
+            let (buyer, seller) = match taker_order.side {
+                OrderSide::BUY => (&*taker_order, maker_order),
+                OrderSide::SELL => (maker_order, &*taker_order),
+            };
+
+            trades.push(Trade::new(buyer, seller, trade_type, price, matched_qty));
+
+            // Update orders; a fully filled maker leaves the book here,
+            // a partial one keeps its place in the queue
             taker_order.filled += matched_qty;
-            maker_order.filled += matched_qty;
-            
-            // Handle maker order
-            if !maker_order.is_filled() {
-                // Put back at front (partial fill)
-                self.orderbook.push_front(maker_order);
-            }
-            // If fully filled, it's already removed (pop_best_* removed it)
-            
+            let maker = self.orderbook.fill(handle, matched_qty);
+            self.record_maker_fill(&maker, matched_qty, &mut tripped);
+
             info!(
                 "Trade executed: {:?} {:?} @ {} (qty: {})",
                 trade_type, taker_order.outcome, price, matched_qty
             );
         }
-        
+        self.pull_quotes(tripped);
+
         Ok(())
     }
-    
+
+    /// Count a fill against the maker's market-maker protection
+    fn record_maker_fill(&mut self, maker: &Order, quantity: Lots, tripped: &mut Vec<(String, Lots, usize)>) {
+        if let Some((filled, fills)) = self.orderbook.mmp.record_fill(&maker.user_id, quantity, Instant::now()) {
+            tripped.push((maker.user_id.clone(), filled, fills));
+        }
+    }
+
+    /// Cancel everything a tripped market maker still has resting here
+    fn pull_quotes(&mut self, tripped: Vec<(String, Lots, usize)>) {
+        for (user_id, filled_quantity, fill_count) in tripped {
+            let cancelled = self.orderbook.remove_user_orders(&user_id);
+            warn!(
+                "MMP tripped for {} in {}: {} filled over {} fills, pulled {} orders",
+                user_id, self.orderbook.market_id, filled_quantity, fill_count, cancelled.len()
+            );
+            self.mmp_triggered.push(MmpTriggered {
+                market_id: self.orderbook.market_id.clone(),
+                user_id,
+                filled_quantity,
+                fill_count,
+                cancelled,
+            });
+        }
+    }
+
     fn validate_order(&self, order: &Order) -> Result<()> {
-        if order.quantity <= Decimal::ZERO {
-            return Err(anyhow::anyhow!("Invalid quantity"));
+        // The registry is checked before the lock is taken; this is what
+        // stops an order that raced the market's expiry
+        if self.orderbook.closed {
+            return Err(RejectReason::MarketClosed.into());
+        }
+        if self.orderbook.bands.is_halted(Instant::now()) {
+            return Err(RejectReason::MarketHalted.into());
+        }
+        if order.outcome.index() >= self.orderbook.outcome_count() {
+            return Err(RejectReason::InvalidOutcome.into());
+        }
+        if order.quantity == 0 {
+            return Err(RejectReason::InvalidQuantity.into());
+        }
+        // Icebergs need a slice no bigger than the order; nothing else has one
+        let display_ok = match (order.order_type, order.display_quantity) {
+            (OrderType::ICEBERG, Some(display)) => display > 0 && display <= order.quantity,
+            (_, display) => order.order_type != OrderType::ICEBERG && display.is_none(),
+        };
+        if !display_ok {
+            return Err(RejectReason::InvalidQuantity.into());
         }
         
-        if order.price < Decimal::ZERO || order.price > Decimal::ONE {
-            return Err(anyhow::anyhow!("Price must be between 0 and 1"));
+        if order.price > self.orderbook.spec.one() {
+            return Err(RejectReason::PriceOutOfRange.into());
+        }
+
+        // The same funds must never back two resting orders
+        if let Some(reservation_id) = &order.reservation_id {
+            if self.orderbook.has_reservation(reservation_id)
+                || self.orderbook.triggers.has_reservation(reservation_id)
+                || self.orderbook.groups.has_reservation(reservation_id)
+            {
+                return Err(RejectReason::DuplicateReservation.into());
+            }
         }
         
         Ok(())
     }
 }
 
+/// (outcome, price) of every print, mints first: the order they execute in
+fn prints_of(trades: &[Trade], complementary: &[ComplementaryMatch], complete_sets: &[CompleteSetMatch]) -> Vec<(Outcome, Ticks)> {
+    let mut prints = Vec::new();
+    for cmatch in complementary {
+        prints.push((Outcome::YES, cmatch.yes_price));
+        prints.push((Outcome::NO, cmatch.no_price));
+    }
+    for set in complete_sets {
+        prints.extend(set.legs.iter().map(|leg| (leg.outcome, leg.price)));
+    }
+    prints.extend(trades.iter().map(|trade| (trade.outcome, trade.price)));
+    prints
+}
+
+/// (order_id, quantity) for every order on either side of these fills
+fn fills_of(trades: &[Trade], complementary: &[ComplementaryMatch], complete_sets: &[CompleteSetMatch]) -> Vec<(Uuid, Lots)> {
+    let mut fills = Vec::new();
+    for cmatch in complementary {
+        fills.push((cmatch.yes_order_id, cmatch.quantity));
+        fills.push((cmatch.no_order_id, cmatch.quantity));
+    }
+    for set in complete_sets {
+        fills.extend(set.legs.iter().map(|leg| (leg.order_id, set.quantity)));
+    }
+    for trade in trades {
+        fills.push((trade.buyer_order_id, trade.quantity));
+        fills.push((trade.seller_order_id, trade.quantity));
+    }
+    fills
+}
+
+/// Pull an elected order that has not fired yet
+fn take_elected(elected: &mut VecDeque<Conditional>, order_id: Uuid) -> Option<Order> {
+    let position = elected.iter().position(|c| c.order.order_id == order_id)?;
+    let mut order = elected.remove(position)?.order;
+    order.order_status = OrderStatus::CANCELLED;
+    Some(order)
+}
+
+fn reduce_elected(elected: &mut VecDeque<Conditional>, order_id: Uuid, quantity: Lots) -> Option<Order> {
+    let position = elected.iter().position(|c| c.order.order_id == order_id)?;
+    let order = &mut elected[position].order;
+    order.quantity -= quantity.min(order.remaining());
+    if order.remaining() == 0 {
+        elected.remove(position).map(|c| c.order)
+    } else {
+        Some(order.clone())
+    }
+}
+
+/// Why the matcher refused an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum RejectReason {
+    #[error("Self-trade not allowed")]
+    SelfTrade,
+    #[error("Market is not listed")]
+    UnknownMarket,
+    #[error("Market is not open for trading")]
+    MarketClosed,
+    #[error("Outcome does not exist in this market")]
+    InvalidOutcome,
+    #[error("Invalid quantity")]
+    InvalidQuantity,
+    #[error("Price is not on the market's tick grid")]
+    InvalidPrice,
+    #[error("Price must be between 0 and 1")]
+    PriceOutOfRange,
+    #[error("Market orders are not accepted during the auction")]
+    MarketOrderInAuction,
+    #[error("Reservation already backs a live order")]
+    DuplicateReservation,
+    #[error("client_order_id already used for a different order")]
+    ClientOrderIdReused,
+    #[error("Market-maker protection tripped; reset before quoting again")]
+    MmpTriggered,
+    #[error("Session is not open for this user")]
+    UnknownSession,
+    #[error("Market is halted by its circuit breaker")]
+    MarketHalted,
+    #[error("Price is outside the market's price band")]
+    PriceOutsideBand,
+    #[error("Sell exceeds the position not already offered")]
+    RiskLimit,
+}
+
+impl RejectReason {
+    /// Stable label for metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RejectReason::SelfTrade => "self_trade",
+            RejectReason::UnknownMarket => "unknown_market",
+            RejectReason::MarketClosed => "market_closed",
+            RejectReason::InvalidOutcome => "invalid_outcome",
+            RejectReason::InvalidQuantity => "invalid_quantity",
+            RejectReason::InvalidPrice => "invalid_price",
+            RejectReason::PriceOutOfRange => "price_out_of_range",
+            RejectReason::MarketOrderInAuction => "market_order_in_auction",
+            RejectReason::DuplicateReservation => "duplicate_reservation",
+            RejectReason::ClientOrderIdReused => "client_order_id_reused",
+            RejectReason::MmpTriggered => "mmp_triggered",
+            RejectReason::UnknownSession => "unknown_session",
+            RejectReason::MarketHalted => "market_halted",
+            RejectReason::PriceOutsideBand => "price_outside_band",
+            RejectReason::RiskLimit => "risk_limit",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UncrossResult {
+    /// None when nothing crossed; the book still moves to continuous trading
+    pub clearing: Option<ClearingPrice>,
+    pub trades: Vec<Trade>,
+    pub complementary_matches: Vec<ComplementaryMatch>,
+    /// Conditional orders elected by the uncross price
+    pub triggered: Vec<FiredTrigger>,
+    /// OCO partners and bracket exits cancelled by the uncross fills
+    pub cancelled: Vec<Order>,
+}
+
+#[derive(Debug)]
 pub struct MatchResult {
     pub order: Order,
     pub trades: Vec<Trade>,
     pub complementary_matches: Vec<ComplementaryMatch>,
+    /// Categorical markets only; binary mints go in `complementary_matches`
+    pub complete_set_matches: Vec<CompleteSetMatch>,
+    /// Makers whose quotes were pulled by this order's fills
+    pub mmp_triggered: Vec<MmpTriggered>,
+    /// At most one: the market stops trading once it trips
+    pub breakers_tripped: Vec<BreakerTripped>,
+    /// Conditional orders and bracket take-profits this order's fills
+    /// released, in the order they went in
+    pub triggered: Vec<FiredTrigger>,
+    /// OCO partners and bracket exits cancelled along the way
+    pub cancelled: Vec<Order>,
+}
+
+impl MatchResult {
+    /// An order that was accepted without trading or resting in the book
+    fn untouched(order: Order) -> Self {
+        Self {
+            order,
+            trades: Vec::new(),
+            complementary_matches: Vec::new(),
+            complete_set_matches: Vec::new(),
+            mmp_triggered: Vec::new(),
+            breakers_tripped: Vec::new(),
+            triggered: Vec::new(),
+            cancelled: Vec::new(),
+        }
+    }
+}
+
+/// Every leg of a newly placed OCO pair or bracket
+#[derive(Debug)]
+pub struct GroupResult {
+    pub group_id: Uuid,
+    /// OCO: both legs in the order given. Bracket: entry, take-profit, stop.
+    pub legs: Vec<MatchResult>,
+}
+
+/// An order released into the book by its trigger or bracket, and what it did there
+#[derive(Debug)]
+pub struct FiredTrigger {
+    /// None for a bracket take-profit released by its entry filling
+    pub trigger: Option<Trigger>,
+    pub order: Order,
+    /// Err holds a RejectReason, e.g. SelfTrade against the owner's own quotes
+    pub result: Result<MatchResult>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bands::BandConfig;
+    use crate::market_spec::MarketSpec;
+    use crate::mmp::MmpConfig;
+    use crate::triggers::TriggerKind;
+    use chrono::Utc;
     use rust_decimal_macros::dec;
+    use uuid::Uuid;
+
+    fn limit(user: &str, side: OrderSide, outcome: Outcome, price: Ticks, quantity: Lots) -> Order {
+        Order {
+            order_id: Uuid::new_v4(),
+            user_id: user.to_string(),
+            market_id: "market_test".to_string(),
+            side,
+            outcome,
+            order_type: OrderType::LIMIT,
+            price,
+            quantity,
+            filled: 0,
+            order_status: OrderStatus::PENDING,
+            reservation_id: Some(format!("{}_res", user)),
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+        }
+    }
 
     #[test]
     fn test_complementary_match_btreemap() {
-        let orderbook = OrderBook::new("market_test".to_string());
-        let matcher = Matcher::new(orderbook);
+        let spec = MarketSpec::default();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        let mut matcher = Matcher::new(&mut orderbook);
 
         let alice = Order {
             order_id: Uuid::new_v4(),
@@ -415,11 +1021,13 @@ mod tests {
             side: OrderSide::BUY,
             outcome: Outcome::YES,
             order_type: OrderType::LIMIT,
-            price: dec!(0.60),
-            quantity: dec!(100),
-            filled: dec!(0),
+            price: spec.price_to_ticks(dec!(0.60)).unwrap(),
+            quantity: spec.quantity_to_lots(dec!(100)).unwrap(),
+            filled: 0,
             order_status: OrderStatus::PENDING,
             reservation_id: Some("alice_res".to_string()),
+            display_quantity: None,
+            reduce_only: false,
             created_at: Utc::now(),
         };
 
@@ -430,11 +1038,13 @@ mod tests {
             side: OrderSide::BUY,
             outcome: Outcome::NO,
             order_type: OrderType::LIMIT,
-            price: dec!(0.40),
-            quantity: dec!(100),
-            filled: dec!(0),
+            price: spec.price_to_ticks(dec!(0.40)).unwrap(),
+            quantity: spec.quantity_to_lots(dec!(100)).unwrap(),
+            filled: 0,
             order_status: OrderStatus::PENDING,
             reservation_id: Some("bob_res".to_string()),
+            display_quantity: None,
+            reduce_only: false,
             created_at: Utc::now(),
         };
 
@@ -443,7 +1053,373 @@ mod tests {
 
         assert!(!result.complementary_matches.is_empty());
         let cmatch = &result.complementary_matches[0];
-        assert_eq!(cmatch.quantity, dec!(100));
-        assert_eq!(cmatch.yes_price + cmatch.no_price, dec!(1.0));
+        assert_eq!(spec.lots_to_quantity(cmatch.quantity), dec!(100));
+        assert_eq!(cmatch.yes_price + cmatch.no_price, spec.one());
+        assert_eq!(
+            spec.ticks_to_price(cmatch.yes_price) + spec.ticks_to_price(cmatch.no_price),
+            dec!(1.0)
+        );
+    }
+
+    #[test]
+    fn test_auction_accumulates_then_uncrosses_at_one_price() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        orderbook.phase = TradingPhase::AUCTION;
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        // Would trade immediately in continuous mode
+        let early = matcher.place_order(limit("alice", OrderSide::BUY, Outcome::YES, 62, 10)).unwrap();
+        assert!(early.trades.is_empty());
+        assert_eq!(early.order.order_status, OrderStatus::OPEN);
+        matcher.place_order(limit("bob", OrderSide::SELL, Outcome::YES, 58, 6)).unwrap();
+        matcher.place_order(limit("carol", OrderSide::BUY, Outcome::NO, 40, 4)).unwrap();
+
+        let mut market = limit("dave", OrderSide::BUY, Outcome::YES, 0, 1);
+        market.order_type = OrderType::MARKET;
+        let err = matcher.place_order(market).unwrap_err();
+        assert_eq!(err.downcast_ref::<RejectReason>(), Some(&RejectReason::MarketOrderInAuction));
+
+        let result = matcher.uncross();
+        let clearing = result.clearing.unwrap();
+        assert_eq!(clearing.volume, 10);
+        assert_eq!(clearing.price, 60);
+
+        // bob's 6 sell to alice, carol's NO bid mints the other 4, all at 0.60
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!((result.trades[0].price, result.trades[0].quantity), (60, 6));
+        assert_eq!(result.complementary_matches.len(), 1);
+        let cmatch = &result.complementary_matches[0];
+        assert_eq!((cmatch.yes_price, cmatch.no_price, cmatch.quantity), (60, 40, 4));
+        assert_eq!(cmatch.no_reservation_id.as_deref(), Some("carol_res"));
+
+        assert!(orderbook.is_empty());
+        assert_eq!(orderbook.phase, TradingPhase::CONTINUOUS);
+    }
+
+    #[test]
+    fn test_pro_rata_splits_level_by_size() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        orderbook.allocation = Allocation::PRORATA;
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        matcher.place_order(limit("mm1", OrderSide::SELL, Outcome::YES, 55, 10)).unwrap();
+        matcher.place_order(limit("mm2", OrderSide::SELL, Outcome::YES, 55, 30)).unwrap();
+        matcher.place_order(limit("mm3", OrderSide::SELL, Outcome::YES, 56, 50)).unwrap();
+
+        let result = matcher.place_order(limit("taker", OrderSide::BUY, Outcome::YES, 55, 20)).unwrap();
+        let fills: Vec<(&str, Lots)> =
+            result.trades.iter().map(|t| (t.seller_id.as_str(), t.quantity)).collect();
+        assert_eq!(fills, vec![("mm1", 5), ("mm2", 15)]);
+        assert_eq!(result.order.order_status, OrderStatus::FILLED);
+        assert_eq!(orderbook.best_ask(Outcome::YES), Some(55));
+        assert_eq!(orderbook.len(), 3);
+    }
+
+    #[test]
+    fn test_reservation_backs_one_live_order() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        matcher.place_order(limit("alice", OrderSide::BUY, Outcome::YES, 40, 10)).unwrap();
+        let err = matcher
+            .place_order(limit("alice", OrderSide::BUY, Outcome::YES, 41, 10))
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<RejectReason>(), Some(&RejectReason::DuplicateReservation));
+
+        // Once the first order is filled the reservation is free again
+        matcher.place_order(limit("bob", OrderSide::SELL, Outcome::YES, 40, 10)).unwrap();
+        matcher.place_order(limit("alice", OrderSide::BUY, Outcome::YES, 41, 10)).unwrap();
+    }
+
+    #[test]
+    fn test_categorical_mints_complete_set() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::with_outcomes("market_test".to_string(), spec, 3);
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        matcher.place_order(limit("alice", OrderSide::BUY, Outcome(0), 50, 10)).unwrap();
+        matcher.place_order(limit("bob", OrderSide::BUY, Outcome(1), 30, 4)).unwrap();
+
+        // 0.50 + 0.30 + 0.15 < 1.00: nothing mints yet
+        let short = matcher.place_order(limit("carol", OrderSide::BUY, Outcome(2), 15, 10)).unwrap();
+        assert!(short.complete_set_matches.is_empty());
+
+        let result = matcher.place_order(limit("dave", OrderSide::BUY, Outcome(2), 20, 10)).unwrap();
+        assert!(result.complementary_matches.is_empty());
+        assert_eq!(result.complete_set_matches.len(), 1);
+        let set = &result.complete_set_matches[0];
+        assert_eq!(set.quantity, 4);
+        let legs: Vec<(u8, &str, Ticks)> =
+            set.legs.iter().map(|l| (l.outcome.0, l.buyer_id.as_str(), l.price)).collect();
+        assert_eq!(legs, vec![(0, "alice", 50), (1, "bob", 30), (2, "dave", 20)]);
+        assert_eq!(result.order.filled, 4);
+
+        let err = matcher.place_order(limit("erin", OrderSide::BUY, Outcome(3), 10, 1)).unwrap_err();
+        assert_eq!(err.downcast_ref::<RejectReason>(), Some(&RejectReason::InvalidOutcome));
+    }
+
+    #[test]
+    fn test_mmp_pulls_quotes_after_rapid_fills() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        orderbook.mmp.configure(
+            "mm",
+            MmpConfig {
+                window: std::time::Duration::from_secs(1),
+                max_quantity: None,
+                max_fills: Some(2),
+            },
+        );
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        for (price, res) in [(55, "r1"), (56, "r2"), (57, "r3")] {
+            let mut quote = limit("mm", OrderSide::SELL, Outcome::YES, price, 5);
+            quote.reservation_id = Some(res.to_string());
+            matcher.place_order(quote).unwrap();
+        }
+
+        let result = matcher.place_order(limit("taker", OrderSide::BUY, Outcome::YES, 56, 10)).unwrap();
+        assert_eq!(result.trades.len(), 2);
+        assert_eq!(result.mmp_triggered.len(), 1);
+        let event = &result.mmp_triggered[0];
+        assert_eq!((event.user_id.as_str(), event.filled_quantity, event.fill_count), ("mm", 10, 2));
+        let pulled: Vec<Ticks> = event.cancelled.iter().map(|o| o.price).collect();
+        assert_eq!(pulled, vec![57]);
+
+        // Frozen until reset
+        let err = matcher.place_order(limit("mm", OrderSide::SELL, Outcome::YES, 58, 5)).unwrap_err();
+        assert_eq!(err.downcast_ref::<RejectReason>(), Some(&RejectReason::MmpTriggered));
+        assert!(orderbook.is_empty());
+        assert!(orderbook.mmp.reset("mm"));
+    }
+
+    #[test]
+    fn test_mmp_pulls_whole_groups() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        orderbook.mmp.configure(
+            "mm",
+            MmpConfig {
+                window: std::time::Duration::from_secs(1),
+                max_quantity: None,
+                max_fills: Some(1),
+            },
+        );
+        let mut matcher = Matcher::new(&mut orderbook);
+        matcher.place_order(limit("mm", OrderSide::SELL, Outcome::YES, 55, 5)).unwrap();
+        let mut profit = limit("mm", OrderSide::SELL, Outcome::YES, 70, 5);
+        profit.reservation_id = Some("mm_profit".to_string());
+        let mut stop = limit("mm", OrderSide::SELL, Outcome::YES, 40, 5);
+        stop.reservation_id = Some("mm_stop".to_string());
+        let stop_trigger = Trigger { kind: TriggerKind::STOP, price: 45 };
+        matcher.place_oco([(profit, None), (stop, Some(stop_trigger))]).unwrap();
+
+        let result = matcher.place_order(limit("taker", OrderSide::BUY, Outcome::YES, 55, 5)).unwrap();
+        let pulled: Vec<Ticks> = result.mmp_triggered[0].cancelled.iter().map(|o| o.price).collect();
+        assert_eq!(pulled, vec![70, 40]);
+        assert!(orderbook.is_empty());
+        assert!(orderbook.groups.is_empty());
+        assert!(!orderbook.groups.has_reservation("mm_stop"));
+    }
+
+    #[test]
+    fn test_iceberg_sweeps_slice_by_slice() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        let mut iceberg = limit("whale", OrderSide::SELL, Outcome::YES, 55, 30);
+        iceberg.order_type = OrderType::ICEBERG;
+        iceberg.display_quantity = Some(10);
+        matcher.place_order(iceberg.clone()).unwrap();
+
+        let result = matcher.place_order(limit("taker", OrderSide::BUY, Outcome::YES, 55, 25)).unwrap();
+        let fills: Vec<Lots> = result.trades.iter().map(|t| t.quantity).collect();
+        assert_eq!(fills, vec![10, 10, 5]);
+
+        // A slice larger than the order is refused
+        iceberg.display_quantity = Some(31);
+        iceberg.reservation_id = None;
+        let err = matcher.place_order(iceberg).unwrap_err();
+        assert_eq!(err.downcast_ref::<RejectReason>(), Some(&RejectReason::InvalidQuantity));
+
+        assert_eq!(orderbook.get_depth(Outcome::YES, 1).asks[0].quantity, 5);
+        assert_eq!(orderbook.get_full_depth(Outcome::YES, 1).asks[0].quantity, 5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_stop_fires_when_last_trade_crosses() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        matcher.place_order(limit("bob", OrderSide::BUY, Outcome::YES, 50, 10)).unwrap();
+        matcher.place_order(limit("carol", OrderSide::BUY, Outcome::YES, 45, 10)).unwrap();
+
+        let mut stop = limit("alice", OrderSide::SELL, Outcome::YES, 0, 5);
+        stop.order_type = OrderType::MARKET;
+        let held = matcher
+            .place_conditional(stop, Trigger { kind: TriggerKind::STOP, price: 48 })
+            .unwrap();
+        assert_eq!(held.order.order_status, OrderStatus::UNTRIGGERED);
+
+        // Trading at 0.50 leaves the stop alone
+        let above = matcher.place_order(limit("dave", OrderSide::SELL, Outcome::YES, 50, 10)).unwrap();
+        assert!(above.triggered.is_empty());
+
+        // A print at 0.45 elects it, and it sells into what is left of carol
+        let result = matcher.place_order(limit("erin", OrderSide::SELL, Outcome::YES, 45, 5)).unwrap();
+        assert_eq!(result.triggered.len(), 1);
+        let fired = result.triggered[0].result.as_ref().unwrap();
+        assert_eq!(fired.order.user_id, "alice");
+        assert_eq!(fired.order.order_status, OrderStatus::FILLED);
+        let fills: Vec<(&str, Ticks, Lots)> =
+            fired.trades.iter().map(|t| (t.buyer_id.as_str(), t.price, t.quantity)).collect();
+        assert_eq!(fills, vec![("carol", 45, 5)]);
+        assert!(orderbook.triggers.is_empty());
+    }
+
+    #[test]
+    fn test_bracket_exits_follow_the_position() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        // Both exits offer the same ten tokens; only one of them can fill
+        orderbook.inventory.enforced = true;
+        orderbook.inventory.set("bob", Outcome::YES, 10);
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        let entry = limit("alice", OrderSide::BUY, Outcome::YES, 40, 10);
+        let mut take_profit = limit("alice", OrderSide::SELL, Outcome::YES, 60, 10);
+        take_profit.reservation_id = Some("alice_tp".to_string());
+        let mut stop = limit("alice", OrderSide::SELL, Outcome::YES, 0, 10);
+        stop.order_type = OrderType::MARKET;
+        stop.reservation_id = Some("alice_stop".to_string());
+        let stop_id = stop.order_id;
+        let placed = matcher
+            .place_bracket((entry, None), take_profit, stop, Trigger { kind: TriggerKind::STOP, price: 30 })
+            .unwrap();
+        assert_eq!(placed.legs[1].order.order_status, OrderStatus::UNTRIGGERED);
+        assert!(matcher.orderbook.triggers.is_empty());
+
+        // Filling the entry rests the take-profit and arms the stop
+        let filled = matcher.place_order(limit("bob", OrderSide::SELL, Outcome::YES, 40, 10)).unwrap();
+        assert_eq!(filled.triggered.len(), 1);
+        assert!(filled.triggered[0].trigger.is_none());
+        assert_eq!(filled.triggered[0].result.as_ref().unwrap().order.order_status, OrderStatus::OPEN);
+        assert!(matcher.orderbook.triggers.contains(&stop_id));
+
+        // Closing the position at the take-profit pulls what is left of the stop
+        matcher.place_order(limit("carol", OrderSide::BUY, Outcome::YES, 60, 4)).unwrap();
+        let closed = matcher.place_order(limit("dave", OrderSide::BUY, Outcome::YES, 60, 6)).unwrap();
+        assert_eq!(closed.cancelled.len(), 1);
+        assert_eq!(closed.cancelled[0].order_id, stop_id);
+        assert_eq!(closed.cancelled[0].remaining(), 6);
+        assert!(orderbook.triggers.is_empty());
+        assert!(orderbook.groups.is_empty());
+    }
+
+    #[test]
+    fn test_market_order_stops_at_price_band() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        orderbook.bands.configure(BandConfig { width: Some(10), breaker: None });
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        matcher.place_order(limit("mm1", OrderSide::SELL, Outcome::YES, 50, 6)).unwrap();
+        matcher.place_order(limit("mm2", OrderSide::SELL, Outcome::YES, 55, 5)).unwrap();
+        matcher.place_order(limit("mm3", OrderSide::SELL, Outcome::YES, 70, 5)).unwrap();
+        // Sets the reference at 0.50, so the band is 0.40..0.60
+        matcher.place_order(limit("bob", OrderSide::BUY, Outcome::YES, 50, 1)).unwrap();
+
+        let rejected = matcher.place_order(limit("carol", OrderSide::BUY, Outcome::YES, 65, 1)).unwrap_err();
+        assert_eq!(rejected.downcast_ref::<RejectReason>(), Some(&RejectReason::PriceOutsideBand));
+
+        let mut sweep = limit("alice", OrderSide::BUY, Outcome::YES, 0, 15);
+        sweep.order_type = OrderType::MARKET;
+        let result = matcher.place_order(sweep).unwrap();
+        let prices: Vec<Ticks> = result.trades.iter().map(|t| t.price).collect();
+        assert_eq!(prices, vec![50, 55]);
+        assert_eq!(result.order.filled, 10);
+        assert_eq!(orderbook.best_ask(Outcome::YES), Some(70));
+    }
+
+    #[test]
+    fn test_sells_are_limited_to_unoffered_position() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        orderbook.inventory.enforced = true;
+        orderbook.inventory.set("alice", Outcome::YES, 10);
+        let mut matcher = Matcher::new(&mut orderbook);
+
+        matcher.place_order(limit("alice", OrderSide::SELL, Outcome::YES, 60, 8)).unwrap();
+        let mut over = limit("alice", OrderSide::SELL, Outcome::YES, 65, 5);
+        over.reservation_id = None;
+        let rejected = matcher.place_order(over.clone()).unwrap_err();
+        assert_eq!(rejected.downcast_ref::<RejectReason>(), Some(&RejectReason::RiskLimit));
+
+        // Reduce-only is trimmed to the 2 not already offered at 0.60
+        over.reduce_only = true;
+        let trimmed = matcher.place_order(over).unwrap();
+        assert_eq!(trimmed.order.quantity, 2);
+
+        // Buyers of a fill or a mint hold the tokens afterwards
+        matcher.place_order(limit("bob", OrderSide::BUY, Outcome::YES, 60, 8)).unwrap();
+        matcher.place_order(limit("carol", OrderSide::BUY, Outcome::NO, 40, 3)).unwrap();
+        matcher.place_order(limit("dave", OrderSide::BUY, Outcome::YES, 60, 3)).unwrap();
+        assert_eq!(orderbook.inventory.held("alice", Outcome::YES), 2);
+        assert_eq!(orderbook.inventory.available("alice", Outcome::YES), 0);
+        assert_eq!(orderbook.inventory.held("bob", Outcome::YES), 8);
+        assert_eq!(orderbook.inventory.held("carol", Outcome::NO), 3);
+        assert_eq!(orderbook.inventory.held("dave", Outcome::YES), 3);
+    }
+
+    #[test]
+    fn test_held_sells_are_checked_again_when_they_fire() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        orderbook.inventory.enforced = true;
+        orderbook.inventory.set("alice", Outcome::YES, 10);
+        orderbook.inventory.set("bob", Outcome::YES, 1);
+        let stop = Trigger { kind: TriggerKind::STOP, price: 45 };
+        let mut reduce_only = limit("alice", OrderSide::SELL, Outcome::YES, 40, 6);
+        reduce_only.reduce_only = true;
+        let mut plain = limit("alice", OrderSide::SELL, Outcome::YES, 40, 4);
+        plain.reservation_id = None;
+        Matcher::new(&mut orderbook).place_conditional(reduce_only, stop).unwrap();
+        Matcher::new(&mut orderbook).place_conditional(plain, stop).unwrap();
+
+        // The held stops already offer all ten
+        assert_eq!(orderbook.inventory.available("alice", Outcome::YES), 0);
+        let mut over = limit("alice", OrderSide::SELL, Outcome::YES, 60, 1);
+        over.reservation_id = None;
+        let rejected = Matcher::new(&mut orderbook).place_order(over).unwrap_err();
+        assert_eq!(rejected.downcast_ref::<RejectReason>(), Some(&RejectReason::RiskLimit));
+
+        // Six of the tokens go before the stops fire
+        orderbook.inventory.set("alice", Outcome::YES, 4);
+        Matcher::new(&mut orderbook).place_order(limit("bob", OrderSide::SELL, Outcome::YES, 45, 1)).unwrap();
+        let result = Matcher::new(&mut orderbook).place_order(limit("carol", OrderSide::BUY, Outcome::YES, 45, 1)).unwrap();
+        assert_eq!(result.triggered.len(), 2);
+        let trimmed = result.triggered[0].result.as_ref().unwrap();
+        assert_eq!((trimmed.order.quantity, trimmed.order.order_status), (4, OrderStatus::OPEN));
+        let refused = result.triggered[1].result.as_ref().unwrap_err();
+        assert_eq!(refused.downcast_ref::<RejectReason>(), Some(&RejectReason::RiskLimit));
+        assert_eq!(orderbook.inventory.committed("alice", Outcome::YES), 4);
+    }
+
+    #[test]
+    fn test_closed_book_refuses_orders() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut orderbook = OrderBook::new("market_test".to_string(), spec);
+        orderbook.closed = true;
+
+        let rejected = Matcher::new(&mut orderbook)
+            .place_order(limit("alice", OrderSide::BUY, Outcome::YES, 60, 5))
+            .unwrap_err();
+        assert_eq!(rejected.downcast_ref::<RejectReason>(), Some(&RejectReason::MarketClosed));
+        assert_eq!(orderbook.len(), 0);
+    }
+}
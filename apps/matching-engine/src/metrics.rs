@@ -0,0 +1,228 @@
+use anyhow::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use rust_decimal::prelude::ToPrimitive;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::order::OrderSide;
+use crate::orderbook::OrderBook;
+
+// Matching runs in microseconds; the tail buckets catch lock contention
+const LATENCY_BUCKETS: &[f64] = &[
+    0.00001, 0.000025, 0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.05, 0.25,
+];
+
+pub struct Metrics {
+    registry: Registry,
+
+    pub orders_total: IntCounterVec,
+    pub match_latency_seconds: HistogramVec,
+    pub resting_orders: IntGaugeVec,
+    pub book_levels: IntGaugeVec,
+    pub book_quantity: GaugeVec,
+    pub rejects_total: IntCounterVec,
+    pub grpc_requests_total: IntCounterVec,
+    pub mmp_triggers_total: IntCounterVec,
+    pub circuit_breaker_trips_total: IntCounterVec,
+    pub trade_corrections_total: IntCounterVec,
+    /// 1 while the liveness watchdog can reach every book, 0 when wedged
+    pub engine_up: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("matching_engine".to_string()), None)?;
+
+        let orders_total = IntCounterVec::new(
+            Opts::new("orders_total", "Orders received, by market"),
+            &["market_id", "side", "outcome", "order_type"],
+        )?;
+        let match_latency_seconds = HistogramVec::new(
+            HistogramOpts::new("match_latency_seconds", "Time spent in Matcher::place_order")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["market_id"],
+        )?;
+        let resting_orders = IntGaugeVec::new(
+            Opts::new("resting_orders", "Orders resting in the book"),
+            &["market_id"],
+        )?;
+        let book_levels = IntGaugeVec::new(
+            Opts::new("book_levels", "Non-empty price levels per book side"),
+            &["market_id", "outcome", "side"],
+        )?;
+        let book_quantity = GaugeVec::new(
+            Opts::new("book_quantity", "Resting quantity per book side"),
+            &["market_id", "outcome", "side"],
+        )?;
+        let rejects_total = IntCounterVec::new(
+            Opts::new("rejects_total", "Orders rejected, by reason"),
+            &["market_id", "reason"],
+        )?;
+        let grpc_requests_total = IntCounterVec::new(
+            Opts::new("grpc_requests_total", "gRPC requests handled, by method and status code"),
+            &["method", "code"],
+        )?;
+        let mmp_triggers_total = IntCounterVec::new(
+            Opts::new("mmp_triggers_total", "Times market-maker protection pulled a user's quotes"),
+            &["market_id"],
+        )?;
+        let circuit_breaker_trips_total = IntCounterVec::new(
+            Opts::new("circuit_breaker_trips_total", "Times a market's circuit breaker tripped, by action"),
+            &["market_id", "action"],
+        )?;
+        let trade_corrections_total = IntCounterVec::new(
+            Opts::new("trade_corrections_total", "Trades busted or repriced, by action"),
+            &["market_id", "action"],
+        )?;
+        let engine_up = IntGauge::new("up", "Liveness watchdog result")?;
+
+        registry.register(Box::new(orders_total.clone()))?;
+        registry.register(Box::new(match_latency_seconds.clone()))?;
+        registry.register(Box::new(resting_orders.clone()))?;
+        registry.register(Box::new(book_levels.clone()))?;
+        registry.register(Box::new(book_quantity.clone()))?;
+        registry.register(Box::new(rejects_total.clone()))?;
+        registry.register(Box::new(grpc_requests_total.clone()))?;
+        registry.register(Box::new(mmp_triggers_total.clone()))?;
+        registry.register(Box::new(circuit_breaker_trips_total.clone()))?;
+        registry.register(Box::new(trade_corrections_total.clone()))?;
+        registry.register(Box::new(engine_up.clone()))?;
+
+        engine_up.set(1);
+
+        Ok(Self {
+            registry,
+            orders_total,
+            match_latency_seconds,
+            resting_orders,
+            book_levels,
+            book_quantity,
+            rejects_total,
+            grpc_requests_total,
+            mmp_triggers_total,
+            circuit_breaker_trips_total,
+            trade_corrections_total,
+            engine_up,
+        })
+    }
+
+    pub fn record_grpc(&self, method: &str, code: tonic::Code) {
+        self.grpc_requests_total
+            .with_label_values(&[method, &format!("{:?}", code)])
+            .inc();
+    }
+
+    pub fn record_reject(&self, market_id: &str, reason: &str) {
+        self.rejects_total.with_label_values(&[market_id, reason]).inc();
+    }
+
+    /// Refresh the resting-order and depth gauges for one book
+    pub fn observe_book(&self, book: &OrderBook) {
+        let market_id = book.market_id.as_str();
+        self.resting_orders
+            .with_label_values(&[market_id])
+            .set(book.len() as i64);
+
+        for outcome in book.outcomes() {
+            let outcome_label = outcome.label(book.outcome_count());
+            for side in [OrderSide::BUY, OrderSide::SELL] {
+                let (levels, quantity) = book.side_totals(side, outcome);
+                let labels = [market_id, outcome_label.as_str(), side.as_str()];
+                self.book_levels.with_label_values(&labels).set(levels as i64);
+                self.book_quantity
+                    .with_label_values(&labels)
+                    .set(book.spec.lots_to_quantity(quantity).to_f64().unwrap_or(f64::MAX));
+            }
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn handle(&self, req: Request<Body>) -> Response<Body> {
+        let response = Response::builder();
+        match (req.method(), req.uri().path()) {
+            (&Method::GET, "/metrics") => match self.encode() {
+                Ok(body) => response
+                    .header(hyper::header::CONTENT_TYPE, TextEncoder::new().format_type())
+                    .body(Body::from(body)),
+                Err(e) => response
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::from(e.to_string())),
+            },
+            (&Method::GET, "/healthz") if self.engine_up.get() == 1 => response.body(Body::from("OK")),
+            (&Method::GET, "/healthz") => response
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(Body::from("NOT_SERVING")),
+            _ => response.status(StatusCode::NOT_FOUND).body(Body::empty()),
+        }
+        .expect("static response parts are valid")
+    }
+}
+
+/// Serve `/metrics` (Prometheus text format) and `/healthz` over plain HTTP
+pub async fn start_metrics_server(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let make_svc = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let metrics = metrics.clone();
+                async move { Ok::<_, Infallible>(metrics.handle(req)) }
+            }))
+        }
+    });
+
+    info!("Metrics server listening on {}", addr);
+    Server::bind(&addr).serve(make_svc).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_spec::MarketSpec;
+    use crate::order::{Order, OrderStatus, OrderType, Outcome};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_book_gauges_are_exported() {
+        let metrics = Metrics::new().unwrap();
+        let mut book = OrderBook::new("m1".to_string(), MarketSpec::default());
+        book.add_order(Order {
+            order_id: Uuid::new_v4(),
+            user_id: "alice".to_string(),
+            market_id: "m1".to_string(),
+            side: OrderSide::BUY,
+            outcome: Outcome::YES,
+            order_type: OrderType::LIMIT,
+            price: 4000,
+            quantity: 2_500_000,
+            filled: 0,
+            order_status: OrderStatus::OPEN,
+            reservation_id: None,
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+        });
+
+        metrics.observe_book(&book);
+        metrics.record_reject("m1", "self_trade");
+
+        let text = String::from_utf8(metrics.encode().unwrap()).unwrap();
+        assert!(text.contains(r#"matching_engine_resting_orders{market_id="m1"} 1"#));
+        assert!(text.contains(r#"matching_engine_book_quantity{market_id="m1",outcome="YES",side="BUY"} 2.5"#));
+        assert!(text.contains(r#"matching_engine_rejects_total{market_id="m1",reason="self_trade"} 1"#));
+        assert!(text.contains("matching_engine_up 1"));
+    }
+}
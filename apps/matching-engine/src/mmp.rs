@@ -0,0 +1,148 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::order::{Lots, Order};
+
+/// Limits on how much of a user's resting liquidity may fill within `window`
+/// before all their quotes in the market are pulled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmpConfig {
+    pub window: Duration,
+    /// Trips once filled quantity in the window reaches this
+    pub max_quantity: Option<Lots>,
+    /// Trips once the number of fills in the window reaches this
+    pub max_fills: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct MmpState {
+    config: MmpConfig,
+    fills: VecDeque<(Instant, Lots)>,
+    window_quantity: Lots,
+    frozen: bool,
+}
+
+impl MmpState {
+    fn new(config: MmpConfig) -> Self {
+        Self {
+            config,
+            fills: VecDeque::new(),
+            window_quantity: 0,
+            frozen: false,
+        }
+    }
+
+    fn record_fill(&mut self, quantity: Lots, now: Instant) -> bool {
+        if self.frozen {
+            return false;
+        }
+
+        while let Some(&(at, qty)) = self.fills.front() {
+            if now.duration_since(at) < self.config.window {
+                break;
+            }
+            self.fills.pop_front();
+            self.window_quantity -= qty;
+        }
+        self.fills.push_back((now, quantity));
+        self.window_quantity += quantity;
+
+        self.frozen = self.config.max_quantity.is_some_and(|max| self.window_quantity >= max)
+            || self.config.max_fills.is_some_and(|max| self.fills.len() >= max);
+        self.frozen
+    }
+
+    fn reset(&mut self) {
+        self.fills.clear();
+        self.window_quantity = 0;
+        self.frozen = false;
+    }
+}
+
+/// Market-maker protection for every opted-in user of one market
+#[derive(Debug, Default)]
+pub struct MarketMakerProtection {
+    users: HashMap<String, MmpState>,
+}
+
+impl MarketMakerProtection {
+    /// Opt a user in, or change their limits. The fill window is kept, and a
+    /// frozen user stays frozen until `reset`.
+    pub fn configure(&mut self, user_id: &str, config: MmpConfig) {
+        self.users
+            .entry(user_id.to_string())
+            .and_modify(|state| state.config = config)
+            .or_insert_with(|| MmpState::new(config));
+    }
+
+    pub fn remove(&mut self, user_id: &str) {
+        self.users.remove(user_id);
+    }
+
+    pub fn is_frozen(&self, user_id: &str) -> bool {
+        self.users.get(user_id).is_some_and(|state| state.frozen)
+    }
+
+    /// Count a fill against one of the user's resting orders. Returns the
+    /// window totals `(quantity, fills)` when this fill trips protection.
+    pub fn record_fill(&mut self, user_id: &str, quantity: Lots, now: Instant) -> Option<(Lots, usize)> {
+        let state = self.users.get_mut(user_id)?;
+        let tripped = state.record_fill(quantity, now);
+        tripped.then_some((state.window_quantity, state.fills.len()))
+    }
+
+    /// Unfreeze and start a fresh window. Returns whether the user was frozen.
+    pub fn reset(&mut self, user_id: &str) -> bool {
+        match self.users.get_mut(user_id) {
+            Some(state) => {
+                let was_frozen = state.frozen;
+                state.reset();
+                was_frozen
+            }
+            None => false,
+        }
+    }
+}
+
+/// Emitted when a user's quotes are pulled
+#[derive(Debug, Clone, Serialize)]
+pub struct MmpTriggered {
+    pub market_id: String,
+    pub user_id: String,
+    pub filled_quantity: Lots,
+    pub fill_count: usize,
+    pub cancelled: Vec<Order>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trips_within_window_and_resets() {
+        let mut mmp = MarketMakerProtection::default();
+        mmp.configure(
+            "mm",
+            MmpConfig {
+                window: Duration::from_secs(1),
+                max_quantity: Some(100),
+                max_fills: None,
+            },
+        );
+        let start = Instant::now();
+
+        assert_eq!(mmp.record_fill("mm", 60, start), None);
+        // The first fill has aged out, so 60 + 30 never counts together
+        assert_eq!(mmp.record_fill("mm", 30, start + Duration::from_secs(2)), None);
+        assert_eq!(mmp.record_fill("mm", 70, start + Duration::from_millis(2500)), Some((100, 2)));
+        assert!(mmp.is_frozen("mm"));
+
+        // Frozen users don't trip twice; other users aren't tracked at all
+        assert_eq!(mmp.record_fill("mm", 500, start + Duration::from_secs(3)), None);
+        assert_eq!(mmp.record_fill("taker", 500, start), None);
+
+        assert!(mmp.reset("mm"));
+        assert!(!mmp.is_frozen("mm"));
+    }
+}
@@ -1,7 +1,12 @@
 use chrono::{DateTime,Utc};
-use rust_decimal::Decimal;
 use serde::{Serialize,Deserialize};
 use uuid::Uuid;
+
+/// Price as a whole number of the market's tick size
+pub type Ticks = u64;
+/// Quantity as a whole number of the market's lot size
+pub type Lots = u64;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 
 pub struct  Order{
@@ -11,31 +16,38 @@ pub struct  Order{
     pub side : OrderSide,
     pub outcome : Outcome,
     pub order_type : OrderType,
-    pub price : Decimal,
-    pub quantity : Decimal,
-    pub filled : Decimal,
+    pub price : Ticks,
+    pub quantity : Lots,
+    pub filled : Lots,
     pub order_status : OrderStatus,
     pub reservation_id : Option<String>,
+    /// ICEBERG only: the slice shown in the book at any one time
+    #[serde(default)]
+    pub display_quantity : Option<Lots>,
+    /// SELL only: never more than the seller holds and has not already offered
+    #[serde(default)]
+    pub reduce_only : bool,
     pub created_at : DateTime<Utc>,
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OrderSide {
     BUY,    
     SELL
 }
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Outcome {
-    YES,
-    NO,
-}
+/// Index of an outcome within its market. Binary markets trade YES (0) and
+/// NO (1); categorical markets number their outcomes 0..N.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Outcome(pub u8);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum  OrderType {
     LIMIT,
     MARKET,
-    POSTONLY,    
+    POSTONLY,
+    /// Limit order that shows only `display_quantity` at a time
+    ICEBERG,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -45,16 +57,88 @@ pub enum OrderStatus {
     OPEN,
     PARTIAL,
     FILLED,
-    CANCELLED,    
+    CANCELLED,
+    /// Held in the trigger book until its stop/take-profit price trades
+    UNTRIGGERED,
 }
 
 impl  Order {
     
-    pub fn remaining(&self)->Decimal{
+    pub fn remaining(&self)->Lots{
         self.quantity - self.filled
     }
 
     pub fn is_filled(&self) -> bool{
         self.filled >= self.quantity
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl Order {
+    /// A new LIMIT order in `market_test`
+    pub fn limit(user: &str, side: OrderSide, outcome: Outcome, price: Ticks, quantity: Lots) -> Self {
+        Order {
+            order_id: Uuid::new_v4(),
+            user_id: user.to_string(),
+            market_id: "market_test".to_string(),
+            side,
+            outcome,
+            order_type: OrderType::LIMIT,
+            price,
+            quantity,
+            filled: 0,
+            order_status: OrderStatus::PENDING,
+            reservation_id: None,
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+        }
+    }
+}
+impl OrderSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderSide::BUY => "BUY",
+            OrderSide::SELL => "SELL",
+        }
+    }
+}
+
+impl Outcome {
+    pub const YES: Outcome = Outcome(0);
+    pub const NO: Outcome = Outcome(1);
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// String form used by the v1 API and metrics: YES/NO in a binary
+    /// market, the outcome index otherwise
+    pub fn label(self, outcome_count: usize) -> String {
+        match self {
+            Outcome::YES if outcome_count == 2 => "YES".to_string(),
+            Outcome::NO if outcome_count == 2 => "NO".to_string(),
+            Outcome(index) => index.to_string(),
+        }
+    }
+
+    /// Inverse of `label`; YES/NO are accepted for any market
+    pub fn parse(label: &str) -> Option<Outcome> {
+        match label {
+            "YES" => Some(Outcome::YES),
+            "NO" => Some(Outcome::NO),
+            index => index.parse().ok().map(Outcome),
+        }
+    }
+}
+
+impl OrderType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderType::LIMIT => "LIMIT",
+            OrderType::MARKET => "MARKET",
+            OrderType::POSTONLY => "POSTONLY",
+            OrderType::ICEBERG => "ICEBERG",
+        }
+    }
+}
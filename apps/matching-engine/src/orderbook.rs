@@ -1,234 +1,561 @@
-use anyhow::{Result};
-use dashmap::DashMap;
-use ordered_float::OrderedFloat;
-use rust_decimal::Decimal;
-use tonic::transport::Body;
-use std::collections::{BTreeMap,VecDeque};
+use slab::Slab;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 
-use crate::order::{self, Order, OrderSide, Outcome};
-
-type PriceLevel = BTreeMap<Decimal,VecDeque<Order>>;
+use crate::allocation::Allocation;
+use crate::auction::TradingPhase;
+use crate::bands::PriceBands;
+use crate::corrections::TradeLog;
+use crate::groups::OrderGroups;
+use crate::inventory::Inventory;
+use crate::market_spec::MarketSpec;
+use crate::marketdata::{Journal, OrderEvent, OrderEventKind, Print, PublicOrder};
+use crate::mmp::MarketMakerProtection;
+use crate::order::{Lots, Order, OrderSide, OrderStatus, Outcome, Ticks};
+use crate::triggers::{Conditional, Trigger, TriggerBook};
+
+/// Shared handle the gRPC layer keeps per market
+pub type SharedOrderBook = Arc<RwLock<OrderBook>>;
+
+/// Key of a resting order inside the book's slab.
+/// Only valid while the order is resting; slab slots are reused after removal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OrderHandle(usize);
+
+/// An order plus its intrusive links to its neighbours at the same price
+struct OrderNode {
+    order: Order,
+    // What is left of the displayed slice; the full remaining quantity
+    // unless the order is an iceberg
+    visible: Lots,
+    // The id the level-3 feed knows this order (or iceberg slice) by
+    public_id: u64,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
 
-pub struct  OrderBook{
-    pub market_id :String,
+/// FIFO time queue for one price level, threaded through the slab
+#[derive(Debug, Default)]
+struct PriceQueue {
+    head: Option<usize>,
+    tail: Option<usize>,
+    order_count: usize,
+    // Sum of remaining quantity, kept in step with every fill
+    quantity: Lots,
+    // Sum of the displayed slices; what get_depth shows
+    visible: Lots,
+}
 
-    pub yes_bids : Arc<RwLock<PriceLevel>>,
-    pub yes_asks : Arc<RwLock<PriceLevel>>,
-    pub no_bids : Arc<RwLock<PriceLevel>>,
-    pub no_asks : Arc<RwLock<PriceLevel>>,
+type BookSide = BTreeMap<Ticks, PriceQueue>;
 
-    pub orders : Arc<DashMap<Uuid,Order>>
+/// Bids and asks for one outcome
+#[derive(Default)]
+struct OutcomeBook {
+    bids: BookSide,
+    asks: BookSide,
+}
 
+pub struct OrderBook {
+    pub market_id: String,
+    pub spec: MarketSpec,
+    pub phase: TradingPhase,
+    /// Set under the write lock when the market expires; every order is
+    /// refused from then on
+    pub closed: bool,
+    pub allocation: Allocation,
+    pub mmp: MarketMakerProtection,
+    /// Price bands and circuit breaker
+    pub bands: PriceBands,
+    /// Users' token holdings; resting SELLs are committed against them here
+    pub inventory: Inventory,
+    /// Stop and take-profit orders; not part of the visible sides below
+    pub triggers: TriggerBook,
+    /// OCO pairs and brackets
+    pub groups: OrderGroups,
+    /// What it traded within the correction window
+    pub trades: TradeLog,
+
+    // Every resting order lives exactly once, here
+    slab: Slab<OrderNode>,
+    index: HashMap<Uuid, usize>,
+    // reservation_id -> slab key; a reservation backs at most one live order
+    reservations: HashMap<String, usize>,
+    // Resting orders as they stood after each fill, until the engine takes
+    // them for execution reports
+    filled: Vec<Order>,
+    // What has changed since the engine last published market data
+    journal: Journal,
+    last_public_id: u64,
+
+    // Indexed by Outcome
+    outcomes: Vec<OutcomeBook>,
 }
 
-impl  Clone for OrderBook {
-    fn clone(&self) -> Self {
-        Self{ 
-            market_id : self.market_id.clone(),
-            yes_bids : Arc::clone(&self.yes_bids),
-            yes_asks : Arc::clone(&self.yes_asks),
-            no_bids: Arc::clone(&self.no_bids),
-            no_asks : Arc::clone(&self.no_asks),
-            orders : Arc::clone(&self.orders)
-        }
+impl OrderBook {
+    /// A binary YES/NO market
+    pub fn new(market_id: String, spec: MarketSpec) -> Self {
+        Self::with_outcomes(market_id, spec, 2)
     }
-}
 
-impl OrderBook{
-    pub fn new(market_id:String)->Self{
-        Self{ 
+    /// A market with `outcome_count` mutually exclusive outcomes, exactly one
+    /// of which pays out 1.00
+    pub fn with_outcomes(market_id: String, spec: MarketSpec, outcome_count: usize) -> Self {
+        assert!(
+            (2..=u8::MAX as usize + 1).contains(&outcome_count),
+            "a market needs between 2 and 256 outcomes"
+        );
+        Self {
             market_id,
-            yes_bids:  Arc::new(RwLock::new(BTreeMap::new())),
-            yes_asks : Arc::new(RwLock::new(BTreeMap::new())),
-            no_bids:   Arc::new(RwLock::new(BTreeMap::new())),
-            no_asks :  Arc::new(RwLock::new(BTreeMap::new())),
-            orders :  Arc::new(DashMap::new())
+            spec,
+            phase: TradingPhase::CONTINUOUS,
+            closed: false,
+            allocation: Allocation::FIFO,
+            mmp: MarketMakerProtection::default(),
+            bands: PriceBands::new(outcome_count),
+            inventory: Inventory::default(),
+            triggers: TriggerBook::new(outcome_count),
+            groups: OrderGroups::default(),
+            trades: TradeLog::default(),
+            slab: Slab::new(),
+            index: HashMap::new(),
+            reservations: HashMap::new(),
+            filled: Vec::new(),
+            journal: Journal::default(),
+            last_public_id: 0,
+            outcomes: (0..outcome_count).map(|_| OutcomeBook::default()).collect(),
         }
     }
 
-    // Higest buy price == Best buy price 
-    pub fn best_bid(&self,outcome:Outcome)->Option<Decimal>{
-        let bids = self.get_bids(outcome);
-        let book = bids.read().unwrap();
+    pub fn outcome_count(&self) -> usize {
+        self.outcomes.len()
+    }
 
-        book.iter().rev()
-             .find(|(_,orders)| !orders.is_empty())
-             .map(|(price,_)| *price)
+    pub fn is_binary(&self) -> bool {
+        self.outcomes.len() == 2
     }
-    // Best sell order means Lowes amoung all the price 
-    pub fn best_ask(&self,outcome:Outcome)->Option<Decimal>{
-        let asks = self.get_asks(outcome);
 
-        let book = asks.read().unwrap();
+    /// Every outcome of this market, in index order
+    pub fn outcomes(&self) -> impl Iterator<Item = Outcome> {
+        (0..self.outcomes.len()).map(|i| Outcome(i as u8))
+    }
 
-        book.iter()
-            .find(|(_,orders)| !orders.is_empty())
-            .map(|(price,_)| *price)
+    /// Number of resting orders across all books
+    pub fn len(&self) -> usize {
+        self.slab.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.slab.is_empty()
+    }
 
-    pub fn add_order(&self,order:Order) {
-        // it checks that in which order book we need to add the order or we can say the change the orderbook?
-        let book = self.get_side_mut(order.side, order.outcome);
-        // Then in this we will give a write Permission to That order book 
-        let mut book_guard = book.write().unwrap(); 
+    // Higest buy price == Best buy price
+    // Empty levels are always removed, so the last key is the best bid
+    pub fn best_bid(&self, outcome: Outcome) -> Option<Ticks> {
+        self.side(OrderSide::BUY, outcome).keys().next_back().copied()
+    }
 
-        book_guard.entry(order.price)
-                  .or_insert_with(VecDeque::new).
-                  push_back(order.clone());
+    // Best sell order means Lowest among all the prices
+    pub fn best_ask(&self, outcome: Outcome) -> Option<Ticks> {
+        self.side(OrderSide::SELL, outcome).keys().next().copied()
+    }
 
-        self.orders.insert(order.order_id, order);
+    /// Front order of the best ask level (next maker for a BUY taker)
+    pub fn best_ask_order(&self, outcome: Outcome) -> Option<OrderHandle> {
+        self.side(OrderSide::SELL, outcome)
+            .values()
+            .next()
+            .and_then(|q| q.head)
+            .map(OrderHandle)
     }
 
-    pub fn remove_order(&self, order_id: Uuid) -> Option<Order> {
-        // O(1) lookup in DashMap
-        let (_, order) = self.orders.remove(&order_id)?;
-        
-        // O(log n) lookup in BTreeMap + O(n) removal from VecDeque
-        let book = self.get_side_mut(order.side, order.outcome);
-        let mut book_guard = book.write().unwrap();
-        
-        if let Some(queue) = book_guard.get_mut(&order.price) {
-            queue.retain(|o| o.order_id != order_id);
-            
-            // Clean up empty price levels
-            if queue.is_empty() {
-                book_guard.remove(&order.price);
-            }
+    /// Front order of the best bid level (next maker for a SELL taker)
+    pub fn best_bid_order(&self, outcome: Outcome) -> Option<OrderHandle> {
+        self.side(OrderSide::BUY, outcome)
+            .values()
+            .next_back()
+            .and_then(|q| q.head)
+            .map(OrderHandle)
+    }
+
+    pub fn get(&self, order_id: &Uuid) -> Option<&Order> {
+        self.index.get(order_id).map(|&key| &self.slab[key].order)
+    }
+
+    pub fn handle(&self, order_id: &Uuid) -> Option<OrderHandle> {
+        self.index.get(order_id).copied().map(OrderHandle)
+    }
+
+    pub fn has_reservation(&self, reservation_id: &str) -> bool {
+        self.reservations.contains_key(reservation_id)
+    }
+
+    /// The resting order a reservation backs
+    pub fn by_reservation(&self, reservation_id: &str) -> Option<&Order> {
+        self.reservations.get(reservation_id).map(|key| &self.slab[*key].order)
+    }
+
+    /// Every order resting in the book, in no particular order
+    pub fn resting_orders(&self) -> impl Iterator<Item = &Order> {
+        self.slab.iter().map(|(_, node)| &node.order)
+    }
+
+    /// Panics if the handle is stale
+    pub fn order(&self, handle: OrderHandle) -> &Order {
+        &self.slab[handle.0].order
+    }
+
+    /// Quantity a taker can reach at this order right now: the current
+    /// iceberg slice, or everything that is left
+    pub fn visible(&self, handle: OrderHandle) -> Lots {
+        self.slab[handle.0].visible
+    }
+
+    /// Append an order to the back of its price level's time queue
+    pub fn add_order(&mut self, order: Order) -> OrderHandle {
+        let (side, outcome, price, order_id) = (order.side, order.outcome, order.price, order.order_id);
+        let remaining = order.remaining();
+        let visible = display_slice(&order);
+        let reservation_id = order.reservation_id.clone();
+        if side == OrderSide::SELL {
+            self.inventory.commit(&order.user_id, outcome, remaining);
+        }
+
+        self.last_public_id += 1;
+        let public_id = self.last_public_id;
+        let key = self.slab.insert(OrderNode { order, visible, public_id, prev: None, next: None });
+        self.index.insert(order_id, key);
+        if let Some(reservation_id) = reservation_id {
+            self.reservations.insert(reservation_id, key);
+        }
+
+        let queue = self.side_mut(side, outcome).entry(price).or_default();
+        queue.order_count += 1;
+        queue.quantity += remaining;
+        queue.visible += visible;
+        self.push_back(key);
+        self.journal.touched.push((side, outcome, price));
+        self.journal_order(OrderEventKind::ADDED, key, 0);
+
+        OrderHandle(key)
+    }
+
+    /// Cancel path: index lookup + unlink, no scan of the price level
+    pub fn remove_order(&mut self, order_id: Uuid) -> Option<Order> {
+        let key = self.index.get(&order_id).copied()?;
+        Some(self.unlink(key))
+    }
+
+    /// An order resting in the book or held in the trigger book
+    pub fn working_order(&self, order_id: &Uuid) -> Option<&Order> {
+        match self.index.get(order_id) {
+            Some(&key) => Some(&self.slab[key].order),
+            None => self.triggers.get(order_id),
         }
-        
+    }
+
+    /// Cancel an order resting in the book or held in the trigger book
+    pub fn cancel(&mut self, order_id: Uuid) -> Option<Order> {
+        let mut order = match self.remove_order(order_id) {
+            Some(order) => order,
+            None => {
+                let order = self.triggers.remove(&order_id)?;
+                self.release_held(&order);
+                order
+            }
+        };
+        order.order_status = OrderStatus::CANCELLED;
         Some(order)
     }
-    // get best sell price it means it get Lowest sell price 
-    pub fn pop_best_ask(&self,outcome:Outcome) -> Option<Order>{
-        let ask = &self.get_asks(outcome);
-        let mut book = ask.write().unwrap();
-
-        let best_price = book.iter()
-                                 .find(|(_,orders)| !orders.is_empty())
-                                 .map(|(price, _)| *price)?;
-         
-        let queue = book.get_mut(&best_price)?;
-        let order = queue.pop_front()?;
-        
-        // Clean up empty level
-        if queue.is_empty() {
-            book.remove(&best_price);
+
+    /// Hold an order in the trigger book. A SELL's tokens are spoken for
+    /// from now on, as if it were resting. Returns its sequence number.
+    pub fn hold(&mut self, order: Order, trigger: Trigger) -> u64 {
+        if order.side == OrderSide::SELL {
+            self.inventory.commit(&order.user_id, order.outcome, order.remaining());
+        }
+        self.triggers.add(order, trigger)
+    }
+
+    /// Shrink a held order by up to `quantity`, as `TriggerBook::reduce`
+    pub fn reduce_held(&mut self, order_id: &Uuid, quantity: Lots) -> Option<Order> {
+        let before = self.triggers.get(order_id)?.remaining();
+        let order = self.triggers.reduce(order_id, quantity)?;
+        if order.side == OrderSide::SELL {
+            self.inventory.release(&order.user_id, order.outcome, before - order.remaining());
         }
-        
-        // Remove from orders map
-        self.orders.remove(&order.order_id);
-        
         Some(order)
     }
 
-    /// Pop best bid (for sell order matching)
-    /// Returns the front order at the best bid price
-    pub fn pop_best_bid(&self, outcome: Outcome) -> Option<Order> {
-        let bids = self.get_bids(outcome);
-        let mut book = bids.write().unwrap();
-        
-        // Find highest price with orders (reverse iteration)
-        let best_price = book
+    /// Take out every held order the last prices elect, in sequence order.
+    /// They are checked against the position again before they are placed.
+    pub fn elect(&mut self) -> Vec<Conditional> {
+        let elected = self.triggers.elect();
+        for conditional in &elected {
+            self.release_held(&conditional.order);
+        }
+        elected
+    }
+
+    // A held order has left the trigger book
+    fn release_held(&mut self, order: &Order) {
+        if order.side == OrderSide::SELL {
+            self.inventory.release(&order.user_id, order.outcome, order.remaining());
+        }
+    }
+
+    /// Cancel every working leg of an OCO pair or bracket, including exits
+    /// that were never activated
+    pub fn cancel_group(&mut self, group_id: &Uuid) -> Vec<Order> {
+        let Some(group) = self.groups.cancel(group_id) else {
+            return Vec::new();
+        };
+        let mut cancelled: Vec<Order> = group.live.into_iter().filter_map(|id| self.cancel(id)).collect();
+        cancelled.extend(group.held.into_iter().map(|mut order| {
+            order.order_status = OrderStatus::CANCELLED;
+            order
+        }));
+        cancelled
+    }
+
+    /// Cancel everything working in the market: resting orders, held
+    /// conditionals and bracket exits that were never activated
+    pub fn cancel_all(&mut self) -> Vec<Order> {
+        let mut cancelled: Vec<Order> = Vec::new();
+        for group_id in self.groups.group_ids() {
+            cancelled.extend(self.cancel_group(&group_id));
+        }
+        let keys: Vec<usize> = self.slab.iter().map(|(key, _)| key).collect();
+        let mut rest: Vec<Order> = keys.into_iter().map(|key| self.unlink(key)).collect();
+        for order in self.triggers.drain() {
+            self.release_held(&order);
+            rest.push(order);
+        }
+        for order in &mut rest {
+            order.order_status = OrderStatus::CANCELLED;
+        }
+        cancelled.extend(rest);
+        cancelled
+    }
+
+    /// Shrink a resting order by up to `quantity` without moving it in its
+    /// queue. Returns the order afterwards; it has left the book if nothing
+    /// remains.
+    pub fn reduce(&mut self, order_id: Uuid, quantity: Lots) -> Option<Order> {
+        let key = self.index.get(&order_id).copied()?;
+        let node = &mut self.slab[key];
+        let cut = quantity.min(node.order.remaining());
+        node.order.quantity -= cut;
+        let old_visible = node.visible;
+        node.visible = old_visible.min(node.order.remaining());
+        let new_visible = node.visible;
+        let (side, outcome, price) = (node.order.side, node.order.outcome, node.order.price);
+        if side == OrderSide::SELL {
+            self.inventory.release(&self.slab[key].order.user_id, outcome, cut);
+        }
+
+        if let Some(queue) = self.side_mut(side, outcome).get_mut(&price) {
+            queue.quantity -= cut;
+            queue.visible -= old_visible - new_visible;
+        }
+        self.journal.touched.push((side, outcome, price));
+        // Cutting into an iceberg's reserve changes nothing anyone can see
+        if new_visible != old_visible {
+            self.journal_order(OrderEventKind::REDUCED, key, old_visible - new_visible);
+        }
+
+        if self.slab[key].order.remaining() == 0 {
+            Some(self.unlink(key))
+        } else {
+            Some(self.slab[key].order.clone())
+        }
+    }
+
+    /// Pull every resting order a user has in this market, along with the
+    /// rest of any group one of them belongs to
+    pub fn remove_user_orders(&mut self, user_id: &str) -> Vec<Order> {
+        let order_ids: Vec<Uuid> = self
+            .slab
             .iter()
-            .rev()
-            .find(|(_, orders)| !orders.is_empty())
-            .map(|(price, _)| *price)?;
-        
-        let queue = book.get_mut(&best_price)?;
-        let order = queue.pop_front()?;
-        
-        // Clean up empty level
-        if queue.is_empty() {
-            book.remove(&best_price);
+            .filter(|(_, node)| node.order.user_id == user_id)
+            .map(|(_, node)| node.order.order_id)
+            .collect();
+        let mut cancelled = Vec::new();
+        for order_id in order_ids {
+            match self.groups.group_of(&order_id) {
+                Some(group_id) => cancelled.extend(self.cancel_group(&group_id)),
+                // Already gone if an earlier order's group took it
+                None => cancelled.extend(self.cancel(order_id)),
+            }
         }
-        
-        // Remove from orders map
-        self.orders.remove(&order.order_id);
-        
-        Some(order)
+        cancelled
+    }
+
+    /// Apply a fill to a resting order. The order is removed from the book
+    /// once fully filled. An iceberg whose slice is used up shows a fresh one
+    /// and goes to the back of the level. Returns the order's state after the fill.
+    pub fn fill(&mut self, handle: OrderHandle, quantity: Lots) -> Order {
+        let node = &mut self.slab[handle.0];
+        node.order.filled += quantity;
+        let old_visible = node.visible;
+        let replenish = quantity >= old_visible;
+        node.visible = if replenish { display_slice(&node.order) } else { old_visible - quantity };
+        let new_visible = node.visible;
+        let (side, outcome, price) = (node.order.side, node.order.outcome, node.order.price);
+        if side == OrderSide::SELL {
+            self.inventory.release(&self.slab[handle.0].order.user_id, outcome, quantity);
+        }
+
+        if let Some(queue) = self.side_mut(side, outcome).get_mut(&price) {
+            queue.quantity -= quantity;
+            queue.visible = queue.visible + new_visible - old_visible;
+        }
+        self.journal.touched.push((side, outcome, price));
+        self.journal.orders.push(OrderEvent {
+            kind: OrderEventKind::FILLED,
+            public_id: self.slab[handle.0].public_id,
+            side,
+            outcome,
+            price,
+            quantity: if replenish { 0 } else { new_visible },
+            change: quantity,
+        });
+
+        let after = if self.slab[handle.0].order.is_filled() {
+            self.unlink(handle.0)
+        } else {
+            if replenish {
+                // A refreshed slice loses time priority, and to the level-3
+                // feed it is a new order
+                self.journal_order(OrderEventKind::REMOVED, handle.0, 0);
+                self.last_public_id += 1;
+                self.slab[handle.0].public_id = self.last_public_id;
+                self.detach(handle.0);
+                self.push_back(handle.0);
+                self.journal_order(OrderEventKind::ADDED, handle.0, 0);
+            }
+            self.slab[handle.0].order.clone()
+        };
+        self.filled.push(after.clone());
+        after
     }
 
+    /// Every resting order filled since the last call, as it stood after
+    /// each fill, oldest first
+    pub fn take_fills(&mut self) -> Vec<Order> {
+        std::mem::take(&mut self.filled)
+    }
 
-    pub fn peek_best_ask(&self, outcome: Outcome) -> Option<Order> {
-        let asks = self.get_asks(outcome);
-        let book = asks.read().unwrap();
-        
-        book.iter()
-            .find(|(_, orders)| !orders.is_empty())
-            .and_then(|(_, orders)| orders.front().cloned())
+    /// Public trades for the market data feed, in the order they executed
+    pub fn record_prints(&mut self, prints: Vec<Print>) {
+        self.journal.prints.extend(prints);
     }
-    
-    /// Peek at best bid without removing
-    pub fn peek_best_bid(&self, outcome: Outcome) -> Option<Order> {
-        let bids = self.get_bids(outcome);
-        let book = bids.read().unwrap();
-        
-        book.iter()
-            .rev()
-            .find(|(_, orders)| !orders.is_empty())
-            .and_then(|(_, orders)| orders.front().cloned())
+
+    /// Everything that changed since the last call
+    pub fn take_journal(&mut self) -> Journal {
+        std::mem::take(&mut self.journal)
     }
 
+    /// Every resting order as the level-3 feed shows it: per outcome, bids
+    /// best price first then asks, each level in time priority
+    pub fn public_orders(&self) -> Vec<PublicOrder> {
+        let mut orders = Vec::new();
+        for outcome in self.outcomes() {
+            let bids = self.side(OrderSide::BUY, outcome).values().rev();
+            let asks = self.side(OrderSide::SELL, outcome).values();
+            for queue in bids.chain(asks) {
+                orders.extend(self.queue_iter(queue).map(|(key, order)| PublicOrder {
+                    public_id: self.slab[key].public_id,
+                    side: order.side,
+                    outcome: order.outcome,
+                    price: order.price,
+                    quantity: self.slab[key].visible,
+                }));
+            }
+        }
+        orders
+    }
 
-    /// Get full orderbook depth (for UI display)
-    pub fn get_depth(
+    /// One level's displayed quantity and order count; None if nothing rests there
+    pub fn level(&self, side: OrderSide, outcome: Outcome, price: Ticks) -> Option<PriceLevelSummary> {
+        self.side(side, outcome).get(&price).map(|queue| PriceLevelSummary {
+            price,
+            quantity: queue.visible,
+            order_count: queue.order_count,
+        })
+    }
+
+    /// Every order resting at exactly `price`, in time priority
+    pub fn level_handles(&self, side: OrderSide, outcome: Outcome, price: Ticks) -> Vec<OrderHandle> {
+        self.side(side, outcome)
+            .get(&price)
+            .map(|q| self.queue_iter(q).map(|(key, _)| OrderHandle(key)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Resting orders on one side at `price` or better, best price first,
+    /// then in time priority within each level
+    pub fn handles_at_or_better(
         &self,
+        side: OrderSide,
         outcome: Outcome,
-        levels: usize,
-    ) -> OrderbookDepth {
-        let bids_book = self.get_bids(outcome);
-        let asks_book = self.get_asks(outcome);
-        
-        let bids_guard = bids_book.read().unwrap();
-        let asks_guard = asks_book.read().unwrap();
-        
+        price: Ticks,
+    ) -> Vec<OrderHandle> {
+        let book = self.side(side, outcome);
+        let levels: Box<dyn Iterator<Item = &PriceQueue>> = match side {
+            OrderSide::BUY => Box::new(book.range(price..).rev().map(|(_, q)| q)),
+            OrderSide::SELL => Box::new(book.range(..=price).map(|(_, q)| q)),
+        };
+
+        levels
+            .flat_map(|q| self.queue_iter(q))
+            .map(|(key, _)| OrderHandle(key))
+            .collect()
+    }
+
+    /// Get full orderbook depth (for UI display). Icebergs count only
+    /// their displayed slice.
+    pub fn get_depth(&self, outcome: Outcome, levels: usize) -> OrderbookDepth {
+        self.depth(outcome, levels, |queue| queue.visible)
+    }
+
+    /// Depth including iceberg reserves. Never show this to clients; the
+    /// auction uses it because hidden quantity takes part in the uncross.
+    pub fn get_full_depth(&self, outcome: Outcome, levels: usize) -> OrderbookDepth {
+        self.depth(outcome, levels, |queue| queue.quantity)
+    }
+
+    fn depth(&self, outcome: Outcome, levels: usize, quantity: impl Fn(&PriceQueue) -> Lots) -> OrderbookDepth {
+        let summary = |(price, queue): (&Ticks, &PriceQueue)| PriceLevelSummary {
+            price: *price,
+            quantity: quantity(queue),
+            order_count: queue.order_count,
+        };
+
         // Top N bid levels (highest prices first)
-        let bids: Vec<PriceLevelSummary> = bids_guard
+        let bids = self
+            .side(OrderSide::BUY, outcome)
             .iter()
             .rev()
-            .filter(|(_, orders)| !orders.is_empty())
             .take(levels)
-            .map(|(price, orders)| {
-                let quantity: Decimal = orders.iter().map(|o| o.remaining()).sum();
-                PriceLevelSummary {
-                    price: *price,
-                    quantity,
-                    order_count: orders.len(),
-                }
-            })
+            .map(summary)
             .collect();
-        
+
         // Top N ask levels (lowest prices first)
-        let asks: Vec<PriceLevelSummary> = asks_guard
+        let asks = self
+            .side(OrderSide::SELL, outcome)
             .iter()
-            .filter(|(_, orders)| !orders.is_empty())
             .take(levels)
-            .map(|(price, orders)| {
-                let quantity: Decimal = orders.iter().map(|o| o.remaining()).sum();
-                PriceLevelSummary {
-                    price: *price,
-                    quantity,
-                    order_count: orders.len(),
-                }
-            })
+            .map(summary)
             .collect();
-        
+
         OrderbookDepth { bids, asks }
     }
 
-    pub fn push_front(&self, order: Order) {
-        let book = self.get_side_mut(order.side, order.outcome);
-        let mut book_guard = book.write().unwrap();
-        
-        book_guard
-            .entry(order.price)
-            .or_insert_with(VecDeque::new)
-            .push_front(order.clone());
-        
-        self.orders.insert(order.order_id, order);
+    /// Level count and total resting quantity for one side of the book
+    pub fn side_totals(&self, side: OrderSide, outcome: Outcome) -> (usize, Lots) {
+        let book = self.side(side, outcome);
+        (book.len(), book.values().map(|q| q.quantity).sum())
     }
 
     pub fn would_self_trade(
@@ -236,62 +563,148 @@ impl OrderBook{
         user_id: &str,
         side: OrderSide,
         outcome: Outcome,
-        price: Decimal,
+        price: Ticks,
     ) -> bool {
-        let opposite = match side {
-            OrderSide::BUY => self.get_asks(outcome),
-            OrderSide::SELL => self.get_bids(outcome),
-        };
-        
-        let book = opposite.read().unwrap();
-        
         // For BUY: check asks at or below our price
         // For SELL: check bids at or above our price
-        let mut range: Box<dyn Iterator<Item = (&Decimal, &VecDeque<Order>)>> = match side {
-            OrderSide::BUY => Box::new(
-                book.range(..=price)
-            ),
-            OrderSide::SELL => Box::new(
-                book.range(price..)
-            ),
+        let opposite = match side {
+            OrderSide::BUY => OrderSide::SELL,
+            OrderSide::SELL => OrderSide::BUY,
         };
-        
-        range.any(|(_, orders)| {
-            orders.iter().any(|o| o.user_id == user_id)
-        })
+
+        self.handles_at_or_better(opposite, outcome, price)
+            .into_iter()
+            .any(|h| self.order(h).user_id == user_id)
     }
 
-    fn get_bids(&self,outcome:Outcome)->&Arc<RwLock<PriceLevel>>{
-        match  outcome {
-            Outcome::YES => &self.yes_bids,
-            Outcome::NO => &self.no_bids
+    fn unlink(&mut self, key: usize) -> Order {
+        let visible = self.slab[key].visible;
+        self.journal_order(OrderEventKind::REMOVED, key, visible);
+        self.detach(key);
+        let node = self.slab.remove(key);
+        self.index.remove(&node.order.order_id);
+        if let Some(reservation_id) = &node.order.reservation_id {
+            self.reservations.remove(reservation_id);
+        }
+
+        let order = node.order;
+        if order.side == OrderSide::SELL {
+            self.inventory.release(&order.user_id, order.outcome, order.remaining());
+        }
+        let book = self.side_mut(order.side, order.outcome);
+        if let Some(queue) = book.get_mut(&order.price) {
+            queue.order_count -= 1;
+            queue.quantity -= order.remaining();
+            queue.visible -= node.visible;
+
+            // Clean up empty price levels
+            if queue.order_count == 0 {
+                book.remove(&order.price);
+            }
         }
+        self.journal.touched.push((order.side, order.outcome, order.price));
+
+        order
+    }
+
+    /// Note an event for the slab entry as it stands now
+    fn journal_order(&mut self, kind: OrderEventKind, key: usize, change: Lots) {
+        let node = &self.slab[key];
+        let quantity = match kind {
+            OrderEventKind::REMOVED => 0,
+            _ => node.visible,
+        };
+        self.journal.orders.push(OrderEvent {
+            kind,
+            public_id: node.public_id,
+            side: node.order.side,
+            outcome: node.order.outcome,
+            price: node.order.price,
+            quantity,
+            change,
+        });
     }
 
-    fn get_asks(&self,outcome:Outcome)->&Arc<RwLock<PriceLevel>>{
-        match outcome {
-            Outcome::YES => &self.yes_asks,
-            Outcome::NO => &self.no_asks
+    /// Link a slab entry in at the tail of its level, which must exist
+    fn push_back(&mut self, key: usize) {
+        let order = &self.slab[key].order;
+        let (side, outcome, price) = (order.side, order.outcome, order.price);
+        let queue = self.side_mut(side, outcome).get_mut(&price).expect("price level exists");
+        let old_tail = queue.tail.replace(key);
+        if queue.head.is_none() {
+            queue.head = Some(key);
+        }
+
+        self.slab[key].prev = old_tail;
+        self.slab[key].next = None;
+        if let Some(tail) = old_tail {
+            self.slab[tail].next = Some(key);
         }
     }
 
-    fn get_side_mut(&self,side:OrderSide,outcome:Outcome) -> &Arc<RwLock<PriceLevel>>{
-        match  (side,outcome) {
-            (OrderSide::BUY, Outcome::YES) => &self.yes_bids,
-            (OrderSide::SELL,Outcome::YES) => &self.yes_asks,
-            (OrderSide::BUY, Outcome::NO) => &self.no_bids,
-            (OrderSide::SELL, Outcome::NO) => &self.no_asks,
+    /// Take a slab entry out of its level's linked list, leaving the level's
+    /// counters alone
+    fn detach(&mut self, key: usize) {
+        let node = &self.slab[key];
+        let (prev, next) = (node.prev, node.next);
+        let (side, outcome, price) = (node.order.side, node.order.outcome, node.order.price);
+
+        if let Some(prev) = prev {
+            self.slab[prev].next = next;
         }
+        if let Some(next) = next {
+            self.slab[next].prev = prev;
+        }
+        if let Some(queue) = self.side_mut(side, outcome).get_mut(&price) {
+            if queue.head == Some(key) {
+                queue.head = next;
+            }
+            if queue.tail == Some(key) {
+                queue.tail = prev;
+            }
+        }
+        self.slab[key].prev = None;
+        self.slab[key].next = None;
     }
 
+    fn queue_iter<'a>(&'a self, queue: &PriceQueue) -> impl Iterator<Item = (usize, &'a Order)> + 'a {
+        let mut cursor = queue.head;
+        std::iter::from_fn(move || {
+            let key = cursor?;
+            let node = &self.slab[key];
+            cursor = node.next;
+            Some((key, &node.order))
+        })
+    }
+
+    // Callers validate the outcome against outcome_count() first
+    fn side(&self, side: OrderSide, outcome: Outcome) -> &BookSide {
+        let book = &self.outcomes[outcome.index()];
+        match side {
+            OrderSide::BUY => &book.bids,
+            OrderSide::SELL => &book.asks,
+        }
+    }
 
+    fn side_mut(&mut self, side: OrderSide, outcome: Outcome) -> &mut BookSide {
+        let book = &mut self.outcomes[outcome.index()];
+        match side {
+            OrderSide::BUY => &mut book.bids,
+            OrderSide::SELL => &mut book.asks,
+        }
+    }
 }
 
+/// How much of an order is on display: one iceberg slice, or all of it
+fn display_slice(order: &Order) -> Lots {
+    let remaining = order.remaining();
+    order.display_quantity.map_or(remaining, |display| display.min(remaining))
+}
 
 #[derive(Debug, Clone)]
 pub struct PriceLevelSummary {
-    pub price: Decimal,
-    pub quantity: Decimal,
+    pub price: Ticks,
+    pub quantity: Lots,
     pub order_count: usize,
 }
 
@@ -299,4 +712,125 @@ pub struct PriceLevelSummary {
 pub struct OrderbookDepth {
     pub bids: Vec<PriceLevelSummary>,
     pub asks: Vec<PriceLevelSummary>,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::{OrderStatus, OrderType};
+    use crate::triggers::{Trigger, TriggerKind};
+    use chrono::Utc;
+
+    fn order(user: &str, side: OrderSide, price: Ticks, qty: Lots) -> Order {
+        Order {
+            order_id: Uuid::new_v4(),
+            user_id: user.to_string(),
+            market_id: "market_test".to_string(),
+            side,
+            outcome: Outcome::YES,
+            order_type: OrderType::LIMIT,
+            price,
+            quantity: qty,
+            filled: 0,
+            order_status: OrderStatus::OPEN,
+            reservation_id: None,
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_cancel_from_middle_keeps_time_priority() {
+        let mut book = OrderBook::new("market_test".to_string(), MarketSpec::default());
+        let a = order("a", OrderSide::SELL, 5500, 10);
+        let b = order("b", OrderSide::SELL, 5500, 20);
+        let c = order("c", OrderSide::SELL, 5500, 30);
+        let (a_id, b_id, c_id) = (a.order_id, b.order_id, c.order_id);
+        book.add_order(a);
+        book.add_order(b);
+        book.add_order(c);
+
+        assert_eq!(book.remove_order(b_id).unwrap().user_id, "b");
+        assert!(book.get(&b_id).is_none());
+
+        let depth = book.get_depth(Outcome::YES, 10);
+        assert_eq!(depth.asks[0].quantity, 40);
+        assert_eq!(depth.asks[0].order_count, 2);
+
+        let queue: Vec<Uuid> = book
+            .handles_at_or_better(OrderSide::SELL, Outcome::YES, 5500)
+            .into_iter()
+            .map(|h| book.order(h).order_id)
+            .collect();
+        assert_eq!(queue, vec![a_id, c_id]);
+
+        book.remove_order(a_id);
+        book.remove_order(c_id);
+        assert!(book.is_empty());
+        assert!(book.best_ask(Outcome::YES).is_none());
+    }
+
+    #[test]
+    fn test_partial_fill_updates_single_copy() {
+        let mut book = OrderBook::new("market_test".to_string(), MarketSpec::default());
+        let bid = order("a", OrderSide::BUY, 4000, 100);
+        let bid_id = bid.order_id;
+        let handle = book.add_order(bid);
+
+        let after = book.fill(handle, 30);
+        assert_eq!(after.filled, 30);
+        assert_eq!(book.get(&bid_id).unwrap().remaining(), 70);
+        assert_eq!(book.get_depth(Outcome::YES, 1).bids[0].quantity, 70);
+
+        let after = book.fill(handle, 70);
+        assert!(after.is_filled());
+        assert!(book.get(&bid_id).is_none());
+        assert!(book.best_bid(Outcome::YES).is_none());
+    }
+
+    #[test]
+    fn test_iceberg_shows_slice_and_requeues_on_refresh() {
+        let mut book = OrderBook::new("market_test".to_string(), MarketSpec::default());
+        let mut iceberg = order("a", OrderSide::SELL, 5500, 30);
+        iceberg.order_type = OrderType::ICEBERG;
+        iceberg.display_quantity = Some(10);
+        let a = book.add_order(iceberg);
+        book.add_order(order("b", OrderSide::SELL, 5500, 10));
+
+        assert_eq!(book.get_depth(Outcome::YES, 1).asks[0].quantity, 20);
+        assert_eq!(book.get_full_depth(Outcome::YES, 1).asks[0].quantity, 40);
+
+        // A partial fill of the slice keeps a at the front
+        book.fill(a, 4);
+        assert_eq!(book.visible(a), 6);
+        assert_eq!(book.order(book.best_ask_order(Outcome::YES).unwrap()).user_id, "a");
+
+        // Using up the slice shows the next one behind b
+        let after = book.fill(a, 6);
+        assert_eq!(after.remaining(), 20);
+        assert_eq!(book.visible(a), 10);
+        assert_eq!(book.order(book.best_ask_order(Outcome::YES).unwrap()).user_id, "b");
+        assert_eq!(book.get_depth(Outcome::YES, 1).asks[0].quantity, 20);
+    }
+
+    #[test]
+    fn test_cancel_all_empties_book_triggers_and_groups() {
+        let mut book = OrderBook::new("market_test".to_string(), MarketSpec::default());
+        let mut bid = order("a", OrderSide::BUY, 4000, 10);
+        bid.reservation_id = Some("a_res".to_string());
+        let (oco_a, oco_b) = (order("b", OrderSide::SELL, 6000, 10), order("b", OrderSide::SELL, 6500, 10));
+        book.groups.add_oco("b", [&oco_a, &oco_b]);
+        book.add_order(bid);
+        book.add_order(oco_a);
+        book.add_order(oco_b);
+        let stop = Trigger { kind: TriggerKind::STOP, price: 3000 };
+        book.triggers.add(order("c", OrderSide::SELL, 0, 5), stop);
+
+        let cancelled = book.cancel_all();
+        assert_eq!(cancelled.len(), 4);
+        assert!(cancelled.iter().all(|o| o.order_status == OrderStatus::CANCELLED));
+        assert!(book.is_empty() && book.triggers.is_empty() && book.groups.is_empty());
+        assert!(!book.has_reservation("a_res"));
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::{anyhow, bail, Result};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tonic::metadata::MetadataMap;
+use tonic::{Code, Request, Status};
+
+use crate::auth::{Access, Caller};
+
+/// Whose budget a call is charged to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// The user id the call acts for
+    USER,
+    /// The authenticated caller making it
+    CALLER,
+}
+
+/// Budgets are kept apart so a flood of orders can't stop a user cancelling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallClass {
+    PLACE,
+    CANCEL,
+    QUERY,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 2] = [Scope::USER, Scope::CALLER];
+}
+
+impl CallClass {
+    pub const ALL: [CallClass; 3] = [CallClass::PLACE, CallClass::CANCEL, CallClass::QUERY];
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::USER => write!(f, "user"),
+            Scope::CALLER => write!(f, "caller"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "user" => Ok(Scope::USER),
+            "caller" => Ok(Scope::CALLER),
+            other => bail!("Unknown rate limit scope: {}", other),
+        }
+    }
+}
+
+impl fmt::Display for CallClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallClass::PLACE => write!(f, "place"),
+            CallClass::CANCEL => write!(f, "cancel"),
+            CallClass::QUERY => write!(f, "query"),
+        }
+    }
+}
+
+impl FromStr for CallClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "place" => Ok(CallClass::PLACE),
+            "cancel" => Ok(CallClass::CANCEL),
+            "query" => Ok(CallClass::QUERY),
+            other => bail!("Unknown call class: {}", other),
+        }
+    }
+}
+
+/// A token bucket refilling at `rate` calls per second, holding up to `burst`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub rate: u32,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(rate: u32, burst: u32) -> Result<Self> {
+        if rate == 0 || burst == 0 {
+            bail!("Rate and burst must be positive");
+        }
+        Ok(Self { rate, burst })
+    }
+}
+
+/// `<per second>` or `<per second>:<burst>`; the burst defaults to the rate
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (rate, burst) = s.split_once(':').unwrap_or((s, s));
+        let parse = |n: &str| n.trim().parse::<u32>().map_err(|_| anyhow!("Invalid rate limit: {}", s));
+        RateLimit::new(parse(rate)?, parse(burst)?)
+    }
+}
+
+/// A call turned away, and when the budget will next have room for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throttled {
+    pub scope: Scope,
+    pub class: CallClass,
+    pub retry_after: Duration,
+}
+
+impl From<Throttled> for Status {
+    fn from(throttled: Throttled) -> Self {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("retry-after-ms", throttled.retry_after.as_millis().to_string().parse().unwrap());
+        Status::with_metadata(
+            Code::ResourceExhausted,
+            format!("Rate limited ({} {} calls); retry in {}ms", throttled.scope, throttled.class, throttled.retry_after.as_millis()),
+            metadata,
+        )
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let earned = now.saturating_duration_since(self.updated).as_secs_f64() * limit.rate as f64;
+        self.tokens = (self.tokens + earned).min(limit.burst as f64);
+        self.updated = now;
+    }
+}
+
+type Key = (Scope, String, CallClass);
+
+/// Token buckets per user id and per caller. A scope and class without a
+/// limit is not counted at all.
+#[derive(Default)]
+pub struct RateLimiter {
+    defaults: DashMap<(Scope, CallClass), RateLimit>,
+    // Per user id or caller, taking precedence over the default; None exempts
+    overrides: DashMap<Key, Option<RateLimit>>,
+    buckets: DashMap<Key, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(defaults: HashMap<(Scope, CallClass), RateLimit>) -> Self {
+        Self { defaults: defaults.into_iter().collect(), ..Self::default() }
+    }
+
+    fn limit(&self, key: &Key) -> Option<RateLimit> {
+        match self.overrides.get(key) {
+            Some(limit) => *limit,
+            None => self.defaults.get(&(key.0, key.2)).map(|l| *l),
+        }
+    }
+
+    /// The limit for everyone in `scope` without an override; None lifts it
+    pub fn set_default(&self, scope: Scope, class: CallClass, limit: Option<RateLimit>) {
+        match limit {
+            Some(limit) => {
+                self.defaults.insert((scope, class), limit);
+            }
+            None => {
+                self.defaults.remove(&(scope, class));
+            }
+        }
+    }
+
+    /// The limit for one user id or caller; None exempts them
+    pub fn set_override(&self, scope: Scope, subject: &str, class: CallClass, limit: Option<RateLimit>) {
+        self.overrides.insert((scope, subject.to_string(), class), limit);
+    }
+
+    pub fn clear_override(&self, scope: Scope, subject: &str, class: CallClass) {
+        self.overrides.remove(&(scope, subject.to_string(), class));
+    }
+
+    /// Take one token from each of `subjects`' buckets, or none if any is empty
+    pub fn check(&self, subjects: &[(Scope, &str)], class: CallClass, now: Instant) -> Result<(), Throttled> {
+        let mut taken: Vec<Key> = Vec::new();
+        for (scope, subject) in subjects {
+            let key = (*scope, subject.to_string(), class);
+            let Some(limit) = self.limit(&key) else {
+                continue;
+            };
+            let mut bucket = self
+                .buckets
+                .entry(key.clone())
+                .or_insert(Bucket { tokens: limit.burst as f64, updated: now });
+            bucket.refill(limit, now);
+            if bucket.tokens < 1.0 {
+                let retry_after = Duration::from_secs_f64((1.0 - bucket.tokens) / limit.rate as f64);
+                drop(bucket);
+                // Hand back what the earlier scopes were charged
+                for key in taken {
+                    if let Some(mut bucket) = self.buckets.get_mut(&key) {
+                        bucket.tokens += 1.0;
+                    }
+                }
+                return Err(Throttled { scope: *scope, class, retry_after });
+            }
+            bucket.tokens -= 1.0;
+            taken.push(key);
+        }
+        Ok(())
+    }
+
+    /// Charge a call to the caller the interceptor attached and, when it acts
+    /// for a user, to that user too
+    pub fn admit<T>(&self, request: &Request<T>, class: CallClass, access: Access) -> Result<(), Throttled> {
+        let caller = request.extensions().get::<Caller>().map_or("", |caller| caller.subject.as_str());
+        match access {
+            Access::Trade(user_id) => self.check(&[(Scope::CALLER, caller), (Scope::USER, user_id)], class, Instant::now()),
+            Access::Read | Access::Admin => self.check(&[(Scope::CALLER, caller)], class, Instant::now()),
+        }
+    }
+
+    /// Forget buckets that have refilled; they start full again anyway
+    pub fn purge_idle(&self, now: Instant) {
+        self.buckets.retain(|key, bucket| match self.limit(key) {
+            Some(limit) => {
+                bucket.refill(limit, now);
+                bucket.tokens < limit.burst as f64
+            }
+            None => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_and_budgets_are_separate() {
+        let limiter = RateLimiter::new(HashMap::from([
+            ((Scope::USER, CallClass::PLACE), "2:3".parse().unwrap()),
+            ((Scope::CALLER, CallClass::PLACE), RateLimit::new(1, 5).unwrap()),
+        ]));
+        let start = Instant::now();
+        let place = |user, at| limiter.check(&[(Scope::CALLER, "svc"), (Scope::USER, user)], CallClass::PLACE, at);
+
+        for _ in 0..3 {
+            assert!(place("bot", start).is_ok());
+        }
+        let throttled = place("bot", start).unwrap_err();
+        assert_eq!(throttled.scope, Scope::USER);
+        assert_eq!(throttled.retry_after, Duration::from_millis(500));
+        // Cancels have their own budget, unlimited here
+        assert!(limiter.check(&[(Scope::USER, "bot")], CallClass::CANCEL, start).is_ok());
+        assert!(place("bot", start + Duration::from_millis(500)).is_ok());
+
+        // The refused call was refunded to the shared caller, which has room
+        // for one more
+        assert!(place("other", start + Duration::from_millis(500)).is_ok());
+        assert_eq!(place("other", start + Duration::from_millis(500)).unwrap_err().scope, Scope::CALLER);
+
+        limiter.set_override(Scope::USER, "bot", CallClass::PLACE, None);
+        limiter.set_default(Scope::CALLER, CallClass::PLACE, None);
+        assert!(place("bot", start + Duration::from_millis(500)).is_ok());
+    }
+}
@@ -0,0 +1,291 @@
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::db::OrderRow;
+use crate::engine::Engine;
+use crate::order::{Order, OrderSide, OrderStatus, OrderType, Outcome, Ticks};
+use crate::orderbook::OrderBook;
+
+/// Why an order the database has as resting was not put back in the book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    UnknownMarket,
+    /// Side or outcome the market doesn't have
+    InvalidOrder(String),
+    /// Price or quantity off the market's grid
+    OffGrid(String),
+    NothingRemaining,
+    /// Would trade against (or mint with) what has been rebuilt so far
+    Crossed,
+    /// The same id already backs a resting order
+    Duplicate,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::UnknownMarket => write!(f, "unknown market"),
+            SkipReason::InvalidOrder(detail) => write!(f, "invalid order: {}", detail),
+            SkipReason::OffGrid(detail) => write!(f, "off grid: {}", detail),
+            SkipReason::NothingRemaining => write!(f, "nothing remaining"),
+            SkipReason::Crossed => write!(f, "crosses the book"),
+            SkipReason::Duplicate => write!(f, "duplicate"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Skipped {
+    pub order_id: String,
+    pub market_id: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Default)]
+pub struct RecoveryReport {
+    pub restored: usize,
+    pub skipped: Vec<Skipped>,
+}
+
+/// An order whose resting quantity differs between the engine and the
+/// database. None means that side doesn't have it resting at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub order_id: String,
+    pub market_id: String,
+    pub engine_remaining: Option<Decimal>,
+    pub db_remaining: Option<Decimal>,
+}
+
+/// Put every open database order back in its book, oldest first so each
+/// price level comes back in time priority. Nothing is matched: an order
+/// that would trade is reported instead.
+pub fn restore(engine: &Engine, rows: &[OrderRow]) -> RecoveryReport {
+    let mut report = RecoveryReport::default();
+    for row in rows {
+        let orderbook = engine.listed_book(&row.market_id).filter(|_| row.market_exists);
+        let result = match orderbook {
+            Some(orderbook) => restore_order(&mut orderbook.write().unwrap(), row),
+            None => Err(SkipReason::UnknownMarket),
+        };
+
+        match result {
+            Ok(order) => {
+                if let Some(reservation_id) = &order.reservation_id {
+                    engine.reservations.insert(reservation_id, &row.market_id);
+                }
+                report.restored += 1;
+            }
+            Err(reason) => {
+                warn!("Not restoring order {} in {}: {}", row.id, row.market_id, reason);
+                report.skipped.push(Skipped {
+                    order_id: row.id.clone(),
+                    market_id: row.market_id.clone(),
+                    reason,
+                });
+            }
+        }
+    }
+
+    for book in engine.orderbooks.iter() {
+        let mut book = book.write().unwrap();
+        engine.metrics.observe_book(&book);
+        // So the restored orders are in the level-3 feed's sequence
+        engine.market_data.publish(&mut book);
+    }
+    info!("Restored {} orders, skipped {}", report.restored, report.skipped.len());
+    report
+}
+
+/// Rest one database order in `book` as-is
+pub fn restore_order(book: &mut OrderBook, row: &OrderRow) -> Result<Order, SkipReason> {
+    let side = match row.side.as_str() {
+        "BUY" => OrderSide::BUY,
+        "SELL" => OrderSide::SELL,
+        other => return Err(SkipReason::InvalidOrder(format!("side {}", other))),
+    };
+    let outcome = Outcome::parse(&row.outcome)
+        .filter(|o| o.index() < book.outcome_count())
+        .ok_or_else(|| SkipReason::InvalidOrder(format!("outcome {}", row.outcome)))?;
+    let off_grid = |e| SkipReason::OffGrid(format!("{}", e));
+    let price = book.spec.price_to_ticks(row.price).map_err(off_grid)?;
+    let quantity = book.spec.quantity_to_lots(row.quantity).map_err(off_grid)?;
+    let filled = book.spec.quantity_to_lots(row.filled_quantity).map_err(off_grid)?;
+    if filled >= quantity {
+        return Err(SkipReason::NothingRemaining);
+    }
+    if book.has_reservation(&row.id) {
+        return Err(SkipReason::Duplicate);
+    }
+    if crosses(book, side, outcome, price) {
+        return Err(SkipReason::Crossed);
+    }
+
+    let order = Order {
+        // The database id is the reservation; keep it as the order id too when it is one
+        order_id: Uuid::parse_str(&row.id).unwrap_or_else(|_| Uuid::new_v4()),
+        user_id: row.user_id.clone(),
+        market_id: row.market_id.clone(),
+        side,
+        outcome,
+        order_type: OrderType::LIMIT,
+        price,
+        quantity,
+        filled,
+        order_status: if filled > 0 { OrderStatus::PARTIAL } else { OrderStatus::OPEN },
+        reservation_id: Some(row.id.clone()),
+        display_quantity: None,
+        reduce_only: false,
+        created_at: row.created_at,
+    };
+    if book.get(&order.order_id).is_some() {
+        return Err(SkipReason::Duplicate);
+    }
+    book.add_order(order.clone());
+    Ok(order)
+}
+
+// Against the other side of its own outcome, or for a BUY, with the best
+// bid on every other outcome to mint a complete set
+fn crosses(book: &OrderBook, side: OrderSide, outcome: Outcome, price: Ticks) -> bool {
+    match side {
+        OrderSide::SELL => book.best_bid(outcome).is_some_and(|bid| bid >= price),
+        OrderSide::BUY => {
+            if book.best_ask(outcome).is_some_and(|ask| ask <= price) {
+                return true;
+            }
+            let others: Option<Ticks> = book
+                .outcomes()
+                .filter(|o| *o != outcome)
+                .map(|o| book.best_bid(o))
+                .sum();
+            others.is_some_and(|others| others + price >= book.spec.one())
+        }
+    }
+}
+
+/// Compare what every book has resting against the database's open orders.
+/// Only orders placed with a reservation (as the order service does) are
+/// compared, by that reservation.
+pub fn check_consistency(engine: &Engine, rows: &[OrderRow]) -> Vec<Mismatch> {
+    let mut by_market: HashMap<&str, Vec<&OrderRow>> = HashMap::new();
+    for row in rows {
+        by_market.entry(row.market_id.as_str()).or_default().push(row);
+    }
+
+    let mut mismatches = Vec::new();
+    for entry in engine.orderbooks.iter() {
+        let book = entry.read().unwrap();
+        let rows = by_market.remove(book.market_id.as_str()).unwrap_or_default();
+        mismatches.extend(compare_book(&book, &rows));
+    }
+    // Markets the engine has no book for at all
+    for row in by_market.into_values().flatten() {
+        mismatches.push(Mismatch {
+            order_id: row.id.clone(),
+            market_id: row.market_id.clone(),
+            engine_remaining: None,
+            db_remaining: Some(row.remaining()),
+        });
+    }
+
+    mismatches.sort_by(|a, b| (&a.market_id, &a.order_id).cmp(&(&b.market_id, &b.order_id)));
+    mismatches
+}
+
+/// Mismatches between one book and the database rows for its market
+pub fn compare_book(book: &OrderBook, rows: &[&OrderRow]) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    for row in rows {
+        let engine_remaining = book
+            .by_reservation(&row.id)
+            .map(|order| book.spec.lots_to_quantity(order.remaining()));
+        if engine_remaining != Some(row.remaining()) {
+            mismatches.push(Mismatch {
+                order_id: row.id.clone(),
+                market_id: row.market_id.clone(),
+                engine_remaining,
+                db_remaining: Some(row.remaining()),
+            });
+        }
+    }
+
+    let in_db: Vec<&str> = rows.iter().map(|row| row.id.as_str()).collect();
+    for order in book.resting_orders() {
+        let Some(reservation_id) = &order.reservation_id else { continue };
+        if !in_db.contains(&reservation_id.as_str()) {
+            mismatches.push(Mismatch {
+                order_id: reservation_id.clone(),
+                market_id: book.market_id.clone(),
+                engine_remaining: Some(book.spec.lots_to_quantity(order.remaining())),
+                db_remaining: None,
+            });
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_spec::MarketSpec;
+    use chrono::{Duration, Utc};
+    use rust_decimal_macros::dec;
+
+    fn row(id: &str, side: &str, outcome: &str, price: Decimal, filled: Decimal) -> OrderRow {
+        OrderRow {
+            id: id.to_string(),
+            user_id: format!("{}_user", id),
+            market_id: "market_test".to_string(),
+            side: side.to_string(),
+            outcome: outcome.to_string(),
+            price,
+            quantity: dec!(10),
+            filled_quantity: filled,
+            created_at: Utc::now(),
+            market_exists: true,
+        }
+    }
+
+    #[test]
+    fn test_rebuild_keeps_time_priority_and_reports_crossed_and_drift() {
+        let spec = MarketSpec::new(dec!(0.01), dec!(1)).unwrap();
+        let mut book = OrderBook::new("market_test".to_string(), spec);
+
+        let mut first = row("a", "SELL", "YES", dec!(0.60), dec!(4));
+        first.created_at -= Duration::seconds(5);
+        let rows = [
+            first,
+            row("b", "SELL", "YES", dec!(0.60), dec!(0)),
+            row("c", "BUY", "YES", dec!(0.60), dec!(0)),
+            row("d", "BUY", "NO", dec!(0.45), dec!(0)),
+            // 0.45 NO + 0.55 YES would mint a set
+            row("e", "BUY", "YES", dec!(0.55), dec!(0)),
+        ];
+        let results: Vec<Result<Order, SkipReason>> = rows.iter().map(|r| restore_order(&mut book, r)).collect();
+        assert!(results[0].is_ok() && results[1].is_ok() && results[3].is_ok());
+        assert_eq!(results[2].as_ref().unwrap_err(), &SkipReason::Crossed);
+        assert_eq!(results[4].as_ref().unwrap_err(), &SkipReason::Crossed);
+
+        // The older order is ahead at 0.60, with only its remainder resting
+        let front = book.best_ask_order(Outcome::YES).unwrap();
+        assert_eq!(book.order(front).reservation_id.as_deref(), Some("a"));
+        assert_eq!(book.order(front).remaining(), 6);
+
+        // The skipped ones show up as missing from the engine, plus any drift
+        let filled_in_db = OrderRow { filled_quantity: dec!(10), ..rows[3].clone() };
+        let db: Vec<&OrderRow> = vec![&rows[0], &rows[1], &rows[2], &filled_in_db];
+        let mismatches = compare_book(&book, &db);
+        let summary: Vec<(&str, Option<Decimal>, Option<Decimal>)> = mismatches
+            .iter()
+            .map(|m| (m.order_id.as_str(), m.engine_remaining, m.db_remaining))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("c", None, Some(dec!(10))), ("d", Some(dec!(10)), Some(dec!(0)))]
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::{Ok, Result};
-use redis::aio::Connection;
+use redis::aio::{Connection, PubSub};
 use redis::{AsyncCommands, Client};
 use tracing::info;
 
@@ -34,5 +34,23 @@ impl RedisClient{
         
         Ok(())
     }
-}
 
+    pub async fn publish(&self, channel: &str, payload: &str) -> Result<()> {
+        let mut conn = self.get_connection().await?;
+        let _: () = conn.publish(channel, payload).await?;
+        Ok(())
+    }
+
+    /// Push onto a list a worker pops from, so nothing is lost while it is down
+    pub async fn enqueue(&self, queue: &str, payload: &str) -> Result<()> {
+        let mut conn = self.get_connection().await?;
+        let _: () = conn.lpush(queue, payload).await?;
+        Ok(())
+    }
+
+    pub async fn subscribe(&self, channel: &str) -> Result<PubSub> {
+        let mut pubsub = self.get_connection().await?.into_pubsub();
+        pubsub.subscribe(channel).await?;
+        Ok(pubsub)
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::db::load_markets;
+use crate::market_spec::MarketSpec;
+
+/// Whoever changes the `markets` table publishes here so the engine reloads
+pub const MARKETS_UPDATED_CHANNEL: &str = "markets:updated";
+/// Reload this often anyway, for changes made straight in the database
+pub const MARKETS_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+#[error("Unknown market state: {0}")]
+pub struct ParseMarketStateError(String);
+
+/// Mirrors the database's MarketState; only OPEN markets take orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketState {
+    OPEN,
+    CLOSE,
+    CREATED,
+    RESOLVED,
+    RESOLVING,
+    PAUSED,
+}
+
+impl fmt::Display for MarketState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketState::OPEN => write!(f, "OPEN"),
+            MarketState::CLOSE => write!(f, "CLOSE"),
+            MarketState::CREATED => write!(f, "CREATED"),
+            MarketState::RESOLVED => write!(f, "RESOLVED"),
+            MarketState::RESOLVING => write!(f, "RESOLVING"),
+            MarketState::PAUSED => write!(f, "PAUSED"),
+        }
+    }
+}
+
+impl FromStr for MarketState {
+    type Err = ParseMarketStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "OPEN" => Ok(MarketState::OPEN),
+            "CLOSE" => Ok(MarketState::CLOSE),
+            "CREATED" => Ok(MarketState::CREATED),
+            "RESOLVED" => Ok(MarketState::RESOLVED),
+            "RESOLVING" => Ok(MarketState::RESOLVING),
+            "PAUSED" => Ok(MarketState::PAUSED),
+            other => Err(ParseMarketStateError(other.to_string())),
+        }
+    }
+}
+
+/// A market the engine may open a book for
+#[derive(Debug, Clone)]
+pub struct MarketInfo {
+    pub market_id: String,
+    pub state: MarketState,
+    /// None for markets created through the admin API
+    pub expires_at: Option<DateTime<Utc>>,
+    pub spec: MarketSpec,
+}
+
+impl MarketInfo {
+    pub fn is_tradable(&self) -> bool {
+        self.state == MarketState::OPEN
+    }
+}
+
+/// Where the list of markets comes from
+#[async_trait]
+pub trait MarketSource: Send + Sync {
+    async fn load(&self) -> Result<Vec<MarketInfo>>;
+}
+
+/// The `markets` table. It has no tick or lot size, so every market trades
+/// on `spec`.
+pub struct PostgresMarkets {
+    database_url: String,
+    spec: MarketSpec,
+}
+
+impl PostgresMarkets {
+    pub fn new(database_url: String, spec: MarketSpec) -> Self {
+        Self { database_url, spec }
+    }
+}
+
+#[async_trait]
+impl MarketSource for PostgresMarkets {
+    async fn load(&self) -> Result<Vec<MarketInfo>> {
+        let rows = load_markets(&self.database_url).await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| match row.state.parse() {
+                Ok(state) => Some(MarketInfo {
+                    market_id: row.id,
+                    state,
+                    expires_at: Some(row.expires_at),
+                    spec: self.spec,
+                }),
+                Err(e) => {
+                    warn!("Skipping market {}: {}", row.id, e);
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+/// Markets the engine knows about. Without a source nothing is enforced and
+/// any market id gets a book on first use.
+#[derive(Debug, Default)]
+pub struct MarketRegistry {
+    enforced: bool,
+    // From the last load; replaced wholesale on every refresh
+    loaded: RwLock<HashMap<String, MarketInfo>>,
+    // Created through the admin API; kept across refreshes
+    added: RwLock<HashMap<String, MarketInfo>>,
+    // Closed by the engine at expiry, whatever a later load says
+    closed: RwLock<HashSet<String>>,
+}
+
+impl MarketRegistry {
+    pub fn new(enforced: bool) -> Self {
+        Self { enforced, ..Self::default() }
+    }
+
+    pub fn is_enforced(&self) -> bool {
+        self.enforced
+    }
+
+    pub fn get(&self, market_id: &str) -> Option<MarketInfo> {
+        let loaded = self.loaded.read().unwrap().get(market_id).cloned();
+        let mut market = loaded.or_else(|| self.added.read().unwrap().get(market_id).cloned())?;
+        if self.closed.read().unwrap().contains(market_id) {
+            market.state = MarketState::CLOSE;
+        }
+        Some(market)
+    }
+
+    /// Markets past their expiry that have not been closed yet
+    pub fn expired(&self, now: DateTime<Utc>) -> Vec<MarketInfo> {
+        let closed = self.closed.read().unwrap();
+        self.loaded
+            .read()
+            .unwrap()
+            .values()
+            .filter(|m| m.expires_at.is_some_and(|at| at <= now) && !closed.contains(&m.market_id))
+            .cloned()
+            .collect()
+    }
+
+    /// The earliest expiry of a market not closed yet
+    pub fn next_expiry(&self) -> Option<DateTime<Utc>> {
+        let closed = self.closed.read().unwrap();
+        self.loaded
+            .read()
+            .unwrap()
+            .values()
+            .filter(|m| !closed.contains(&m.market_id))
+            .filter_map(|m| m.expires_at)
+            .min()
+    }
+
+    /// Stop a market taking orders for good. Returns false if it already was.
+    pub fn close(&self, market_id: &str) -> bool {
+        self.closed.write().unwrap().insert(market_id.to_string())
+    }
+
+    pub fn is_closed(&self, market_id: &str) -> bool {
+        self.closed.read().unwrap().contains(market_id)
+    }
+
+    /// Swap in a fresh load. Returns how many markets are now listed.
+    pub fn replace(&self, markets: Vec<MarketInfo>) -> usize {
+        let markets: HashMap<String, MarketInfo> =
+            markets.into_iter().map(|m| (m.market_id.clone(), m)).collect();
+        let count = markets.len();
+        *self.loaded.write().unwrap() = markets;
+        count + self.added.read().unwrap().len()
+    }
+
+    pub fn add(&self, market: MarketInfo) {
+        self.added.write().unwrap().insert(market.market_id.clone(), market);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+
+    fn market(market_id: &str, state: MarketState) -> MarketInfo {
+        MarketInfo {
+            market_id: market_id.to_string(),
+            state,
+            expires_at: Some(Utc::now() + Duration::hours(1)),
+            spec: MarketSpec::new(dec!(0.01), dec!(1)).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_refresh_replaces_loaded_markets_but_keeps_added_ones() {
+        let registry = MarketRegistry::new(true);
+        registry.replace(vec![market("a", MarketState::OPEN), market("b", MarketState::OPEN)]);
+        registry.add(market("c", MarketState::OPEN));
+
+        let listed = registry.replace(vec![market("a", MarketState::PAUSED)]);
+        assert_eq!(listed, 2);
+        assert!(!registry.get("a").unwrap().is_tradable());
+        assert!(registry.get("b").is_none());
+        assert!(registry.get("c").unwrap().is_tradable());
+        assert_eq!("RESOLVING".parse::<MarketState>().unwrap(), MarketState::RESOLVING);
+    }
+
+    #[test]
+    fn test_expired_markets_close_once_and_stay_closed() {
+        let registry = MarketRegistry::new(true);
+        let now = Utc::now();
+        let mut due = market("due", MarketState::OPEN);
+        due.expires_at = Some(now - Duration::seconds(1));
+        let later = market("later", MarketState::OPEN);
+        let later_expiry = later.expires_at;
+        registry.replace(vec![due.clone(), later.clone()]);
+
+        let expired: Vec<String> = registry.expired(now).into_iter().map(|m| m.market_id).collect();
+        assert_eq!(expired, vec!["due".to_string()]);
+        assert!(registry.close("due"));
+        assert!(!registry.close("due"));
+        assert!(registry.expired(now).is_empty());
+        assert_eq!(registry.next_expiry(), later_expiry);
+
+        // A reload that still has it OPEN doesn't reopen it
+        registry.replace(vec![due, later]);
+        assert_eq!(registry.get("due").unwrap().state, MarketState::CLOSE);
+    }
+}
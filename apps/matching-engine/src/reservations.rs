@@ -0,0 +1,98 @@
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+
+struct Holder {
+    market_id: String,
+    // Placements using the reservation that haven't finished yet
+    in_flight: usize,
+}
+
+/// The market each reservation_id was last placed in, across every book. A
+/// book only knows its own orders, so this is what stops one reservation
+/// backing orders in two markets. Entries whose order has gone are only
+/// noticed when the book is asked, on the next claim or purge.
+#[derive(Default)]
+pub struct ReservationIndex {
+    holders: DashMap<String, Holder>,
+}
+
+impl ReservationIndex {
+    /// Claim `reservation_id` for an order about to be placed in
+    /// `market_id`; the caller must `finish` it once the placement is over.
+    /// Refused while another market is placing an order with it or
+    /// `is_live` says one of its orders still has it. Within a market the
+    /// book checks for itself.
+    pub fn claim(&self, reservation_id: &str, market_id: &str, is_live: impl FnOnce(&str) -> bool) -> bool {
+        match self.holders.entry(reservation_id.to_string()) {
+            Entry::Occupied(mut holder) => {
+                let holder = holder.get_mut();
+                if holder.market_id == market_id {
+                    holder.in_flight += 1;
+                    return true;
+                }
+                if holder.in_flight > 0 || is_live(&holder.market_id) {
+                    return false;
+                }
+                *holder = Holder { market_id: market_id.to_string(), in_flight: 1 };
+                true
+            }
+            Entry::Vacant(holder) => {
+                holder.insert(Holder { market_id: market_id.to_string(), in_flight: 1 });
+                true
+            }
+        }
+    }
+
+    /// A claimed placement is over, whether or not the order rests
+    pub fn finish(&self, reservation_id: &str) {
+        if let Some(mut holder) = self.holders.get_mut(reservation_id) {
+            holder.in_flight = holder.in_flight.saturating_sub(1);
+        }
+    }
+
+    /// Record an order that was put straight into a book, as on recovery
+    pub fn insert(&self, reservation_id: &str, market_id: &str) {
+        self.holders.insert(reservation_id.to_string(), Holder { market_id: market_id.to_string(), in_flight: 0 });
+    }
+
+    /// Forget reservations no order in their market has any more
+    pub fn purge(&self, is_live: impl Fn(&str, &str) -> bool) {
+        self.holders.retain(|reservation_id, holder| holder.in_flight > 0 || is_live(&holder.market_id, reservation_id));
+    }
+
+    pub fn len(&self) -> usize {
+        self.holders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.holders.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_market_at_a_time() {
+        let index = ReservationIndex::default();
+        assert!(index.claim("r1", "m1", |_| unreachable!()));
+        // Still being placed in m1, so m2 can't have it whatever the book says
+        assert!(!index.claim("r1", "m2", |_| false));
+        assert!(index.claim("r1", "m1", |_| unreachable!()));
+        index.finish("r1");
+        index.finish("r1");
+
+        // Resting in m1
+        assert!(!index.claim("r1", "m2", |market_id| market_id == "m1"));
+        // Gone from m1
+        assert!(index.claim("r1", "m2", |_| false));
+        index.finish("r1");
+
+        index.insert("r2", "m1");
+        index.purge(|market_id, reservation_id| (market_id, reservation_id) == ("m1", "r2"));
+        assert_eq!(index.len(), 1);
+        index.purge(|_, _| false);
+        assert!(index.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[derive(Debug)]
+struct Session {
+    user_id: String,
+    last_heartbeat: Instant,
+    /// (market_id, order_id) of the orders placed under the session. Ones
+    /// that have finished are dropped on heartbeat.
+    orders: HashSet<(String, Uuid)>,
+}
+
+/// A session whose orders should now be cancelled
+#[derive(Debug, PartialEq)]
+pub struct ClosedSession {
+    pub session_id: String,
+    pub user_id: String,
+    pub orders: Vec<(String, Uuid)>,
+}
+
+/// Client sessions kept alive by heartbeats. Orders tagged with a session
+/// are cancelled once it goes `timeout` without one.
+pub struct SessionRegistry {
+    sessions: DashMap<String, Session>,
+    timeout: Duration,
+}
+
+impl SessionRegistry {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            sessions: DashMap::new(),
+            timeout,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Open the session on first use, then keep it alive. Returns false if
+    /// the id already belongs to another user.
+    pub fn heartbeat(&self, session_id: &str, user_id: &str, now: Instant) -> bool {
+        match self.sessions.entry(session_id.to_string()) {
+            Entry::Occupied(mut session) => {
+                let session = session.get_mut();
+                if session.user_id != user_id {
+                    return false;
+                }
+                session.last_heartbeat = now;
+                true
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(Session {
+                    user_id: user_id.to_string(),
+                    last_heartbeat: now,
+                    orders: HashSet::new(),
+                });
+                true
+            }
+        }
+    }
+
+    /// Whether `user_id` may place orders under `session_id`
+    pub fn is_open(&self, session_id: &str, user_id: &str) -> bool {
+        self.sessions
+            .get(session_id)
+            .is_some_and(|session| session.user_id == user_id)
+    }
+
+    /// Tie a resting order to the session. Returns false if the session has
+    /// closed in the meantime, in which case the caller cancels the order.
+    pub fn track(&self, session_id: &str, market_id: &str, order_id: Uuid) -> bool {
+        match self.sessions.get_mut(session_id) {
+            Some(mut session) => {
+                session.orders.insert((market_id.to_string(), order_id));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every order tied to the session, for checking which have finished
+    pub fn orders(&self, session_id: &str) -> Vec<(String, Uuid)> {
+        self.sessions
+            .get(session_id)
+            .map(|session| session.orders.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Stop tracking orders that have filled or been cancelled
+    pub fn forget(&self, session_id: &str, orders: &[(String, Uuid)]) {
+        if let Some(mut session) = self.sessions.get_mut(session_id) {
+            for order in orders {
+                session.orders.remove(order);
+            }
+        }
+    }
+
+    /// End a session explicitly, e.g. on a clean client shutdown
+    pub fn close(&self, session_id: &str) -> Option<ClosedSession> {
+        self.sessions
+            .remove(session_id)
+            .map(|(session_id, session)| ClosedSession {
+                session_id,
+                user_id: session.user_id,
+                orders: session.orders.into_iter().collect(),
+            })
+    }
+
+    /// Remove every session that has missed its heartbeat window
+    pub fn expire(&self, now: Instant) -> Vec<ClosedSession> {
+        let stale: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|s| now.duration_since(s.last_heartbeat) >= self.timeout)
+            .map(|s| s.key().clone())
+            .collect();
+        // Re-check under the entry lock so a heartbeat racing the sweep wins
+        stale
+            .into_iter()
+            .filter_map(|id| {
+                self.sessions
+                    .remove_if(&id, |_, s| now.duration_since(s.last_heartbeat) >= self.timeout)
+            })
+            .map(|(session_id, session)| ClosedSession {
+                session_id,
+                user_id: session.user_id,
+                orders: session.orders.into_iter().collect(),
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missed_heartbeats_expire_session_with_its_orders() {
+        let sessions = SessionRegistry::new(Duration::from_secs(5));
+        let start = Instant::now();
+        let order_id = Uuid::new_v4();
+
+        assert!(sessions.heartbeat("s1", "bot", start));
+        assert!(!sessions.heartbeat("s1", "someone_else", start));
+        assert!(sessions.is_open("s1", "bot"));
+        assert!(sessions.track("s1", "m1", order_id));
+        assert!(!sessions.track("s2", "m1", order_id));
+        let filled = Uuid::new_v4();
+        assert!(sessions.track("s1", "m1", filled));
+        sessions.forget("s1", &[("m1".to_string(), filled)]);
+        assert_eq!(sessions.orders("s1"), vec![("m1".to_string(), order_id)]);
+
+        // A heartbeat inside the window keeps it alive
+        assert!(sessions.heartbeat("s1", "bot", start + Duration::from_secs(4)));
+        assert!(sessions.expire(start + Duration::from_secs(8)).is_empty());
+
+        let expired = sessions.expire(start + Duration::from_secs(9));
+        assert_eq!(
+            expired,
+            vec![ClosedSession {
+                session_id: "s1".to_string(),
+                user_id: "bot".to_string(),
+                orders: vec![("m1".to_string(), order_id)],
+            }]
+        );
+        assert!(sessions.is_empty());
+    }
+}
@@ -1,9 +1,8 @@
-use chrono::{Date, DateTime, Utc};
-use rust_decimal::Decimal;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::order::{OrderSide, Outcome};
+use crate::order::{Lots, Order, OrderSide, Outcome, Ticks};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade{
@@ -13,8 +12,8 @@ pub struct Trade{
     pub trade_type : TradeType,
     pub buyer_id : String,
     pub seller_id : String,
-    pub quantity : Decimal,
-    pub price  : Decimal,
+    pub quantity : Lots,
+    pub price  : Ticks,
     pub buyer_order_id : Uuid,
     pub seller_order_id : Uuid,
     pub buyer_reservation_id : Option<String>,
@@ -22,6 +21,32 @@ pub struct Trade{
     pub timestamp : DateTime<Utc>
 }
 
+impl Trade {
+    pub fn new(
+        buyer: &Order,
+        seller: &Order,
+        trade_type: TradeType,
+        price: Ticks,
+        quantity: Lots,
+    ) -> Self {
+        Self {
+            trade_id: Uuid::new_v4(),
+            market_id: buyer.market_id.clone(),
+            outcome: buyer.outcome,
+            trade_type,
+            buyer_id: buyer.user_id.clone(),
+            seller_id: seller.user_id.clone(),
+            quantity,
+            price,
+            buyer_order_id: buyer.order_id,
+            seller_order_id: seller.order_id,
+            buyer_reservation_id: buyer.reservation_id.clone(),
+            seller_reservation_id: seller.reservation_id.clone(),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
 #[derive(Debug, Clone,Copy, Serialize, Deserialize,PartialEq, Eq)]
 pub enum TradeType {
     SECONDARY,      // Transfer existing tokens (no blockchain)
@@ -58,9 +83,9 @@ pub struct  ComplementaryMatch{
     pub market_id : String,
     pub yes_buyer_id : String,
     pub no_buyer_id : String,
-    pub quantity : Decimal,
-    pub yes_price: Decimal,
-    pub no_price : Decimal,
+    pub quantity : Lots,
+    pub yes_price: Ticks,
+    pub no_price : Ticks,
     pub yes_order_id : Uuid,
     pub no_order_id : Uuid,
     pub yes_reservation_id : Option<String>,
@@ -69,7 +94,78 @@ pub struct  ComplementaryMatch{
 }
 
 impl ComplementaryMatch {
-    pub fn collateral_required(&self) -> Decimal {
+    pub fn new(
+        yes: &Order,
+        no: &Order,
+        yes_price: Ticks,
+        no_price: Ticks,
+        quantity: Lots,
+    ) -> Self {
+        Self {
+            trade_id: Uuid::new_v4(),
+            market_id: yes.market_id.clone(),
+            yes_buyer_id: yes.user_id.clone(),
+            no_buyer_id: no.user_id.clone(),
+            quantity,
+            yes_price,
+            no_price,
+            yes_order_id: yes.order_id,
+            no_order_id: no.order_id,
+            yes_reservation_id: yes.reservation_id.clone(),
+            no_reservation_id: no.reservation_id.clone(),
+            timestamp: Utc::now(),
+        }
+    }
+
+
+    pub fn collateral_required(&self) -> Lots {
         self.quantity // 1:1 ratio (1 token = 1 USDC collateral)
     }
 }
+
+/// One buyer in a complete-set mint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintLeg {
+    pub outcome : Outcome,
+    pub buyer_id : String,
+    pub order_id : Uuid,
+    pub reservation_id : Option<String>,
+    pub price : Ticks,
+}
+
+/// N buy orders, one per outcome of a categorical market, whose prices sum
+/// to at least 1.00: `quantity` complete outcome sets are minted and each
+/// buyer receives the tokens of their outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompleteSetMatch {
+    pub trade_id : Uuid,
+    pub market_id : String,
+    pub quantity : Lots,
+    /// In outcome order
+    pub legs : Vec<MintLeg>,
+    pub timestamp : DateTime<Utc>,
+}
+
+impl CompleteSetMatch {
+    pub fn new(orders: &[&Order], quantity: Lots) -> Self {
+        let mut legs: Vec<MintLeg> = orders
+            .iter()
+            .map(|o| MintLeg {
+                outcome: o.outcome,
+                buyer_id: o.user_id.clone(),
+                order_id: o.order_id,
+                reservation_id: o.reservation_id.clone(),
+                price: o.price,
+            })
+            .collect();
+        legs.sort_by_key(|leg| leg.outcome);
+
+        Self {
+            trade_id: Uuid::new_v4(),
+            market_id: orders[0].market_id.clone(),
+            quantity,
+            legs,
+            timestamp: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::order::{Lots, Order, OrderSide, Outcome, Ticks};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerKind {
+    /// Elected when the last trade moves against the order: a SELL stop at
+    /// or below its price, a BUY stop at or above
+    STOP,
+    /// Elected when the last trade moves in the order's favour: a SELL at
+    /// or above its price, a BUY at or below
+    TAKEPROFIT,
+}
+
+impl TriggerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TriggerKind::STOP => "STOP",
+            TriggerKind::TAKEPROFIT => "TAKE_PROFIT",
+        }
+    }
+}
+
+impl fmt::Display for TriggerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TriggerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "STOP" => Ok(TriggerKind::STOP),
+            "TAKE_PROFIT" => Ok(TriggerKind::TAKEPROFIT),
+            other => Err(format!("Unknown trigger type '{}'", other)),
+        }
+    }
+}
+
+/// When a conditional order is released into the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trigger {
+    pub kind: TriggerKind,
+    pub price: Ticks,
+}
+
+impl Trigger {
+    fn is_elected(&self, side: OrderSide, last_price: Ticks) -> bool {
+        match (self.kind, side) {
+            (TriggerKind::STOP, OrderSide::SELL) | (TriggerKind::TAKEPROFIT, OrderSide::BUY) => {
+                last_price <= self.price
+            }
+            (TriggerKind::STOP, OrderSide::BUY) | (TriggerKind::TAKEPROFIT, OrderSide::SELL) => {
+                last_price >= self.price
+            }
+        }
+    }
+}
+
+/// A held order and the trigger that releases it
+#[derive(Debug, Clone)]
+pub struct Conditional {
+    /// Arrival order within the market; elected orders fire in this order
+    pub seq: u64,
+    pub trigger: Trigger,
+    pub order: Order,
+}
+
+/// Stop and take-profit orders waiting on the last trade price. Nothing
+/// here is visible in the book or can be matched against until it fires.
+#[derive(Debug)]
+pub struct TriggerBook {
+    next_seq: u64,
+    pending: BTreeMap<u64, Conditional>,
+    index: HashMap<Uuid, u64>,
+    reservations: HashMap<String, u64>,
+    // Indexed by Outcome
+    last_prices: Vec<Option<Ticks>>,
+}
+
+impl TriggerBook {
+    pub fn new(outcome_count: usize) -> Self {
+        Self {
+            next_seq: 0,
+            pending: BTreeMap::new(),
+            index: HashMap::new(),
+            reservations: HashMap::new(),
+            last_prices: vec![None; outcome_count],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn contains(&self, order_id: &Uuid) -> bool {
+        self.index.contains_key(order_id)
+    }
+
+    pub fn has_reservation(&self, reservation_id: &str) -> bool {
+        self.reservations.contains_key(reservation_id)
+    }
+
+    pub fn get(&self, order_id: &Uuid) -> Option<&Order> {
+        let seq = self.index.get(order_id)?;
+        self.pending.get(seq).map(|c| &c.order)
+    }
+
+    pub fn last_price(&self, outcome: Outcome) -> Option<Ticks> {
+        self.last_prices[outcome.index()]
+    }
+
+    pub fn record_trade(&mut self, outcome: Outcome, price: Ticks) {
+        self.last_prices[outcome.index()] = Some(price);
+    }
+
+    /// Hold an order until its trigger is elected. Returns its sequence number.
+    pub fn add(&mut self, order: Order, trigger: Trigger) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        self.index.insert(order.order_id, seq);
+        if let Some(reservation_id) = &order.reservation_id {
+            self.reservations.insert(reservation_id.clone(), seq);
+        }
+        self.pending.insert(seq, Conditional { seq, trigger, order });
+        seq
+    }
+
+    pub fn remove(&mut self, order_id: &Uuid) -> Option<Order> {
+        let seq = self.index.get(order_id).copied()?;
+        self.take(seq).map(|c| c.order)
+    }
+
+    /// Shrink a held order by up to `quantity`. Returns the order afterwards;
+    /// it is dropped if nothing remains.
+    pub fn reduce(&mut self, order_id: &Uuid, quantity: Lots) -> Option<Order> {
+        let seq = self.index.get(order_id).copied()?;
+        let order = &mut self.pending.get_mut(&seq)?.order;
+        order.quantity -= quantity.min(order.remaining());
+        if order.remaining() == 0 {
+            self.take(seq).map(|c| c.order)
+        } else {
+            Some(order.clone())
+        }
+    }
+
+    /// Take out every order the current last prices elect, in sequence order
+    pub fn elect(&mut self) -> Vec<Conditional> {
+        let elected: Vec<u64> = self
+            .pending
+            .values()
+            .filter(|c| {
+                self.last_prices[c.order.outcome.index()]
+                    .is_some_and(|last| c.trigger.is_elected(c.order.side, last))
+            })
+            .map(|c| c.seq)
+            .collect();
+        elected.into_iter().filter_map(|seq| self.take(seq)).collect()
+    }
+
+    /// Take out every held order, in sequence order
+    pub fn drain(&mut self) -> Vec<Order> {
+        self.index.clear();
+        self.reservations.clear();
+        std::mem::take(&mut self.pending).into_values().map(|c| c.order).collect()
+    }
+
+    fn take(&mut self, seq: u64) -> Option<Conditional> {
+        let conditional = self.pending.remove(&seq)?;
+        self.index.remove(&conditional.order.order_id);
+        if let Some(reservation_id) = &conditional.order.reservation_id {
+            self.reservations.remove(reservation_id);
+        }
+        Some(conditional)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::{OrderStatus, OrderType};
+    use chrono::Utc;
+
+    fn order(side: OrderSide) -> Order {
+        Order {
+            order_id: Uuid::new_v4(),
+            user_id: "trader".to_string(),
+            market_id: "market_test".to_string(),
+            side,
+            outcome: Outcome::YES,
+            order_type: OrderType::MARKET,
+            price: 0,
+            quantity: 10,
+            filled: 0,
+            order_status: OrderStatus::UNTRIGGERED,
+            reservation_id: None,
+            display_quantity: None,
+            reduce_only: false,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_elects_by_direction_in_sequence_order() {
+        let mut triggers = TriggerBook::new(2);
+        let stop = Trigger { kind: TriggerKind::STOP, price: 40 };
+        let take_profit = Trigger { kind: TriggerKind::TAKEPROFIT, price: 70 };
+
+        let late_stop = order(OrderSide::SELL);
+        let early_stop = order(OrderSide::SELL);
+        triggers.add(early_stop.clone(), Trigger { price: 45, ..stop });
+        triggers.add(order(OrderSide::SELL), take_profit);
+        triggers.add(late_stop.clone(), stop);
+
+        // Nothing has traded yet
+        assert!(triggers.elect().is_empty());
+
+        triggers.record_trade(Outcome::YES, 50);
+        assert!(triggers.elect().is_empty());
+
+        // A drop through both stops releases them in arrival order
+        triggers.record_trade(Outcome::YES, 38);
+        let fired: Vec<Uuid> = triggers.elect().into_iter().map(|c| c.order.order_id).collect();
+        assert_eq!(fired, vec![early_stop.order_id, late_stop.order_id]);
+        assert_eq!(triggers.len(), 1);
+
+        triggers.record_trade(Outcome::YES, 75);
+        assert_eq!(triggers.elect().len(), 1);
+        assert!(triggers.is_empty());
+    }
+}
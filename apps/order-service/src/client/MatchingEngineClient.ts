@@ -28,7 +28,8 @@ export interface PlaceOrderRequest{
     order_type : 'MARKET'|'LIMIT'|'POSTONLY',
     price : string,
     quantity : string,
-    reservation_id? : string
+    reservation_id? : string,
+    client_order_id? : string
 }
 
 
@@ -85,8 +86,11 @@ export interface GetOrderbookRequest {
 
   export class MatchingEngineClient{
     private client : any;
+    // Bearer token with role "service", required once the engine has auth on
+    private token? : string;
 
-    constructor(address:string='localhost:50052'){{
+    constructor(address:string='localhost:50052', token:string|undefined=process.env.MATCHING_ENGINE_TOKEN){{
+        this.token = token;
         this.client = new matchingEngineProto.MatchingEngine(
             address,
             grpc.credentials.createInsecure()
@@ -95,9 +99,17 @@ export interface GetOrderbookRequest {
         console.log(`matching engine connected to the address ${address}`);
     }}
 
+    private metadata():grpc.Metadata{
+        const metadata = new grpc.Metadata();
+        if(this.token){
+            metadata.set('authorization', `Bearer ${this.token}`);
+        }
+        return metadata;
+    }
+
     async placeOrder(request:PlaceOrderRequest):Promise<PlaceOrderResponse>{
         return new Promise ((resolve,reject)=>{
-            this.client.PlaceOrder(request,(error:any,response:PlaceOrderResponse)=>{
+            this.client.PlaceOrder(request,this.metadata(),(error:any,response:PlaceOrderResponse)=>{
                 if(error){
                     console.error(' MatchingEngine.PlaceOrder error:', error);
                     reject(error);
@@ -111,7 +123,7 @@ export interface GetOrderbookRequest {
 
     async getOrderbook(request: GetOrderbookRequest): Promise<GetOrderbookResponse> {
         return new Promise((resolve, reject) => {
-          this.client.GetOrderbook(request, (error: any, response: GetOrderbookResponse) => {
+          this.client.GetOrderbook(request, this.metadata(), (error: any, response: GetOrderbookResponse) => {
             if (error) {
               console.error('MatchingEngine.GetOrderbook error:', error);
               reject(error);
@@ -0,0 +1,111 @@
+import { beforeEach, expect, mock, test } from "bun:test";
+
+// Just enough of the tables apply() touches, in memory. A transaction runs
+// on a copy and only keeps it if it finishes.
+let db: any;
+
+function table(name: string) {
+  const key = (where: any) =>
+    where.id ?? Object.values(where).map((k: any) => Object.values(k).join(":"))[0];
+  const change = (row: any, data: any) => {
+    for (const [field, value] of Object.entries<any>(data)) {
+      if (value?.increment !== undefined) row[field] += value.increment;
+      else if (value?.decrement !== undefined) row[field] -= value.decrement;
+      else row[field] = value;
+    }
+  };
+  return {
+    findUnique: async ({ where }: any) => db[name][key(where)] ?? null,
+    update: async ({ where, data }: any) => {
+      const row = db[name][key(where)];
+      if (!row) throw new Error(`No ${name} ${key(where)}`);
+      change(row, data);
+      return row;
+    },
+    updateMany: async ({ where, data }: any) => {
+      const rows = Object.values<any>(db[name]).filter((row) =>
+        Object.entries(where).every(([field, value]) => row[field] === value)
+      );
+      rows.forEach((row) => change(row, data));
+      return { count: rows.length };
+    },
+  };
+}
+
+const client = {
+  tradeCorrection: table("tradeCorrection"),
+  trade: table("trade"),
+  ledger: table("ledger"),
+  position: table("position"),
+};
+const prisma = {
+  ...client,
+  $transaction: async (fn: (tx: typeof client) => Promise<void>) => {
+    const before = structuredClone(db);
+    try {
+      return await fn(client);
+    } catch (err) {
+      db = before;
+      throw err;
+    }
+  },
+};
+
+mock.module("db/client", () => ({ prisma }));
+mock.module("ioredis", () => ({ default: class {} }));
+const { CorrectionService } = await import("./CorrectionService");
+
+beforeEach(() => {
+  db = {
+    tradeCorrection: {
+      c1: { id: "c1", tradeId: "t1", tradeKind: "trade", action: "bust", reason: "fat finger", appliedAt: null },
+    },
+    trade: {},
+    ledger: {
+      "buyer:USDC": { available: 0 },
+      "seller:USDC": { available: 30 },
+    },
+    position: {
+      "buyer:m1": { yesTokens: 50, noTokens: 0 },
+      "seller:m1": { yesTokens: 0, noTokens: 0 },
+    },
+  };
+});
+
+const settled = () => {
+  db.trade.t1 = {
+    id: "t1",
+    marketId: "m1",
+    buyerId: "buyer",
+    sellerId: "seller",
+    outcome: "YES",
+    quantity: 50,
+    price: 0.6,
+    status: "ACTIVE",
+  };
+};
+
+test("a correction is applied once however often it arrives", async () => {
+  settled();
+  const service = new CorrectionService();
+  await Promise.all([service.apply("c1"), service.apply("c1")]);
+  await service.apply("c1");
+
+  expect(db.ledger["buyer:USDC"].available).toBe(30);
+  expect(db.ledger["seller:USDC"].available).toBe(0);
+  expect(db.position["buyer:m1"].yesTokens).toBe(0);
+  expect(db.position["seller:m1"].yesTokens).toBe(50);
+  expect(db.trade.t1.status).toBe("BUSTED");
+  expect(db.tradeCorrection.c1.appliedAt).not.toBeNull();
+});
+
+test("a correction to a trade not settled yet waits for it", async () => {
+  const service = new CorrectionService();
+  await expect(service.apply("c1")).rejects.toThrow("not settled yet");
+  expect(db.tradeCorrection.c1.appliedAt).toBeNull();
+
+  settled();
+  await service.apply("c1");
+  expect(db.ledger["buyer:USDC"].available).toBe(30);
+  expect(db.trade.t1.status).toBe("BUSTED");
+});
@@ -0,0 +1,134 @@
+import { prisma } from "db/client";
+import Redis from "ioredis";
+
+// BRPOP blocks its connection, so the queue gets one to itself
+const queue = new Redis(process.env.REDIS_URL || "redis://localhost:6379");
+
+// The matching engine stores every bust and reprice in trade_corrections
+// before it takes effect, then pushes its id here
+const CORRECTIONS_QUEUE = "trades:corrections";
+
+export class CorrectionService {
+  private running = false;
+
+  async start() {
+    this.running = true;
+    console.log("🧾 Applying trade corrections");
+
+    // Anything queued while nobody was listening is still in the table
+    const pending = await prisma.tradeCorrection.findMany({
+      where: { appliedAt: null },
+      orderBy: { createdAt: "asc" },
+    });
+    for (const correction of pending) {
+      await this.apply(correction.id).catch(async (err) => {
+        console.error("❌ Trade correction failed", err);
+        await queue.lpush(CORRECTIONS_QUEUE, JSON.stringify({ correction_id: correction.id }));
+      });
+    }
+
+    while (this.running) {
+      let popped: [string, string] | null = null;
+      try {
+        popped = await queue.brpop(CORRECTIONS_QUEUE, 5);
+        if (!popped) continue;
+        const { correction_id } = JSON.parse(popped[1]);
+        await this.apply(correction_id);
+      } catch (err) {
+        // Still unapplied in the table; try again behind the rest
+        console.error("❌ Trade correction failed", err);
+        if (popped) {
+          await queue.lpush(CORRECTIONS_QUEUE, popped[1]).catch(() => {});
+        }
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      }
+    }
+  }
+
+  stop() {
+    this.running = false;
+  }
+
+  /**
+   * Reverse a busted secondary trade, or settle the difference of a
+   * repriced one, exactly once. Mints were settled on-chain and are only
+   * flagged for operations.
+   */
+  async apply(correctionId: string) {
+    await prisma.$transaction(async (tx) => {
+      // Claim the row first: a second apply of the same correction waits on
+      // its lock, then finds it applied. Rolled back with the rest on error.
+      const claimed = await tx.tradeCorrection.updateMany({
+        where: { id: correctionId, appliedAt: null },
+        data: { appliedAt: new Date() },
+      });
+      if (claimed.count === 0) return;
+      const correction = await tx.tradeCorrection.findUnique({
+        where: { id: correctionId },
+      });
+      if (!correction) return;
+
+      const trade =
+        correction.tradeKind === "trade"
+          ? await tx.trade.findUnique({ where: { id: correction.tradeId } })
+          : null;
+
+      if (correction.tradeKind === "trade" && !trade) {
+        // Not settled yet, e.g. a fired trigger's still queued on
+        // triggers:fired; left unapplied to be retried
+        throw new Error(`Trade ${correction.tradeId} of correction ${correction.id} is not settled yet`);
+      }
+
+      if (!trade) {
+        console.warn(
+          `⚠️ ${correction.action} of ${correction.tradeKind} ${correction.tradeId} needs settling by hand: ${correction.reason}`
+        );
+      } else if (correction.action === "bust") {
+        const quantity = Number(trade.quantity);
+        const amount = quantity * Number(trade.price);
+        const tokens = trade.outcome === "YES" ? "yesTokens" : "noTokens";
+
+        // Cash and tokens go back the way they came
+        await tx.ledger.update({
+          where: { userId_asset: { userId: trade.buyerId, asset: "USDC" } },
+          data: { available: { increment: amount } },
+        });
+        await tx.ledger.update({
+          where: { userId_asset: { userId: trade.sellerId, asset: "USDC" } },
+          data: { available: { decrement: amount } },
+        });
+        await tx.position.update({
+          where: { userId_marketId: { userId: trade.buyerId, marketId: trade.marketId } },
+          data: { [tokens]: { decrement: quantity } },
+        });
+        await tx.position.update({
+          where: { userId_marketId: { userId: trade.sellerId, marketId: trade.marketId } },
+          data: { [tokens]: { increment: quantity } },
+        });
+        await tx.trade.update({
+          where: { id: trade.id },
+          data: { status: "BUSTED" },
+        });
+      } else {
+        const newPrice = Number(correction.newPrice);
+        const difference = Number(trade.quantity) * (newPrice - Number(trade.price));
+
+        // Tokens stand; the buyer pays (or gets back) the difference
+        await tx.ledger.update({
+          where: { userId_asset: { userId: trade.buyerId, asset: "USDC" } },
+          data: { available: { decrement: difference } },
+        });
+        await tx.ledger.update({
+          where: { userId_asset: { userId: trade.sellerId, asset: "USDC" } },
+          data: { available: { increment: difference } },
+        });
+        await tx.trade.update({
+          where: { id: trade.id },
+          data: { price: newPrice, status: "CORRECTED" },
+        });
+      }
+
+      console.log(`✅ Applied ${correction.action} of trade ${correction.tradeId}`);
+    });
+  }
+}
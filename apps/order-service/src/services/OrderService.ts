@@ -7,6 +7,9 @@ import { MatchingEngineClient } from "../client/MatchingEngineClient";
 const redis = new Redis(process.env.REDIS_URL || "redis://localhost:6379");
 const balanceService = new BalanceService();
 
+// How long a fired trigger waits for the placement that fired it
+const PLACEMENT_SETTLE_TIMEOUT_MS = 30_000;
+
 const matchingEngine = new MatchingEngineClient(
   process.env.MATCHING_ENGINE_URL || "localhost:50052"
 );
@@ -90,6 +93,8 @@ export class OrderService {
         price: params.price.toString(),
         quantity: quantity.toString(),
         reservation_id: order.id,
+        // Lets the engine recognise a retry of this same order
+        client_order_id: order.id,
       });
 
       console.log(
@@ -98,62 +103,31 @@ export class OrderService {
 
       // Complementary → mint flow
       for (const cmatch of result.complementary_matches) {
-        const yesCanonicalOrderId = cmatch.yes_reservation_id;
-        const noCanonicalOrderId = cmatch.no_reservation_id;
-
-        await redis.lpush(
-          "mint:queue",
-          JSON.stringify({
-            trade_id: cmatch.trade_id,
-            market_id: cmatch.market_id,
-            yes_user_id: cmatch.yes_buyer_id,
-            no_user_id: cmatch.no_buyer_id,
-            // Canonical DB order IDs from reservation_id; do not use matching-engine internal order_id.
-            yes_order_id : yesCanonicalOrderId,
-            no_order_id : noCanonicalOrderId,
-            yes_reservation_id: yesCanonicalOrderId,
-            no_reservation_id: noCanonicalOrderId,
-            pairs: String(Math.round(Number(cmatch.quantity))),  // ✅ String, not number
-            yes_price: String(Number(cmatch.yes_price)),         // ✅ String
-            no_price: String(Number(cmatch.no_price)), 
-            market_pda: market.marketPda,
-            escrow_vault_pda: market.escrowVaultPda,
-            usdc_vault : market.usdcVault,
-            yes_token_mint: market.yesTokenMint,
-            no_token_mint: market.noTokenMint,
-            timestamp: new Date().toISOString(),
-          })
-        );
-
-        await prisma.order.update({
-          where: { id: order.id },
-          data: { status: "MATCHED" },
-        });
+        await this.queueMint(cmatch, market);
       }
 
       // Secondary trades
+      let filledQuantity = 0;
       for (const trade of result.trades) {
         console.log("RAW TRADE FROM GRPC:", trade);
         await this.executeSecondaryTrade(trade,params.marketId);
-
-        await prisma.order.update({
-          where: { id: order.id },
-          data: {
-            status: "FILLED",
-            filledQuantity: { increment: Number(trade.quantity) },
-          },
-        });
+        filledQuantity += Number(trade.quantity);
       }
 
-      if (
-        result.trades.length === 0 &&
-        result.complementary_matches.length === 0
-      ) {
-        await prisma.order.update({
-          where: { id: order.id },
-          data: { status: "OPEN" },
-        });
-      }
+      // Leaving PENDING marks the placement settled; triggers it fired
+      // wait for that before settling their own fills
+      await prisma.order.update({
+        where: { id: order.id },
+        data: {
+          status:
+            result.trades.length > 0
+              ? "FILLED"
+              : result.complementary_matches.length > 0
+                ? "MATCHED"
+                : "OPEN",
+          filledQuantity,
+        },
+      });
 
       return {
         orderId: order.id,
@@ -163,9 +137,9 @@ export class OrderService {
     } catch (err) {
       console.error("❌ Matching failed", err);
 
-      // Rollback BUY reserve
-      if (params.side === "BUY") {
-        await prisma.$transaction(async (tx) => {
+      await prisma.$transaction(async (tx) => {
+        // Rollback BUY reserve
+        if (params.side === "BUY") {
           await tx.ledger.update({
             where: {
               userId_asset: {
@@ -178,18 +152,115 @@ export class OrderService {
               reserved: { decrement: params.amount },
             },
           });
+        }
 
-          await tx.order.update({
-            where: { id: order.id },
-            data: { status: "FAILED" },
-          });
+        await tx.order.update({
+          where: { id: order.id },
+          data: { status: "FAILED" },
         });
-      }
+      });
 
       throw err;
     }
   }
 
+  /**
+   * Settle a stop or take-profit the engine fired. What it traded is in no
+   * placement response, so it comes through triggers:fired instead, after
+   * the placement that fired it has been settled.
+   */
+  async applyFiredTrigger(fired: any) {
+    if (fired.placed_by) {
+      await this.waitForPlacement(fired.placed_by);
+    }
+
+    if (fired.status === "REJECTED") {
+      console.warn(`⚠️ Triggered order ${fired.order_id} was rejected: ${fired.reject_reason}`);
+      if (fired.reservation_id) {
+        await prisma.order.updateMany({
+          where: { id: fired.reservation_id },
+          data: { status: "FAILED" },
+        });
+      }
+      return;
+    }
+
+    const market = await prisma.market.findUnique({
+      where: { id: fired.market_id },
+    });
+    if (!market) {
+      throw new Error(`Fired trigger for unknown market ${fired.market_id}`);
+    }
+
+    for (const cmatch of fired.complementary_matches) {
+      await this.queueMint(cmatch, market);
+    }
+    // The engine sends the outcome index here: 0 is YES, 1 is NO
+    for (const trade of fired.trades) {
+      const outcome = trade.outcome === 0 ? "YES" : "NO";
+      await this.executeSecondaryTrade({ ...trade, outcome }, fired.market_id);
+    }
+    if (fired.complete_set_matches.length > 0) {
+      console.warn(`⚠️ Triggered order ${fired.order_id} minted complete sets; settle by hand`);
+    }
+
+    if (fired.reservation_id && ["OPEN", "PARTIAL", "FILLED", "CANCELLED"].includes(fired.status)) {
+      await prisma.order.updateMany({
+        where: { id: fired.reservation_id },
+        data: { status: fired.status, filledQuantity: Number(fired.filled) },
+      });
+    }
+    console.log(`✅ Settled triggered order ${fired.order_id}: ${fired.trades.length} trades`);
+  }
+
+  /**
+   * Wait while an order placed through here is still being settled; a
+   * bracket's take-profit sells what its entry bought. Gives up after
+   * PLACEMENT_SETTLE_TIMEOUT_MS, e.g. if the placing process died.
+   */
+  private async waitForPlacement(orderId: string) {
+    const deadline = Date.now() + PLACEMENT_SETTLE_TIMEOUT_MS;
+    while (Date.now() < deadline) {
+      const order = await prisma.order.findUnique({
+        where: { id: orderId },
+        select: { status: true },
+      });
+      // Not placed through the order service, or already settled
+      if (!order || order.status !== "PENDING") return;
+      await new Promise((resolve) => setTimeout(resolve, 100));
+    }
+    console.warn(`⚠️ Placement ${orderId} still unsettled, settling what it fired anyway`);
+  }
+
+  private async queueMint(cmatch: any, market: any) {
+    const yesCanonicalOrderId = cmatch.yes_reservation_id;
+    const noCanonicalOrderId = cmatch.no_reservation_id;
+
+    await redis.lpush(
+      "mint:queue",
+      JSON.stringify({
+        trade_id: cmatch.trade_id,
+        market_id: cmatch.market_id ?? market.id,
+        yes_user_id: cmatch.yes_buyer_id,
+        no_user_id: cmatch.no_buyer_id,
+        // Canonical DB order IDs from reservation_id; do not use matching-engine internal order_id.
+        yes_order_id : yesCanonicalOrderId,
+        no_order_id : noCanonicalOrderId,
+        yes_reservation_id: yesCanonicalOrderId,
+        no_reservation_id: noCanonicalOrderId,
+        pairs: String(Math.round(Number(cmatch.quantity))),  // ✅ String, not number
+        yes_price: String(Number(cmatch.yes_price)),         // ✅ String
+        no_price: String(Number(cmatch.no_price)), 
+        market_pda: market.marketPda,
+        escrow_vault_pda: market.escrowVaultPda,
+        usdc_vault : market.usdcVault,
+        yes_token_mint: market.yesTokenMint,
+        no_token_mint: market.noTokenMint,
+        timestamp: new Date().toISOString(),
+      })
+    );
+  }
+
   private async executeSecondaryTrade(trade: any,marketId:string) {
     console.log("FULL TRADE OBJECT:", JSON.stringify(trade));
     console.log("OUTCOME TYPE:", typeof trade.outcome, "VALUE:", trade.outcome);
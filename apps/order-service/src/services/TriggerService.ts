@@ -0,0 +1,40 @@
+import Redis from "ioredis";
+import { OrderService } from "./OrderService";
+
+// BRPOP blocks its connection, so the queue gets one to itself
+const queue = new Redis(process.env.REDIS_URL || "redis://localhost:6379");
+const orderService = new OrderService();
+
+// The matching engine pushes every stop and take-profit that fires here,
+// with what it traded
+const FIRED_TRIGGERS_QUEUE = "triggers:fired";
+// Ones that could not be settled, kept for someone to look at
+const FAILED_QUEUE = "triggers:fired:failed";
+
+export class TriggerService {
+  private running = false;
+
+  async start() {
+    this.running = true;
+    console.log("🎯 Settling triggered orders");
+
+    while (this.running) {
+      let popped: [string, string] | null = null;
+      try {
+        popped = await queue.brpop(FIRED_TRIGGERS_QUEUE, 5);
+        if (!popped) continue;
+        await orderService.applyFiredTrigger(JSON.parse(popped[1]));
+      } catch (err) {
+        console.error("❌ Settling a triggered order failed", err);
+        if (popped) {
+          await queue.lpush(FAILED_QUEUE, popped[1]).catch(() => {});
+        }
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+      }
+    }
+  }
+
+  stop() {
+    this.running = false;
+  }
+}
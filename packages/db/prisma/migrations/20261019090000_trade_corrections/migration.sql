@@ -0,0 +1,29 @@
+-- CreateEnum
+CREATE TYPE "TradeStatus" AS ENUM ('ACTIVE', 'BUSTED', 'CORRECTED');
+
+-- AlterTable
+ALTER TABLE "trades" ADD COLUMN     "status" "TradeStatus" NOT NULL DEFAULT 'ACTIVE';
+
+-- CreateTable
+CREATE TABLE "trade_corrections" (
+    "id" TEXT NOT NULL,
+    "trade_id" TEXT NOT NULL,
+    "market_id" TEXT NOT NULL,
+    "trade_kind" TEXT NOT NULL,
+    "action" TEXT NOT NULL,
+    "old_price" DECIMAL(5,4),
+    "new_price" DECIMAL(5,4),
+    "actor" TEXT NOT NULL,
+    "reason" TEXT NOT NULL,
+    "trade" TEXT NOT NULL,
+    "created_at" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "applied_at" TIMESTAMP(3),
+
+    CONSTRAINT "trade_corrections_pkey" PRIMARY KEY ("id")
+);
+
+-- CreateIndex
+CREATE INDEX "trade_corrections_trade_id_idx" ON "trade_corrections"("trade_id");
+
+-- CreateIndex
+CREATE INDEX "trade_corrections_applied_at_idx" ON "trade_corrections"("applied_at");